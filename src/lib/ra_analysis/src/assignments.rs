@@ -0,0 +1,205 @@
+use super::identity::identity_items;
+use super::items::{block_name, is_declaration, item_paths, reference_path};
+use super::types::item_input;
+use ra_lexer::cursor::Position;
+use ra_lexer::diagnostics::Diagnostic;
+use ra_lexer::token::TokenKind;
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::output_expression::{
+    ExpressionMember, MathOperation, OperationKind, OutputExpression,
+};
+use ra_parser::Positioned;
+use std::fmt;
+
+/// How an assignment changes its target
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AssignmentKind {
+    /// `= value` replaces the value
+    Set,
+    /// `+= value` increments the value, concurrent increments add up
+    Add,
+    /// `-= value` decrements the value, concurrent decrements add up
+    Subtract,
+}
+
+impl AssignmentKind {
+    pub fn from_operation(operation: OperationKind) -> Option<Self> {
+        match operation {
+            OperationKind::Assign => Some(AssignmentKind::Set),
+            OperationKind::MathOperation(MathOperation::AddAssign) => Some(AssignmentKind::Add),
+            OperationKind::MathOperation(MathOperation::SubtractAssign) => {
+                Some(AssignmentKind::Subtract)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AssignmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssignmentKind::Set => write!(f, "="),
+            AssignmentKind::Add => write!(f, "+="),
+            AssignmentKind::Subtract => write!(f, "-="),
+        }
+    }
+}
+
+/// Assignment declared in a sequence.
+///
+/// `votesCount += 1` names its target, a bare `+= 1`
+/// nested in an item assigns to that item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment<'b, 'a> {
+    /// Path of the assigned item, as written when it can't be resolved
+    pub target: String,
+    pub kind: AssignmentKind,
+    pub value: &'b ExpressionMember<'a>,
+    pub block: &'b Block<'a>,
+}
+
+/// Assignments in document order
+pub fn assignments<'b, 'a>(program: &'b Block<'a>) -> Vec<Assignment<'b, 'a>> {
+    let items: Vec<String> = item_paths(program)
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+    let mut found = Vec::new();
+    collect_assignments(program, "", &items, &mut found);
+    found
+}
+
+fn collect_assignments<'b, 'a>(
+    block: &'b Block<'a>,
+    scope: &str,
+    items: &[String],
+    found: &mut Vec<Assignment<'b, 'a>>,
+) {
+    for child in block.children.iter() {
+        if let BlockKind::Output(OutputExpression {
+            left: target,
+            operation: Some(operation),
+            right: Some(value),
+        }) = &child.kind
+        {
+            if let Some(kind) = AssignmentKind::from_operation(*operation) {
+                found.push(Assignment {
+                    target: resolve_target(target, scope, items),
+                    kind,
+                    value,
+                    block: child,
+                });
+            }
+        }
+
+        let scope = match block_name(child) {
+            Some(name) if !is_declaration(child) && scope.is_empty() => name.content.to_owned(),
+            Some(name) if !is_declaration(child) => format!("{}.{}", scope, name.content),
+            _ => scope.to_owned(),
+        };
+        collect_assignments(child, &scope, items, found);
+    }
+}
+
+/// Names are looked up from the innermost enclosing item outwards
+fn resolve_target(target: &ExpressionMember, scope: &str, items: &[String]) -> String {
+    let name = match target {
+        ExpressionMember::Nil => return scope.to_owned(),
+        ExpressionMember::ReferenceExpression(reference) => reference_path(reference).join("."),
+        _ => return String::new(),
+    };
+    let mut prefix = scope;
+    loop {
+        let candidate = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        if items.contains(&candidate) || prefix.is_empty() {
+            return candidate;
+        }
+        prefix = prefix.rsplit_once('.').map_or("", |(parent, _)| parent);
+    }
+}
+
+/// Items without an input can be assigned,
+/// inputs only change by clients answering them
+pub fn is_assignable(item: &Block) -> bool {
+    item_input(item).is_none()
+}
+
+/// Paths of assigned items, each once
+pub fn assigned_items(program: &Block) -> Vec<String> {
+    let mut targets: Vec<String> = Vec::new();
+    for assignment in assignments(program) {
+        if !targets.contains(&assignment.target) {
+            targets.push(assignment.target);
+        }
+    }
+    targets
+}
+
+/// Reports assignments the runtime would refuse
+pub fn lint_assignments(program: &Block) -> Vec<Diagnostic> {
+    let items = item_paths(program);
+    let identity = identity_items(program);
+    assignments(program)
+        .into_iter()
+        .filter_map(|assignment| {
+            let position = position(&assignment);
+            let target = &assignment.target;
+            let error = |message: String, label: &str| {
+                Some(Diagnostic::error("A0002", message).with_primary_label(position, label))
+            };
+            if target.is_empty() {
+                return error(
+                    format!("`{}` needs an item to assign to", assignment.kind),
+                    "nothing to assign to",
+                );
+            }
+            let item = match items.iter().find(|(path, _)| path == target) {
+                Some((_, item)) => item,
+                None => {
+                    return error(
+                        format!("can't assign to unknown item `{}`", target),
+                        "unknown item",
+                    )
+                }
+            };
+            if identity.contains(target) {
+                return error(
+                    format!("`{}` identifies clients and can't be assigned", target),
+                    "identity item",
+                );
+            }
+            if !is_assignable(item) {
+                return error(
+                    format!("`{}` accepts input and can't be assigned", target),
+                    "inputs only change by clients answering them",
+                );
+            }
+            match (assignment.kind, assignment.value) {
+                (AssignmentKind::Add, ExpressionMember::Literal(token))
+                | (AssignmentKind::Subtract, ExpressionMember::Literal(token))
+                    if matches!(token.kind, Some(TokenKind::StringLiteral(_))) =>
+                {
+                    error(
+                        format!("`{}` needs a number", assignment.kind),
+                        "not a number",
+                    )
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Position of the assigned name, or of the value of a bare assignment
+fn position(assignment: &Assignment) -> (Position, Position) {
+    match &assignment.block.kind {
+        BlockKind::Output(expression) if *expression.left != ExpressionMember::Nil => {
+            expression.left.get_position()
+        }
+        _ => assignment.value.get_position(),
+    }
+}
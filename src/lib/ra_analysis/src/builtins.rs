@@ -0,0 +1,66 @@
+/// Rules known to the runtime, name and short description
+pub const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("text", "Accepts plain text"),
+    (
+        "number",
+        "Accepts numbers, `:min` and `:max` limit the value",
+    ),
+    ("file", "Accepts files, `:type` limits the kind of a file"),
+    ("options", "Offers a set of options to choose from"),
+    (
+        "choice",
+        "Choice of options, `:multiple` allows choosing many",
+    ),
+    ("select", "Selection of options, `:single` or `:multiple`"),
+    (
+        "pattern",
+        "Validates text against a known pattern, e.g. `:email`",
+    ),
+    ("format", "Validates text format, e.g. `:email=true`"),
+    ("required", "Input must be answered"),
+    (
+        "identity",
+        "Item identifies a client, its values are only visible to the runtime",
+    ),
+    ("maxLength", "Maximum length of a text"),
+    ("length", "Length of a text, `:min` and `:max` limit it"),
+    ("minCount", "Minimum number of entries in a list"),
+    (
+        "count",
+        "Number of entries in a list, `:min` and `:max` limit it",
+    ),
+    ("default", "Default value of an input"),
+    ("if", "Shows its children when condition is true"),
+    ("else", "Shows its children when preceding `!if` is false"),
+    ("switch", "Shows the first matching `!case`"),
+    ("case", "Branch of a `!switch`"),
+    ("iterate", "Repeats its children for every entry of a list"),
+    ("includes", "True when a list includes a value"),
+    ("sort", "Sorts a list, `:desc` reverses the order"),
+    ("filter", "Keeps entries of a list a condition holds for"),
+];
+
+/// Known annotation contexts, name and short description
+pub const ANNOTATION_KEYS: &[(&str, &str)] = &[
+    ("ln", "Natural language of the content, e.g. `#ln:en`"),
+    (
+        "lang",
+        "Language of a code snippet, e.g. `#lang:typescript`",
+    ),
+    ("kind", "Kind of media, e.g. `#kind:image`"),
+    ("env", "Environment the content is meant for"),
+];
+
+pub fn builtin_rule(name: &str) -> Option<&'static str> {
+    BUILTIN_RULES
+        .iter()
+        .find(|(rule, _)| *rule == name)
+        .map(|(_, description)| *description)
+}
+
+pub fn annotation_key(name: &str) -> Option<&'static str> {
+    ANNOTATION_KEYS
+        .iter()
+        .find(|(key, _)| *key == name)
+        .map(|(_, description)| *description)
+}
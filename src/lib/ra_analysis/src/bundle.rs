@@ -0,0 +1,425 @@
+use super::builtins::builtin_rule;
+use super::checks::{check_locale, reference_key};
+use super::desugar::desugar;
+use super::items::{block_name, find_declaration, is_declaration, item_paths, reference_path};
+use super::types::{default_value, infer_input_type, input_rules, item_input, Argument, InputType};
+use ra_lexer::cursor::Position;
+use ra_lexer::diagnostics::{Diagnostic, ToDiagnostic};
+use ra_lexer::line_index::LineIndex;
+use ra_lexer::span::Span;
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::annotation_expression::AnnotationExpression;
+use ra_parser::expressions::content::ContentBlockMember;
+use ra_parser::owned;
+use ra_parser::parser::parse;
+use std::fmt;
+use std::path::Path;
+
+/// Version of the bundle format.
+///
+/// Minor versions only add optional fields,
+/// anything else bumps the major version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct FormatVersion {
+    pub major: u16,
+    pub minor: u16,
+}
+
+/// Version bundles are compiled to,
+/// 2.0 changed the shape of references in the program, 2.1 marks raw strings
+pub const FORMAT_VERSION: FormatVersion = FormatVersion { major: 2, minor: 1 };
+
+impl FormatVersion {
+    /// Runtimes load bundles of their own major version
+    /// which are not newer than they are
+    pub fn can_load(&self, bundle: FormatVersion) -> bool {
+        self.major == bundle.major && bundle.minor <= self.minor
+    }
+}
+
+impl fmt::Display for FormatVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Sequences compiled together,
+/// runtimes run them without parsing the sources again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub version: FormatVersion,
+    pub sequences: Vec<CompiledSequence>,
+}
+
+impl Bundle {
+    pub fn sequence(&self, name: &str) -> Option<&CompiledSequence> {
+        self.sequences.iter().find(|sequence| sequence.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompiledSequence {
+    /// Name other sequences reference it by, the file name without extension
+    pub name: String,
+    /// File the sequence was compiled from
+    pub file: String,
+    pub program: owned::Block,
+    /// Locale numbers clients type are read in, declared as `#locale:de` on the top level
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Items accepting input in document order
+    pub fields: Vec<CompiledField>,
+    pub references: Vec<ResolvedReference>,
+    pub contents: Vec<ContentVariant>,
+    pub source_map: Vec<SourceLocation>,
+}
+
+impl CompiledSequence {
+    pub fn field(&self, path: &str) -> Option<&CompiledField> {
+        self.fields.iter().find(|field| field.path == path)
+    }
+
+    /// Where an item is declared in the source
+    pub fn location(&self, item: &str) -> Option<&SourceLocation> {
+        self.source_map
+            .iter()
+            .find(|location| location.item == item)
+    }
+
+    /// Contents of an item, one per variant like `#ln:ru`
+    pub fn variants(&self, item: &str) -> Vec<&ContentVariant> {
+        self.contents
+            .iter()
+            .filter(|content| content.item == item)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompiledField {
+    pub path: String,
+    pub input_type: InputType,
+    pub multiple: bool,
+    pub rules: Vec<RuleBinding>,
+    pub default: Option<String>,
+}
+
+/// Rule invoked on an input with its arguments
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleBinding {
+    pub rule: String,
+    pub arguments: Vec<Argument>,
+    pub origin: RuleOrigin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RuleOrigin {
+    Builtin,
+    /// Declared in the sequence as `:rule`
+    Declared,
+    /// Neither built in nor declared, left for the runtime to provide
+    External,
+}
+
+/// Reference `@sequence.item` resolved to an item of another sequence
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResolvedReference {
+    /// Item the reference is in, empty on the top level
+    pub from: String,
+    pub sequence: String,
+    /// Referenced item, empty when the whole sequence is referenced
+    pub item: String,
+    /// Trailing segments after the item, e.g. `count`
+    pub rest: Vec<String>,
+}
+
+/// Content block with the annotations it's shown under,
+/// `#ln:ru` makes it the `ru` variant of the `ln` context
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContentVariant {
+    /// Item the content belongs to, empty on the top level
+    pub item: String,
+    pub annotations: Vec<Annotation>,
+    pub parts: Vec<ContentPart>,
+}
+
+impl ContentVariant {
+    /// Option of the context the content is annotated with
+    pub fn option(&self, context: &str) -> Option<&str> {
+        self.annotations
+            .iter()
+            .find(|annotation| annotation.context == context)
+            .and_then(|annotation| annotation.option.as_deref())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub context: String,
+    pub option: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ContentPart {
+    Text(String),
+    /// Value of an item shown in the text `{{item}}` or `{{item.1}}`
+    Template(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub item: String,
+    pub span: Span,
+    pub start: Position,
+    pub end: Position,
+}
+
+/// Diagnostic of a file which couldn't be compiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError {
+    pub file: String,
+    pub diagnostic: Diagnostic,
+}
+
+/// Compiles files `(path, source)` into a bundle.
+///
+/// `@name.item` references resolve to items of the file named `name`,
+/// every file has to parse, every reference has to resolve
+/// and a `#locale` has to be known.
+/// Programs are lowered to the core forms before they're compiled.
+pub fn compile(files: &[(&str, &str)]) -> Result<Bundle, Vec<CompileError>> {
+    let mut errors = Vec::new();
+    let mut parsed = Vec::new();
+    for (file, source) in files.iter() {
+        match parse(source) {
+            Ok(mut program) => {
+                desugar(&mut program);
+                parsed.push((*file, *source, program))
+            }
+            Err((parse_errors, _)) => {
+                errors.extend(parse_errors.iter().map(|error| CompileError {
+                    file: (*file).to_owned(),
+                    diagnostic: error.to_diagnostic(),
+                }))
+            }
+        }
+    }
+
+    let known: Vec<(String, Vec<String>)> = parsed
+        .iter()
+        .map(|(file, _, program)| {
+            (
+                sequence_name(file),
+                item_paths(program)
+                    .into_iter()
+                    .map(|(path, _)| path)
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let mut sequences = Vec::new();
+    for (file, source, program) in parsed.iter() {
+        let mut compiler = Compiler {
+            known: &known,
+            references: Vec::new(),
+            contents: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        compiler.collect(program, "", &[]);
+        compiler.diagnostics.extend(check_locale(program));
+        errors.extend(
+            compiler
+                .diagnostics
+                .into_iter()
+                .map(|diagnostic| CompileError {
+                    file: (*file).to_owned(),
+                    diagnostic,
+                }),
+        );
+        sequences.push(CompiledSequence {
+            name: sequence_name(file),
+            file: (*file).to_owned(),
+            program: owned::Block::from(program),
+            locale: locale(program),
+            fields: fields(program),
+            references: compiler.references,
+            contents: compiler.contents,
+            source_map: source_map(source, program),
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(Bundle {
+            version: FORMAT_VERSION,
+            sequences,
+        })
+    } else {
+        Err(errors)
+    }
+}
+
+/// `examples/foodie/recipe.ra` is referenced as `@recipe`
+pub fn sequence_name(file: &str) -> String {
+    Path::new(file)
+        .file_stem()
+        .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+}
+
+/// Option of a top level `#locale` annotation
+fn locale(program: &Block) -> Option<String> {
+    program
+        .children
+        .iter()
+        .filter_map(|child| match &child.kind {
+            BlockKind::Annotation(Some(expression)) => Some(annotation(expression)),
+            _ => None,
+        })
+        .find(|annotation| annotation.context == "locale")
+        .and_then(|annotation| annotation.option)
+}
+
+fn fields(program: &Block) -> Vec<CompiledField> {
+    item_paths(program)
+        .into_iter()
+        .filter_map(|(path, item)| {
+            let input = item_input(item)?;
+            let input_type = infer_input_type(input);
+            Some(CompiledField {
+                path,
+                multiple: matches!(input_type, InputType::List(_)),
+                input_type,
+                rules: input_rules(input)
+                    .into_iter()
+                    .map(|(rule, arguments)| RuleBinding {
+                        rule: rule.to_owned(),
+                        arguments,
+                        origin: if find_declaration(program, rule).is_some() {
+                            RuleOrigin::Declared
+                        } else if builtin_rule(rule).is_some() {
+                            RuleOrigin::Builtin
+                        } else {
+                            RuleOrigin::External
+                        },
+                    })
+                    .collect(),
+                default: default_value(input),
+            })
+        })
+        .collect()
+}
+
+fn source_map(source: &str, program: &Block) -> Vec<SourceLocation> {
+    let index = LineIndex::new(source);
+    item_paths(program)
+        .into_iter()
+        .map(|(item, block)| SourceLocation {
+            item,
+            span: block.span,
+            start: index.position(block.span.start),
+            end: index.position(block.span.end),
+        })
+        .collect()
+}
+
+struct Compiler<'k> {
+    /// Compiled sequences with paths of their items
+    known: &'k [(String, Vec<String>)],
+    references: Vec<ResolvedReference>,
+    contents: Vec<ContentVariant>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'k> Compiler<'k> {
+    fn collect(&mut self, block: &Block, scope: &str, annotations: &[Annotation]) {
+        for child in block.children.iter() {
+            let mut annotations = annotations.to_vec();
+            match &child.kind {
+                BlockKind::Annotation(Some(expression)) => annotations.push(annotation(expression)),
+                BlockKind::Content(content) => self.contents.push(ContentVariant {
+                    item: scope.to_owned(),
+                    annotations: annotations.clone(),
+                    parts: content
+                        .members()
+                        .into_iter()
+                        .map(|member| match member {
+                            ContentBlockMember::Body(text) => ContentPart::Text(text.clone()),
+                            ContentBlockMember::Template(reference) => {
+                                ContentPart::Template(reference_key(reference))
+                            }
+                        })
+                        .collect(),
+                }),
+                BlockKind::Reference(Some(reference)) => {
+                    let path = reference_path(reference);
+                    match self.resolve(&path) {
+                        Ok((sequence, item, rest)) => self.references.push(ResolvedReference {
+                            from: scope.to_owned(),
+                            sequence,
+                            item,
+                            rest,
+                        }),
+                        Err(label) => self.diagnostics.push(
+                            Diagnostic::error(
+                                "A0007",
+                                format!("`@{}` can't be resolved", path.join(".")),
+                            )
+                            .with_primary_label(reference.name.position, label)
+                            .with_note("referenced sequences are compiled together"),
+                        ),
+                    }
+                }
+                _ => {}
+            }
+
+            let scope = match block_name(child) {
+                Some(name) if !is_declaration(child) && scope.is_empty() => name.content.to_owned(),
+                Some(name) if !is_declaration(child) => format!("{}.{}", scope, name.content),
+                _ => scope.to_owned(),
+            };
+            self.collect(child, &scope, &annotations);
+        }
+    }
+
+    /// Sequence, its longest item the path starts with and the remaining segments
+    fn resolve(&self, path: &[&str]) -> Result<(String, String, Vec<String>), String> {
+        let (name, path) = path.split_first().ok_or_else(String::new)?;
+        let (sequence, items) = self
+            .known
+            .iter()
+            .find(|(known, _)| known == name)
+            .ok_or_else(|| format!("no sequence `{}` is compiled", name))?;
+        let length = (1..=path.len())
+            .rev()
+            .find(|length| items.contains(&path[..*length].join(".")))
+            .unwrap_or(0);
+        if length == 0 && !path.is_empty() {
+            return Err(format!("`{}` has no item `{}`", sequence, path[0]));
+        }
+        Ok((
+            sequence.clone(),
+            path[..length].join("."),
+            path[length..]
+                .iter()
+                .map(|segment| (*segment).to_owned())
+                .collect(),
+        ))
+    }
+}
+
+/// `#context:option`, further segments are part of the option
+fn annotation(expression: &AnnotationExpression) -> Annotation {
+    let segments: Vec<&str> = expression
+        .segments()
+        .skip(1)
+        .map(|segment| segment.content)
+        .collect();
+    Annotation {
+        context: expression.name.content.to_owned(),
+        option: if segments.is_empty() {
+            None
+        } else {
+            Some(segments.join(":"))
+        },
+    }
+}
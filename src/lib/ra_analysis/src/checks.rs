@@ -0,0 +1,655 @@
+use super::dependencies::{DependencyGraph, DependencyKind};
+use super::items::{block_name, find_item, item_paths, named_children, reference_path};
+use super::locale::number_separators;
+use super::types::{infer_input_type, item_input, InputType};
+use ra_lexer::cursor::Position;
+use ra_lexer::diagnostics::Diagnostic;
+use ra_lexer::token::{Token, TokenKind};
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::input_expression::{ArgumentType, InputExpression, ValueType};
+use ra_parser::expressions::output_expression::{
+    ComparisonOperation, ExpressionMember, LogicOperation, MathOperation, OperationKind,
+    OutputExpression, UnaryOperation,
+};
+use ra_parser::expressions::reference_expression::ReferenceExpression;
+use ra_parser::visit::{walk_block, walk_reference_expression, Visitor};
+use std::collections::{HashMap, VecDeque};
+
+/// Static checks of a sequence: reference cycles, unreachable branches,
+/// `!switch` blocks without `!default`, duplicated `!case` values,
+/// indices not matching the shape of the data and unknown locales
+pub fn check_sequence(program: &Block) -> Vec<Diagnostic> {
+    let mut diagnostics = check_cycles(program);
+    check_branches(program, &mut diagnostics);
+    diagnostics.extend(check_indices(program));
+    diagnostics.extend(check_locale(program));
+    diagnostics
+}
+
+/// Top level `#locale:tag` annotations numbers can't be read in
+pub fn check_locale(program: &Block) -> Vec<Diagnostic> {
+    program
+        .children
+        .iter()
+        .filter_map(|child| match &child.kind {
+            BlockKind::Annotation(Some(expression)) if expression.name.content == "locale" => {
+                expression.segments().nth(1)
+            }
+            _ => None,
+        })
+        .filter(|tag| number_separators(tag.content).is_none())
+        .map(|tag| {
+            Diagnostic::error("A0009", format!("unknown locale `{}`", tag.content))
+                .with_primary_label(tag.position, "numbers can't be read in this locale")
+                .with_note("use a language tag like `de` or `de-CH`")
+        })
+        .collect()
+}
+
+/// Items depending on themselves, directly or through other items
+pub fn check_cycles(program: &Block) -> Vec<Diagnostic> {
+    let graph = DependencyGraph::new(program);
+    let positions: HashMap<String, (Position, Position)> = item_paths(program)
+        .into_iter()
+        .filter_map(|(path, item)| Some((path, block_name(item)?.position)))
+        .collect();
+
+    let order: HashMap<&str, usize> = graph
+        .items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| (item.as_str(), index))
+        .collect();
+    let adjacency: Vec<Vec<usize>> = graph
+        .items
+        .iter()
+        .map(|item| {
+            edges(&graph, item)
+                .into_iter()
+                .filter_map(|target| order.get(target).copied())
+                .collect()
+        })
+        .collect();
+
+    let mut cycles: Vec<Vec<usize>> = strongly_connected(&adjacency)
+        .into_iter()
+        .filter(|component| component.len() > 1 || adjacency[component[0]].contains(&component[0]))
+        .map(|component| cycle_through(&adjacency, &component))
+        .collect();
+    cycles.sort();
+
+    cycles
+        .into_iter()
+        .map(|cycle| {
+            let cycle: Vec<&String> = cycle.into_iter().map(|index| &graph.items[index]).collect();
+            let mut through: Vec<&str> = cycle.iter().map(|item| item.as_str()).collect();
+            through.push(cycle[0]);
+            let first = positions.get(cycle[0]).copied().unwrap_or_default();
+            let mut diagnostic =
+                Diagnostic::error("A0003", format!("`{}` depends on itself", cycle[0]))
+                    .with_primary_label(first, "this item can't be shown before itself")
+                    .with_note(format!("reference cycle {}", through.join(" -> ")));
+            for item in cycle.iter().skip(1) {
+                if let Some(position) = positions.get(*item) {
+                    diagnostic = diagnostic.with_secondary_label(*position, "part of the cycle");
+                }
+            }
+            diagnostic
+        })
+        .collect()
+}
+
+/// Strongly connected components of a graph by Tarjan's algorithm,
+/// each with its items in ascending order
+fn strongly_connected(adjacency: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'g> {
+        adjacency: &'g [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl<'g> Tarjan<'g> {
+        fn connect(&mut self, item: usize) {
+            self.index[item] = Some(self.next);
+            self.low[item] = self.next;
+            self.next += 1;
+            self.stack.push(item);
+            self.on_stack[item] = true;
+
+            for &target in &self.adjacency[item] {
+                match self.index[target] {
+                    None => {
+                        self.connect(target);
+                        self.low[item] = self.low[item].min(self.low[target]);
+                    }
+                    Some(index) if self.on_stack[target] => {
+                        self.low[item] = self.low[item].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low[item]) == self.index[item] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == item {
+                        break;
+                    }
+                }
+                component.sort_unstable();
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        adjacency,
+        index: vec![None; adjacency.len()],
+        low: vec![0; adjacency.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; adjacency.len()],
+        next: 0,
+        components: Vec::new(),
+    };
+    for item in 0..adjacency.len() {
+        if tarjan.index[item].is_none() {
+            tarjan.connect(item);
+        }
+    }
+    tarjan.components
+}
+
+/// Shortest cycle from the earliest item of a component back to it,
+/// staying within the component
+fn cycle_through(adjacency: &[Vec<usize>], component: &[usize]) -> Vec<usize> {
+    let start = component[0];
+    let mut previous: HashMap<usize, usize> = HashMap::new();
+    let mut queue = VecDeque::from(vec![start]);
+    while let Some(item) = queue.pop_front() {
+        for &target in &adjacency[item] {
+            if target == start {
+                let mut cycle = vec![item];
+                while let Some(&before) = previous.get(cycle.last().unwrap()) {
+                    cycle.push(before);
+                }
+                cycle.reverse();
+                return cycle;
+            }
+            if component.binary_search(&target).is_ok() && !previous.contains_key(&target) {
+                previous.insert(target, item);
+                queue.push_back(target);
+            }
+        }
+    }
+    vec![start]
+}
+
+/// Items an item can't be shown without: its dependencies and,
+/// for a group, the items in it.
+/// Items may show the group they're in, that's not a cycle.
+fn edges<'g>(graph: &'g DependencyGraph, item: &str) -> Vec<&'g str> {
+    let nested = format!("{}.", item);
+    let mut targets: Vec<&str> = graph
+        .dependencies_of(item)
+        .into_iter()
+        .filter(|dependency| {
+            dependency.kind != DependencyKind::Template
+                || !nested.starts_with(&format!("{}.", dependency.to))
+        })
+        .map(|dependency| dependency.to.as_str())
+        .collect();
+    targets.extend(graph.items.iter().map(String::as_str).filter(|child| {
+        child
+            .strip_prefix(&nested)
+            .is_some_and(|name| !name.contains('.'))
+    }));
+    targets.dedup();
+    targets
+}
+
+/// References indexing an item which isn't a list, e.g. `name.1` of a text `name`,
+/// by something other than an integer, or reaching into elements without fields.
+/// An access right after `?` isn't checked, the value may be missing.
+pub fn check_indices(program: &Block) -> Vec<Diagnostic> {
+    let mut references = References::default();
+    references.visit_block(program);
+    references
+        .0
+        .into_iter()
+        .filter_map(|reference| check_index(program, reference))
+        .collect()
+}
+
+/// References of a sequence, `@` references to other sequences are left out
+#[derive(Default)]
+struct References<'b, 'a>(Vec<&'b ReferenceExpression<'a>>);
+
+impl<'b, 'a: 'b> Visitor<'b, 'a> for References<'b, 'a> {
+    fn visit_block(&mut self, block: &'b Block<'a>) {
+        match block.kind {
+            BlockKind::Reference(_) => block
+                .children
+                .iter()
+                .for_each(|child| self.visit_block(child)),
+            _ => walk_block(self, block),
+        }
+    }
+
+    fn visit_reference_expression(&mut self, expression: &'b ReferenceExpression<'a>) {
+        self.0.push(expression);
+        walk_reference_expression(self, expression)
+    }
+}
+
+fn check_index(program: &Block, reference: &ReferenceExpression) -> Option<Diagnostic> {
+    let path = reference_path(reference);
+    let item = find_item(program, &path)?;
+    let item_type = infer_input_type(item_input(item)?);
+    let mut parts = reference.parts().skip(path.len() - 1);
+    let (last, index) = (parts.next()?, parts.next()?);
+    let name = path.join(".");
+    if last.safe {
+        return None;
+    }
+
+    let element = match &item_type {
+        InputType::List(element) => element.as_ref(),
+        InputType::Choice { multiple: true } | InputType::Unknown | InputType::Rule(_) => {
+            return None
+        }
+        _ => {
+            return Some(
+                Diagnostic::error("A0008", format!("`{}` can't be indexed", name))
+                    .with_primary_label(index.name.position, format!("`{}` is {}", name, item_type))
+                    .with_note("lists are `+>` inputs and inputs with `!count`"),
+            )
+        }
+    };
+
+    if let Some((_, expression)) = &index.index {
+        match expression_constant(expression) {
+            None => {}
+            Some(Constant::Number(value)) if value.fract() == 0.0 => {}
+            Some(_) => {
+                return Some(
+                    Diagnostic::error(
+                        "A0008",
+                        format!("`{}` is indexed by `{}`", name, expression_key(expression)),
+                    )
+                    .with_primary_label(index.name.position, "indices are integers"),
+                )
+            }
+        }
+    }
+
+    let field = parts.next()?;
+    let message = if field.is_index() {
+        format!("elements of `{}` can't be indexed", name)
+    } else {
+        format!("elements of `{}` have no `{}`", name, field.name.content)
+    };
+    match element {
+        InputType::Unknown | InputType::Rule(_) => None,
+        _ if index.safe => None,
+        _ => Some(
+            Diagnostic::error("A0008", message)
+                .with_primary_label(field.name.position, format!("`{}` is {}", name, item_type)),
+        ),
+    }
+}
+
+fn check_branches(block: &Block, diagnostics: &mut Vec<Diagnostic>) {
+    let mut preceding_if: Option<Option<bool>> = None;
+    for child in block.children.iter() {
+        if let BlockKind::Invocation(Some(token), expression) = &child.kind {
+            match token.content {
+                "if" => {
+                    let truth = expression.as_ref().and_then(constant_truth);
+                    if truth == Some(false) {
+                        diagnostics.push(unreachable(token, child, "condition is always false"));
+                    }
+                    preceding_if = Some(truth);
+                }
+                "else" => {
+                    if preceding_if == Some(Some(true)) {
+                        diagnostics.push(unreachable(
+                            token,
+                            child,
+                            "condition of the preceding `!if` is always true",
+                        ));
+                    }
+                    preceding_if = None;
+                }
+                "case" => {
+                    if expression.as_ref().and_then(constant_truth) == Some(false)
+                        && !matches!(&block.kind, BlockKind::Invocation(Some(t), Some(_)) if t.content == "switch")
+                    {
+                        diagnostics.push(unreachable(token, child, "condition is always false"));
+                    }
+                }
+                "switch" => check_switch(token, child, diagnostics),
+                _ => preceding_if = None,
+            }
+        } else {
+            preceding_if = None;
+        }
+        check_branches(child, diagnostics);
+    }
+}
+
+fn check_switch(switch: &Token, block: &Block, diagnostics: &mut Vec<Diagnostic>) {
+    let invocations = block.children.iter().filter_map(|child| match &child.kind {
+        BlockKind::Invocation(Some(token), expression) => Some((token, expression)),
+        _ => None,
+    });
+
+    let mut cases: Vec<(String, &Token)> = Vec::new();
+    let mut has_default = false;
+    for (token, expression) in invocations {
+        match token.content {
+            "default" => has_default = true,
+            "case" => {
+                let key = match expression.as_ref().and_then(case_key) {
+                    Some(key) => key,
+                    None => continue,
+                };
+                match cases.iter().find(|(known, _)| *known == key) {
+                    Some((_, first)) => diagnostics.push(
+                        Diagnostic::warning("A0006", format!("duplicated `!case` value `{}`", key))
+                            .with_primary_label(token.position, "this case is never reached")
+                            .with_secondary_label(
+                                first.position,
+                                "same value is matched here first",
+                            ),
+                    ),
+                    None => cases.push((key, token)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if !has_default {
+        diagnostics.push(
+            Diagnostic::warning("A0005", "`!switch` has no `!default`")
+                .with_primary_label(switch.position, "nothing is shown when no case matches")
+                .with_note("add `!default` to handle the remaining values"),
+        );
+    }
+}
+
+fn unreachable(condition: &Token, branch: &Block, reason: &str) -> Diagnostic {
+    let mut diagnostic = Diagnostic::warning(
+        "A0004",
+        format!("`!{}` branch is never shown", condition.content),
+    )
+    .with_primary_label(condition.position, reason);
+    for item in named_children(branch) {
+        if let Some(name) = block_name(item) {
+            diagnostic = diagnostic.with_secondary_label(name.position, "unreachable item");
+        }
+    }
+    diagnostic
+}
+
+/// Value known without collected data
+#[derive(Debug, Clone, PartialEq)]
+enum Constant {
+    Null,
+    Bool(bool),
+    Number(f64),
+    Text(String),
+}
+
+impl Constant {
+    fn truth(&self) -> bool {
+        match self {
+            Constant::Null => false,
+            Constant::Bool(value) => *value,
+            Constant::Number(value) => *value != 0.0,
+            Constant::Text(value) => !value.is_empty(),
+        }
+    }
+}
+
+/// Truth of a condition made of literals only, e.g. `!if:0` or `!if:1 > 2`
+fn constant_truth(expression: &InputExpression) -> Option<bool> {
+    let InputExpression {
+        argument, value, ..
+    } = expression;
+    let constant = match (argument, value) {
+        (_, Some(value)) => value_constant(value)?,
+        _ => return None,
+    };
+    Some(constant.truth())
+}
+
+fn value_constant(value: &ValueType) -> Option<Constant> {
+    match value {
+        ValueType::Literal(token) => literal_constant(token),
+        ValueType::OutputExpression(expression) => expression_constant(expression),
+        ValueType::ReferenceExpression(_) | ValueType::Content(_) => None,
+    }
+}
+
+fn literal_constant(token: &Token) -> Option<Constant> {
+    match token.kind? {
+        TokenKind::Int(value) => Some(Constant::Number(value as f64)),
+        TokenKind::Float(value) => Some(Constant::Number(value)),
+        TokenKind::StringLiteral(_) => Some(Constant::Text(token.value()?.into_owned())),
+        TokenKind::Boolean(value) => Some(Constant::Bool(value)),
+        TokenKind::Null => Some(Constant::Null),
+        _ => None,
+    }
+}
+
+fn member_constant(member: &ExpressionMember) -> Option<Constant> {
+    match member {
+        ExpressionMember::Literal(token) => literal_constant(token),
+        ExpressionMember::OutputExpression(_, Some(expression))
+        | ExpressionMember::Operation(expression) => expression_constant(expression),
+        ExpressionMember::Unary(UnaryOperation::Negate, operand) => match member_constant(operand)?
+        {
+            Constant::Number(value) => Some(Constant::Number(-value)),
+            _ => None,
+        },
+        ExpressionMember::Unary(UnaryOperation::Not, operand) => {
+            Some(Constant::Bool(!member_constant(operand)?.truth()))
+        }
+        _ => None,
+    }
+}
+
+fn expression_constant(expression: &OutputExpression) -> Option<Constant> {
+    let OutputExpression {
+        left: first,
+        operation,
+        right: last,
+    } = expression;
+    let left = member_constant(first)?;
+    let (operation, right) = match (operation, last) {
+        (Some(operation), Some(last)) => (*operation, member_constant(last)?),
+        (None, None) => return Some(left),
+        _ => return None,
+    };
+    match operation {
+        OperationKind::MathOperation(operation) => {
+            let (a, b) = match (&left, &right) {
+                (Constant::Number(a), Constant::Number(b)) => (*a, *b),
+                _ => return None,
+            };
+            let value = match operation {
+                MathOperation::Sum => a + b,
+                MathOperation::Subtract => a - b,
+                MathOperation::Multiply => a * b,
+                MathOperation::Divide => a / b,
+                MathOperation::Reminder => a % b,
+                MathOperation::Power => a.powf(b),
+                MathOperation::AddAssign | MathOperation::SubtractAssign => return None,
+            };
+            Some(Constant::Number(value))
+        }
+        OperationKind::ComparisonOperation(operation) => {
+            let ordering = match (&left, &right) {
+                (Constant::Number(a), Constant::Number(b)) => a.partial_cmp(b)?,
+                (Constant::Text(a), Constant::Text(b)) => a.cmp(b),
+                (Constant::Bool(a), Constant::Bool(b)) => a.cmp(b),
+                _ => return None,
+            };
+            Some(Constant::Bool(match operation {
+                ComparisonOperation::EqCompare => ordering.is_eq(),
+                ComparisonOperation::NEqCompare => ordering.is_ne(),
+                ComparisonOperation::GtCompare => ordering.is_gt(),
+                ComparisonOperation::LsCompare => ordering.is_lt(),
+                ComparisonOperation::GtEqCompare => ordering.is_ge(),
+                ComparisonOperation::LsEqCompare => ordering.is_le(),
+            }))
+        }
+        OperationKind::LogicOperation(operation) => {
+            let (a, b) = (left.truth(), right.truth());
+            Some(Constant::Bool(match operation {
+                LogicOperation::AND => a && b,
+                LogicOperation::OR => a || b,
+                LogicOperation::XOR => a != b,
+                LogicOperation::NAND => !(a && b),
+                LogicOperation::NOR => !(a || b),
+                LogicOperation::XNOR => a == b,
+                LogicOperation::NOT => return None,
+            }))
+        }
+        OperationKind::Assign => None,
+    }
+}
+
+/// Value a `!case` matches, `!case:0` and `!case:case=0` are the same
+fn case_key(expression: &InputExpression) -> Option<String> {
+    let InputExpression {
+        argument, value, ..
+    } = expression;
+    match (argument, value) {
+        (_, Some(value)) => value_key(value),
+        (Some(ArgumentType::Named(token, false)), None) => Some(token.content.to_owned()),
+        _ => None,
+    }
+}
+
+fn value_key(value: &ValueType) -> Option<String> {
+    match value {
+        ValueType::Literal(token) => Some(literal_key(token)),
+        ValueType::ReferenceExpression(reference) => Some(reference_key(reference)),
+        ValueType::OutputExpression(expression) => Some(expression_key(expression)),
+        ValueType::Content(_) => None,
+    }
+}
+
+fn literal_key(token: &Token) -> String {
+    match token.kind {
+        Some(TokenKind::StringLiteral(_)) => format!("{:?}", token.value().unwrap_or_default()),
+        _ => token.content.to_owned(),
+    }
+}
+
+/// Reference as written, `a.b.1`, `a?.b` or `a[i + 1]`
+pub(crate) fn reference_key(reference: &ReferenceExpression) -> String {
+    let mut key = String::new();
+    for part in reference.parts() {
+        match &part.index {
+            Some((_, index)) => key.push_str(&format!("[{}]", expression_key(index))),
+            None if key.is_empty() => key.push_str(part.name.content),
+            None => key.push_str(&format!(".{}", part.name.content)),
+        }
+        if part.safe {
+            key.push('?');
+        }
+    }
+    key
+}
+
+fn expression_key(expression: &OutputExpression) -> String {
+    let OutputExpression {
+        left: first,
+        operation,
+        right: last,
+    } = expression;
+    let mut key = member_key(first);
+    if let Some(operation) = operation {
+        key.push_str(&format!(" {} ", operator(*operation)));
+    }
+    if let Some(last) = last {
+        key.push_str(&member_key(last));
+    }
+    key
+}
+
+fn member_key(member: &ExpressionMember) -> String {
+    match member {
+        ExpressionMember::Literal(token) => literal_key(token),
+        ExpressionMember::ReferenceExpression(reference) => reference_key(reference),
+        ExpressionMember::OutputExpression(_, Some(expression))
+        | ExpressionMember::Operation(expression) => {
+            format!("({})", expression_key(expression))
+        }
+        ExpressionMember::Unary(UnaryOperation::Negate, operand) => {
+            format!("-{}", member_key(operand))
+        }
+        ExpressionMember::Unary(UnaryOperation::Not, operand) => {
+            format!("!{}", member_key(operand))
+        }
+        ExpressionMember::List(_, items) => {
+            let items: Vec<String> = items.iter().map(expression_key).collect();
+            format!("[{}]", items.join(", "))
+        }
+        ExpressionMember::Map(_, entries) => {
+            let entries: Vec<String> = entries
+                .iter()
+                .filter_map(|entry| match (&entry.key, &entry.value) {
+                    (Some(key), Some(value)) => {
+                        Some(format!("{}: {}", literal_key(key), expression_key(value)))
+                    }
+                    _ => None,
+                })
+                .collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+        _ => String::new(),
+    }
+}
+
+fn operator(operation: OperationKind) -> &'static str {
+    match operation {
+        OperationKind::Assign => "=",
+        OperationKind::MathOperation(operation) => match operation {
+            MathOperation::Sum => "+",
+            MathOperation::Subtract => "-",
+            MathOperation::Divide => "/",
+            MathOperation::Reminder => "%",
+            MathOperation::Multiply => "*",
+            MathOperation::Power => "^",
+            MathOperation::AddAssign => "+=",
+            MathOperation::SubtractAssign => "-=",
+        },
+        OperationKind::ComparisonOperation(operation) => match operation {
+            ComparisonOperation::EqCompare => "==",
+            ComparisonOperation::GtCompare => ">",
+            ComparisonOperation::LsCompare => "<",
+            ComparisonOperation::GtEqCompare => ">=",
+            ComparisonOperation::LsEqCompare => "<=",
+            ComparisonOperation::NEqCompare => "!=",
+        },
+        OperationKind::LogicOperation(operation) => match operation {
+            LogicOperation::AND => "&",
+            LogicOperation::OR => "|",
+            LogicOperation::NOT => "!",
+            LogicOperation::NAND => "!&",
+            LogicOperation::NOR => "!!",
+            LogicOperation::XOR => "||",
+            LogicOperation::XNOR => "!|",
+        },
+    }
+}
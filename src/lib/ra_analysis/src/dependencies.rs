@@ -0,0 +1,350 @@
+use super::items::{block_name, is_declaration, item_paths, reference_path};
+use super::types::item_input;
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::content::ContentBlockMember;
+use ra_parser::expressions::input_expression::{ArgumentType, InputExpression, ValueType};
+use ra_parser::expressions::output_expression::{ExpressionMember, OutputExpression};
+use ra_parser::expressions::reference_expression::ReferenceExpression;
+use std::collections::BTreeSet;
+
+/// Why an item depends on another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum DependencyKind {
+    /// Item is shown under `!if`, `!else`, `!switch` or `!case`
+    Condition,
+    /// Options of the item come from another item
+    Options,
+    /// Item is repeated by `!iterate`
+    Iterate,
+    /// Content of the item shows another item `{{other}}`
+    Template,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Dependency {
+    pub from: String,
+    pub to: String,
+    pub kind: DependencyKind,
+}
+
+/// Items which have to be answered before others can be shown.
+///
+/// References are resolved from the innermost enclosing item outwards,
+/// ones pointing outside of the sequence, e.g. `@resources.countries`,
+/// are left out.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DependencyGraph {
+    /// Items in document order
+    pub items: Vec<String>,
+    /// Items accepting input in document order
+    pub fields: Vec<String>,
+    pub dependencies: Vec<Dependency>,
+}
+
+/// Group of fields answered together
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Step {
+    pub fields: Vec<String>,
+}
+
+impl DependencyGraph {
+    pub fn new(program: &Block) -> Self {
+        let paths = item_paths(program);
+        let mut graph = Self {
+            items: paths.iter().map(|(path, _)| path.clone()).collect(),
+            fields: paths
+                .iter()
+                .filter(|(_, item)| item_input(item).is_some())
+                .map(|(path, _)| path.clone())
+                .collect(),
+            dependencies: Vec::new(),
+        };
+        let mut found = BTreeSet::new();
+        graph.collect(program, "", &[], &mut found);
+        graph.dependencies = found.into_iter().collect();
+        graph
+    }
+
+    /// What `item` directly depends on
+    pub fn dependencies_of(&self, item: &str) -> Vec<&Dependency> {
+        self.dependencies
+            .iter()
+            .filter(|dependency| dependency.from == item)
+            .collect()
+    }
+
+    /// What directly depends on `item`
+    pub fn dependents_of(&self, item: &str) -> Vec<&Dependency> {
+        self.dependencies
+            .iter()
+            .filter(|dependency| dependency.to == item)
+            .collect()
+    }
+
+    /// Fields which have to be answered before `item` can be shown.
+    ///
+    /// Depending on a group means depending on every field in it,
+    /// dependencies of groups and items without input are followed through.
+    pub fn field_dependencies(&self, item: &str) -> BTreeSet<String> {
+        let mut fields = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        // items are shown within their groups
+        let mut pending: Vec<&str> = item
+            .match_indices('.')
+            .map(|(index, _)| &item[..index])
+            .chain(Some(item))
+            .collect();
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current.to_owned()) {
+                continue;
+            }
+            for dependency in self.dependencies_of(current) {
+                let target = dependency.to.as_str();
+                let nested = format!("{}.", target);
+                for field in self.fields.iter() {
+                    if field == target || field.starts_with(&nested) {
+                        fields.insert(field.clone());
+                    }
+                }
+                if !self.fields.iter().any(|field| field == target) {
+                    pending.push(target);
+                }
+            }
+        }
+        fields.remove(item);
+        fields
+    }
+
+    /// Splits fields into steps in document order,
+    /// a field starts a new step when it depends on one in the current step.
+    ///
+    /// Dependencies on later fields can't be answered by earlier steps
+    /// and don't split them.
+    pub fn steps(&self) -> Vec<Step> {
+        let mut steps: Vec<Step> = Vec::new();
+        let mut current: Vec<String> = Vec::new();
+        for field in self.fields.iter() {
+            let dependencies = self.field_dependencies(field);
+            if current
+                .iter()
+                .any(|answered| dependencies.contains(answered))
+            {
+                steps.push(Step {
+                    fields: std::mem::take(&mut current),
+                });
+            }
+            current.push(field.clone());
+        }
+        if !current.is_empty() {
+            steps.push(Step { fields: current });
+        }
+        steps
+    }
+
+    /// Index of the step a field is asked in
+    pub fn step_of(&self, field: &str) -> Option<usize> {
+        self.steps()
+            .iter()
+            .position(|step| step.fields.iter().any(|f| f == field))
+    }
+
+    fn collect(
+        &self,
+        block: &Block,
+        scope: &str,
+        inherited: &[(String, DependencyKind)],
+        found: &mut BTreeSet<Dependency>,
+    ) {
+        let mut preceding_condition: Vec<String> = Vec::new();
+        for child in block.children.iter() {
+            let mut inherited = inherited.to_vec();
+            let mut scope = scope.to_owned();
+            match &child.kind {
+                BlockKind::Invocation(Some(token), expression) => {
+                    let references = || {
+                        expression
+                            .as_ref()
+                            .map(|expression| {
+                                self.resolve_all(&input_references(expression), &scope)
+                            })
+                            .unwrap_or_default()
+                    };
+                    match token.content {
+                        "if" | "switch" | "case" => {
+                            preceding_condition = references();
+                            inherited.extend(
+                                preceding_condition
+                                    .iter()
+                                    .map(|target| (target.clone(), DependencyKind::Condition)),
+                            );
+                        }
+                        "else" => inherited.extend(
+                            preceding_condition
+                                .iter()
+                                .map(|target| (target.clone(), DependencyKind::Condition)),
+                        ),
+                        "iterate" => inherited.extend(
+                            references()
+                                .into_iter()
+                                .map(|target| (target, DependencyKind::Iterate)),
+                        ),
+                        "options" => {
+                            let mut options = references();
+                            options.extend(
+                                self.resolve_all(
+                                    &child
+                                        .children
+                                        .iter()
+                                        .filter_map(|option| match &option.kind {
+                                            BlockKind::Reference(Some(reference)) => {
+                                                Some(reference_path(reference))
+                                            }
+                                            _ => None,
+                                        })
+                                        .collect::<Vec<_>>(),
+                                    &scope,
+                                ),
+                            );
+                            for target in options {
+                                self.add(&scope, target, DependencyKind::Options, found);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                BlockKind::Content(content) => {
+                    let templates: Vec<Vec<&str>> = content
+                        .members()
+                        .into_iter()
+                        .flat_map(|member| match member {
+                            ContentBlockMember::Template(reference) => {
+                                reference_references(reference)
+                            }
+                            ContentBlockMember::Body(_) => Vec::new(),
+                        })
+                        .collect();
+                    for target in self.resolve_all(&templates, &scope) {
+                        self.add(&scope, target, DependencyKind::Template, found);
+                    }
+                }
+                _ => {}
+            }
+
+            if let Some(name) = block_name(child).filter(|_| !is_declaration(child)) {
+                scope = if scope.is_empty() {
+                    name.content.to_owned()
+                } else {
+                    format!("{}.{}", scope, name.content)
+                };
+                for (target, kind) in inherited.iter() {
+                    self.add(&scope, target.clone(), *kind, found);
+                }
+            }
+            self.collect(child, &scope, &inherited, found);
+        }
+    }
+
+    /// Items may show their own value, any other dependency on itself is a cycle
+    fn add(&self, from: &str, to: String, kind: DependencyKind, found: &mut BTreeSet<Dependency>) {
+        if !from.is_empty() && (from != to || kind != DependencyKind::Template) {
+            found.insert(Dependency {
+                from: from.to_owned(),
+                to,
+                kind,
+            });
+        }
+    }
+
+    fn resolve_all(&self, references: &[Vec<&str>], scope: &str) -> Vec<String> {
+        references
+            .iter()
+            .filter_map(|path| self.resolve(path, scope))
+            .collect()
+    }
+
+    /// Longest known item the path starts with,
+    /// trailing segments like `.count` aggregate the item
+    fn resolve(&self, path: &[&str], scope: &str) -> Option<String> {
+        let mut prefix = scope;
+        loop {
+            for length in (1..=path.len()).rev() {
+                let name = path[..length].join(".");
+                let candidate = if prefix.is_empty() {
+                    name
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                if self.items.contains(&candidate) {
+                    return Some(candidate);
+                }
+            }
+            if prefix.is_empty() {
+                return None;
+            }
+            prefix = prefix.rsplit_once('.').map_or("", |(parent, _)| parent);
+        }
+    }
+}
+
+/// Reference paths in arguments, bare names like `!options:countries` included
+fn input_references<'a>(expression: &InputExpression<'a>) -> Vec<Vec<&'a str>> {
+    let InputExpression {
+        argument,
+        value,
+        next,
+    } = expression;
+    let mut references = Vec::new();
+    if let Some(ArgumentType::Named(token, false)) = argument {
+        references.push(vec![token.content]);
+    }
+    match value {
+        Some(ValueType::ReferenceExpression(reference)) => {
+            references.extend(reference_references(reference))
+        }
+        Some(ValueType::OutputExpression(expression)) => {
+            references.extend(output_references(expression))
+        }
+        _ => {}
+    }
+    if let Some(next) = next {
+        references.extend(input_references(next));
+    }
+    references
+}
+
+/// Reference paths in an expression, e.g. `a.count > b`
+pub fn output_references<'a>(expression: &OutputExpression<'a>) -> Vec<Vec<&'a str>> {
+    let OutputExpression {
+        left: first,
+        right: last,
+        ..
+    } = expression;
+    let mut references = member_references(first);
+    if let Some(last) = last {
+        references.extend(member_references(last));
+    }
+    references
+}
+
+/// Path of a reference and the references in its indices, `b` of `a[b + 1]`
+fn reference_references<'a>(reference: &ReferenceExpression<'a>) -> Vec<Vec<&'a str>> {
+    let mut references = vec![reference_path(reference)];
+    references.extend(reference.indices().flat_map(output_references));
+    references
+}
+
+fn member_references<'a>(member: &ExpressionMember<'a>) -> Vec<Vec<&'a str>> {
+    match member {
+        ExpressionMember::ReferenceExpression(reference) => reference_references(reference),
+        ExpressionMember::OutputExpression(_, Some(expression))
+        | ExpressionMember::Operation(expression) => output_references(expression),
+        ExpressionMember::Unary(_, operand) => member_references(operand),
+        ExpressionMember::List(_, items) => items.iter().flat_map(output_references).collect(),
+        ExpressionMember::Map(_, entries) => entries
+            .iter()
+            .filter_map(|entry| entry.value.as_ref())
+            .flat_map(output_references)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,165 @@
+//! Lowering of the surface syntax into the core forms.
+//!
+//! Several constructs are shorthands for others:
+//!
+//! - `!case:0` is `!case:case=0`, a rule's first ordered argument is named after the rule
+//! - `> :default=6` is `>` with `!default:default=6`
+//! - `+>` is `>` with `!count`
+//!
+//! Passes rewrite a tree in place, once every pass ran it only has the core forms:
+//! invocation arguments are named, inputs are `>` with no `:default`
+//! and list inputs are the ones with a `!count` rule.
+//! Synthesized tokens point to the source they were lowered from.
+
+use ra_lexer::token::{Token, TokenKind};
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::input_expression::{ArgumentType, InputExpression};
+use ra_parser::visit::{walk_block_mut, VisitorMut};
+
+/// Rewrite of a tree
+pub trait Pass<'a> {
+    fn run(&mut self, program: &mut Block<'a>);
+}
+
+impl<'a, V: VisitorMut<'a>> Pass<'a> for V {
+    fn run(&mut self, program: &mut Block<'a>) {
+        self.visit_block_mut(program)
+    }
+}
+
+/// Passes lowering every shorthand, in the order they run
+pub fn passes<'a>() -> Vec<Box<dyn Pass<'a>>> {
+    vec![
+        Box::new(OrderedArguments),
+        Box::new(DefaultArguments),
+        Box::new(ListInputs),
+    ]
+}
+
+/// Lowers a tree into the core forms
+pub fn desugar(program: &mut Block) {
+    for mut pass in passes() {
+        pass.run(program);
+    }
+}
+
+/// `!case:0` into `!case:case=0`
+pub struct OrderedArguments;
+
+impl<'a> VisitorMut<'a> for OrderedArguments {
+    fn visit_block_mut(&mut self, block: &mut Block<'a>) {
+        if let BlockKind::Invocation(Some(rule), Some(expression)) = &mut block.kind {
+            if let Some(ArgumentType::Ordered(0)) = expression.argument {
+                if expression.value.is_some() {
+                    expression.argument = Some(ArgumentType::Named(*rule, true));
+                }
+            }
+        }
+        walk_block_mut(self, block)
+    }
+}
+
+/// `> :default=6` into `>` with `!default:default=6`
+pub struct DefaultArguments;
+
+impl<'a> VisitorMut<'a> for DefaultArguments {
+    fn visit_block_mut(&mut self, block: &mut Block<'a>) {
+        if let BlockKind::Input(multiple, Some(expression)) = &block.kind {
+            let (defaults, rest): (Vec<_>, Vec<_>) = unlink(expression.clone())
+                .into_iter()
+                .partition(|argument| match &argument.argument {
+                    Some(ArgumentType::Named(token, true)) => {
+                        token.content == "default" && argument.value.is_some()
+                    }
+                    _ => false,
+                });
+            if let Some(default) = defaults.into_iter().next() {
+                let name = match &default.argument {
+                    Some(ArgumentType::Named(token, _)) => *token,
+                    _ => unreachable!(),
+                };
+                let rule = invocation(block, name, Some(default));
+                block.kind = BlockKind::Input(*multiple, link(rest));
+                block.children.insert(0, rule);
+            }
+        }
+        walk_block_mut(self, block)
+    }
+}
+
+/// `+>` into `>` with `!count`
+pub struct ListInputs;
+
+impl<'a> VisitorMut<'a> for ListInputs {
+    fn visit_block_mut(&mut self, block: &mut Block<'a>) {
+        if let BlockKind::Input(true, expression) = &block.kind {
+            let has_count = block.children.iter().any(|child| match &child.kind {
+                BlockKind::Invocation(Some(rule), _) => rule.content == "count",
+                _ => false,
+            });
+            let plus = block.first_token;
+            block.kind = BlockKind::Input(false, expression.clone());
+            if !has_count {
+                let count = synthesized(&plus, TokenKind::Identifier("count"), "count");
+                let rule = invocation(block, count, None);
+                block.children.push(rule);
+            }
+        }
+        walk_block_mut(self, block)
+    }
+}
+
+/// Token of a construct lowered from the one at `origin`
+fn synthesized<'a>(origin: &Token<'a>, kind: TokenKind<'a>, content: &'a str) -> Token<'a> {
+    Token {
+        kind: Some(kind),
+        content,
+        ..*origin
+    }
+}
+
+/// Invocation `!rule:arguments` as a child of a block
+fn invocation<'a>(
+    parent: &Block<'a>,
+    rule: Token<'a>,
+    expression: Option<InputExpression<'a>>,
+) -> Block<'a> {
+    let mut first_token = synthesized(&rule, TokenKind::Exclamation, "!");
+    first_token.level = parent.first_token.level + 1;
+    Block {
+        kind: BlockKind::Invocation(Some(rule), expression),
+        children: vec![],
+        span: rule.span,
+        first_token,
+    }
+}
+
+/// Arguments of an expression, each one on its own
+fn unlink(expression: InputExpression) -> Vec<InputExpression> {
+    let mut arguments = vec![];
+    let mut next = Some(Box::new(expression));
+    while let Some(argument) = next {
+        let InputExpression {
+            argument,
+            value,
+            next: rest,
+        } = *argument;
+        arguments.push(InputExpression {
+            argument,
+            value,
+            next: None,
+        });
+        next = rest;
+    }
+    arguments
+}
+
+/// Expression of arguments in order
+fn link<'a>(arguments: Vec<InputExpression<'a>>) -> Option<InputExpression<'a>> {
+    arguments.into_iter().rev().fold(None, |next, argument| {
+        Some(InputExpression {
+            next: next.map(Box::new),
+            ..argument
+        })
+    })
+}
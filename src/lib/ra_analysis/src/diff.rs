@@ -0,0 +1,457 @@
+use super::items::{block_name, item_paths, named_children};
+use super::types::{infer_input_type, input_rules, item_input, Argument, InputType};
+use ra_parser::block::Block;
+use std::fmt;
+
+/// How rules of an input changed between versions
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum RulesChange {
+    /// Values accepted before may be rejected now
+    Tightened,
+    /// Everything accepted before is still accepted
+    Loosened,
+    Changed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ItemChange {
+    Added(String),
+    Removed(String),
+    Renamed {
+        from: String,
+        to: String,
+    },
+    InputTypeChanged {
+        path: String,
+        from: InputType,
+        to: InputType,
+    },
+    RulesChanged {
+        path: String,
+        change: RulesChange,
+        from: Vec<String>,
+        to: Vec<String>,
+    },
+}
+
+impl fmt::Display for ItemChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemChange::Added(path) => write!(f, "+ {}", path),
+            ItemChange::Removed(path) => write!(f, "- {}", path),
+            ItemChange::Renamed { from, to } => write!(f, "~ {} renamed to {}", from, to),
+            ItemChange::InputTypeChanged { path, from, to } => {
+                write!(f, "~ {} input changed from {} to {}", path, from, to)
+            }
+            ItemChange::RulesChanged {
+                path,
+                change,
+                from,
+                to,
+            } => {
+                let change = match change {
+                    RulesChange::Tightened => "tightened",
+                    RulesChange::Loosened => "loosened",
+                    RulesChange::Changed => "changed",
+                };
+                write!(
+                    f,
+                    "~ {} rules {}: [{}] -> [{}]",
+                    path,
+                    change,
+                    from.join(" "),
+                    to.join(" ")
+                )
+            }
+        }
+    }
+}
+
+/// Stored field which can't be used as is
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Conversion {
+    pub from: String,
+    pub to: String,
+    pub reason: String,
+}
+
+/// What happens to data stored with the old version of a sequence
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct MigrationReport {
+    /// Old and new paths of fields whose values still fit
+    pub compatible: Vec<(String, String)>,
+    /// Fields which no longer exist
+    pub orphaned: Vec<String>,
+    pub conversions: Vec<Conversion>,
+    /// Fields without any stored values
+    pub added: Vec<String>,
+}
+
+impl fmt::Display for MigrationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "compatible:")?;
+        for (from, to) in self.compatible.iter() {
+            if from == to {
+                writeln!(f, "    {}", from)?;
+            } else {
+                writeln!(f, "    {} -> {}", from, to)?;
+            }
+        }
+        writeln!(f, "orphaned:")?;
+        for path in self.orphaned.iter() {
+            writeln!(f, "    {}", path)?;
+        }
+        writeln!(f, "needs conversion:")?;
+        for conversion in self.conversions.iter() {
+            writeln!(
+                f,
+                "    {} -> {}: {}",
+                conversion.from, conversion.to, conversion.reason
+            )?;
+        }
+        writeln!(f, "added:")?;
+        for path in self.added.iter() {
+            writeln!(f, "    {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct SequenceDiff {
+    pub changes: Vec<ItemChange>,
+    pub migration: MigrationReport,
+}
+
+impl fmt::Display for SequenceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            writeln!(f, "{}", change)?;
+        }
+        if !self.changes.is_empty() {
+            writeln!(f)?;
+        }
+        write!(f, "{}", self.migration)
+    }
+}
+
+/// Item of a sequence reduced to what matters for stored data
+#[derive(Debug, PartialEq)]
+struct ItemSummary {
+    path: String,
+    input: Option<InputType>,
+    rules: Vec<String>,
+    children: Vec<String>,
+}
+
+impl ItemSummary {
+    fn new(path: String, item: &Block) -> Self {
+        let input = item_input(item);
+        Self {
+            path,
+            input: input.map(infer_input_type),
+            rules: input
+                .map(|input| {
+                    input_rules(input)
+                        .into_iter()
+                        .map(|(name, arguments)| rule_to_string(name, &arguments))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            children: named_children(item)
+                .into_iter()
+                .filter_map(block_name)
+                .map(|token| token.content.to_owned())
+                .collect(),
+        }
+    }
+
+    fn parent(&self) -> &str {
+        parent(&self.path)
+    }
+
+    /// Same input and at least some of the children in common
+    fn same_shape(&self, other: &ItemSummary) -> bool {
+        self.input == other.input
+            && self.rules == other.rules
+            && (self.children == other.children
+                || self
+                    .children
+                    .iter()
+                    .any(|child| other.children.contains(child)))
+    }
+}
+
+fn parent(path: &str) -> &str {
+    path.rfind('.').map_or("", |dot| &path[..dot])
+}
+
+fn summaries(program: &Block) -> Vec<ItemSummary> {
+    item_paths(program)
+        .into_iter()
+        .map(|(path, item)| ItemSummary::new(path, item))
+        .collect()
+}
+
+/// Structural difference of two versions of a sequence.
+///
+/// Items are matched by path, an item which disappeared is
+/// considered renamed when an item of the same shape appeared
+/// next to it, paths of its descendants follow the rename.
+pub fn diff(old: &Block, new: &Block) -> SequenceDiff {
+    let old_items = summaries(old);
+    let new_items = summaries(new);
+    let mut matched = vec![false; new_items.len()];
+    let mut renames: Vec<(String, String)> = Vec::new();
+    let mut result = SequenceDiff::default();
+
+    for old_item in old_items.iter() {
+        let path = renamed_path(&old_item.path, &renames);
+        let found = new_items
+            .iter()
+            .position(|item| item.path == path)
+            .filter(|index| !matched[*index]);
+
+        let index = match found {
+            Some(index) => index,
+            None => {
+                let parent = parent(&path);
+                let candidate = new_items.iter().enumerate().position(|(index, item)| {
+                    !matched[index]
+                        && item.parent() == parent
+                        && item.same_shape(old_item)
+                        && !old_items
+                            .iter()
+                            .any(|old| renamed_path(&old.path, &renames) == item.path)
+                });
+                match candidate {
+                    Some(index) => {
+                        result.changes.push(ItemChange::Renamed {
+                            from: old_item.path.clone(),
+                            to: new_items[index].path.clone(),
+                        });
+                        renames.push((path, new_items[index].path.clone()));
+                        index
+                    }
+                    None => {
+                        result
+                            .changes
+                            .push(ItemChange::Removed(old_item.path.clone()));
+                        if old_item.input.is_some() {
+                            result.migration.orphaned.push(old_item.path.clone());
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+
+        matched[index] = true;
+        compare(old_item, &new_items[index], &mut result);
+    }
+
+    for (index, item) in new_items.iter().enumerate() {
+        if !matched[index] {
+            result.changes.push(ItemChange::Added(item.path.clone()));
+            if item.input.is_some() {
+                result.migration.added.push(item.path.clone());
+            }
+        }
+    }
+
+    result
+}
+
+fn renamed_path(path: &str, renames: &[(String, String)]) -> String {
+    let mut path = path.to_owned();
+    for (from, to) in renames.iter() {
+        if path == *from {
+            path = to.clone();
+        } else if path.starts_with(&format!("{}.", from)) {
+            path = format!("{}{}", to, &path[from.len()..]);
+        }
+    }
+    path
+}
+
+fn compare(old: &ItemSummary, new: &ItemSummary, result: &mut SequenceDiff) {
+    let (old_input, new_input) = match (&old.input, &new.input) {
+        (Some(old_input), Some(new_input)) => (old_input, new_input),
+        (Some(_), None) => {
+            result.migration.orphaned.push(old.path.clone());
+            return;
+        }
+        (None, Some(_)) => {
+            result.migration.added.push(new.path.clone());
+            return;
+        }
+        (None, None) => return,
+    };
+
+    let mut reason = None;
+    if !same_input(old_input, new_input) {
+        result.changes.push(ItemChange::InputTypeChanged {
+            path: new.path.clone(),
+            from: old_input.clone(),
+            to: new_input.clone(),
+        });
+        reason = Some(format!("input changed from {} to {}", old_input, new_input));
+    }
+
+    if old.rules != new.rules {
+        let change = rules_change(&old.rules, &new.rules);
+        result.changes.push(ItemChange::RulesChanged {
+            path: new.path.clone(),
+            change,
+            from: old.rules.clone(),
+            to: new.rules.clone(),
+        });
+        if change != RulesChange::Loosened && reason.is_none() {
+            reason = Some(match change {
+                RulesChange::Tightened => "rules tightened".to_owned(),
+                _ => "rules changed".to_owned(),
+            });
+        }
+    }
+
+    match reason {
+        Some(reason) => result.migration.conversions.push(Conversion {
+            from: old.path.clone(),
+            to: new.path.clone(),
+            reason,
+        }),
+        None => result
+            .migration
+            .compatible
+            .push((old.path.clone(), new.path.clone())),
+    }
+}
+
+/// Inputs are plain text by default
+fn same_input(old: &InputType, new: &InputType) -> bool {
+    fn plain(input: &InputType) -> &InputType {
+        match input {
+            InputType::Unknown => &InputType::Text,
+            input => input,
+        }
+    }
+    plain(old) == plain(new)
+}
+
+/// Rules which don't restrict accepted values
+const NEUTRAL_RULES: &[&str] = &[
+    "text", "default", "if", "else", "switch", "case", "iterate", "sort",
+];
+
+fn rules_change(old: &[String], new: &[String]) -> RulesChange {
+    let restricting = |rules: &[String]| -> Vec<String> {
+        rules
+            .iter()
+            .filter(|rule| !NEUTRAL_RULES.contains(&rule_name(rule)))
+            .cloned()
+            .collect()
+    };
+    let old = restricting(old);
+    let new = restricting(new);
+
+    let mut tightened = false;
+    let mut loosened = false;
+    for rule in new.iter() {
+        match old.iter().find(|old| rule_name(old) == rule_name(rule)) {
+            None => tightened = true,
+            Some(old) if old != rule => match limit_change(old, rule) {
+                Some(RulesChange::Tightened) => tightened = true,
+                Some(RulesChange::Loosened) => loosened = true,
+                _ => return RulesChange::Changed,
+            },
+            Some(_) => {}
+        }
+    }
+    if old
+        .iter()
+        .any(|old| !new.iter().any(|rule| rule_name(rule) == rule_name(old)))
+    {
+        loosened = true;
+    }
+
+    match (tightened, loosened) {
+        (true, false) => RulesChange::Tightened,
+        (false, _) => RulesChange::Loosened,
+        (true, true) => RulesChange::Changed,
+    }
+}
+
+/// Compares numeric limits like `!maxLength:10` or `!number:min=0`
+fn limit_change(old: &str, new: &str) -> Option<RulesChange> {
+    let old_limits = limits(old);
+    let new_limits = limits(new);
+    if old_limits.len() != new_limits.len() {
+        return None;
+    }
+
+    let mut tightened = false;
+    let mut loosened = false;
+    for ((old_name, old_value), (new_name, new_value)) in old_limits.iter().zip(new_limits.iter()) {
+        if old_name != new_name {
+            return None;
+        }
+        let name = if old_name.is_empty() {
+            rule_name(old)
+        } else {
+            old_name.as_str()
+        };
+        let (old_value, new_value) = match (old_value, new_value) {
+            (Some(old_value), Some(new_value)) => (old_value, new_value),
+            _ if old_value == new_value => continue,
+            _ => return None,
+        };
+        let upper = name.starts_with("max");
+        let lower = name.starts_with("min");
+        if new_value == old_value {
+            continue;
+        } else if (upper && new_value < old_value) || (lower && new_value > old_value) {
+            tightened = true;
+        } else if upper || lower {
+            loosened = true;
+        } else {
+            return None;
+        }
+    }
+
+    match (tightened, loosened) {
+        (true, false) => Some(RulesChange::Tightened),
+        (false, true) => Some(RulesChange::Loosened),
+        _ => None,
+    }
+}
+
+fn limits(rule: &str) -> Vec<(String, Option<f64>)> {
+    rule.split(':')
+        .skip(1)
+        .map(|argument| match argument.split_once('=') {
+            Some((name, value)) => (name.to_owned(), value.parse().ok()),
+            None => (String::new(), argument.parse().ok()),
+        })
+        .collect()
+}
+
+fn rule_name(rule: &str) -> &str {
+    rule.trim_start_matches('!')
+        .split(':')
+        .next()
+        .unwrap_or_default()
+}
+
+fn rule_to_string(name: &str, arguments: &[Argument]) -> String {
+    let mut rule = format!("!{}", name);
+    for argument in arguments.iter() {
+        rule.push(':');
+        match (&argument.name, &argument.value) {
+            (Some(name), Some(value)) => rule.push_str(&format!("{}={}", name, value)),
+            (Some(name), None) => rule.push_str(name),
+            (None, Some(value)) => rule.push_str(value),
+            (None, None) => {}
+        }
+    }
+    rule
+}
@@ -0,0 +1,51 @@
+use super::items::item_paths;
+use super::types::{input_rules, item_input};
+use ra_parser::block::{Block, BlockKind};
+
+/// Rule marking an item as providing client identity
+pub const IDENTITY_RULE: &str = "identity";
+
+/// Item is marked with `!identity` either directly
+/// or on its input
+pub fn is_identity_item(item: &Block) -> bool {
+    let direct = item.children.iter().any(|child| match &child.kind {
+        BlockKind::Invocation(Some(token), _) => token.content == IDENTITY_RULE,
+        _ => false,
+    });
+    direct
+        || item_input(item).is_some_and(|input| {
+            input_rules(input)
+                .iter()
+                .any(|(name, _)| *name == IDENTITY_RULE)
+        })
+}
+
+/// Paths of identity items and items nested in them
+pub fn identity_items(program: &Block) -> Vec<String> {
+    let items = item_paths(program);
+    let marked: Vec<&String> = items
+        .iter()
+        .filter(|(_, item)| is_identity_item(item))
+        .map(|(path, _)| path)
+        .collect();
+    items
+        .iter()
+        .map(|(path, _)| path)
+        .filter(|path| {
+            marked
+                .iter()
+                .any(|marked| *path == *marked || path.starts_with(&format!("{}.", marked)))
+        })
+        .cloned()
+        .collect()
+}
+
+/// Identity items accepting input, their values identify a client
+pub fn identity_fields(program: &Block) -> Vec<String> {
+    let identity = identity_items(program);
+    item_paths(program)
+        .into_iter()
+        .filter(|(path, item)| identity.contains(path) && item_input(item).is_some())
+        .map(|(path, _)| path)
+        .collect()
+}
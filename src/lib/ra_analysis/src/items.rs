@@ -0,0 +1,118 @@
+use ra_lexer::token::Token;
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::reference_expression::ReferenceExpression;
+
+/// Token naming an item `name` or a declaration `:name`
+pub fn block_name<'b, 'a>(block: &'b Block<'a>) -> Option<&'b Token<'a>> {
+    block.name()
+}
+
+pub fn is_declaration(block: &Block) -> bool {
+    matches!(block.kind, BlockKind::Declaration(Some(_)))
+}
+
+/// All items and declarations in document order
+pub fn named_blocks<'b, 'a>(block: &'b Block<'a>) -> Vec<&'b Block<'a>> {
+    let mut blocks = Vec::new();
+    for child in block.children.iter() {
+        if block_name(child).is_some() {
+            blocks.push(child);
+        }
+        blocks.extend(named_blocks(child));
+    }
+    blocks
+}
+
+/// Closest named descendants,
+/// unnamed blocks like inputs or conditions are looked through
+pub fn named_children<'b, 'a>(block: &'b Block<'a>) -> Vec<&'b Block<'a>> {
+    let mut blocks = Vec::new();
+    for child in block.children.iter() {
+        if block_name(child).is_some() {
+            blocks.push(child);
+        } else {
+            blocks.extend(named_children(child));
+        }
+    }
+    blocks
+}
+
+/// Items with their paths `a.b.c` in document order,
+/// declarations are left out
+pub fn item_paths<'b, 'a>(root: &'b Block<'a>) -> Vec<(String, &'b Block<'a>)> {
+    let mut items = Vec::new();
+    collect_paths(root, "", &mut items);
+    items
+}
+
+fn collect_paths<'b, 'a>(
+    block: &'b Block<'a>,
+    prefix: &str,
+    items: &mut Vec<(String, &'b Block<'a>)>,
+) {
+    for child in named_children(block) {
+        if is_declaration(child) {
+            continue;
+        }
+        let name = block_name(child)
+            .map(|token| token.content)
+            .unwrap_or_default();
+        let path = if prefix.is_empty() {
+            name.to_owned()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        items.push((path.clone(), child));
+        collect_paths(child, &path, items);
+    }
+}
+
+/// Finds an item by its path `a.b.c`.
+///
+/// The first segment may be anywhere in the program,
+/// the following ones are looked up among named children.
+pub fn find_item<'b, 'a>(root: &'b Block<'a>, path: &[&str]) -> Option<&'b Block<'a>> {
+    let (first, rest) = path.split_first()?;
+    named_blocks(root)
+        .into_iter()
+        .filter(|block| name_is(block, first))
+        .find_map(|block| resolve(block, rest))
+}
+
+/// Finds a rule declaration `:name`
+pub fn find_declaration<'b, 'a>(root: &'b Block<'a>, name: &str) -> Option<&'b Block<'a>> {
+    named_blocks(root)
+        .into_iter()
+        .find(|block| is_declaration(block) && name_is(block, name))
+}
+
+/// Named block whose name token contains `offset`
+pub fn named_block_at<'b, 'a>(root: &'b Block<'a>, offset: usize) -> Option<&'b Block<'a>> {
+    named_blocks(root)
+        .into_iter()
+        .find(|block| block_name(block).is_some_and(|token| token.span.contains(offset)))
+}
+
+/// Segments of a reference `a.b.c` up to its first index,
+/// `a.b[0].c` and `a.b.1` are paths into the value of `a.b`
+pub fn reference_path<'a>(reference: &ReferenceExpression<'a>) -> Vec<&'a str> {
+    reference
+        .parts()
+        .take_while(|part| !part.is_index())
+        .map(|part| part.name.content)
+        .collect()
+}
+
+fn name_is(block: &Block, name: &str) -> bool {
+    block_name(block).is_some_and(|token| token.content == name)
+}
+
+fn resolve<'b, 'a>(block: &'b Block<'a>, path: &[&str]) -> Option<&'b Block<'a>> {
+    match path.split_first() {
+        None => Some(block),
+        Some((first, rest)) => named_children(block)
+            .into_iter()
+            .filter(|child| name_is(child, first))
+            .find_map(|child| resolve(child, rest)),
+    }
+}
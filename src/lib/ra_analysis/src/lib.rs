@@ -0,0 +1,20 @@
+#[macro_use]
+extern crate serde_derive;
+
+extern crate serde;
+
+pub mod assignments;
+pub mod builtins;
+pub mod bundle;
+pub mod checks;
+pub mod dependencies;
+pub mod desugar;
+pub mod diff;
+pub mod identity;
+pub mod items;
+pub mod locale;
+pub mod privacy;
+pub mod types;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,31 @@
+/// Languages with their decimal separator and separators of thousands
+const LOCALES: &[(&[&str], char, &[char])] = &[
+    (&["en", "he", "hi", "ja", "ko", "th", "zh"], '.', &[',']),
+    (
+        &["da", "de", "el", "es", "id", "it", "nl", "pt", "ro", "tr"],
+        ',',
+        &['.'],
+    ),
+    (
+        &[
+            "be", "bg", "cs", "fi", "fr", "hu", "nb", "pl", "ru", "sk", "sv", "uk",
+        ],
+        ',',
+        &[' ', '\u{a0}', '\u{202f}'],
+    ),
+];
+
+/// Decimal separator and separators of thousands of a language tag like `de` or `de-CH`,
+/// `None` when it's not known
+pub fn number_separators(tag: &str) -> Option<(char, &'static [char])> {
+    let mut subtags = tag.split(['-', '_']);
+    let language = subtags.next()?.to_lowercase();
+    let region = subtags.next().map(str::to_uppercase);
+    match (language.as_str(), region.as_deref()) {
+        ("de", Some("CH")) | ("de", Some("LI")) => Some(('.', &['\'', '’'])),
+        (language, _) => LOCALES
+            .iter()
+            .find(|(languages, _, _)| languages.contains(&language))
+            .map(|(_, decimal, grouping)| (*decimal, *grouping)),
+    }
+}
@@ -0,0 +1,145 @@
+use super::identity::identity_items;
+use super::items::{block_name, item_paths};
+use super::types::{infer_input_type, input_rules, item_input, InputType};
+use ra_lexer::diagnostics::Diagnostic;
+use ra_parser::block::Block;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Name of the per project allowlist file
+pub const ALLOWLIST_FILE: &str = "privacy.allow";
+
+/// Item names which usually ask for identifying information
+const IDENTIFYING_NAMES: &[&str] = &[
+    "name",
+    "firstname",
+    "lastname",
+    "fullname",
+    "surname",
+    "phone",
+    "mobile",
+    "address",
+    "email",
+    "birthday",
+    "birthdate",
+    "dateofbirth",
+    "passport",
+    "ssn",
+];
+
+/// Kinds of files which may show a person
+const IDENTIFYING_FILES: &[&str] = &["image", "photo", "video", "audio"];
+
+/// Items allowed to ask for identifying information,
+/// one path like `recipe.name` or a bare item name per line
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Allowlist {
+    entries: Vec<String>,
+}
+
+impl Allowlist {
+    pub fn parse(text: &str) -> Self {
+        Self {
+            entries: text
+                .lines()
+                .map(|line| line.split("//").next().unwrap_or_default().trim())
+                .filter(|line| !line.is_empty())
+                .map(str::to_owned)
+                .collect(),
+        }
+    }
+
+    /// Reads the closest `privacy.allow` from `dir` or its ancestors
+    pub fn find(dir: &Path) -> io::Result<Self> {
+        for dir in dir.ancestors() {
+            let path = dir.join(ALLOWLIST_FILE);
+            if path.is_file() {
+                return Ok(Self::parse(&fs::read_to_string(path)?));
+            }
+        }
+        Ok(Self::default())
+    }
+
+    pub fn allows(&self, path: &str) -> bool {
+        let name = path.rsplit('.').next().unwrap_or(path);
+        self.entries
+            .iter()
+            .any(|entry| entry == path || entry == name)
+    }
+}
+
+/// Warns about inputs which may ask for identifying information
+/// outside of identity items
+pub fn lint_privacy(program: &Block, allowlist: &Allowlist) -> Vec<Diagnostic> {
+    let identity = identity_items(program);
+    item_paths(program)
+        .into_iter()
+        .filter(|(path, _)| !identity.contains(path) && !allowlist.allows(path))
+        .filter_map(|(path, item)| {
+            let reason = identifying_reason(&path, item)?;
+            let name = block_name(item)?;
+            Some(
+                Diagnostic::warning(
+                    "A0001",
+                    format!("`{}` may ask for identifying information", path),
+                )
+                .with_primary_label(name.position, reason)
+                .with_note("values of identity items are only visible to the runtime")
+                .with_note(format!(
+                    "mark the item with `!identity` or add `{}` to {}",
+                    path, ALLOWLIST_FILE
+                )),
+            )
+        })
+        .collect()
+}
+
+fn identifying_reason(path: &str, item: &Block) -> Option<String> {
+    let input = item_input(item)?;
+
+    for (name, arguments) in input_rules(input) {
+        let values = || {
+            arguments
+                .iter()
+                .flat_map(|argument| argument.name.iter().chain(argument.value.iter()))
+        };
+        match name {
+            "pattern" | "format" => {
+                if let Some(pattern) = values().find(|value| is_identifying_name(value)) {
+                    return Some(format!("asks for {} matching `!{}`", pattern, name));
+                }
+            }
+            "file" => {
+                if let Some(kind) =
+                    values().find(|value| IDENTIFYING_FILES.contains(&value.as_str()))
+                {
+                    return Some(format!("asks for a file of type {}", kind));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if infer_input_type(input) == InputType::Email {
+        return Some("asks for an email".to_owned());
+    }
+
+    let name = path.rsplit('.').next().unwrap_or(path);
+    if is_identifying_name(name) {
+        return Some(format!("`{}` usually identifies a person", name));
+    }
+    None
+}
+
+fn is_identifying_name(name: &str) -> bool {
+    let normalized: String = name
+        .chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    IDENTIFYING_NAMES.contains(&normalized.as_str())
+        || ["phone", "address", "email"]
+            .iter()
+            .any(|word| normalized.contains(word))
+}
@@ -0,0 +1,756 @@
+mod items {
+    use crate::items::{block_name, find_declaration, find_item, named_block_at, named_blocks};
+    use ra_parser::parser::parse;
+
+    const SOURCE: &str = "recipe\n    name\n        >\n            !text\n    ingredients\n        amount\n:unit\n    gram\n";
+
+    #[test]
+    fn it_should_list_named_blocks() {
+        let program = parse(SOURCE).expect("can't parse");
+        let names: Vec<&str> = named_blocks(&program)
+            .into_iter()
+            .filter_map(block_name)
+            .map(|token| token.content)
+            .collect();
+        assert_eq!(
+            names,
+            vec!["recipe", "name", "ingredients", "amount", "unit", "gram"]
+        );
+    }
+
+    #[test]
+    fn it_should_find_items_by_path() {
+        let program = parse(SOURCE).expect("can't parse");
+        let amount = find_item(&program, &["recipe", "ingredients", "amount"]).expect("no item");
+        assert_eq!(block_name(amount).unwrap().span.start, 68);
+        assert_eq!(
+            find_item(&program, &["ingredients", "amount"]),
+            Some(amount)
+        );
+        assert!(find_item(&program, &["recipe", "amount"]).is_none());
+    }
+
+    #[test]
+    fn it_should_find_declarations() {
+        let program = parse(SOURCE).expect("can't parse");
+        assert!(find_declaration(&program, "unit").is_some());
+        assert!(find_declaration(&program, "recipe").is_none());
+    }
+
+    #[test]
+    fn it_should_find_named_block_at_offset() {
+        let program = parse(SOURCE).expect("can't parse");
+        let block = named_block_at(&program, 13).expect("no block");
+        assert_eq!(block_name(block).unwrap().content, "name");
+        assert!(named_block_at(&program, 20).is_none());
+    }
+}
+
+mod types {
+    use crate::items::find_item;
+    use crate::types::{default_value, infer_input_type, item_input, InputType};
+    use ra_parser::parser::parse;
+
+    fn infer(source: &str) -> InputType {
+        let program = parse(source).expect("can't parse");
+        let item = find_item(&program, &["a"]).expect("no item");
+        infer_input_type(item_input(item).expect("no input"))
+    }
+
+    #[test]
+    fn it_should_infer_types_from_rules() {
+        assert_eq!(infer("a\n    >\n        !text\n"), InputType::Text);
+        assert_eq!(
+            infer("a\n    >\n        !number:min=0\n"),
+            InputType::Number
+        );
+        assert_eq!(
+            infer("a\n    >\n        !text\n        !pattern:email\n"),
+            InputType::Email
+        );
+        assert_eq!(
+            infer("a\n    >\n        !file:type=image\n"),
+            InputType::File(Some("image".to_owned()))
+        );
+        assert_eq!(
+            infer("a\n    >\n        !choice:multiple\n        !options:\n            `x`\n"),
+            InputType::Choice { multiple: true }
+        );
+        assert_eq!(
+            infer("a\n    >\n        !searchInput\n"),
+            InputType::Rule("searchInput".to_owned())
+        );
+    }
+
+    #[test]
+    fn it_should_infer_lists() {
+        assert_eq!(
+            infer("a\n    +>\n        !text\n"),
+            InputType::List(Box::new(InputType::Text))
+        );
+    }
+
+    #[test]
+    fn it_should_infer_types_from_default() {
+        let program = parse("a\n    > :default=42\n").expect("can't parse");
+        let input = item_input(find_item(&program, &["a"]).unwrap()).unwrap();
+        assert_eq!(infer_input_type(input), InputType::Number);
+        assert_eq!(default_value(input), Some("42".to_owned()));
+    }
+}
+
+mod desugar {
+    use crate::desugar::{desugar, DefaultArguments, Pass};
+    use crate::items::find_item;
+    use crate::types::{
+        default_value, infer_input_type, input_rules, item_input, Argument, InputType,
+    };
+    use ra_parser::block::{Block, BlockKind};
+    use ra_parser::parser::parse;
+
+    fn lowered(source: &str) -> Block<'_> {
+        let mut program = parse(source).unwrap();
+        desugar(&mut program);
+        program
+    }
+
+    type Rules = Vec<(String, Vec<(Option<String>, Option<String>)>)>;
+
+    /// Rules of an item's input as `(rule, [(name, value)])`
+    fn rules(program: &Block, item: &str) -> Rules {
+        let input = item_input(find_item(program, &[item]).unwrap()).unwrap();
+        input_rules(input)
+            .into_iter()
+            .map(|(rule, arguments)| {
+                (
+                    rule.to_owned(),
+                    arguments
+                        .into_iter()
+                        .map(|Argument { name, value }| (name, value))
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn it_should_name_ordered_arguments() {
+        let short = lowered("c\n    >\n        !case:0\n");
+        let long = lowered("c\n    >\n        !case:case=0\n");
+        assert_eq!(rules(&short, "c"), rules(&long, "c"));
+        assert_eq!(
+            rules(&short, "c"),
+            vec![(
+                "case".to_owned(),
+                vec![(Some("case".to_owned()), Some("0".to_owned()))]
+            )]
+        );
+    }
+
+    #[test]
+    fn it_should_move_default_argument_into_rule() {
+        let short = lowered("a\n    > :default=6\n");
+        let long = lowered("a\n    >\n        !default:6\n");
+        assert_eq!(rules(&short, "a"), rules(&long, "a"));
+        let input = item_input(find_item(&short, &["a"]).unwrap()).unwrap();
+        assert_eq!(input.kind, BlockKind::Input(false, None));
+        assert_eq!(default_value(input).as_deref(), Some("6"));
+        assert_eq!(infer_input_type(input), InputType::Number);
+    }
+
+    #[test]
+    fn it_should_lower_list_inputs() {
+        let short = lowered("b\n    +>\n        !number\n");
+        let long = lowered("b\n    >\n        !number\n        !count\n");
+        assert_eq!(rules(&short, "b"), rules(&long, "b"));
+        let input = item_input(find_item(&short, &["b"]).unwrap()).unwrap();
+        assert!(matches!(input.kind, BlockKind::Input(false, _)));
+        assert_eq!(
+            infer_input_type(input),
+            InputType::List(Box::new(InputType::Number))
+        );
+    }
+
+    #[test]
+    fn it_should_keep_existing_count() {
+        let program = lowered("b\n    +>\n        !count:max=3\n");
+        assert_eq!(
+            rules(&program, "b"),
+            vec![(
+                "count".to_owned(),
+                vec![(Some("max".to_owned()), Some("3".to_owned()))]
+            )]
+        );
+    }
+
+    #[test]
+    fn it_should_point_synthesized_blocks_to_source() {
+        let source = "a\n    > :default=6\n";
+        let program = lowered(source);
+        let input = item_input(find_item(&program, &["a"]).unwrap()).unwrap();
+        assert_eq!(
+            &source[input.children[0].span.start..input.children[0].span.end],
+            "default"
+        );
+    }
+
+    #[test]
+    fn it_should_run_single_pass() {
+        let mut program = parse("a\n    +> :default=6\n").unwrap();
+        DefaultArguments.run(&mut program);
+        let input = item_input(find_item(&program, &["a"]).unwrap()).unwrap();
+        assert_eq!(input.kind, BlockKind::Input(true, None));
+        assert_eq!(input.children.len(), 1);
+    }
+
+    #[test]
+    fn it_should_be_idempotent() {
+        let mut program = lowered("a\n    +> :default=6\n        !case:0\n");
+        let once = program.clone();
+        desugar(&mut program);
+        assert_eq!(program, once);
+    }
+}
+
+mod diff {
+    use crate::diff::{diff, ItemChange, RulesChange, SequenceDiff};
+    use crate::types::InputType;
+    use ra_parser::parser::parse;
+
+    const OLD: &str = "recipe\n    name\n        >\n            !length:max=100\n    servings\n        >\n            !number:min=1\n    notes\n        >\n";
+
+    fn diff_sources(old: &str, new: &str) -> SequenceDiff {
+        diff(
+            &parse(old).expect("can't parse old"),
+            &parse(new).expect("can't parse new"),
+        )
+    }
+
+    #[test]
+    fn it_should_find_no_changes() {
+        let result = diff_sources(OLD, OLD);
+        assert!(result.changes.is_empty());
+        assert_eq!(result.migration.compatible.len(), 3);
+    }
+
+    #[test]
+    fn it_should_find_added_and_removed_items() {
+        let new = "recipe\n    name\n        >\n            !length:max=100\n    servings\n        >\n            !number:min=1\n    rating\n        >\n            !number\n";
+        let result = diff_sources(OLD, new);
+        assert_eq!(
+            result.changes,
+            vec![
+                ItemChange::Removed("recipe.notes".to_owned()),
+                ItemChange::Added("recipe.rating".to_owned()),
+            ]
+        );
+        assert_eq!(result.migration.orphaned, vec!["recipe.notes"]);
+        assert_eq!(result.migration.added, vec!["recipe.rating"]);
+    }
+
+    #[test]
+    fn it_should_follow_renames() {
+        let new = OLD.replace("recipe", "dish").replace("notes", "comments");
+        let result = diff_sources(OLD, &new);
+        assert_eq!(
+            result.changes,
+            vec![
+                ItemChange::Renamed {
+                    from: "recipe".to_owned(),
+                    to: "dish".to_owned()
+                },
+                ItemChange::Renamed {
+                    from: "recipe.notes".to_owned(),
+                    to: "dish.comments".to_owned()
+                },
+            ]
+        );
+        assert!(result
+            .migration
+            .compatible
+            .contains(&("recipe.name".to_owned(), "dish.name".to_owned())));
+        assert!(result.migration.orphaned.is_empty());
+    }
+
+    #[test]
+    fn it_should_classify_rule_and_type_changes() {
+        let new = "recipe\n    name\n        >\n            !length:max=50\n    servings\n        >\n            !number:min=0\n    notes\n        >\n            !number\n";
+        let result = diff_sources(OLD, new);
+        assert_eq!(
+            result.changes,
+            vec![
+                ItemChange::RulesChanged {
+                    path: "recipe.name".to_owned(),
+                    change: RulesChange::Tightened,
+                    from: vec!["!length:max=100".to_owned()],
+                    to: vec!["!length:max=50".to_owned()],
+                },
+                ItemChange::RulesChanged {
+                    path: "recipe.servings".to_owned(),
+                    change: RulesChange::Loosened,
+                    from: vec!["!number:min=1".to_owned()],
+                    to: vec!["!number:min=0".to_owned()],
+                },
+                ItemChange::InputTypeChanged {
+                    path: "recipe.notes".to_owned(),
+                    from: InputType::Unknown,
+                    to: InputType::Number,
+                },
+                ItemChange::RulesChanged {
+                    path: "recipe.notes".to_owned(),
+                    change: RulesChange::Tightened,
+                    from: vec![],
+                    to: vec!["!number".to_owned()],
+                },
+            ]
+        );
+        let converted: Vec<&str> = result
+            .migration
+            .conversions
+            .iter()
+            .map(|conversion| conversion.from.as_str())
+            .collect();
+        assert_eq!(converted, vec!["recipe.name", "recipe.notes"]);
+        assert_eq!(
+            result.migration.compatible,
+            vec![("recipe.servings".to_owned(), "recipe.servings".to_owned())]
+        );
+    }
+}
+
+mod identity {
+    use crate::identity::{identity_fields, identity_items};
+    use ra_parser::parser::parse;
+
+    #[test]
+    fn it_should_find_identity_fields() {
+        let source = "contact\n    !identity\n    email\n        >\n    phone\n        >\nnickname\n    >\n        !identity\nage\n    >\n        !number\n";
+        let program = parse(source).expect("can't parse");
+        assert_eq!(
+            identity_items(&program),
+            vec!["contact", "contact.email", "contact.phone", "nickname"]
+        );
+        assert_eq!(
+            identity_fields(&program),
+            vec!["contact.email", "contact.phone", "nickname"]
+        );
+    }
+}
+
+mod privacy {
+    use crate::privacy::{lint_privacy, Allowlist};
+    use ra_parser::parser::parse;
+
+    const SOURCE: &str = "contact\n    !identity\n    email\n        >\n    phone\n        >\nrecipe\n    name\n        >\n    photo\n        >\n            !file:type=image\n    author\n        >\n            !pattern:email\n    servings\n        >\n            !number\n";
+
+    fn warned(allowlist: &Allowlist) -> Vec<String> {
+        let program = parse(SOURCE).expect("can't parse");
+        lint_privacy(&program, allowlist)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect()
+    }
+
+    #[test]
+    fn it_should_warn_about_identifying_inputs_outside_identity_items() {
+        assert_eq!(
+            warned(&Allowlist::default()),
+            vec![
+                "`recipe.name` may ask for identifying information",
+                "`recipe.photo` may ask for identifying information",
+                "`recipe.author` may ask for identifying information",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_skip_allowed_items() {
+        let allowlist = Allowlist::parse("// recipes aren't people\nrecipe.name\nphoto\n");
+        assert_eq!(
+            warned(&allowlist),
+            vec!["`recipe.author` may ask for identifying information"]
+        );
+    }
+
+    #[test]
+    fn it_should_label_the_item_name() {
+        let program = parse("phoneNumber\n    >\n").expect("can't parse");
+        let diagnostics = lint_privacy(&program, &Allowlist::default());
+        let label = diagnostics[0].primary_label().expect("no label");
+        assert_eq!((label.position.0).1, 0);
+        assert_eq!((label.position.1).1, 11);
+    }
+}
+
+mod assignments {
+    use crate::assignments::{assigned_items, assignments, lint_assignments, AssignmentKind};
+    use ra_lexer::cursor::Position;
+    use ra_parser::parser::parse;
+
+    const SOURCE: &str = "recipe\n    rating\n        >\n    votesCount\n        += 1\n    votesCount -= 2\nreset\n    = 0\n";
+
+    #[test]
+    fn it_should_resolve_assignment_targets() {
+        let program = parse(SOURCE).expect("can't parse");
+        let found: Vec<(String, AssignmentKind)> = assignments(&program)
+            .into_iter()
+            .map(|assignment| (assignment.target, assignment.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("recipe.votesCount".to_owned(), AssignmentKind::Add),
+                ("recipe.votesCount".to_owned(), AssignmentKind::Subtract),
+                ("reset".to_owned(), AssignmentKind::Set),
+            ]
+        );
+        assert_eq!(assigned_items(&program), vec!["recipe.votesCount", "reset"]);
+        assert!(lint_assignments(&program).is_empty());
+    }
+
+    #[test]
+    fn it_should_refuse_assigning_inputs_and_unknown_items() {
+        let program = parse("rating\n    >\n    += 1\nemail\n    >\n        !identity\nemail = 'x'\ntotal -= 1\nvotes\n    += 'one'\n").expect("can't parse");
+        let messages: Vec<String> = lint_assignments(&program)
+            .into_iter()
+            .map(|diagnostic| diagnostic.message)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "`rating` accepts input and can't be assigned",
+                "`email` identifies clients and can't be assigned",
+                "can't assign to unknown item `total`",
+                "`+=` needs a number",
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_label_the_assigned_path() {
+        let program = parse(
+            "recipe
+    votes
+        = 0
+recipe.voters -= 1
+",
+        )
+        .expect("can't parse");
+        let diagnostics = lint_assignments(&program);
+        let label = diagnostics[0].primary_label().expect("no label");
+        assert_eq!(label.position, (Position(4, 0), Position(4, 13)));
+    }
+}
+
+mod dependencies {
+    use crate::dependencies::{Dependency, DependencyGraph, DependencyKind};
+    use ra_parser::parser::parse;
+
+    const SOURCE: &str = "pets\n    +>\nname\n    >\nage\n    >\nadult\n    !if:age\n        drink\n            >\n                !options:pets\n    !else\n        juice\n            >\ngreeting\n    `Hi {{name}}`\n    mood\n        >\neach\n    !iterate:pets\n        petAge\n            >\n";
+
+    fn dependency(from: &str, to: &str, kind: DependencyKind) -> Dependency {
+        Dependency {
+            from: from.to_owned(),
+            to: to.to_owned(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn it_should_collect_dependencies() {
+        let program = parse(SOURCE).expect("can't parse");
+        let graph = DependencyGraph::new(&program);
+        assert_eq!(
+            graph.dependencies,
+            vec![
+                dependency("adult.drink", "age", DependencyKind::Condition),
+                dependency("adult.drink", "pets", DependencyKind::Options),
+                dependency("adult.juice", "age", DependencyKind::Condition),
+                dependency("each.petAge", "pets", DependencyKind::Iterate),
+                dependency("greeting", "name", DependencyKind::Template),
+            ]
+        );
+        assert_eq!(graph.dependents_of("age").len(), 2);
+    }
+
+    #[test]
+    fn it_should_depend_on_references_in_indices() {
+        let program =
+            parse("pets\n    +>\nage\n    >\nx\n    `{{pets[age - 1]}}`\n").expect("can't parse");
+        let graph = DependencyGraph::new(&program);
+        assert_eq!(
+            graph.dependencies,
+            vec![
+                dependency("x", "age", DependencyKind::Template),
+                dependency("x", "pets", DependencyKind::Template),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_group_fields_into_steps() {
+        let program = parse(SOURCE).expect("can't parse");
+        let graph = DependencyGraph::new(&program);
+        let steps: Vec<Vec<String>> = graph.steps().into_iter().map(|step| step.fields).collect();
+        assert_eq!(
+            steps,
+            vec![
+                vec!["pets", "name", "age"],
+                vec!["adult.drink", "adult.juice", "greeting.mood", "each.petAge"],
+            ]
+        );
+        assert_eq!(graph.step_of("greeting.mood"), Some(1));
+        assert!(graph.field_dependencies("greeting.mood").contains("name"));
+    }
+
+    #[test]
+    fn it_should_keep_independent_fields_together() {
+        let program = parse("a\n    >\nb\n    >\nc\n    !if:b\n        d\n            >\n        e\n            !if:d\n                f\n                    >\n").expect("can't parse");
+        let graph = DependencyGraph::new(&program);
+        let steps: Vec<Vec<String>> = graph.steps().into_iter().map(|step| step.fields).collect();
+        assert_eq!(steps, vec![vec!["a", "b"], vec!["c.d"], vec!["c.e.f"]]);
+    }
+}
+
+mod checks {
+    use crate::checks::check_sequence;
+    use ra_parser::parser::parse;
+
+    fn check(source: &str) -> Vec<(&'static str, String)> {
+        let program = parse(source).expect("can't parse");
+        check_sequence(&program)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn it_should_report_reference_cycles() {
+        assert_eq!(
+            check("a\n    !if:b\n        x\n            >\nb\n    !if:a.x\n        y\n            >\n"),
+            vec![("A0003", "`a.x` depends on itself".to_owned())]
+        );
+        assert_eq!(
+            check("a\n    !if:a\n        x\n            >\n"),
+            vec![("A0003", "`a` depends on itself".to_owned())]
+        );
+        assert!(check(
+            "a\n    `{{a}}`\n    x\n        >\nb\n    !if:a.x\n        y\n            >\n"
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn it_should_report_each_cycle_once() {
+        assert_eq!(
+            check("a\n    `{{b}}`\nb\n    `{{a}}`\nc\n    !if:c\n        x\n            >\n"),
+            vec![
+                ("A0003", "`a` depends on itself".to_owned()),
+                ("A0003", "`c` depends on itself".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_check_dense_dependencies_quickly() {
+        let items = 30;
+        let templates: Vec<String> = (0..items).map(|i| format!("{{{{i{}}}}}", i)).collect();
+        let source: String = (0..items)
+            .map(|i| format!("i{}\n    `{}`\n", i, templates.join(" ")))
+            .collect();
+        let started = std::time::Instant::now();
+        let diagnostics = check(&source);
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(
+            diagnostics,
+            vec![("A0003", "`i0` depends on itself".to_owned())]
+        );
+    }
+
+    #[test]
+    fn it_should_report_unreachable_branches() {
+        let program = parse("a\n    !if:false\n        x\n            >\n    !else\n        y\n            >\nb\n    !if:case=1\n        z\n            >\n    !else\n        w\n            >\n").expect("can't parse");
+        let diagnostics = check_sequence(&program);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "`!if` branch is never shown");
+        assert_eq!(diagnostics[0].labels[1].message, "unreachable item");
+        assert_eq!(diagnostics[1].message, "`!else` branch is never shown");
+        assert_eq!(
+            diagnostics[1].labels[0].message,
+            "condition of the preceding `!if` is always true"
+        );
+    }
+
+    #[test]
+    fn it_should_check_switch_cases() {
+        assert_eq!(
+            check("s\n    !switch:a\n        !case:done\n            `zero`\n        !case:case=done\n            `again`\n"),
+            vec![
+                ("A0006", "duplicated `!case` value `done`".to_owned()),
+                ("A0005", "`!switch` has no `!default`".to_owned()),
+            ]
+        );
+        assert!(check("s\n    !switch:a\n        !case:case=0\n            `zero`\n        !default\n            `other`\n").is_empty());
+    }
+
+    #[test]
+    fn it_should_check_indices() {
+        const ITEMS: &str = "name\n    >\n        !text\npets\n    +>\n        !text\n";
+        let check_with = |source: &str| check(&format!("{}{}", ITEMS, source));
+        assert_eq!(
+            check_with("x\n    !if:name.1\n        y\n            >\n"),
+            vec![("A0008", "`name` can't be indexed".to_owned())]
+        );
+        assert_eq!(
+            check_with("x\n    `{{pets.0.age}}`\n"),
+            vec![("A0008", "elements of `pets` have no `age`".to_owned())]
+        );
+        assert_eq!(
+            check_with("x\n    !if:pets[1.5] > 2\n        y\n            >\n"),
+            vec![("A0008", "`pets` is indexed by `1.5`".to_owned())]
+        );
+        assert!(check_with(
+            "x\n    `{{pets.-1}} {{pets[name]}} {{pets.0?.age}} {{name?[0]}} {{other.1.a}}`\n"
+        )
+        .is_empty());
+    }
+}
+
+mod bundle {
+    use crate::bundle::{
+        compile, sequence_name, Annotation, ContentPart, FormatVersion, RuleOrigin, FORMAT_VERSION,
+    };
+    use crate::types::InputType;
+
+    const MAIN: &str = "name\n    > :default=guest\n        !text\n        !nickname\n    #ln:ru `имя {{name}}`\n    #ln:en `name`\n@recipe.rating.avg\n\n:nickname\n    !pattern:nick\n";
+    const RECIPE: &str = "rating\n    >\n        !number\n";
+
+    #[test]
+    fn it_should_compile_fields_with_rule_bindings() {
+        let bundle = compile(&[("main.ra", MAIN), ("lib/recipe.ra", RECIPE)]).unwrap();
+        assert_eq!(bundle.version, FORMAT_VERSION);
+        let main = bundle.sequence("main").unwrap();
+        let name = main.field("name").unwrap();
+        assert_eq!(name.input_type, InputType::Text);
+        assert_eq!(name.default.as_deref(), Some("guest"));
+        assert_eq!(
+            name.rules
+                .iter()
+                .map(|rule| (rule.rule.as_str(), rule.origin))
+                .collect::<Vec<_>>(),
+            vec![
+                ("default", RuleOrigin::Builtin),
+                ("text", RuleOrigin::Builtin),
+                ("nickname", RuleOrigin::Declared)
+            ]
+        );
+        assert_eq!(
+            bundle
+                .sequence("recipe")
+                .unwrap()
+                .field("rating")
+                .unwrap()
+                .input_type,
+            InputType::Number
+        );
+    }
+
+    #[test]
+    fn it_should_compile_sequence_locale() {
+        let bundle = compile(&[
+            ("main.ra", MAIN),
+            ("recipe.ra", &format!("#locale:de\n{}", RECIPE)),
+        ])
+        .unwrap();
+        assert_eq!(
+            bundle.sequence("recipe").unwrap().locale.as_deref(),
+            Some("de")
+        );
+        assert_eq!(bundle.sequence("main").unwrap().locale, None);
+
+        let errors = compile(&[("recipe.ra", &format!("#locale:tlh\n{}", RECIPE))]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].diagnostic.code, "A0009");
+        assert_eq!(errors[0].diagnostic.message, "unknown locale `tlh`");
+    }
+
+    #[test]
+    fn it_should_resolve_references_across_files() {
+        let bundle = compile(&[("main.ra", MAIN), ("recipe.ra", RECIPE)]).unwrap();
+        let reference = &bundle.sequence("main").unwrap().references[0];
+        assert_eq!(reference.sequence, "recipe");
+        assert_eq!(reference.item, "rating");
+        assert_eq!(reference.rest, vec!["avg".to_owned()]);
+
+        let errors = compile(&[("main.ra", MAIN)]).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].file, "main.ra");
+        assert_eq!(errors[0].diagnostic.code, "A0007");
+        let errors = compile(&[("main.ra", MAIN), ("recipe.ra", "score\n    >\n")]).unwrap_err();
+        assert_eq!(
+            errors[0].diagnostic.labels[0].message,
+            "`recipe` has no item `rating`"
+        );
+    }
+
+    #[test]
+    fn it_should_collect_content_variants() {
+        let bundle = compile(&[("main.ra", MAIN), ("recipe.ra", RECIPE)]).unwrap();
+        let variants = bundle.sequence("main").unwrap().variants("name");
+        assert_eq!(variants.len(), 2);
+        assert_eq!(
+            variants[0].annotations,
+            vec![Annotation {
+                context: "ln".to_owned(),
+                option: Some("ru".to_owned())
+            }]
+        );
+        assert_eq!(
+            variants[0].parts,
+            vec![
+                ContentPart::Text("имя ".to_owned()),
+                ContentPart::Template("name".to_owned())
+            ]
+        );
+        assert_eq!(variants[1].option("ln"), Some("en"));
+    }
+
+    #[test]
+    fn it_should_map_items_to_source() {
+        let bundle = compile(&[("recipe.ra", RECIPE)]).unwrap();
+        let location = bundle
+            .sequence("recipe")
+            .unwrap()
+            .location("rating")
+            .unwrap();
+        assert_eq!(
+            &RECIPE[location.span.start..location.span.end],
+            RECIPE.trim_end()
+        );
+        assert_eq!(location.start.0, 1);
+    }
+
+    #[test]
+    fn it_should_report_parse_errors_of_every_file() {
+        let errors =
+            compile(&[("a.ra", "a\n    b\n   c\n"), ("b.ra", "b\n    c\n   d\n")]).unwrap_err();
+        let files: Vec<&str> = errors.iter().map(|error| error.file.as_str()).collect();
+        assert!(files.contains(&"a.ra") && files.contains(&"b.ra"));
+    }
+
+    #[test]
+    fn it_should_load_older_minor_versions_only() {
+        let runtime = FormatVersion { major: 1, minor: 2 };
+        assert!(runtime.can_load(FormatVersion { major: 1, minor: 0 }));
+        assert!(runtime.can_load(runtime));
+        assert!(!runtime.can_load(FormatVersion { major: 1, minor: 3 }));
+        assert!(!runtime.can_load(FormatVersion { major: 2, minor: 0 }));
+        assert!(!runtime.can_load(FormatVersion { major: 0, minor: 2 }));
+    }
+
+    #[test]
+    fn it_should_name_sequences_after_files() {
+        assert_eq!(sequence_name("examples/foodie/recipe.ra"), "recipe");
+        assert_eq!(sequence_name("main"), "main");
+    }
+}
@@ -0,0 +1,213 @@
+use super::builtins::builtin_rule;
+use super::items::reference_path;
+use ra_lexer::token::TokenKind;
+use ra_parser::block::{Block, BlockKind};
+use ra_parser::expressions::input_expression::{ArgumentType, InputExpression, ValueType};
+use std::fmt;
+
+/// Type of an input inferred from its rules and default value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum InputType {
+    Unknown,
+    Text,
+    Number,
+    Email,
+    File(Option<String>),
+    Choice { multiple: bool },
+    Rule(String),
+    List(Box<InputType>),
+}
+
+impl fmt::Display for InputType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputType::Unknown => write!(f, "unknown"),
+            InputType::Text => write!(f, "text"),
+            InputType::Number => write!(f, "number"),
+            InputType::Email => write!(f, "email"),
+            InputType::File(None) => write!(f, "file"),
+            InputType::File(Some(kind)) => write!(f, "file of {}", kind),
+            InputType::Choice { multiple: false } => write!(f, "choice"),
+            InputType::Choice { multiple: true } => write!(f, "multiple choice"),
+            InputType::Rule(name) => write!(f, "!{}", name),
+            InputType::List(input_type) => write!(f, "list of {}", input_type),
+        }
+    }
+}
+
+/// Argument of an input or an invocation.
+///
+/// Flags like `:multiple` have a value but no name,
+/// assignments like `:min=1` have both.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Argument {
+    pub name: Option<String>,
+    pub value: Option<String>,
+}
+
+pub fn arguments(expression: &InputExpression) -> Vec<Argument> {
+    let InputExpression {
+        argument,
+        value,
+        next,
+    } = expression;
+    let value = value.as_ref().map(value_to_string);
+    let mut result = match argument {
+        Some(ArgumentType::Named(token, true)) => vec![Argument {
+            name: Some(token.content.to_owned()),
+            value,
+        }],
+        Some(ArgumentType::Named(token, false)) => vec![Argument {
+            name: None,
+            value: Some(token.content.to_owned()),
+        }],
+        Some(ArgumentType::Ordered(_)) | None if value.is_some() => {
+            vec![Argument { name: None, value }]
+        }
+        _ => vec![],
+    };
+    if let Some(next) = next {
+        result.extend(arguments(next));
+    }
+    result
+}
+
+/// Input block `>` or `+>` of an item
+pub fn item_input<'b, 'a>(item: &'b Block<'a>) -> Option<&'b Block<'a>> {
+    item.children
+        .iter()
+        .find(|child| matches!(child.kind, BlockKind::Input(..)))
+}
+
+/// Rules invoked on an input, name and arguments
+pub fn input_rules<'a>(input: &Block<'a>) -> Vec<(&'a str, Vec<Argument>)> {
+    input
+        .children
+        .iter()
+        .filter_map(|child| match &child.kind {
+            BlockKind::Invocation(Some(token), expression) => Some((
+                token.content,
+                expression.as_ref().map(arguments).unwrap_or_default(),
+            )),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Default value given either as `> :default=x` or as `!default:x`
+pub fn default_value(input: &Block) -> Option<String> {
+    let from_expression = match &input.kind {
+        BlockKind::Input(_, Some(expression)) => arguments(expression)
+            .into_iter()
+            .find(|argument| argument.name.as_deref() == Some("default"))
+            .and_then(|argument| argument.value),
+        _ => None,
+    };
+
+    from_expression.or_else(|| {
+        input_rules(input)
+            .into_iter()
+            .find(|(name, _)| *name == "default")
+            .and_then(|(_, arguments)| arguments.into_iter().next())
+            .and_then(|argument| argument.value)
+    })
+}
+
+/// Type of an input, `+>` and inputs with a `!count` rule are lists
+pub fn infer_input_type(input: &Block) -> InputType {
+    let multiple = match input.kind {
+        BlockKind::Input(multiple, _) => {
+            multiple || input_rules(input).iter().any(|(name, _)| *name == "count")
+        }
+        _ => return InputType::Unknown,
+    };
+
+    let mut input_type = InputType::Unknown;
+    for (name, arguments) in input_rules(input) {
+        let mentions = |word: &str| {
+            arguments.iter().any(|argument| {
+                argument.name.as_deref() == Some(word) || argument.value.as_deref() == Some(word)
+            })
+        };
+        input_type = match (name, input_type) {
+            ("number", _) => InputType::Number,
+            ("file", _) => InputType::File(
+                arguments
+                    .iter()
+                    .find(|argument| argument.name.as_deref() == Some("type"))
+                    .and_then(|argument| argument.value.clone()),
+            ),
+            ("pattern", _) | ("format", _) if mentions("email") => InputType::Email,
+            ("options", previous) | ("choice", previous) | ("select", previous) => {
+                InputType::Choice {
+                    multiple: mentions("multiple")
+                        || previous == InputType::Choice { multiple: true },
+                }
+            }
+            ("text", InputType::Unknown) => InputType::Text,
+            (name, InputType::Unknown) if builtin_rule(name).is_none() => {
+                InputType::Rule(name.to_owned())
+            }
+            (_, input_type) => input_type,
+        };
+    }
+
+    if input_type == InputType::Unknown {
+        let rule_defaults = input.children.iter().filter_map(|child| match &child.kind {
+            BlockKind::Invocation(Some(token), expression) if token.content == "default" => {
+                expression.as_ref()
+            }
+            _ => None,
+        });
+        let expression = match &input.kind {
+            BlockKind::Input(_, expression) => expression.as_ref(),
+            _ => None,
+        };
+        input_type = expression
+            .into_iter()
+            .chain(rule_defaults)
+            .find_map(default_type)
+            .unwrap_or(InputType::Unknown);
+    }
+
+    if multiple {
+        InputType::List(Box::new(input_type))
+    } else {
+        input_type
+    }
+}
+
+fn default_type(expression: &InputExpression) -> Option<InputType> {
+    let InputExpression {
+        argument,
+        value,
+        next,
+    } = expression;
+    match (argument, value) {
+        (Some(ArgumentType::Named(token, true)), Some(ValueType::Literal(literal)))
+            if token.content == "default" =>
+        {
+            match literal.kind {
+                Some(TokenKind::Int(_)) | Some(TokenKind::Float(_)) => Some(InputType::Number),
+                _ => Some(InputType::Text),
+            }
+        }
+        (Some(ArgumentType::Named(token, true)), Some(ValueType::Content(_)))
+            if token.content == "default" =>
+        {
+            Some(InputType::Text)
+        }
+        _ => next.as_ref().and_then(|next| default_type(next)),
+    }
+}
+
+fn value_to_string(value: &ValueType) -> String {
+    match value {
+        ValueType::Literal(token) => token
+            .value()
+            .map_or_else(|| token.content.to_owned(), |value| value.into_owned()),
+        ValueType::ReferenceExpression(reference) => reference_path(reference).join("."),
+        ValueType::Content(_) => "`...`".to_owned(),
+        ValueType::OutputExpression(_) => "(...)".to_owned(),
+    }
+}
@@ -0,0 +1,297 @@
+use ra_lexer::cursor::{is_end_of_line, is_whitespace};
+use ra_lexer::errors::LexerError;
+use ra_lexer::token::{Token, TokenKind};
+use ra_lexer::try_tokenize;
+
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Spaces per indentation level
+    pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self { indent_width: 4 }
+    }
+}
+
+/// Spacing differs between output expressions and arguments,
+/// `a = b` is an assignment while `:a=b` is an argument
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Expression,
+    Arguments,
+}
+
+/// One or more tokens printed together.
+///
+/// Operators written without whitespace in between, like `==`, `+>` or `..`,
+/// are lexed as separate tokens but must stay together.
+#[derive(Debug)]
+struct Unit<'a> {
+    kind: TokenKind<'a>,
+    text: &'a str,
+    glued: bool,
+    start: usize,
+    end: usize,
+    start_line: u32,
+    end_line: u32,
+    level: u16,
+}
+
+impl<'a> Unit<'a> {
+    fn is(&self, kind: TokenKind) -> bool {
+        !self.glued && self.kind == kind
+    }
+
+    fn is_number(&self) -> bool {
+        matches!(self.kind, TokenKind::Int(_) | TokenKind::Float(_))
+    }
+}
+
+/// Formats a source with canonical indentation and spacing.
+///
+/// Fails when the source can't be tokenized.
+pub fn format(input: &str, options: &FormatOptions) -> Result<String, LexerError> {
+    let tokens = try_tokenize(input).collect::<Result<Vec<Token>, LexerError>>()?;
+    let units = units(input, &tokens);
+    let source_width = source_indent_width(input);
+
+    let mut output = String::new();
+    let mut mode = Mode::Expression;
+    let mut line_start = 0;
+
+    for (index, unit) in units.iter().enumerate() {
+        let previous = if index > 0 {
+            units.get(index - 1)
+        } else {
+            None
+        };
+
+        match previous {
+            Some(previous) if unit.start_line == previous.end_line => {
+                let first_on_line = index - 1 == line_start;
+                if needs_space(
+                    input,
+                    &units[line_start],
+                    previous,
+                    unit,
+                    mode,
+                    first_on_line,
+                ) {
+                    output.push(' ');
+                }
+            }
+            _ => {
+                if let Some(previous) = previous {
+                    output.push('\n');
+                    if unit.start_line > previous.end_line + 1 {
+                        output.push('\n');
+                    }
+                }
+                line_start = index;
+                mode = match unit.kind {
+                    TokenKind::Identifier(_)
+                    | TokenKind::Int(_)
+                    | TokenKind::Float(_)
+                    | TokenKind::StringLiteral(_)
+                    | TokenKind::Boolean(_)
+                    | TokenKind::Null
+                    | TokenKind::OpenParentheses => Mode::Expression,
+                    _ => Mode::Arguments,
+                };
+                output.push_str(&" ".repeat(usize::from(unit.level) * options.indent_width));
+            }
+        }
+
+        if unit.is(TokenKind::Colon) {
+            mode = Mode::Arguments;
+        }
+
+        match unit.kind {
+            TokenKind::ContentBlock => {
+                output.push_str(&reindent(unit.text, source_width, options.indent_width));
+            }
+            _ => output.push_str(unit.text.trim_end()),
+        }
+    }
+
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+fn units<'a>(input: &'a str, tokens: &[Token<'a>]) -> Vec<Unit<'a>> {
+    let mut units: Vec<Unit> = Vec::new();
+    for token in tokens.iter() {
+        let kind = match token.kind {
+            Some(kind) => kind,
+            None => continue,
+        };
+
+        if let Some(last) = units.last_mut() {
+            if last.end == token.span.start && glues(last.kind, kind) {
+                last.end = token.span.end;
+                last.text = &input[last.start..last.end];
+                last.glued = true;
+                continue;
+            }
+        }
+
+        units.push(Unit {
+            kind,
+            text: &input[token.span.start..token.span.end],
+            glued: false,
+            start: token.span.start,
+            end: token.span.end,
+            start_line: (token.position.0).0,
+            end_line: (token.position.1).0,
+            level: token.level,
+        });
+    }
+    units
+}
+
+fn glues(previous: TokenKind, next: TokenKind) -> bool {
+    (is_operator(previous) && is_operator(next))
+        || (previous == TokenKind::Dot && next == TokenKind::Dot)
+}
+
+fn is_operator(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Ampersand
+            | TokenKind::Asterisk
+            | TokenKind::Equals
+            | TokenKind::Exclamation
+            | TokenKind::ForwardSlash
+            | TokenKind::Greater
+            | TokenKind::Less
+            | TokenKind::Minus
+            | TokenKind::Percent
+            | TokenKind::Pipe
+            | TokenKind::Plus
+            | TokenKind::Power
+            | TokenKind::Question
+            | TokenKind::Slash
+            | TokenKind::Tilde
+    )
+}
+
+fn needs_space(
+    input: &str,
+    first: &Unit,
+    previous: &Unit,
+    next: &Unit,
+    mode: Mode,
+    first_on_line: bool,
+) -> bool {
+    let written_apart = input[previous.end..next.start]
+        .chars()
+        .any(|ch| is_whitespace(&ch));
+
+    if next.kind == TokenKind::Comment {
+        return true;
+    }
+    // joining these would change how they're lexed, e.g. `1 .5` or `- 1`,
+    // minus is also allowed in identifiers like `user-account`
+    if (previous.is_number() && next.is(TokenKind::Dot))
+        || previous.is(TokenKind::Minus)
+        || next.is(TokenKind::Minus)
+    {
+        return written_apart;
+    }
+    if previous.is(TokenKind::Dot) || next.is(TokenKind::Dot) {
+        return false;
+    }
+    // safe access and indices of references `a?.b` and `a?[0][1]`
+    let ends_reference = matches!(
+        previous.kind,
+        TokenKind::Identifier(_) | TokenKind::Int(_) | TokenKind::CloseSquareBrace
+    ) && !previous.glued;
+    if (ends_reference || previous.is(TokenKind::Question))
+        && (next.is(TokenKind::Question) || next.is(TokenKind::OpenSquareBrace))
+    {
+        return false;
+    }
+    if next.is(TokenKind::Coma) || next.is(TokenKind::SemiColon) {
+        return false;
+    }
+    if previous.is(TokenKind::Coma) || previous.is(TokenKind::SemiColon) {
+        return true;
+    }
+    if matches!(
+        previous.kind,
+        TokenKind::OpenParentheses | TokenKind::OpenSquareBrace | TokenKind::OpenCurlyBrace
+    ) || matches!(
+        next.kind,
+        TokenKind::CloseParentheses | TokenKind::CloseSquareBrace | TokenKind::CloseCurlyBrace
+    ) {
+        return false;
+    }
+    if previous.is(TokenKind::At)
+        || previous.is(TokenKind::HashPound)
+        || previous.is(TokenKind::Exclamation)
+    {
+        return false;
+    }
+    if first_on_line && (previous.is(TokenKind::Plus) || previous.is(TokenKind::Colon)) {
+        return false;
+    }
+    if next.is(TokenKind::Colon) {
+        return previous.text.ends_with('>');
+    }
+    if previous.is(TokenKind::Colon) {
+        return false;
+    }
+    if mode == Mode::Arguments
+        && (previous.is(TokenKind::Equals)
+            || next.is(TokenKind::Equals)
+            || previous.is(TokenKind::Pipe)
+            || next.is(TokenKind::Pipe))
+    {
+        return false;
+    }
+    if next.kind == TokenKind::ContentBlock && first.is(TokenKind::HashPound) {
+        return false;
+    }
+    true
+}
+
+/// Indentation width of the source, found the same way lexer does,
+/// by the first line starting with a whitespace
+fn source_indent_width(input: &str) -> usize {
+    let mut lines = input.split(|ch| is_end_of_line(&ch));
+    lines.next();
+    lines
+        .map(|line| line.chars().take_while(is_whitespace).count())
+        .find(|width| *width > 0)
+        .unwrap_or(1)
+}
+
+/// Moves lines of a content block to the new indentation
+/// keeping their level relative to each other
+fn reindent(text: &str, source_width: usize, width: usize) -> String {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let last = lines.len() - 1;
+
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| {
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if index == 0 {
+                return line.to_owned();
+            }
+            let indent = line.chars().take_while(is_whitespace).count();
+            let rest: String = line.chars().skip(indent).collect();
+            if rest.is_empty() && index != last {
+                return String::new();
+            }
+            let indent = indent / source_width * width + indent % source_width;
+            format!("{}{}", " ".repeat(indent), rest)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,11 @@
+//! Canonical formatting of `.ra` sources.
+//!
+//! Works on the token stream, so comments are kept
+//! and sources which don't parse yet can still be formatted.
+
+mod formatter;
+
+pub use formatter::{format, FormatOptions};
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,129 @@
+mod lib {
+    use crate::{format, FormatOptions};
+    use ra_lexer::errors::LexerError;
+    use ra_lexer::token::TokenKind;
+    use ra_lexer::tokenize;
+
+    fn fmt(input: &str) -> String {
+        format(input, &FormatOptions::default()).expect("can't format")
+    }
+
+    #[test]
+    fn it_should_normalize_indentation() {
+        assert_eq!(fmt("a\n  b\n    c\n  d"), "a\n    b\n        c\n    d\n");
+        assert_eq!(
+            format("a\n    b\n", &FormatOptions { indent_width: 2 }).unwrap(),
+            "a\n  b\n"
+        );
+    }
+
+    #[test]
+    fn it_should_normalize_spacing_in_arguments() {
+        assert_eq!(
+            fmt("!my_rule : argument_1 = 10"),
+            "!my_rule:argument_1=10\n"
+        );
+        assert_eq!(fmt("a\n    >  :default = 42"), "a\n    > :default=42\n");
+        assert_eq!(fmt("!case: a | b"), "!case:a|b\n");
+        assert_eq!(fmt("a\n    + >"), "a\n    +>\n");
+    }
+
+    #[test]
+    fn it_should_normalize_spacing_in_expressions() {
+        assert_eq!(fmt("a=b+  1"), "a = b + 1\n");
+        assert_eq!(fmt("a == ( b.c )"), "a == (b.c)\n");
+        assert_eq!(fmt("a  !=b"), "a != b\n");
+    }
+
+    #[test]
+    fn it_should_join_indices_and_safe_access() {
+        assert_eq!(fmt("a = b ?. c"), "a = b?.c\n");
+        assert_eq!(fmt("a = b [i + 1] [0]"), "a = b[i + 1][0]\n");
+        assert_eq!(fmt("a = b.1 ? [0]"), "a = b.1?[0]\n");
+        assert_eq!(fmt("a = [ 1, 2 ]"), "a = [1, 2]\n");
+    }
+
+    #[test]
+    fn it_should_keep_operators_written_together() {
+        assert_eq!(fmt("a==b"), "a == b\n");
+        assert_eq!(fmt("a = = b"), "a = = b\n");
+        assert_eq!(fmt("a - 1\nb-c"), "a - 1\nb-c\n");
+    }
+
+    #[test]
+    fn it_should_space_union_commas() {
+        assert_eq!(fmt("a ,b ,c"), "a, b, c\n");
+        assert_eq!(fmt("a\n    > ,"), "a\n    >,\n");
+    }
+
+    #[test]
+    fn it_should_keep_comments() {
+        assert_eq!(
+            fmt("a    // first\n/* second */\nb"),
+            "a // first\n/* second */\nb\n"
+        );
+    }
+
+    #[test]
+    fn it_should_collapse_blank_lines() {
+        assert_eq!(fmt("\n\na\n\n\n\nb\n\n\n"), "a\n\nb\n");
+    }
+
+    #[test]
+    fn it_should_reindent_content_blocks() {
+        assert_eq!(
+            fmt("a\n  #ln:en `\n    text\n      more\n\n  `"),
+            "a\n    #ln:en`\n        text\n            more\n\n    `\n"
+        );
+    }
+
+    #[test]
+    fn it_should_keep_tokens() {
+        let input = "{1:N}\nx, y\n    > :default=`a`\n        !number:min=1:max=2\n    z != 1 .. 2";
+        fn kinds(input: &str) -> Vec<(Option<TokenKind<'_>>, u16)> {
+            tokenize(input)
+                .map(|token| (token.kind, token.level))
+                .collect()
+        }
+        assert_eq!(kinds(&fmt(input)), kinds(input));
+    }
+
+    #[test]
+    fn it_should_fail_on_lexer_errors() {
+        match format("a\n    b\n      c", &FormatOptions::default()) {
+            Err(LexerError::UnexpectedIndentWidth(..)) => {}
+            other => panic!("expected indentation error, got {:?}", other),
+        }
+        assert!(format("`a", &FormatOptions::default()).is_err());
+    }
+
+    use ra_dev_tools::insta::assert_snapshot;
+    use ra_dev_tools::make_example_tests;
+
+    /// Tokens with content blocks compared by their trimmed lines
+    fn signature(input: &str) -> Vec<(Option<TokenKind<'_>>, String, u16)> {
+        tokenize(input)
+            .map(|token| {
+                let content = match token.kind {
+                    Some(TokenKind::ContentBlock) => token
+                        .content
+                        .lines()
+                        .map(str::trim)
+                        .collect::<Vec<&str>>()
+                        .join("\n"),
+                    _ => token.content.trim_end().to_owned(),
+                };
+                (token.kind, content, token.level)
+            })
+            .collect()
+    }
+
+    #[make_example_tests]
+    #[test]
+    fn it_should_format_idempotently(contents: String, file_name: String) {
+        let formatted = fmt(&contents);
+        assert_eq!(fmt(&formatted), formatted);
+        assert_eq!(signature(&formatted), signature(&contents));
+        assert_snapshot!(file_name, formatted);
+    }
+}
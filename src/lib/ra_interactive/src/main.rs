@@ -1,8 +1,10 @@
 use ra_parser::parser::parse;
+use ra_parser::diagnostics::{Renderer, ToDiagnostic};
 use std::panic;
 use std::io::{stdin, stdout, Write};
 
 fn main() {
+    let renderer = Renderer::new(true);
     loop {
         print!("ra_ ");
         stdout().flush();
@@ -16,7 +18,12 @@ fn main() {
             let result = panic::catch_unwind(|| parse(&input));
     
             match result {
-                Ok(r) => println!("{:?}", r),
+                Ok(Ok(r)) => println!("{:?}", r),
+                Ok(Err((errors, _))) => {
+                    for error in errors.iter() {
+                        println!("{}", renderer.render(&input, &error.to_diagnostic()));
+                    }
+                },
                 Err(e) => println!("{:?}", e)
             }
         }
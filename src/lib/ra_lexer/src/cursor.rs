@@ -1,13 +1,14 @@
 use std::str::Chars;
 use std::fmt;
 use super::errors::LexerError;
-use serde::{Serialize};
+use super::span::Span;
+use serde::{Deserialize, Serialize};
 
 pub const EOF_CHAR: char = '\0';
 pub const EOL_CHAR: char = '\n';
 
-#[derive(Copy, Clone, PartialEq, Serialize)]
-pub struct Position(pub u16, pub u16);
+#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position(pub u32, pub u32);
 
 impl Default for Position {
     fn default() -> Self {
@@ -30,8 +31,10 @@ impl fmt::Display for Position {
 pub struct Cursor<'a> {
     input: &'a str,
     initial_len: usize,
+    offset: usize,
     chars: Chars<'a>,
     initial_level: Option<u16>,
+    indent_error: Option<LexerError>,
     pub position: Position,
     pub level: u16,
     pub indent_width: u16,
@@ -87,19 +90,28 @@ impl <'a> Cursor<'a> {
         Cursor {
             input,
             initial_len: input.len(),
+            offset: 0,
             chars: input.chars(),
             position,
             level,
             indent_width,
-            initial_level
+            initial_level,
+            indent_error: None,
         }
     }
 
+    /// Sets byte offset of `input` within the original source,
+    /// used when a part of the source is tokenized separately.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
     /// Returns nth character relative to the current cursor position.
     /// If requested position doesn't exist, `EOF_CHAR` is returned.
     /// However, getting `EOF_CHAR` doesn't always mean actual end of file,
     /// it should be checked with `is_eof` method.
-    fn nth_char(&self, n: usize) -> char {
+    pub(crate) fn nth_char(&self, n: usize) -> char {
         match self.chars().nth(n).unwrap_or(EOF_CHAR) {
             c if is_end_of_line(&c) => EOL_CHAR,
             t => t
@@ -121,6 +133,11 @@ impl <'a> Cursor<'a> {
         self.nth_char(1)
     }
 
+    /// Peeks the third symbol from the input stream without consuming it.
+    pub fn third_ahead(&self) -> char {
+        self.nth_char(2)
+    }
+
     /// Checks if there is nothing more to consume.
     pub fn is_eof(&self) -> bool {
         self.chars.as_str().trim().is_empty()
@@ -132,9 +149,7 @@ impl <'a> Cursor<'a> {
         match character {
             Some(ch) => {
                 if is_end_of_line(&ch) {
-                    self.position.0 += 1;
-                    self.consume_indent();
-                    self.position.1 = self.level * self.indent_width;
+                    self.new_line(ch);
                     self.bump()
                 }
                 else {
@@ -146,11 +161,71 @@ impl <'a> Cursor<'a> {
         }
     }
 
-    /// Returns amount of already consumed symbols.
+    /// Moves to the next character keeping line breaks and indentation as they are,
+    /// for text spanning lines that isn't indented with the block, like raw strings.
+    pub fn bump_verbatim(&mut self) -> Option<char> {
+        let character = self.chars.next()?;
+        if is_end_of_line(&character) {
+            if character == '\r' && self.chars.clone().next() == Some('\n') {
+                self.chars.next();
+            }
+            self.position.0 += 1;
+            self.position.1 = 0;
+        }
+        else {
+            self.position.1 += 1;
+        }
+        Some(character)
+    }
+
+    /// Skips whitespace and line breaks without consuming the next token's first character.
+    pub fn eat_trivia(&mut self) {
+        loop {
+            match self.chars.clone().next() {
+                Some(ch) if is_end_of_line(&ch) => {
+                    self.chars.next();
+                    self.new_line(ch);
+                }
+                Some(ch) if is_whitespace(&ch) => {
+                    self.chars.next();
+                    self.position.1 += 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Moves position to the next line and consumes its indent.
+    /// `\r\n` is treated as a single line break.
+    fn new_line(&mut self, ch: char) {
+        if ch == '\r' && self.chars.clone().next() == Some('\n') {
+            self.chars.next();
+        }
+        self.position = Position(self.position.0 + 1, 0);
+        self.consume_indent();
+    }
+
+    /// Takes the first indentation error met since the last call,
+    /// lines with invalid indentation get the level of the whole indents they start with.
+    pub fn take_indent_error(&mut self) -> Option<LexerError> {
+        self.indent_error.take()
+    }
+
+    /// Returns amount of already consumed bytes.
     pub fn len_consumed(&self) -> usize {
         self.initial_len - self.chars.as_str().len()
     }
 
+    /// Returns span from `start_consumed` to current position within the original source.
+    pub fn span_from(&self, start_consumed: usize) -> Span {
+        Span::new(self.offset + start_consumed, self.offset())
+    }
+
+    /// Returns byte offset within the original source.
+    pub fn offset(&self) -> usize {
+        self.offset + self.len_consumed()
+    }
+
     pub fn slice(&self, start: usize, end: usize) -> &'a str {
         &self.input[start..end]
     }
@@ -173,6 +248,7 @@ impl <'a> Cursor<'a> {
             else {
                 let initial_level = self.initial_level.clone();
                 let indent_width = self.indent_width.clone();
+                let line_start = self.position;
 
                 let inner_width = self.eat_while(|c, eaten| {
                     match initial_level {
@@ -180,11 +256,9 @@ impl <'a> Cursor<'a> {
                         None => is_whitespace(&c)
                     }
                 });
-                if inner_width % indent_width == 0 {
-                    self.level =  inner_width / indent_width;
-                } 
-                else {
-                    panic!(LexerError::UnexpectedIndentLevel(inner_width, self.position));
+                self.level = inner_width / indent_width;
+                if !inner_width.is_multiple_of(indent_width) && self.indent_error.is_none() {
+                    self.indent_error = Some(LexerError::UnexpectedIndentWidth(inner_width, line_start));
                 }
             }
         }
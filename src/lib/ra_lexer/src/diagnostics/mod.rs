@@ -0,0 +1,141 @@
+use crate::cursor::Position;
+use std::fmt;
+
+mod render;
+
+pub use render::Renderer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+            Severity::Help => write!(f, "help"),
+        }
+    }
+}
+
+/// Marks a span of source code.
+///
+/// Primary labels point at the cause of a diagnostic,
+/// secondary labels give additional context.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Label {
+    pub position: (Position, Position),
+    pub message: String,
+    pub primary: bool,
+}
+
+/// Replacement which is likely to fix a diagnostic
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub message: String,
+    pub position: (Position, Position),
+    pub replacement: String,
+}
+
+/// Diagnostic reported by lexer, parser or any other pass.
+///
+/// `code` is stable between releases and can be used to look up
+/// or suppress a particular kind of diagnostic.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, code, message)
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, code, message)
+    }
+
+    pub fn with_primary_label(mut self, position: (Position, Position), message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            position,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    pub fn with_secondary_label(mut self, position: (Position, Position), message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            position,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_suggestion(
+        mut self,
+        message: impl Into<String>,
+        position: (Position, Position),
+        replacement: impl Into<String>,
+    ) -> Self {
+        self.suggestions.push(Suggestion {
+            message: message.into(),
+            position,
+            replacement: replacement.into(),
+        });
+        self
+    }
+
+    /// First primary label, if any
+    pub fn primary_label(&self) -> Option<&Label> {
+        self.labels.iter().find(|label| label.primary)
+    }
+
+    /// Start of the primary label
+    pub fn position(&self) -> Option<Position> {
+        self.primary_label().map(|label| label.position.0)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(position) = self.position() {
+            write!(f, " at {}", position)?;
+        }
+        Ok(())
+    }
+}
+
+/// Implemented by errors which can be reported to the user
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
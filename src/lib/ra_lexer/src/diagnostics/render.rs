@@ -0,0 +1,177 @@
+use super::{Diagnostic, Label, Severity};
+use crate::cursor::is_end_of_line;
+
+const RESET: &str = "\u{1b}[0m";
+const BOLD: &str = "\u{1b}[1m";
+const RED: &str = "\u{1b}[1;31m";
+const YELLOW: &str = "\u{1b}[1;33m";
+const GREEN: &str = "\u{1b}[1;32m";
+const CYAN: &str = "\u{1b}[1;36m";
+const BLUE: &str = "\u{1b}[1;34m";
+
+/// Renders diagnostics together with the offending source lines
+///
+/// ```text
+/// error[P0003]: expected `:`, found `{`
+///  --> recipe.ra:2:4
+///   |
+/// 2 | {1:{
+///   |    ^ expected `:`
+/// ```
+pub struct Renderer {
+    colored: bool,
+    file_name: Option<String>,
+}
+
+impl Renderer {
+    pub fn new(colored: bool) -> Self {
+        Self {
+            colored,
+            file_name: None,
+        }
+    }
+
+    pub fn with_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    pub fn render(&self, source: &str, diagnostic: &Diagnostic) -> String {
+        let lines: Vec<&str> = source.split(|c| is_end_of_line(&c)).collect();
+        let mut labels: Vec<&Label> = diagnostic
+            .labels
+            .iter()
+            .filter(|label| (label.position.0).0 > 0 && ((label.position.0).0 as usize) <= lines.len())
+            .collect();
+        labels.sort_by_key(|label| ((label.position.0).0, (label.position.0).1, !label.primary));
+
+        let gutter_width = labels
+            .iter()
+            .map(|label| (label.position.0).0.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(gutter_width);
+        let severity_color = self.severity_color(diagnostic.severity);
+
+        let mut output = format!(
+            "{}{}[{}]{}{}: {}{}\n",
+            self.paint(severity_color),
+            diagnostic.severity,
+            diagnostic.code,
+            self.paint(RESET),
+            self.paint(BOLD),
+            diagnostic.message,
+            self.paint(RESET)
+        );
+
+        let location = match (diagnostic.position(), &self.file_name) {
+            (Some(position), Some(file_name)) => Some(format!("{}:{}:{}", file_name, position.0, position.1 + 1)),
+            (Some(position), None) => Some(format!("{}:{}", position.0, position.1 + 1)),
+            (None, Some(file_name)) => Some(file_name.clone()),
+            (None, None) => None,
+        };
+
+        if let Some(location) = location {
+            output.push_str(&format!("{}{}-->{} {}\n", gutter, self.paint(BLUE), self.paint(RESET), location));
+        }
+
+        if !labels.is_empty() {
+            output.push_str(&format!("{} {}|{}\n", gutter, self.paint(BLUE), self.paint(RESET)));
+        }
+
+        let mut current_line = 0;
+        for label in labels.iter() {
+            let (start, end) = label.position;
+            let line = lines[start.0 as usize - 1];
+
+            if start.0 != current_line {
+                current_line = start.0;
+                output.push_str(&format!(
+                    "{}{:>width$} |{} {}\n",
+                    self.paint(BLUE),
+                    start.0,
+                    self.paint(RESET),
+                    line,
+                    width = gutter_width
+                ));
+            }
+
+            let line_len = line.chars().count();
+            let start_column = (start.1 as usize).min(line_len);
+            let end_column = {
+                if end.0 == start.0 {
+                    (end.1 as usize).min(line_len)
+                } else {
+                    line_len
+                }
+            };
+
+            let padding: String = line
+                .chars()
+                .take(start_column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+
+            let (marker, color) = if label.primary {
+                ('^', severity_color)
+            } else {
+                ('-', BLUE)
+            };
+            let markers = marker.to_string().repeat(end_column.saturating_sub(start_column).max(1));
+
+            output.push_str(&format!(
+                "{} {}|{} {}{}{}",
+                gutter,
+                self.paint(BLUE),
+                self.paint(RESET),
+                padding,
+                self.paint(color),
+                markers
+            ));
+            if !label.message.is_empty() {
+                output.push_str(&format!(" {}", label.message));
+            }
+            output.push_str(&format!("{}\n", self.paint(RESET)));
+        }
+
+        for note in diagnostic.notes.iter() {
+            output.push_str(&format!(
+                "{} {}= note:{} {}\n",
+                gutter,
+                self.paint(BOLD),
+                self.paint(RESET),
+                note
+            ));
+        }
+
+        for suggestion in diagnostic.suggestions.iter() {
+            output.push_str(&format!(
+                "{} {}= help:{} {}: `{}`\n",
+                gutter,
+                self.paint(GREEN),
+                self.paint(RESET),
+                suggestion.message,
+                suggestion.replacement
+            ));
+        }
+
+        output
+    }
+
+    fn severity_color(&self, severity: Severity) -> &'static str {
+        match severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => CYAN,
+            Severity::Help => GREEN,
+        }
+    }
+
+    fn paint(&self, code: &'static str) -> &'static str {
+        if self.colored {
+            code
+        } else {
+            ""
+        }
+    }
+}
@@ -1,7 +1,6 @@
-use std::error;
-use std::fmt;
 use std::num;
 use super::Position;
+use super::diagnostics::{Diagnostic, ToDiagnostic};
 
 extern crate failure;
 use failure::Fail;
@@ -11,6 +10,8 @@ use failure::Fail;
 pub enum LexerError {
     #[fail(display = "Unexpected indentation level: {} at {}", _0, _1)]
     UnexpectedIndentLevel(u16, Position),
+    #[fail(display = "Unexpected indentation width: {} at {}", _0, _1)]
+    UnexpectedIndentWidth(u16, Position),
     #[fail(display = "Unexpected character: {} at {}", _0, _1)]
     UnexpectedCharacter(char, Position),
     #[fail(display = "Unsupported token at {}", _0)]
@@ -22,5 +23,89 @@ pub enum LexerError {
     #[fail(display = "Invalid floating point number: {} at {}", _0, _1)]
     InvalidFloat(#[cause] num::ParseFloatError, Position),
     #[fail(display = "Invalid number: {} at {}", _0, _1)]
-    InvalidInt(#[cause] num::ParseIntError, Position)
+    InvalidInt(#[cause] num::ParseIntError, Position),
+    #[fail(display = "Invalid escape sequence at {}", _0)]
+    InvalidEscape(Position)
+}
+
+impl LexerError {
+    /// Stable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexerError::UnexpectedIndentLevel(..) => "L0001",
+            LexerError::UnexpectedCharacter(..) => "L0002",
+            LexerError::UnsupportedToken(..) => "L0003",
+            LexerError::UnexpectedEndOfInput(..) => "L0004",
+            LexerError::UnexpectedEndOfLine(..) => "L0005",
+            LexerError::InvalidFloat(..) => "L0006",
+            LexerError::InvalidInt(..) => "L0007",
+            LexerError::InvalidEscape(..) => "L0008",
+            LexerError::UnexpectedIndentWidth(..) => "L0009",
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match self {
+            LexerError::UnexpectedIndentLevel(_, position)
+            | LexerError::UnexpectedIndentWidth(_, position)
+            | LexerError::UnexpectedCharacter(_, position)
+            | LexerError::UnsupportedToken(position)
+            | LexerError::UnexpectedEndOfInput(position)
+            | LexerError::UnexpectedEndOfLine(position)
+            | LexerError::InvalidFloat(_, position)
+            | LexerError::InvalidInt(_, position)
+            | LexerError::InvalidEscape(position) => *position,
+        }
+    }
+}
+
+impl ToDiagnostic for LexerError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        let position = self.position();
+        let span = (position, Position(position.0, position.1 + 1));
+
+        match self {
+            LexerError::UnexpectedIndentLevel(level, _) => {
+                Diagnostic::error(self.code(), format!("unexpected indentation level {}", level))
+                    .with_primary_label(span, "indentation doesn't match any enclosing block")
+                    .with_note("indentation must be a multiple of the first indent in the file")
+            }
+            LexerError::UnexpectedIndentWidth(width, _) => {
+                Diagnostic::error(self.code(), format!("unexpected indentation width {}", width))
+                    .with_primary_label((position, Position(position.0, position.1 + u32::from(*width))), "not a multiple of the first indent")
+                    .with_note("indentation must be a multiple of the first indent in the file")
+            }
+            LexerError::UnexpectedCharacter(ch, _) => {
+                Diagnostic::error(self.code(), format!("unexpected character `{}`", ch))
+                    .with_primary_label(span, "not allowed here")
+            }
+            LexerError::UnsupportedToken(_) => {
+                Diagnostic::error(self.code(), "unsupported token")
+                    .with_primary_label(span, "")
+            }
+            LexerError::UnexpectedEndOfInput(_) => {
+                Diagnostic::error(self.code(), "unexpected end of input")
+                    .with_primary_label(span, "input ends here")
+                    .with_note("content blocks must be closed with a backtick at the same indentation level")
+            }
+            LexerError::UnexpectedEndOfLine(_) => {
+                Diagnostic::error(self.code(), "unexpected end of line")
+                    .with_primary_label(span, "string literal is not closed")
+                    .with_note("end the line with `\\` to continue the string, or use a raw string `r\"..\"` or a content block")
+            }
+            LexerError::InvalidFloat(e, _) => {
+                Diagnostic::error(self.code(), format!("invalid floating point number: {}", e))
+                    .with_primary_label(span, "")
+            }
+            LexerError::InvalidInt(e, _) => {
+                Diagnostic::error(self.code(), format!("invalid number: {}", e))
+                    .with_primary_label(span, "")
+            }
+            LexerError::InvalidEscape(_) => {
+                Diagnostic::error(self.code(), "invalid escape sequence")
+                    .with_primary_label(span, "unknown escape")
+                    .with_note("escapes are `\\n`, `\\t`, `\\r`, `\\0`, `\\\\`, `\\\"`, `\\'` and `\\u{..}`, raw strings `r\"..\"` keep backslashes")
+            }
+        }
+    }
 }
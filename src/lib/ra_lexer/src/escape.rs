@@ -0,0 +1,50 @@
+use crate::cursor::{is_end_of_line, is_whitespace};
+use std::borrow::Cow;
+
+/// Text of a string literal with its escapes decoded, borrowed when it has none.
+///
+/// A `\` ending a line drops the line break and the indentation of the next line,
+/// escapes the lexer refuses are kept as written.
+pub fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('\\') {
+        return Cow::Borrowed(text);
+    }
+
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            value.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some('u') if chars.peek() == Some(&'{') => {
+                let code: String = chars.by_ref().skip(1).take_while(|ch| *ch != '}').collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32) {
+                    Some(ch) => value.push(ch),
+                    None => {
+                        value.push_str("\\u{");
+                        value.push_str(&code);
+                        value.push('}');
+                    }
+                }
+            }
+            Some(ch) if is_end_of_line(&ch) => {
+                while chars.peek().is_some_and(|ch| is_whitespace(ch) || is_end_of_line(ch)) {
+                    chars.next();
+                }
+            }
+            Some(ch @ '\\') | Some(ch @ '"') | Some(ch @ '\'') => value.push(ch),
+            Some(ch) => {
+                value.push('\\');
+                value.push(ch);
+            }
+            None => value.push('\\'),
+        }
+    }
+    Cow::Owned(value)
+}
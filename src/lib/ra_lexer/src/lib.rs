@@ -4,7 +4,11 @@
 extern crate serde;
 
 pub mod cursor;
+pub mod diagnostics;
 pub mod errors;
+pub mod escape;
+pub mod line_index;
+pub mod span;
 pub mod token;
 
 use token::{Token, TokenKind};
@@ -16,23 +20,59 @@ pub fn tokenize<'a>(input: &'a str) -> impl Iterator<Item = Token<'a>> + 'a {
     tokenize_cursor(Cursor::new(input, Position(1, 0), 0, 0))
 }
 
-fn tokenize_cursor<'a>(mut cursor: Cursor<'a>) -> impl Iterator<Item = Token<'a>> + 'a {
+/// Tokenizes with a cursor prepared by the caller,
+/// e.g. positioned and offset within a bigger source.
+pub fn tokenize_cursor<'a>(cursor: Cursor<'a>) -> impl Iterator<Item = Token<'a>> + 'a {
+    try_tokenize_cursor(cursor).map(|result| {
+        match result {
+            Ok(t) => t,
+            Err(e) => panic!("{:?}", e)
+        }
+    })
+}
+
+/// Same as `tokenize` but yields an error instead of panicking,
+/// the stream ends after the first error.
+pub fn try_tokenize<'a>(input: &'a str) -> impl Iterator<Item = Result<Token<'a>, LexerError>> + 'a {
+    try_tokenize_cursor(Cursor::new(input, Position(1, 0), 0, 0))
+}
+
+/// Indentation width the lexer settles on for `input`, 0 when nothing is indented.
+/// Lexing stops at the first indented line.
+pub fn indent_width(input: &str) -> u16 {
+    let mut cursor = Cursor::new(input, Position(1, 0), 0, 0);
+    while cursor.indent_width == 0 && !cursor.is_eof() {
+        if cursor.advance_token().is_err() {
+            break;
+        }
+    }
+    cursor.indent_width
+}
+
+pub fn try_tokenize_cursor<'a>(mut cursor: Cursor<'a>) -> impl Iterator<Item = Result<Token<'a>, LexerError>> + 'a {
+    let mut failed = false;
     std::iter::from_fn(move || {
-        if cursor.is_eof() {
+        if failed || cursor.is_eof() {
             return None;
         }
-        match cursor.advance_token() {
+        let token = cursor.advance_token();
+        if let Some(e) = cursor.take_indent_error() {
+            failed = true;
+            return Some(Err(e));
+        }
+        match token {
             Ok(t) => {
                 match t.kind {
-                    Some(_) => Some(t),
+                    Some(_) => Some(Ok(t)),
                     None => {
-                        panic!("{:?}", LexerError::UnsupportedToken(cursor.position))
+                        failed = true;
+                        Some(Err(LexerError::UnsupportedToken(cursor.position)))
                     }
                 }
             },
             Err(e) => {
-                println!("{:?}", cursor.position);
-                panic!("{:?}", e);
+                failed = true;
+                Some(Err(e))
             }
         }
     })
@@ -41,23 +81,19 @@ fn tokenize_cursor<'a>(mut cursor: Cursor<'a>) -> impl Iterator<Item = Token<'a>
 
 impl <'a> Cursor<'a> {
     fn advance_token(&mut self) -> Result<Token<'a>, LexerError> {
-        let mut start_position = self.position.clone();
+        self.eat_trivia();
+
+        let start_position = self.position;
+        let start_consumed = self.len_consumed();
         let first_char = match self.bump() {
             Some(ch) => ch,
             None => return Err(LexerError::UnexpectedEndOfInput(self.position))
         };
- 
-        if self.position.0 > start_position.0 {
-            start_position = Position(self.position.0, self.position.1 - 1); // adjust position for after line change
-        }
-
-        let start_consumed = self.len_consumed() - 1;
-
 
         match first_char {
             '/' => match self.first_ahead() {
-                '/' => self.single_line_comment(start_position.clone()),
-                '*' => self.multi_line_comment(start_position.clone()),
+                '/' => self.single_line_comment(start_position, start_consumed),
+                '*' => self.multi_line_comment(start_position, start_consumed),
                 _ => self.single_character_token(TokenKind::Slash, start_position, start_consumed),
             },
             '>' => self.single_character_token(TokenKind::Greater, start_position, start_consumed),
@@ -74,6 +110,7 @@ impl <'a> Cursor<'a> {
             ',' => self.single_character_token(TokenKind::Coma, start_position, start_consumed),
             '.' => self.single_character_token(TokenKind::Dot, start_position, start_consumed),
             '+' => self.single_character_token(TokenKind::Plus, start_position, start_consumed),
+            '*' => self.single_character_token(TokenKind::Asterisk, start_position, start_consumed),
             '=' => self.single_character_token(TokenKind::Equals, start_position, start_consumed),
             ';' => self.single_character_token(TokenKind::SemiColon, start_position, start_consumed),
             '&' => self.single_character_token(TokenKind::Ampersand, start_position, start_consumed),
@@ -85,18 +122,18 @@ impl <'a> Cursor<'a> {
             '$' => self.single_character_token(TokenKind::Dollar, start_position, start_consumed),
             '^' => self.single_character_token(TokenKind::Power, start_position, start_consumed),
             '~' => self.single_character_token(TokenKind::Tilde, start_position, start_consumed),
-            '`' => self.content_block(start_position.clone()),
+            '`' => self.content_block(start_position, start_consumed),
             '-' => {
                 match self.first_ahead() {
-                    c if c.is_numeric() => self.number(start_position),
+                    c if c.is_numeric() => self.number(start_position, start_consumed),
                     _ => self.single_character_token(TokenKind::Minus, start_position, start_consumed)
                 }
             },
-            c if c.is_alphabetic() || c == '_' => self.identifier(start_position),
-            c if c.is_numeric() => self.number(start_position),
-            c if c == '\'' => self.string_literal(c, start_position),
-            c if c == '"' => self.string_literal(c, start_position),
-            c if is_whitespace(&c) || is_end_of_line(&c) => self.advance_token(),
+            'r' if self.is_raw_string_start() => self.raw_string_literal(start_position, start_consumed),
+            c if c.is_alphabetic() || c == '_' => self.identifier(start_position, start_consumed),
+            c if c.is_numeric() => self.number(start_position, start_consumed),
+            c if c == '\'' => self.string_literal(c, start_position, start_consumed),
+            c if c == '"' => self.string_literal(c, start_position, start_consumed),
             c => Err(LexerError::UnexpectedCharacter(c.clone(), start_position))
         }
     }
@@ -106,123 +143,205 @@ impl <'a> Cursor<'a> {
             kind: Some(kind),
             position: (start_position, self.position.clone()),
             content: self.slice(start_consumed, self.len_consumed()),
+            span: self.span_from(start_consumed),
             level: self.level.clone(),
             len: 1,
             ..Default::default()
         })
     }
 
-    fn string_literal(&mut self, opening_quote: char, start_position: Position) -> Result<Token<'a>, LexerError> {
-        let mut string_literal = Token {
-            ..Default::default()
-        };
+    /// String literal `"say \"hi\""` or `'a'`.
+    ///
+    /// Escapes are checked here and decoded by `Token::value`,
+    /// a `\` at the end of a line continues the string on the next one.
+    fn string_literal(&mut self, opening_quote: char, start_position: Position, start_consumed: usize) -> Result<Token<'a>, LexerError> {
+        loop {
+            let position = self.position;
+            match self.bump_verbatim() {
+                Some('\\') => self.escape(position)?,
+                Some(ch) if ch == opening_quote => break,
+                Some(ch) if is_end_of_line(&ch) => return Err(LexerError::UnexpectedEndOfLine(position)),
+                Some(_) => {}
+                None => return Err(LexerError::UnexpectedEndOfInput(self.position))
+            }
+        }
 
-        let start_consumed = self.len_consumed() - 1;
+        let end_consumed = self.len_consumed() - 1;
+        Ok(self.string_token(start_position, start_consumed, start_consumed + 1, end_consumed, false))
+    }
+
+    /// Raw string literal `r"C:\docs"` or `r#"say "hi""#`, taken as written.
+    ///
+    /// It may span lines and ends at a quote followed by as many `#` as it opened with.
+    fn raw_string_literal(&mut self, start_position: Position, start_consumed: usize) -> Result<Token<'a>, LexerError> {
+        let mut hashes = 0;
+        while self.first_ahead() == '#' {
+            self.bump();
+            hashes += 1;
+        }
+        self.bump();
 
+        let content_consumed = self.len_consumed();
         loop {
-            if self.position.0 > start_position.0 {
-                return Err(LexerError::UnexpectedEndOfLine(self.position));
+            match self.bump_verbatim() {
+                Some('"') if (0..hashes).all(|n| self.nth_char(n) == '#') => break,
+                Some(_) => {}
+                None => return Err(LexerError::UnexpectedEndOfInput(self.position))
             }
+        }
+
+        let end_consumed = self.len_consumed() - 1;
+        for _ in 0..hashes {
+            self.bump();
+        }
+        Ok(self.string_token(start_position, start_consumed, content_consumed, end_consumed, true))
+    }
+
+    /// True after an `r` starting a raw string rather than an identifier
+    fn is_raw_string_start(&self) -> bool {
+        let hashes = (0..).take_while(|n| self.nth_char(*n) == '#').count();
+        self.nth_char(hashes) == '"'
+    }
 
-            match self.bump() {
-                Some(c) => {
-                    match c {
-                        ch if ch == opening_quote => break,
-                        _ => {}
+    fn string_token(&self, start_position: Position, start_consumed: usize, content_consumed: usize, end_consumed: usize, raw: bool) -> Token<'a> {
+        let content = self.slice(content_consumed, end_consumed);
+        Token {
+            kind: Some(TokenKind::StringLiteral(content)),
+            len: (self.len_consumed() - start_consumed).try_into().unwrap(),
+            content,
+            position: (start_position, self.position.clone()),
+            span: self.span_from(start_consumed),
+            level: self.level.clone(),
+            raw,
+        }
+    }
+
+    /// Escape following a `\` at `position`: `\n`, `\t`, `\r`, `\0`, `\\`,
+    /// `\"`, `\'`, `\u{1F600}` or a line break
+    fn escape(&mut self, position: Position) -> Result<(), LexerError> {
+        match self.bump_verbatim() {
+            Some('n') | Some('t') | Some('r') | Some('0') | Some('\\') | Some('"') | Some('\'') => Ok(()),
+            Some(ch) if is_end_of_line(&ch) => Ok(()),
+            Some('u') => {
+                if self.bump_verbatim() != Some('{') {
+                    return Err(LexerError::InvalidEscape(position));
+                }
+                let mut code = String::new();
+                loop {
+                    match self.bump_verbatim() {
+                        Some('}') => break,
+                        Some(ch) if ch.is_ascii_hexdigit() && code.len() < 6 => code.push(ch),
+                        _ => return Err(LexerError::InvalidEscape(position)),
                     }
                 }
-                None => return Err(LexerError::UnexpectedEndOfInput(self.position))
+                match u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32) {
+                    Some(_) => Ok(()),
+                    None => Err(LexerError::InvalidEscape(position)),
+                }
             }
+            _ => Err(LexerError::InvalidEscape(position)),
         }
-
-        string_literal.level = self.level.clone();
-        string_literal.position = (start_position, self.position.clone());
-        string_literal.len = (self.len_consumed() - start_consumed).try_into().unwrap();
-        string_literal.content = self.slice(start_consumed + 1, self.len_consumed() - 1);
-        string_literal.kind = Some(TokenKind::StringLiteral(string_literal.content));
-
-        Ok(string_literal)
     }
 
-    fn number(&mut self, start_position: Position) -> Result<Token<'a>, LexerError> {
+    /// Numbers `12`, `1_000`, `-1.5`, `2.5e-3` and `0xFF`.
+    ///
+    /// `_` separates digits, a coma is never a part of a number,
+    /// `1,5` are two numbers, numbers clients type are read at runtime by their locale.
+    /// Right after a dot a number is a path segment, `a.0.1` reads the indices `0` and `1`.
+    fn number(&mut self, start_position: Position, start_consumed: usize) -> Result<Token<'a>, LexerError> {
         let mut number = Token {
             ..Default::default()
         };
 
-        let mut first_separator: char = ' ';
-        let mut second_separator: char = ' ';
-        let start_consumed = self.len_consumed() - 1; // add 1 for first token
+        let first_digit = match self.slice(start_consumed, self.len_consumed()) {
+            "-" => self.bump().unwrap_or(EOF_CHAR),
+            first => first.chars().next().unwrap_or(EOF_CHAR),
+        };
 
-        loop {
-            let next_character = self.first_ahead();
-            match next_character {
-                c if is_end_of_line(&c) => break,
-                EOF_CHAR => break,
-                '.'|',' => {
-                    if !self.second_ahead().is_numeric() {
-                        break;
-                    }
-                    if first_separator == ' ' {
-                        first_separator = next_character;
-                    }
-                    else if first_separator != next_character && second_separator == ' ' {
-                        second_separator = next_character;
-                    }
-                    else if second_separator == next_character && first_separator != ' ' {
-                        return Err(LexerError::UnexpectedCharacter(next_character, self.position))
-                    }
+        let hexadecimal = first_digit == '0'
+            && matches!(self.first_ahead(), 'x' | 'X')
+            && self.second_ahead().is_ascii_hexdigit();
+        let segment = self.slice(0, start_consumed).ends_with('.');
+        let mut float = false;
+        if hexadecimal {
+            self.bump();
+            self.bump();
+            self.digits(|c| c.is_ascii_hexdigit())?;
+        } else if segment {
+            self.digits(|c| c.is_ascii_digit())?;
+        } else {
+            self.digits(|c| c.is_ascii_digit())?;
+            if self.first_ahead() == '.' && self.second_ahead().is_ascii_digit() {
+                float = true;
+                self.bump();
+                self.bump();
+                self.digits(|c| c.is_ascii_digit())?;
+            }
+            let signed_exponent = matches!(self.second_ahead(), '+' | '-') && self.third_ahead().is_ascii_digit();
+            if matches!(self.first_ahead(), 'e' | 'E') && (self.second_ahead().is_ascii_digit() || signed_exponent) {
+                float = true;
+                self.bump();
+                if signed_exponent {
                     self.bump();
                 }
-                c if c.is_numeric() => {self.bump();},
-                _ => break
+                self.bump();
+                self.digits(|c| c.is_ascii_digit())?;
             }
         }
 
         number.len = (self.len_consumed() - start_consumed).try_into().unwrap();
+        number.span = self.span_from(start_consumed);
         number.position = (start_position, self.position.clone());
         number.level = self.level.clone();
         number.content = self.slice(start_consumed, self.len_consumed());
 
-        number.kind = {
-            let thousands_separator = match second_separator {
-                ',' => '.',
-                '.' => ',',
-                _ => ' '
-            };
-
-            let decimals_separator = match second_separator {
-                ',' => ',',
-                '.' => '.',
-                _ => first_separator
+        let num = number.content.replace('_', "");
+        number.kind = if hexadecimal {
+            let (sign, digits) = match num.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", num.as_str()),
             };
-
-            let mut num = number.content.to_owned();
-
-            num = num.replace(thousands_separator, "");
-
-            if let Some(_) = num.find(decimals_separator) {
-                num = num.replace(decimals_separator, ".");
-                match num.parse::<f64>() {
-                    Ok(n) => Some(TokenKind::Float(n)),
-                    Err(e) => return Err(LexerError::InvalidFloat(e, self.position))
-                }
+            match i64::from_str_radix(&format!("{}{}", sign, &digits[2..]), 16) {
+                Ok(n) => Some(TokenKind::Int(n)),
+                Err(e) => return Err(LexerError::InvalidInt(e, self.position))
             }
-            else {
-                match num.parse::<i64>() {
-                    Ok(n) => Some(TokenKind::Int(n)),
-                    Err(e) => return Err(LexerError::InvalidInt(e, self.position))
-                }
+        }
+        else if float {
+            match num.parse::<f64>() {
+                Ok(n) => Some(TokenKind::Float(n)),
+                Err(e) => return Err(LexerError::InvalidFloat(e, self.position))
+            }
+        }
+        else {
+            match num.parse::<i64>() {
+                Ok(n) => Some(TokenKind::Int(n)),
+                Err(e) => return Err(LexerError::InvalidInt(e, self.position))
             }
         };
 
         Ok(number)
     }
 
-    fn identifier(&mut self, start_position: Position) -> Result<Token<'a>, LexerError> {
+    /// Digits following the one just consumed, `_` is allowed only between two digits
+    fn digits<F: Fn(char) -> bool>(&mut self, is_digit: F) -> Result<(), LexerError> {
+        loop {
+            match self.first_ahead() {
+                c if is_digit(c) => {
+                    self.bump();
+                }
+                '_' if is_digit(self.second_ahead()) => {
+                    self.bump();
+                }
+                '_' => return Err(LexerError::UnexpectedCharacter('_', self.position)),
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn identifier(&mut self, start_position: Position, start_consumed: usize) -> Result<Token<'a>, LexerError> {
         let mut identifier = Token {
             ..Default::default()
         };
-        let start_consumed = self.len_consumed() - 1; // add 1 for first token
 
         loop {
             let next_character = self.first_ahead();
@@ -236,20 +355,25 @@ impl <'a> Cursor<'a> {
 
         identifier.position = (start_position, self.position.clone());
         identifier.len = (self.len_consumed() - start_consumed).try_into().unwrap();
+        identifier.span = self.span_from(start_consumed);
         identifier.level = self.level.clone();
         identifier.content = self.slice(start_consumed, self.len_consumed());
-        identifier.kind = Some(TokenKind::Identifier(identifier.content));
+        identifier.kind = Some(match identifier.content {
+            "true" => TokenKind::Boolean(true),
+            "false" => TokenKind::Boolean(false),
+            "none" => TokenKind::Null,
+            content => TokenKind::Identifier(content),
+        });
 
         Ok(identifier)
     }
 
-    fn content_block(&mut self, start_position: Position) -> Result<Token<'a>, LexerError> {
+    fn content_block(&mut self, start_position: Position, start_consumed: usize) -> Result<Token<'a>, LexerError> {
         let mut content_block = Token{
             kind: Some(TokenKind::ContentBlock),
             ..Default::default()
         };
         let initial_level = self.level;
-        let start_consumed = self.len_consumed() - 1; // add 1 for first token
         let mut block_closed = false;
         while let Some(ch) = self.bump() {
             if self.level < initial_level {
@@ -257,6 +381,10 @@ impl <'a> Cursor<'a> {
                     return Err(LexerError::UnexpectedIndentLevel(self.level, self.position));
                 }
             }
+            // escaped "\`" doesn't close the block, "\\" is a backslash which can end it
+            else if ch == '\\' && matches!(self.first_ahead(), '`' | '\\') {
+                self.bump();
+            }
             else if self.level == initial_level && ch == '`' {
                 block_closed = true;
                 break;
@@ -270,12 +398,13 @@ impl <'a> Cursor<'a> {
         content_block.content = self.slice(start_consumed + 1, self.len_consumed() - 1);
         content_block.position = (start_position, self.position.clone());
         content_block.len = (self.len_consumed() - start_consumed).try_into().unwrap();
+        content_block.span = self.span_from(start_consumed);
         content_block.level = self.level.clone();
 
         Ok(content_block)
     }
 
-    fn single_line_comment (&mut self, start_position: Position) -> Result<Token<'a>, LexerError> {
+    fn single_line_comment (&mut self, start_position: Position, start_offset: usize) -> Result<Token<'a>, LexerError> {
         self.bump();
         let mut comment = Token{
             kind: Some(TokenKind::Comment),
@@ -294,13 +423,14 @@ impl <'a> Cursor<'a> {
 
         comment.position = (start_position, self.position.clone());
         comment.len = (self.len_consumed() - start_consumed + 2).try_into().unwrap(); // add 2 for "//"
+        comment.span = self.span_from(start_offset);
         comment.level = self.level.clone();
         comment.content = self.slice(start_consumed, self.len_consumed());
         
         Ok(comment)
     }
 
-    fn multi_line_comment (&mut self, start_position: Position) -> Result<Token<'a>, LexerError> {
+    fn multi_line_comment (&mut self, start_position: Position, start_offset: usize) -> Result<Token<'a>, LexerError> {
         self.bump();
         let mut comment = Token{
             kind: Some(TokenKind::Comment),
@@ -309,26 +439,21 @@ impl <'a> Cursor<'a> {
         };
 
         let start_consumed = self.len_consumed();
+        let mut closed = false;
         while let Some(ch) = self.bump() {
-
-            match ch {
-                '*' => match self.first_ahead() {
-                    '/' => {
-                        self.bump();
-                        break;
-                    },
-                    _ => {
-                        self.bump();
-                    }
-                },
-                _ => {
-                    self.bump();
-                }
+            if ch == '*' && self.first_ahead() == '/' {
+                self.bump();
+                closed = true;
+                break;
             }
-        };
+        }
+        if !closed {
+            return Err(LexerError::UnexpectedEndOfInput(self.position));
+        }
 
         comment.position = (start_position, self.position.clone());
         comment.len = (self.len_consumed() - start_consumed + 2).try_into().unwrap(); // add 2 for "/*"
+        comment.span = self.span_from(start_offset);
         comment.content = self.slice(start_consumed, self.len_consumed() - 2);
 
         Ok(comment)
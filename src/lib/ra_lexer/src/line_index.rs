@@ -0,0 +1,144 @@
+use crate::cursor::{is_end_of_line, Position};
+use crate::span::Span;
+
+/// Zero based line and column
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct LineCol {
+    pub line: u32,
+    pub col: u32,
+}
+
+/// Converts byte offsets to lines and columns and back.
+///
+/// Columns are either counted in chars, as the lexer does,
+/// or in UTF-16 code units, as most editors do.
+/// Line breaks are the same as in lexer, `\r\n` counts as a single one.
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(text: &str) -> Self {
+        let mut line_starts = vec![0];
+        let mut chars = text.char_indices().peekable();
+
+        while let Some((offset, ch)) = chars.next() {
+            if is_end_of_line(&ch) {
+                if ch == '\r' {
+                    if let Some((_, '\n')) = chars.peek() {
+                        chars.next();
+                        line_starts.push(offset + 2);
+                        continue;
+                    }
+                }
+                line_starts.push(offset + ch.len_utf8());
+            }
+        }
+
+        Self {
+            text: text.to_owned(),
+            line_starts,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Span of the line without its line break
+    pub fn line_span(&self, line: u32) -> Option<Span> {
+        let start = *self.line_starts.get(line as usize)?;
+        let end = self.text[start..]
+            .char_indices()
+            .find(|(_, ch)| is_end_of_line(ch))
+            .map(|(offset, _)| start + offset)
+            .unwrap_or_else(|| self.text.len());
+        Some(Span::new(start, end))
+    }
+
+    /// Line and column in chars, offsets past the end are clamped
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        self.line_col_by(offset, |_| 1)
+    }
+
+    /// Line and column in UTF-16 code units
+    pub fn line_col_utf16(&self, offset: usize) -> LineCol {
+        self.line_col_by(offset, |ch| ch.len_utf16() as u32)
+    }
+
+    /// Byte offset of a line and column in chars
+    pub fn offset(&self, line_col: LineCol) -> Option<usize> {
+        self.offset_by(line_col, |_| 1)
+    }
+
+    /// Byte offset of a line and column in UTF-16 code units
+    pub fn offset_utf16(&self, line_col: LineCol) -> Option<usize> {
+        self.offset_by(line_col, |ch| ch.len_utf16() as u32)
+    }
+
+    /// Lexer position of a byte offset
+    pub fn position(&self, offset: usize) -> Position {
+        let LineCol { line, col } = self.line_col(offset);
+        Position(line + 1, col)
+    }
+
+    /// Byte offset of a lexer position
+    pub fn position_offset(&self, position: Position) -> Option<usize> {
+        if position.0 == 0 {
+            return None;
+        }
+        self.offset(LineCol {
+            line: position.0 - 1,
+            col: position.1,
+        })
+    }
+
+    fn line_col_by<F>(&self, offset: usize, width: F) -> LineCol
+    where
+        F: Fn(char) -> u32,
+    {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+
+        let col = self.text[self.line_starts[line]..offset].chars().map(width).sum();
+
+        LineCol {
+            line: line as u32,
+            col,
+        }
+    }
+
+    fn offset_by<F>(&self, line_col: LineCol, width: F) -> Option<usize>
+    where
+        F: Fn(char) -> u32,
+    {
+        let line_span = self.line_span(line_col.line)?;
+        let mut col = 0;
+
+        for (offset, ch) in self.text[line_span.start..line_span.end].char_indices() {
+            if col >= line_col.col {
+                return Some(line_span.start + offset);
+            }
+            col += width(ch);
+        }
+
+        if col >= line_col.col {
+            Some(line_span.end)
+        } else {
+            None
+        }
+    }
+}
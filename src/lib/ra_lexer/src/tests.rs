@@ -1,4 +1,5 @@
 use crate::cursor::{Cursor, Position};
+use crate::span::Span;
 use crate::{tokenize, Token, TokenKind};
 
 mod cursor {
@@ -90,7 +91,7 @@ mod cursor {
 }
 
 mod lib {
-    use super::{tokenize, Position, Token, TokenKind};
+    use super::{tokenize, Position, Span, Token, TokenKind};
     
 
     #[test]
@@ -112,8 +113,10 @@ mod lib {
                 kind: Some(TokenKind::Identifier("abc")),
                 content: "abc",
                 position: (Position(2, 1), Position(2, 4)),
+                span: Span::new(6, 9),
                 len: 3,
                 level: 1,
+                raw: false,
             }
         )
     }
@@ -127,12 +130,30 @@ mod lib {
                 kind: Some(TokenKind::Identifier("abc")),
                 content: "abc",
                 position: (Position(1, 0), Position(1, 3)),
+                span: Span::new(0, 3),
                 len: 3,
-                level: 0
+                level: 0,
+                raw: false,
             }
         );
     }
 
+    #[test]
+    fn it_should_parse_keywords() {
+        let kinds: Vec<Option<TokenKind>> = tokenize("true false none nothing")
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                Some(TokenKind::Boolean(true)),
+                Some(TokenKind::Boolean(false)),
+                Some(TokenKind::Null),
+                Some(TokenKind::Identifier("nothing")),
+            ]
+        );
+    }
+
     use ra_dev_tools::insta::assert_json_snapshot;
     use ra_dev_tools::make_example_tests;
     use std::fs::File;
@@ -146,7 +167,8 @@ mod lib {
     }
 
     mod strings {
-        use super::{tokenize, Position, Token, TokenKind};
+        use super::{tokenize, Position, Span, Token, TokenKind};
+        use crate::try_tokenize;
         #[test]
         fn it_should_parse_string_literals_with_double_quotes() {
             let mut stream = tokenize("\"some\"");
@@ -157,6 +179,7 @@ mod lib {
                     content: "some",
                     len: 6,
                     position: (Position(1, 0), Position(1, 6)),
+                    span: Span::new(0, 6),
                     ..Default::default()
                 }
             )
@@ -172,13 +195,75 @@ mod lib {
                     content: "some",
                     len: 6,
                     position: (Position(1, 0), Position(1, 6)),
+                    span: Span::new(0, 6),
                     ..Default::default()
                 }
             )
         }
+
+        #[test]
+        fn it_should_decode_escapes() {
+            let token = tokenize(r#""say \"hi\"\n\t\\ \u{1F600} it's""#).next().unwrap();
+            assert_eq!(token.content, r#"say \"hi\"\n\t\\ \u{1F600} it's"#);
+            assert_eq!(token.value().unwrap(), "say \"hi\"\n\t\\ \u{1F600} it's");
+
+            let token = tokenize(r"'it\'s'").next().unwrap();
+            assert_eq!(token.value().unwrap(), "it's");
+        }
+
+        #[test]
+        fn it_should_continue_strings_after_escaped_line_breaks() {
+            let tokens: Vec<Token> = tokenize("> \"one \\\n    two\" a").collect();
+            assert_eq!(tokens.len(), 3);
+            assert_eq!(tokens[1].value().unwrap(), "one two");
+            assert_eq!(tokens[1].position, (Position(1, 2), Position(2, 8)));
+            assert_eq!(tokens[2].kind, Some(TokenKind::Identifier("a")));
+        }
+
+        #[test]
+        fn it_should_parse_raw_strings() {
+            let token = tokenize(r#"r"C:\docs\n""#).next().unwrap();
+            assert_eq!(token.kind, Some(TokenKind::StringLiteral(r"C:\docs\n")));
+            assert_eq!(token.value().unwrap(), r"C:\docs\n");
+
+            let token = tokenize(r###"r#"say "hi""#"###).next().unwrap();
+            assert_eq!(token.value().unwrap(), r#"say "hi""#);
+            assert_eq!(token.span, Span::new(0, 13));
+            assert!(token.raw);
+            let synthesized = Token { len: 1, ..token };
+            assert_eq!(synthesized.value().unwrap(), r#"say "hi""#);
+            assert!(!tokenize(r#""a\n""#).next().unwrap().raw);
+
+            let tokens: Vec<Token> = tokenize("r \"a\"").collect();
+            assert_eq!(tokens[0].kind, Some(TokenKind::Identifier("r")));
+        }
+
+        #[test]
+        fn it_should_parse_multi_line_raw_strings() {
+            let tokens: Vec<Token> = tokenize("x = r\"one\n  two\"\ny").collect();
+            assert_eq!(tokens[2].value().unwrap(), "one\n  two");
+            assert_eq!(tokens[2].level, 0);
+            assert_eq!(tokens[3].kind, Some(TokenKind::Identifier("y")));
+            assert_eq!(tokens[3].position.0, Position(3, 0));
+        }
+
+        #[test]
+        fn it_should_reject_invalid_escapes() {
+            for source in &[r#""\q""#, r#""\u{}""#, r#""\u{D800}""#, r#""\u{1234567}""#, r#""\u12""#] {
+                let error = try_tokenize(source).find_map(Result::err).expect(source);
+                assert_eq!(error.code(), "L0008", "{}", source);
+            }
+        }
+
+        #[test]
+        fn it_should_reject_unescaped_line_breaks() {
+            let error = try_tokenize("\"one\ntwo\"").find_map(Result::err).unwrap();
+            assert_eq!(error.code(), "L0005");
+            assert_eq!(error.position(), Position(1, 4));
+        }
     }
     mod comments {
-        use super::{tokenize, Position, Token, TokenKind};
+        use super::{tokenize, Position, Span, Token, TokenKind};
         #[test]
         fn it_should_parse_single_line_comments() {
             let mut stream = tokenize("//abc");
@@ -188,8 +273,10 @@ mod lib {
                     kind: Some(TokenKind::Comment),
                     content: "abc",
                     position: (Position(1, 0), Position(1, 5)),
+                    span: Span::new(0, 5),
                     len: 5,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
@@ -202,8 +289,10 @@ mod lib {
                     kind: Some(TokenKind::Comment),
                     content: "abc\nSOME",
                     position: (Position(1, 0), Position(2, 6)),
+                    span: Span::new(0, 12),
                     len: 12,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
@@ -217,8 +306,10 @@ mod lib {
                     kind: Some(TokenKind::Comment),
                     content: "\n* abc\n* SOME\n",
                     position: (Position(1, 0), Position(4, 2)),
+                    span: Span::new(0, 18),
                     len: 18,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
@@ -232,8 +323,10 @@ mod lib {
                     kind: Some(TokenKind::Comment),
                     content: "some",
                     position: (Position(1, 0), Position(1, 6)),
+                    span: Span::new(0, 6),
                     len: 6,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
 
@@ -243,15 +336,31 @@ mod lib {
                     kind: Some(TokenKind::Comment),
                     content: "\n* abc\n* SOME\n",
                     position: (Position(3, 0), Position(6, 2)),
+                    span: Span::new(8, 26),
                     len: 18,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
+
+        #[test]
+        fn it_should_close_multi_line_comments_at_any_offset() {
+            let tokens: Vec<Token> = tokenize("/* a */ b").collect();
+            assert_eq!(tokens[0].content, " a ");
+            assert_eq!(tokens[1].kind, Some(TokenKind::Identifier("b")));
+        }
+
+        #[test]
+        fn it_should_reject_unclosed_multi_line_comments() {
+            let error = crate::try_tokenize("a\n/* b").find_map(Result::err).unwrap();
+            assert_eq!(error.code(), "L0004");
+        }
     }
     
     mod numbers {
-        use super::{tokenize, Position, Token, TokenKind};
+        use super::{tokenize, Position, Span, Token, TokenKind};
+        use crate::try_tokenize;
         #[test]
         fn it_should_parse_numbers() {
             let mut stream = tokenize("123");
@@ -261,92 +370,112 @@ mod lib {
                     kind: Some(TokenKind::Int(123)),
                     content: "123",
                     position: (Position(1, 0), Position(1, 3)),
+                    span: Span::new(0, 3),
                     len: 3,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
 
         #[test]
         fn it_should_parse_numbers_with_decimal_separator() {
-            let mut stream = tokenize("123,321");
+            let mut stream = tokenize("123.321");
             assert_eq!(
                 stream.next().unwrap(),
                 Token {
                     kind: Some(TokenKind::Float(123.321)),
-                    content: "123,321",
+                    content: "123.321",
                     position: (Position(1, 0), Position(1, 7)),
+                    span: Span::new(0, 7),
                     len: 7,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
 
         #[test]
-        fn it_should_parse_numbers_with_another_decimal_separator() {
-            let mut stream = tokenize("123.321");
+        fn it_should_not_take_comas_into_numbers() {
+            let kinds: Vec<_> = tokenize("123,321 1,000.5").map(|token| token.kind.unwrap()).collect();
             assert_eq!(
-                stream.next().unwrap(),
-                Token {
-                    kind: Some(TokenKind::Float(123.321)),
-                    content: "123.321",
-                    position: (Position(1, 0), Position(1, 7)),
-                    len: 7,
-                    level: 0
-                }
+                kinds,
+                vec![
+                    TokenKind::Int(123),
+                    TokenKind::Coma,
+                    TokenKind::Int(321),
+                    TokenKind::Int(1),
+                    TokenKind::Coma,
+                    TokenKind::Float(0.5),
+                ]
             );
         }
 
         #[test]
-        fn it_should_parse_numbers_with_decimal_and_thousands_separator() {
-            let mut stream = tokenize("123.321,456");
+        fn it_should_parse_numbers_with_digit_separators() {
+            let mut stream = tokenize("1_000_000 0.000_1");
             assert_eq!(
                 stream.next().unwrap(),
                 Token {
-                    kind: Some(TokenKind::Float(123321.456)),
-                    content: "123.321,456",
-                    position: (Position(1, 0), Position(1, 11)),
-                    len: 11,
-                    level: 0
+                    kind: Some(TokenKind::Int(1_000_000)),
+                    content: "1_000_000",
+                    position: (Position(1, 0), Position(1, 9)),
+                    span: Span::new(0, 9),
+                    len: 9,
+                    level: 0,
+                    raw: false,
                 }
             );
+            assert_eq!(stream.next().unwrap().kind, Some(TokenKind::Float(0.0001)));
         }
 
         #[test]
-        fn it_should_parse_numbers_with_decimal_and_multiple_thousands_separator() {
-            let mut stream = tokenize("123.321.123,456");
+        fn it_should_parse_exponents() {
+            let kinds: Vec<_> = tokenize("1e3 2.5E-3 -4e+2").map(|token| token.kind.unwrap()).collect();
             assert_eq!(
-                stream.next().unwrap(),
-                Token {
-                    kind: Some(TokenKind::Float(123321123.456)),
-                    content: "123.321.123,456",
-                    position: (Position(1, 0), Position(1, 15)),
-                    len: 15,
-                    level: 0
-                }
+                kinds,
+                vec![
+                    TokenKind::Float(1000.0),
+                    TokenKind::Float(0.0025),
+                    TokenKind::Float(-400.0),
+                ]
             );
         }
 
         #[test]
-        fn it_should_parse_numbers_with_another_decimal_and_multiple_thousands_separators() {
-            let mut stream = tokenize("123,321,123.456");
+        fn it_should_parse_hexadecimal_numbers() {
+            let kinds: Vec<_> = tokenize("0xFF 0x1_0 -0x10").map(|token| token.kind.unwrap()).collect();
             assert_eq!(
-                stream.next().unwrap(),
-                Token {
-                    kind: Some(TokenKind::Float(123321123.456)),
-                    content: "123,321,123.456",
-                    position: (Position(1, 0), Position(1, 15)),
-                    len: 15,
-                    level: 0
-                }
+                kinds,
+                vec![TokenKind::Int(255), TokenKind::Int(16), TokenKind::Int(-16)]
             );
         }
 
         #[test]
-        #[should_panic]
-        fn it_should_panic_when_encountering_multiple_decimal_separators() {
-            let mut stream = tokenize("123.321.123,456,654");
-            stream.next();
+        fn it_should_leave_letters_after_numbers() {
+            let kinds: Vec<_> = tokenize("2em 0x a.1.b").map(|token| token.kind.unwrap()).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    TokenKind::Int(2),
+                    TokenKind::Identifier("em"),
+                    TokenKind::Int(0),
+                    TokenKind::Identifier("x"),
+                    TokenKind::Identifier("a"),
+                    TokenKind::Dot,
+                    TokenKind::Int(1),
+                    TokenKind::Dot,
+                    TokenKind::Identifier("b"),
+                ]
+            );
+        }
+
+        #[test]
+        fn it_should_reject_misplaced_digit_separators() {
+            for source in &["1__0", "1_", "1_.5", "0x1_", "-1_e3"] {
+                let result: Result<Vec<_>, _> = try_tokenize(source).collect();
+                assert!(result.is_err(), "{}", source);
+            }
         }
 
         #[test]
@@ -358,8 +487,10 @@ mod lib {
                     kind: Some(TokenKind::Int(-123)),
                     content: "-123",
                     position: (Position(1, 0), Position(1, 4)),
+                    span: Span::new(0, 4),
                     len: 4,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             )
         }
@@ -373,15 +504,17 @@ mod lib {
                     kind: Some(TokenKind::Float(-123.312)),
                     content: "-123.312",
                     position: (Position(1, 0), Position(1, 8)),
+                    span: Span::new(0, 8),
                     len: 8,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             )
         }
     }
 
     mod content_block {
-        use super::{tokenize, Position, Token, TokenKind};
+        use super::{tokenize, Position, Span, Token, TokenKind};
         #[test]
         fn it_should_parse_content_blocks() {
             let mut stream = tokenize("`abc`");
@@ -391,8 +524,10 @@ mod lib {
                     kind: Some(TokenKind::ContentBlock),
                     content: "abc",
                     position: (Position(1, 0), Position(1, 5)),
+                    span: Span::new(0, 5),
                     len: 5,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
@@ -406,12 +541,28 @@ mod lib {
                     kind: Some(TokenKind::ContentBlock),
                     content: "\n\tabc\n\t\tabc\n",
                     position: (Position(1, 0), Position(4, 1)),
+                    span: Span::new(0, 14),
                     len: 14,
-                    level: 0
+                    level: 0,
+                    raw: false,
                 }
             );
         }
 
+        #[test]
+        fn it_should_not_close_content_blocks_on_escaped_backticks() {
+            let tokens: Vec<Token> = tokenize("`a \\` b` c").collect();
+            assert_eq!(tokens[0].content, "a \\` b");
+            assert_eq!(tokens[1].kind, Some(TokenKind::Identifier("c")));
+        }
+
+        #[test]
+        fn it_should_close_content_blocks_after_escaped_backslashes() {
+            let tokens: Vec<Token> = crate::try_tokenize("x\n    `a\\\\`\n").collect::<Result<_, _>>().unwrap();
+            assert_eq!(tokens[1].kind, Some(TokenKind::ContentBlock));
+            assert_eq!(tokens[1].content, "a\\\\");
+        }
+
         #[test]
         #[should_panic]
         fn it_should_panic_when_content_block_is_not_closed_properly() {
@@ -420,8 +571,46 @@ mod lib {
         }
     }
 
+    mod indentation {
+        use crate::cursor::{Cursor, Position};
+        use crate::diagnostics::ToDiagnostic;
+        use crate::errors::LexerError;
+        use crate::try_tokenize;
+
+        #[test]
+        fn it_should_report_unexpected_indent_width_instead_of_panicking() {
+            let results: Vec<_> = try_tokenize("a\n    b\n      c").collect();
+            match results.last() {
+                Some(Err(LexerError::UnexpectedIndentWidth(6, Position(3, 0)))) => {}
+                other => panic!("expected indentation error, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn it_should_label_misaligned_indentation() {
+            let diagnostic = LexerError::UnexpectedIndentWidth(6, Position(3, 0)).to_diagnostic();
+            assert_eq!(diagnostic.code, "L0009");
+            assert_eq!(format!("{}", diagnostic), "error[L0009]: unexpected indentation width 6 at [3:0]");
+            assert_eq!(diagnostic.labels[0].position, (Position(3, 0), Position(3, 6)));
+        }
+
+        #[test]
+        fn it_should_keep_the_level_of_misaligned_lines() {
+            let mut cursor = Cursor::new("a\n    b\n      c d", Position(1, 0), 0, 0);
+            let levels: Vec<u16> = std::iter::from_fn(|| {
+                if cursor.is_eof() {
+                    return None;
+                }
+                cursor.advance_token().ok().map(|token| token.level)
+            })
+            .collect();
+            assert_eq!(levels, vec![0, 1, 1, 1]);
+            assert!(cursor.take_indent_error().is_some());
+        }
+    }
+
     mod single_char_tokens {
-        use super::{tokenize, Position, Token, TokenKind};
+        use super::{tokenize, Position, Span, Token, TokenKind};
         #[test]
         fn it_should_parse_tokens() {
             let mut stream = tokenize("!?&/#");
@@ -430,6 +619,7 @@ mod lib {
                 Token {
                     kind: Some(TokenKind::Exclamation),
                     position: (Position(1, 0), Position(1, 1)),
+                    span: Span::new(0, 1),
                     content: "!",
                     level: 0,
                     ..Default::default()
@@ -440,6 +630,7 @@ mod lib {
                 Token {
                     kind: Some(TokenKind::Question),
                     position: (Position(1, 1), Position(1, 2)),
+                    span: Span::new(1, 2),
                     content: "?",
                     level: 0,
                     ..Default::default()
@@ -450,6 +641,7 @@ mod lib {
                 Token {
                     kind: Some(TokenKind::Ampersand),
                     position: (Position(1, 2), Position(1, 3)),
+                    span: Span::new(2, 3),
                     content: "&",
                     level: 0,
                     ..Default::default()
@@ -460,6 +652,7 @@ mod lib {
                 Token {
                     kind: Some(TokenKind::Slash),
                     position: (Position(1, 3), Position(1, 4)),
+                    span: Span::new(3, 4),
                     content: "/",
                     level: 0,
                     ..Default::default()
@@ -470,6 +663,7 @@ mod lib {
                 Token {
                     kind: Some(TokenKind::HashPound),
                     position: (Position(1, 4), Position(1, 5)),
+                    span: Span::new(4, 5),
                     content: "#",
                     level: 0,
                     ..Default::default()
@@ -480,3 +674,102 @@ mod lib {
 
 
 }
+
+mod diagnostics {
+    use super::Position;
+    use crate::diagnostics::{Diagnostic, Renderer, Severity, ToDiagnostic};
+    use crate::errors::LexerError;
+
+    #[test]
+    fn it_should_convert_errors_to_diagnostics() {
+        let diagnostic = LexerError::UnexpectedCharacter('^', Position(1, 2)).to_diagnostic();
+        assert_eq!(diagnostic.code, "L0002");
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.position(), Some(Position(1, 2)));
+        assert_eq!(format!("{}", diagnostic), "error[L0002]: unexpected character `^` at [1:2]");
+    }
+
+    #[test]
+    fn it_should_render_source_snippet() {
+        let diagnostic = Diagnostic::error("L0002", "unexpected character `^`")
+            .with_primary_label((Position(2, 4), Position(2, 5)), "not allowed here")
+            .with_secondary_label((Position(1, 0), Position(1, 5)), "in this block")
+            .with_note("a note");
+
+        let rendered = Renderer::new(false)
+            .with_file_name("test.ra")
+            .render("block\n\tab^c", &diagnostic);
+
+        assert_eq!(
+            rendered,
+            "error[L0002]: unexpected character `^`\n \
+             --> test.ra:2:5\n  \
+              |\n\
+             1 | block\n  \
+              | ----- in this block\n\
+             2 | \tab^c\n  \
+              | \t   ^ not allowed here\n  \
+              = note: a note\n"
+        );
+    }
+
+    #[test]
+    fn it_should_render_colors_only_when_asked() {
+        let diagnostic = LexerError::UnexpectedEndOfLine(Position(1, 3)).to_diagnostic();
+        assert!(Renderer::new(true).render("'ab", &diagnostic).contains("\u{1b}[1;31m"));
+        assert!(!Renderer::new(false).render("'ab", &diagnostic).contains("\u{1b}["));
+    }
+}
+
+mod spans {
+    use super::{tokenize, Position, Span, Token};
+    use crate::line_index::{LineCol, LineIndex};
+
+    #[test]
+    fn it_should_track_byte_offsets_of_multibyte_tokens() {
+        let input = "фу `😠`\n\tnext";
+        let tokens: Vec<Token> = tokenize(input).collect();
+        assert_eq!(tokens[0].span, Span::new(0, 4));
+        assert_eq!(tokens[0].content, "фу");
+        assert_eq!(tokens[1].span, Span::new(5, 11));
+        assert_eq!(&input[tokens[2].span.start..tokens[2].span.end], "next");
+        assert_eq!(tokens[2].position.0, Position(2, 1));
+    }
+
+    #[test]
+    fn it_should_treat_crlf_as_single_line_break() {
+        let tokens: Vec<Token> = tokenize("a\r\nb").collect();
+        assert_eq!(tokens[1].position, (Position(2, 0), Position(2, 1)));
+        assert_eq!(tokens[1].span, Span::new(3, 4));
+    }
+
+    #[test]
+    fn it_should_support_more_than_u16_lines() {
+        let input = format!("{}abc", "\n".repeat(70_000));
+        let token = tokenize(&input).next().unwrap();
+        assert_eq!(token.position.0, Position(70_001, 0));
+        assert_eq!(token.span, Span::new(70_000, 70_003));
+    }
+
+    #[test]
+    fn it_should_convert_offsets_to_line_and_column() {
+        let index = LineIndex::new("ab\n😠c\r\nd");
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line_col(0), LineCol { line: 0, col: 0 });
+        assert_eq!(index.line_col(7), LineCol { line: 1, col: 1 });
+        assert_eq!(index.line_col_utf16(7), LineCol { line: 1, col: 2 });
+        assert_eq!(index.line_col(10), LineCol { line: 2, col: 0 });
+        assert_eq!(index.position(7), Position(2, 1));
+    }
+
+    #[test]
+    fn it_should_convert_line_and_column_to_offsets() {
+        let index = LineIndex::new("ab\n😠c\r\nd");
+        assert_eq!(index.offset(LineCol { line: 1, col: 1 }), Some(7));
+        assert_eq!(index.offset_utf16(LineCol { line: 1, col: 2 }), Some(7));
+        assert_eq!(index.offset(LineCol { line: 1, col: 2 }), Some(8));
+        assert_eq!(index.offset(LineCol { line: 1, col: 3 }), None);
+        assert_eq!(index.offset(LineCol { line: 3, col: 0 }), None);
+        assert_eq!(index.position_offset(Position(3, 0)), Some(10));
+    }
+}
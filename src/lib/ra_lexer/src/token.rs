@@ -1,4 +1,7 @@
 use crate::cursor::Position;
+use crate::escape::unescape;
+use crate::span::Span;
+use std::borrow::Cow;
 use std::fmt::Display;
 use serde::{Serialize};
 
@@ -7,6 +10,7 @@ pub enum TokenKind<'a> {
     Ampersand,
     Asterisk,
     At,
+    Boolean(bool),
     CloseCurlyBrace,
     CloseParentheses,
     CloseSquareBrace,
@@ -26,6 +30,7 @@ pub enum TokenKind<'a> {
     Int(i64),
     Less,
     Minus,
+    Null,
     OpenCurlyBrace,
     OpenParentheses,
     OpenSquareBrace,
@@ -42,18 +47,23 @@ pub enum TokenKind<'a> {
 
 /// Token type
 /// pub kind: Option<TokenKind<'a>>,
-/// pub len: u16,
+/// pub len: u32,
 /// pub content: &'a str,
 /// pub position: (Position, Position),
+/// pub span: Span, - byte range of the whole token in the original source
 /// pub level: u16,
+/// pub raw: bool, - string literal written as `r"..."`, its escapes aren't decoded
 /// 
 #[derive(Debug, PartialEq, Clone, Copy, Serialize)]
 pub struct Token<'a> {
     pub kind: Option<TokenKind<'a>>,
-    pub len: u16,
+    pub len: u32,
     pub content: &'a str,
     pub position: (Position, Position),
+    pub span: Span,
     pub level: u16,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub raw: bool,
 }
 
 const EMPTY_CONTENT: &str = "";
@@ -64,18 +74,152 @@ impl<'a> Default for Token<'a> {
             len: 1,
             content: EMPTY_CONTENT,
             position: (Position(0, 0), Position(0, 1)),
+            span: Span::default(),
             level: 0,
             kind: None,
+            raw: false,
         }
     }
 }
 
+impl<'a> TokenKind<'a> {
+    /// Same kind with `content` in place of the borrowed text of
+    /// identifiers and string literals
+    pub fn with_content<'b>(self, content: &'b str) -> TokenKind<'b> {
+        match self {
+            TokenKind::Ampersand => TokenKind::Ampersand,
+            TokenKind::Asterisk => TokenKind::Asterisk,
+            TokenKind::At => TokenKind::At,
+            TokenKind::Boolean(value) => TokenKind::Boolean(value),
+            TokenKind::CloseCurlyBrace => TokenKind::CloseCurlyBrace,
+            TokenKind::CloseParentheses => TokenKind::CloseParentheses,
+            TokenKind::CloseSquareBrace => TokenKind::CloseSquareBrace,
+            TokenKind::Colon => TokenKind::Colon,
+            TokenKind::Coma => TokenKind::Coma,
+            TokenKind::Comment => TokenKind::Comment,
+            TokenKind::ContentBlock => TokenKind::ContentBlock,
+            TokenKind::Dollar => TokenKind::Dollar,
+            TokenKind::Dot => TokenKind::Dot,
+            TokenKind::Equals => TokenKind::Equals,
+            TokenKind::Exclamation => TokenKind::Exclamation,
+            TokenKind::Float(value) => TokenKind::Float(value),
+            TokenKind::ForwardSlash => TokenKind::ForwardSlash,
+            TokenKind::Greater => TokenKind::Greater,
+            TokenKind::HashPound => TokenKind::HashPound,
+            TokenKind::Identifier(_) => TokenKind::Identifier(content),
+            TokenKind::Int(value) => TokenKind::Int(value),
+            TokenKind::Less => TokenKind::Less,
+            TokenKind::Minus => TokenKind::Minus,
+            TokenKind::Null => TokenKind::Null,
+            TokenKind::OpenCurlyBrace => TokenKind::OpenCurlyBrace,
+            TokenKind::OpenParentheses => TokenKind::OpenParentheses,
+            TokenKind::OpenSquareBrace => TokenKind::OpenSquareBrace,
+            TokenKind::Percent => TokenKind::Percent,
+            TokenKind::Pipe => TokenKind::Pipe,
+            TokenKind::Plus => TokenKind::Plus,
+            TokenKind::Power => TokenKind::Power,
+            TokenKind::Question => TokenKind::Question,
+            TokenKind::SemiColon => TokenKind::SemiColon,
+            TokenKind::Slash => TokenKind::Slash,
+            TokenKind::StringLiteral(_) => TokenKind::StringLiteral(content),
+            TokenKind::Tilde => TokenKind::Tilde,
+        }
+    }
+}
+
+impl<'a> Token<'a> {
+    /// Same token lexed from another version of its source,
+    /// where it starts at `span` and `position`.
+    /// Quotes of string literals, `r#"` and `"#` of raw strings
+    /// and backticks of content blocks stay out of the content.
+    pub fn relocated<'b>(&self, source: &'b str, span: Span, position: (Position, Position)) -> Token<'b> {
+        let content = match self.kind {
+            Some(TokenKind::StringLiteral(_)) | Some(TokenKind::ContentBlock) => {
+                let delimiters = self.len as usize - self.content.len();
+                &source[span.start + delimiters - delimiters / 2..span.end - delimiters / 2]
+            }
+            _ => &source[span.start..span.end],
+        };
+
+        Token {
+            kind: self.kind.map(|kind| kind.with_content(content)),
+            len: self.len,
+            content,
+            position,
+            span,
+            level: self.level,
+            raw: self.raw,
+        }
+    }
+
+    /// Text a string literal stands for, escapes decoded and raw strings as written,
+    /// `None` for other tokens
+    pub fn value(&self) -> Option<Cow<'a, str>> {
+        match self.kind {
+            Some(TokenKind::StringLiteral(text)) if self.raw => Some(Cow::Borrowed(text)),
+            Some(TokenKind::StringLiteral(text)) => Some(unescape(text)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Display for TokenKind<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
+        let symbol = match self {
+            TokenKind::Identifier(_) => return write!(f, "identifier"),
+            TokenKind::Int(_) => return write!(f, "integer"),
+            TokenKind::Float(_) => return write!(f, "float"),
+            TokenKind::StringLiteral(_) => return write!(f, "string literal"),
+            TokenKind::Boolean(_) => return write!(f, "boolean"),
+            TokenKind::Null => "none",
+            TokenKind::ContentBlock => return write!(f, "content block"),
+            TokenKind::Comment => return write!(f, "comment"),
+            TokenKind::Ampersand => "&",
+            TokenKind::Asterisk => "*",
+            TokenKind::At => "@",
+            TokenKind::CloseCurlyBrace => "}",
+            TokenKind::CloseParentheses => ")",
+            TokenKind::CloseSquareBrace => "]",
+            TokenKind::Colon => ":",
+            TokenKind::Coma => ",",
+            TokenKind::Dollar => "$",
+            TokenKind::Dot => ".",
+            TokenKind::Equals => "=",
+            TokenKind::Exclamation => "!",
+            TokenKind::ForwardSlash => "\\",
+            TokenKind::Greater => ">",
+            TokenKind::HashPound => "#",
+            TokenKind::Less => "<",
+            TokenKind::Minus => "-",
+            TokenKind::OpenCurlyBrace => "{",
+            TokenKind::OpenParentheses => "(",
+            TokenKind::OpenSquareBrace => "[",
+            TokenKind::Percent => "%",
+            TokenKind::Pipe => "|",
+            TokenKind::Plus => "+",
+            TokenKind::Power => "^",
+            TokenKind::Question => "?",
+            TokenKind::SemiColon => ";",
+            TokenKind::Slash => "/",
+            TokenKind::Tilde => "~",
+        };
+
+        write!(f, "`{}`", symbol)
+    }
+}
+
 impl<'a> Display for Token<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "token {:?}, {} at {} -> {}",
-            self.kind, self.content, self.position.0, self.level
-        )
+        match self.kind {
+            Some(TokenKind::Identifier(_))
+            | Some(TokenKind::Int(_))
+            | Some(TokenKind::Float(_))
+            | Some(TokenKind::Boolean(_))
+            | Some(TokenKind::StringLiteral(_)) => {
+                write!(f, "{} `{}`", self.kind.unwrap(), self.content)
+            }
+            Some(kind) => write!(f, "{}", kind),
+            None => write!(f, "end of input"),
+        }
     }
 }
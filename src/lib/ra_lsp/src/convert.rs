@@ -0,0 +1,94 @@
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Range, Url,
+};
+use ra_lexer::cursor::Position;
+use ra_lexer::diagnostics::{Diagnostic, Severity};
+use ra_lexer::line_index::{LineCol, LineIndex};
+use ra_lexer::span::Span;
+
+/// Editor position of a byte offset, columns are UTF-16 code units
+pub fn position(index: &LineIndex, offset: usize) -> lsp_types::Position {
+    let LineCol { line, col } = index.line_col_utf16(offset);
+    lsp_types::Position::new(line, col)
+}
+
+pub fn range(index: &LineIndex, span: Span) -> Range {
+    Range::new(position(index, span.start), position(index, span.end))
+}
+
+/// Byte offset of an editor position
+pub fn offset(index: &LineIndex, position: lsp_types::Position) -> Option<usize> {
+    index.offset_utf16(LineCol {
+        line: position.line,
+        col: position.character,
+    })
+}
+
+/// Byte offset of a lexer position, clamped to the end of its line
+fn lexer_offset(index: &LineIndex, position: Position) -> usize {
+    index
+        .position_offset(position)
+        .or_else(|| {
+            index
+                .line_span(position.0.saturating_sub(1))
+                .map(|span| span.end)
+        })
+        .unwrap_or_else(|| index.text().len())
+}
+
+pub fn lexer_range(index: &LineIndex, (start, end): (Position, Position)) -> Range {
+    let start = lexer_offset(index, start);
+    let end = lexer_offset(index, end).max(start);
+    range(index, Span::new(start, end))
+}
+
+pub fn diagnostic(uri: &Url, index: &LineIndex, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let range = diagnostic
+        .primary_label()
+        .map(|label| lexer_range(index, label.position))
+        .unwrap_or_default();
+
+    let related_information: Vec<DiagnosticRelatedInformation> = diagnostic
+        .labels
+        .iter()
+        .filter(|label| !label.primary)
+        .map(|label| DiagnosticRelatedInformation {
+            location: Location::new(uri.clone(), lexer_range(index, label.position)),
+            message: label.message.clone(),
+        })
+        .collect();
+
+    let mut message = diagnostic.message.clone();
+    for note in diagnostic.notes.iter() {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    for suggestion in diagnostic.suggestions.iter() {
+        message.push_str(&format!(
+            "\nhelp: {}: `{}`",
+            suggestion.message, suggestion.replacement
+        ));
+    }
+
+    lsp_types::Diagnostic {
+        range,
+        severity: Some(severity(diagnostic.severity)),
+        code: Some(NumberOrString::String(diagnostic.code.to_owned())),
+        source: Some("ra".to_owned()),
+        message,
+        related_information: if related_information.is_empty() {
+            None
+        } else {
+            Some(related_information)
+        },
+        ..lsp_types::Diagnostic::default()
+    }
+}
+
+fn severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+        Severity::Note => DiagnosticSeverity::INFORMATION,
+        Severity::Help => DiagnosticSeverity::HINT,
+    }
+}
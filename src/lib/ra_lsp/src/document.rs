@@ -0,0 +1,77 @@
+use ra_lexer::diagnostics::{Diagnostic, ToDiagnostic};
+use ra_lexer::token::{Token, TokenKind};
+use ra_lexer::try_tokenize;
+use ra_lexer::cursor::Position;
+use ra_parser::block::Block;
+use ra_parser::errors::ParserError;
+use ra_parser::parser::parse;
+use std::panic;
+
+/// Parses a document, parse errors still produce a partial program.
+///
+/// Should the parser panic there is no program,
+/// the document gets an error at the last token that lexed instead.
+pub fn parse_document(text: &str) -> (Option<Block<'_>>, Vec<Diagnostic>) {
+    let parsed = panic::catch_unwind(|| match parse(text) {
+        Ok(program) => (program, Vec::new()),
+        Err((errors, program)) => (program, errors.iter().map(|e| e.to_diagnostic()).collect()),
+    });
+
+    match parsed {
+        Ok((program, diagnostics)) => (Some(program), diagnostics),
+        Err(_) => {
+            let position = tokens(text)
+                .last()
+                .map_or((Position(1, 0), Position(1, 1)), |token| token.position);
+            (None, vec![ParserError::Aborted(position).to_diagnostic()])
+        }
+    }
+}
+
+/// Tokens up to the first lexer error, comments excluded
+pub fn tokens(text: &str) -> Vec<Token<'_>> {
+    try_tokenize(text)
+        .map_while(Result::ok)
+        .filter(|token| token.kind != Some(TokenKind::Comment))
+        .collect()
+}
+
+/// Identifier under the cursor together with the reference path it's part of
+#[derive(Debug)]
+pub struct Reference<'a> {
+    pub token: Token<'a>,
+    /// Path segments up to and including the token, `a.b` when cursor is on `b` of `a.b.c`
+    pub path: Vec<&'a str>,
+    /// Token right before the path, e.g. `!` or `@`
+    pub prefix: Option<TokenKind<'a>>,
+}
+
+pub fn reference_at(text: &str, offset: usize) -> Option<Reference<'_>> {
+    let tokens = tokens(text);
+    let index = tokens.iter().position(|token| {
+        matches!(token.kind, Some(TokenKind::Identifier(_)))
+            && (token.span.contains(offset) || token.span.end == offset)
+    })?;
+
+    let mut start = index;
+    while start >= 2
+        && tokens[start - 1].kind == Some(TokenKind::Dot)
+        && matches!(tokens[start - 2].kind, Some(TokenKind::Identifier(_)))
+    {
+        start -= 2;
+    }
+
+    Some(Reference {
+        token: tokens[index],
+        path: tokens[start..=index]
+            .iter()
+            .step_by(2)
+            .map(|token| token.content)
+            .collect(),
+        prefix: if start > 0 {
+            tokens[start - 1].kind
+        } else {
+            None
+        },
+    })
+}
@@ -0,0 +1,308 @@
+use super::convert;
+use super::document::{parse_document, reference_at};
+use super::world::World;
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, DocumentSymbol,
+    DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, Location, MarkupContent, MarkupKind, SymbolKind, Url,
+};
+use ra_analysis::builtins::{annotation_key, builtin_rule, ANNOTATION_KEYS, BUILTIN_RULES};
+use ra_analysis::assignments::lint_assignments;
+use ra_analysis::checks::check_sequence;
+use ra_analysis::items::{
+    block_name, find_declaration, find_item, is_declaration, named_block_at, named_blocks,
+    named_children,
+};
+use ra_analysis::privacy::{lint_privacy, Allowlist};
+use ra_analysis::types::{default_value, infer_input_type, item_input};
+use ra_lexer::line_index::LineIndex;
+use ra_lexer::span::Span;
+use ra_lexer::token::TokenKind;
+use ra_parser::block::{Block, BlockKind};
+use std::fs;
+
+pub fn diagnostics(uri: &Url, text: &str) -> Vec<lsp_types::Diagnostic> {
+    let index = LineIndex::new(text);
+    let (program, mut diagnostics) = parse_document(text);
+    if let Some(program) = program {
+        let allowlist = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| Allowlist::find(path.parent()?).ok())
+            .unwrap_or_default();
+        diagnostics.extend(lint_privacy(&program, &allowlist));
+        diagnostics.extend(lint_assignments(&program));
+        diagnostics.extend(check_sequence(&program));
+    }
+    diagnostics
+        .iter()
+        .map(|diagnostic| convert::diagnostic(uri, &index, diagnostic))
+        .collect()
+}
+
+pub fn document_symbols(
+    world: &World,
+    params: DocumentSymbolParams,
+) -> Option<DocumentSymbolResponse> {
+    let text = world.document(&params.text_document.uri)?;
+    let index = LineIndex::new(&text);
+    let (program, _) = parse_document(&text);
+    Some(DocumentSymbolResponse::Nested(symbols(&index, &program?)))
+}
+
+#[allow(deprecated)]
+fn symbols(index: &LineIndex, block: &Block) -> Vec<DocumentSymbol> {
+    named_children(block)
+        .into_iter()
+        .filter_map(|child| {
+            let name = block_name(child)?;
+            let (kind, detail) = if is_declaration(child) {
+                (SymbolKind::FUNCTION, None)
+            } else if let Some(input) = item_input(child) {
+                (SymbolKind::FIELD, Some(infer_input_type(input).to_string()))
+            } else {
+                (SymbolKind::OBJECT, None)
+            };
+            let children = symbols(index, child);
+
+            Some(DocumentSymbol {
+                name: name.content.to_owned(),
+                detail,
+                kind,
+                tags: None,
+                deprecated: None,
+                range: convert::range(index, child.span),
+                selection_range: convert::range(index, name.span),
+                children: if children.is_empty() {
+                    None
+                } else {
+                    Some(children)
+                },
+            })
+        })
+        .collect()
+}
+
+pub fn goto_definition(
+    world: &World,
+    params: GotoDefinitionParams,
+) -> Option<GotoDefinitionResponse> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let text = world.document(&uri)?;
+    let index = LineIndex::new(&text);
+    let offset = convert::offset(&index, params.text_document_position_params.position)?;
+    let reference = reference_at(&text, offset)?;
+
+    let location = match reference.prefix {
+        Some(TokenKind::At) => {
+            let (file, path) = reference.path.split_first()?;
+            let target = uri.join(&format!("{}.ra", file)).ok()?;
+            let target_text = world.document(&target)?;
+            let target_index = LineIndex::new(&target_text);
+            let (program, _) = parse_document(&target_text);
+            let span = program
+                .as_ref()
+                .and_then(|program| find_item(program, path))
+                .and_then(block_name)
+                .map(|token| token.span)
+                .unwrap_or_default();
+            Location::new(target, convert::range(&target_index, span))
+        }
+        Some(TokenKind::Exclamation) => {
+            let (program, _) = parse_document(&text);
+            let declaration = find_declaration(program.as_ref()?, reference.token.content)?;
+            Location::new(uri, convert::range(&index, block_name(declaration)?.span))
+        }
+        _ => {
+            let (program, _) = parse_document(&text);
+            let item = find_item(program.as_ref()?, &reference.path)?;
+            Location::new(uri, convert::range(&index, block_name(item)?.span))
+        }
+    };
+
+    Some(GotoDefinitionResponse::Scalar(location))
+}
+
+pub fn hover(world: &World, params: HoverParams) -> Option<Hover> {
+    let uri = params.text_document_position_params.text_document.uri;
+    let text = world.document(&uri)?;
+    let index = LineIndex::new(&text);
+    let offset = convert::offset(&index, params.text_document_position_params.position)?;
+    let reference = reference_at(&text, offset)?;
+    let (program, _) = parse_document(&text);
+
+    let value = match reference.prefix {
+        Some(TokenKind::Exclamation) if reference.path.len() == 1 => {
+            match builtin_rule(reference.token.content) {
+                Some(description) => format!("**!{}**\n\n{}", reference.token.content, description),
+                None => {
+                    find_declaration(program.as_ref()?, reference.token.content)?;
+                    format!("**!{}**\n\ndeclared rule", reference.token.content)
+                }
+            }
+        }
+        Some(TokenKind::HashPound) if reference.path.len() == 1 => format!(
+            "**#{}**\n\n{}",
+            reference.token.content,
+            annotation_key(reference.token.content)?
+        ),
+        _ => describe_item(&text, named_block_at(program.as_ref()?, offset)?)?,
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(convert::range(&index, reference.token.span)),
+    })
+}
+
+fn describe_item(text: &str, item: &Block) -> Option<String> {
+    let name = block_name(item)?.content;
+    if is_declaration(item) {
+        return Some(format!("**:{}**\n\ndeclared rule", name));
+    }
+
+    let input = match item_input(item) {
+        Some(input) => input,
+        None => return Some(format!("**{}**", name)),
+    };
+
+    let mut value = format!("**{}**: `{}`", name, infer_input_type(input));
+    if let Some(default) = default_value(input) {
+        value.push_str(&format!("\n\ndefault: `{}`", default));
+    }
+    let rules: Vec<String> = input
+        .children
+        .iter()
+        .filter(|child| matches!(child.kind, BlockKind::Invocation(..)))
+        .map(|child| format!("`{}`", source(text, child.span)))
+        .collect();
+    if !rules.is_empty() {
+        value.push_str(&format!("\n\nrules: {}", rules.join(", ")));
+    }
+    Some(value)
+}
+
+fn source(text: &str, span: Span) -> &str {
+    text.get(span.start..span.end)
+        .unwrap_or_default()
+        .trim_end()
+}
+
+pub fn completion(world: &World, params: CompletionParams) -> Option<CompletionResponse> {
+    let uri = params.text_document_position.text_document.uri;
+    let text = world.document(&uri)?;
+    let index = LineIndex::new(&text);
+    let offset = convert::offset(&index, params.text_document_position.position)?;
+
+    let word_start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| ch.is_alphanumeric() || *ch == '_')
+        .last()
+        .map_or(offset, |(start, _)| start);
+    let trigger = text[..word_start].chars().last();
+    let (program, _) = parse_document(&text);
+
+    let items = match trigger {
+        Some('!') => {
+            let mut items: Vec<CompletionItem> = BUILTIN_RULES
+                .iter()
+                .map(|(name, description)| {
+                    completion_item(name, CompletionItemKind::FUNCTION, description)
+                })
+                .collect();
+            if let Some(program) = program.as_ref() {
+                items.extend(
+                    named_blocks(program)
+                        .into_iter()
+                        .filter(|block| is_declaration(block))
+                        .filter_map(block_name)
+                        .map(|name| {
+                            completion_item(
+                                name.content,
+                                CompletionItemKind::FUNCTION,
+                                "declared rule",
+                            )
+                        }),
+                );
+            }
+            items
+        }
+        Some('#') => ANNOTATION_KEYS
+            .iter()
+            .map(|(name, description)| {
+                completion_item(name, CompletionItemKind::PROPERTY, description)
+            })
+            .collect(),
+        Some('@') => sibling_files(&uri)
+            .into_iter()
+            .map(|name| completion_item(&name, CompletionItemKind::FILE, "file"))
+            .collect(),
+        _ => program
+            .as_ref()
+            .map(|program| {
+                named_blocks(program)
+                    .into_iter()
+                    .filter(|block| {
+                        !is_declaration(block)
+                            && !block_name(block).is_some_and(|name| name.span.contains(word_start))
+                    })
+                    .filter_map(block_name)
+                    .map(|name| completion_item(name.content, CompletionItemKind::FIELD, "item"))
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    Some(CompletionResponse::Array(dedup(items)))
+}
+
+fn completion_item(label: &str, kind: CompletionItemKind, detail: &str) -> CompletionItem {
+    CompletionItem {
+        label: label.to_owned(),
+        kind: Some(kind),
+        detail: Some(detail.to_owned()),
+        ..CompletionItem::default()
+    }
+}
+
+fn dedup(items: Vec<CompletionItem>) -> Vec<CompletionItem> {
+    let mut result: Vec<CompletionItem> = Vec::new();
+    for item in items {
+        if !result.iter().any(|other| other.label == item.label) {
+            result.push(item);
+        }
+    }
+    result
+}
+
+/// Names of `.ra` files next to the document, usable as `@name`
+fn sibling_files(uri: &Url) -> Vec<String> {
+    let directory = match uri
+        .to_file_path()
+        .ok()
+        .and_then(|path| path.parent().map(|p| p.to_owned()))
+    {
+        Some(directory) => directory,
+        None => return Vec::new(),
+    };
+    let mut names: Vec<String> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "ra"))
+                .filter(|path| Some(path.as_path()) != uri.to_file_path().ok().as_deref())
+                .filter_map(|path| {
+                    path.file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
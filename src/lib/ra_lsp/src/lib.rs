@@ -0,0 +1,166 @@
+//! Language server for `.ra` files.
+//!
+//! Speaks LSP over any `lsp_server::Connection`,
+//! the `ra_lsp` binary runs it over stdio.
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _,
+};
+use lsp_types::{
+    CompletionOptions, HoverProviderCapability, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, Url,
+};
+use std::error::Error;
+
+mod convert;
+mod document;
+mod handlers;
+pub mod world;
+
+use world::World;
+
+#[cfg(test)]
+mod tests;
+
+pub type LspResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["!".to_owned(), "#".to_owned(), "@".to_owned()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    }
+}
+
+/// Runs the server until the client shuts it down
+pub fn run(connection: Connection) -> LspResult<()> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut world = World::default();
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                connection
+                    .sender
+                    .send(Message::Response(handle_request(&world, request)))?;
+            }
+            Message::Notification(notification) => {
+                if let Some(uri) = handle_notification(&mut world, notification)? {
+                    publish_diagnostics(&connection, &world, uri)?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Runs the server over stdin and stdout
+pub fn run_stdio() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    run(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+fn handle_request(world: &World, request: Request) -> Response {
+    let Request { id, method, params } = request;
+    let result = match method.as_str() {
+        DocumentSymbolRequest::METHOD => {
+            dispatch::<DocumentSymbolRequest>(world, params, handlers::document_symbols)
+        }
+        GotoDefinition::METHOD => {
+            dispatch::<GotoDefinition>(world, params, handlers::goto_definition)
+        }
+        HoverRequest::METHOD => dispatch::<HoverRequest>(world, params, handlers::hover),
+        Completion::METHOD => dispatch::<Completion>(world, params, handlers::completion),
+        _ => {
+            return Response::new_err(
+                id,
+                ErrorCode::MethodNotFound as i32,
+                format!("unsupported request {}", method),
+            )
+        }
+    };
+
+    match result {
+        Ok(result) => Response::new_ok(id, result),
+        Err(e) => Response::new_err(id, ErrorCode::InvalidParams as i32, e.to_string()),
+    }
+}
+
+fn dispatch<R>(
+    world: &World,
+    params: serde_json::Value,
+    handler: fn(&World, R::Params) -> R::Result,
+) -> Result<serde_json::Value, serde_json::Error>
+where
+    R: lsp_types::request::Request,
+{
+    let params = serde_json::from_value(params)?;
+    serde_json::to_value(handler(world, params))
+}
+
+/// Applies document changes, returns uri of the changed document
+fn handle_notification(world: &mut World, notification: Notification) -> LspResult<Option<Url>> {
+    let Notification { method, params } = notification;
+    match method.as_str() {
+        DidOpenTextDocument::METHOD => {
+            let params: <DidOpenTextDocument as lsp_types::notification::Notification>::Params =
+                serde_json::from_value(params)?;
+            world.open(params.text_document.uri.clone(), params.text_document.text);
+            Ok(Some(params.text_document.uri))
+        }
+        DidChangeTextDocument::METHOD => {
+            let params: <DidChangeTextDocument as lsp_types::notification::Notification>::Params =
+                serde_json::from_value(params)?;
+            match params.content_changes.into_iter().last() {
+                Some(change) => {
+                    world.open(params.text_document.uri.clone(), change.text);
+                    Ok(Some(params.text_document.uri))
+                }
+                None => Ok(None),
+            }
+        }
+        DidCloseTextDocument::METHOD => {
+            let params: <DidCloseTextDocument as lsp_types::notification::Notification>::Params =
+                serde_json::from_value(params)?;
+            world.close(&params.text_document.uri);
+            Ok(Some(params.text_document.uri))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn publish_diagnostics(connection: &Connection, world: &World, uri: Url) -> LspResult<()> {
+    let diagnostics = match world.open_document(&uri) {
+        Some(text) => handlers::diagnostics(&uri, text),
+        None => Vec::new(),
+    };
+    let params = PublishDiagnosticsParams {
+        uri,
+        diagnostics,
+        version: None,
+    };
+    connection
+        .sender
+        .send(Message::Notification(Notification::new(
+            PublishDiagnostics::METHOD.to_owned(),
+            params,
+        )))?;
+    Ok(())
+}
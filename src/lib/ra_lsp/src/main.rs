@@ -0,0 +1,6 @@
+fn main() {
+    if let Err(e) = ra_lsp::run_stdio() {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,339 @@
+mod server {
+    use crate::run;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId};
+    use lsp_types::notification::{
+        DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as _,
+        PublishDiagnostics,
+    };
+    use lsp_types::request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Initialize, Shutdown,
+    };
+    use lsp_types::{
+        CompletionParams, CompletionResponse, DiagnosticSeverity, DidChangeTextDocumentParams,
+        DidOpenTextDocumentParams, DocumentSymbolParams, DocumentSymbolResponse,
+        GotoDefinitionParams, GotoDefinitionResponse, HoverContents, HoverParams, InitializeParams,
+        InitializedParams, NumberOrString, Position, PublishDiagnosticsParams, Range, SymbolKind,
+        TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+    };
+    use std::thread::{self, JoinHandle};
+
+    const MAIN: &str = "\
+:searchInput
+    >
+        !text
+
+age
+    > :default=42
+        !number:min=0
+tags
+    +>
+        !searchInput
+meals
+    @resources.mealsTypes
+    >
+        !select:single
+";
+
+    const RESOURCES: &str = "\
+:mealsTypes
+    breakfast
+    lunch
+";
+
+    struct Client {
+        connection: Connection,
+        server: Option<JoinHandle<()>>,
+        next_id: i32,
+    }
+
+    impl Client {
+        fn start() -> Self {
+            let (client, server) = Connection::memory();
+            let server = thread::spawn(move || run(server).expect("server failed"));
+            let mut client = Self {
+                connection: client,
+                server: Some(server),
+                next_id: 0,
+            };
+            client.request::<Initialize>(InitializeParams::default());
+            client.notify::<Initialized>(InitializedParams {});
+            client
+        }
+
+        fn request<R: lsp_types::request::Request>(&mut self, params: R::Params) -> R::Result {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection
+                .sender
+                .send(Message::Request(Request::new(
+                    id.clone(),
+                    R::METHOD.to_owned(),
+                    params,
+                )))
+                .unwrap();
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Response(response) if response.id == id => {
+                        assert!(response.error.is_none(), "{:?}", response.error);
+                        return serde_json::from_value(
+                            response.result.unwrap_or(serde_json::Value::Null),
+                        )
+                        .unwrap();
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        fn notify<N: lsp_types::notification::Notification>(&self, params: N::Params) {
+            self.connection
+                .sender
+                .send(Message::Notification(Notification::new(
+                    N::METHOD.to_owned(),
+                    params,
+                )))
+                .unwrap();
+        }
+
+        fn diagnostics(&self) -> PublishDiagnosticsParams {
+            loop {
+                match self.connection.receiver.recv().unwrap() {
+                    Message::Notification(notification)
+                        if notification.method == PublishDiagnostics::METHOD =>
+                    {
+                        return serde_json::from_value(notification.params).unwrap();
+                    }
+                    _ => continue,
+                }
+            }
+        }
+
+        fn open(&self, uri: &Url, text: &str) -> PublishDiagnosticsParams {
+            self.notify::<DidOpenTextDocument>(DidOpenTextDocumentParams {
+                text_document: TextDocumentItem::new(
+                    uri.clone(),
+                    "ra".to_owned(),
+                    1,
+                    text.to_owned(),
+                ),
+            });
+            self.diagnostics()
+        }
+
+        fn shutdown(mut self) {
+            self.request::<Shutdown>(());
+            self.notify::<Exit>(());
+            self.server.take().unwrap().join().unwrap();
+        }
+    }
+
+    fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///project/{}", name)).unwrap()
+    }
+
+    fn position_params(uri: &Url, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        )
+    }
+
+    #[test]
+    fn it_should_publish_diagnostics_on_change() {
+        let client = Client::start();
+        let main = uri("main.ra");
+        assert!(client.open(&main, MAIN).diagnostics.is_empty());
+
+        client.notify::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(main.clone(), 2),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: "a.\n".to_owned(),
+            }],
+        });
+        let published = client.diagnostics();
+        assert_eq!(published.uri, main);
+        assert_eq!(published.diagnostics.len(), 1);
+        let diagnostic = &published.diagnostics[0];
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String("P0002".to_owned()))
+        );
+        assert_eq!(
+            diagnostic.range,
+            Range::new(Position::new(0, 1), Position::new(0, 2))
+        );
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_publish_diagnostics_for_malformed_templates_and_comments() {
+        let client = Client::start();
+        for (name, text) in &[("template.ra", "a\n    `{{\"b}}`\n"), ("comment.ra", "a\n/* b\n")] {
+            let published = client.open(&uri(name), text);
+            let codes: Vec<_> = published
+                .diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.code.clone())
+                .collect();
+            assert_eq!(codes, vec![Some(NumberOrString::String("L0004".to_owned()))], "{}", text);
+        }
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_warn_about_identifying_inputs() {
+        let client = Client::start();
+        let main = uri("main.ra");
+        let published = client.open(&main, "phone\n    >\n");
+        assert_eq!(published.diagnostics.len(), 1);
+        let diagnostic = &published.diagnostics[0];
+        assert_eq!(
+            diagnostic.code,
+            Some(NumberOrString::String("A0001".to_owned()))
+        );
+        assert_eq!(diagnostic.severity, Some(DiagnosticSeverity::WARNING));
+        assert!(client
+            .open(&main, "phone\n    >\n        !identity\n")
+            .diagnostics
+            .is_empty());
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_list_document_symbols() {
+        let mut client = Client::start();
+        let main = uri("main.ra");
+        client.open(&main, MAIN);
+
+        let symbols = match client.request::<DocumentSymbolRequest>(DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(main),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }) {
+            Some(DocumentSymbolResponse::Nested(symbols)) => symbols,
+            other => panic!("unexpected response {:?}", other),
+        };
+
+        let names: Vec<(&str, SymbolKind, Option<&str>)> = symbols
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.kind, symbol.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("searchInput", SymbolKind::FUNCTION, None),
+                ("age", SymbolKind::FIELD, Some("number")),
+                ("tags", SymbolKind::FIELD, Some("list of !searchInput")),
+                ("meals", SymbolKind::FIELD, Some("choice")),
+            ]
+        );
+        assert_eq!(
+            symbols[1].selection_range,
+            Range::new(Position::new(4, 0), Position::new(4, 3))
+        );
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_go_to_definitions() {
+        let mut client = Client::start();
+        let main = uri("main.ra");
+        let resources = uri("resources.ra");
+        client.open(&main, MAIN);
+        client.open(&resources, RESOURCES);
+
+        let definition = |client: &mut Client, line, character| {
+            client.request::<GotoDefinition>(GotoDefinitionParams {
+                text_document_position_params: position_params(&main, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+            })
+        };
+
+        match definition(&mut client, 9, 12) {
+            Some(GotoDefinitionResponse::Scalar(location)) => {
+                assert_eq!(location.uri, main);
+                assert_eq!(
+                    location.range,
+                    Range::new(Position::new(0, 1), Position::new(0, 12))
+                );
+            }
+            other => panic!("unexpected response {:?}", other),
+        }
+
+        match definition(&mut client, 11, 20) {
+            Some(GotoDefinitionResponse::Scalar(location)) => {
+                assert_eq!(location.uri, resources);
+                assert_eq!(
+                    location.range,
+                    Range::new(Position::new(0, 1), Position::new(0, 11))
+                );
+            }
+            other => panic!("unexpected response {:?}", other),
+        }
+
+        match definition(&mut client, 11, 8) {
+            Some(GotoDefinitionResponse::Scalar(location)) => {
+                assert_eq!(location.uri, resources);
+                assert_eq!(location.range, Range::default());
+            }
+            other => panic!("unexpected response {:?}", other),
+        }
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_hover_inferred_types() {
+        let mut client = Client::start();
+        let main = uri("main.ra");
+        client.open(&main, MAIN);
+
+        let hover = client
+            .request::<HoverRequest>(HoverParams {
+                text_document_position_params: position_params(&main, 4, 1),
+                work_done_progress_params: Default::default(),
+            })
+            .expect("no hover");
+        match hover.contents {
+            HoverContents::Markup(content) => assert_eq!(
+                content.value,
+                "**age**: `number`\n\ndefault: `42`\n\nrules: `!number:min=0`"
+            ),
+            other => panic!("unexpected contents {:?}", other),
+        }
+        client.shutdown();
+    }
+
+    #[test]
+    fn it_should_complete_rules_and_annotations() {
+        let mut client = Client::start();
+        let main = uri("main.ra");
+        let text = format!("{}a\n    >\n        !nu\n#l", MAIN);
+        client.open(&main, &text);
+
+        let labels = |client: &mut Client, line, character| -> Vec<String> {
+            match client.request::<Completion>(CompletionParams {
+                text_document_position: position_params(&main, line, character),
+                work_done_progress_params: Default::default(),
+                partial_result_params: Default::default(),
+                context: None,
+            }) {
+                Some(CompletionResponse::Array(items)) => {
+                    items.into_iter().map(|item| item.label).collect()
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+
+        let rules = labels(&mut client, 16, 11);
+        assert!(rules.contains(&"number".to_owned()));
+        assert!(rules.contains(&"searchInput".to_owned()));
+
+        let keys = labels(&mut client, 17, 2);
+        assert_eq!(keys, vec!["ln", "lang", "kind", "env"]);
+        client.shutdown();
+    }
+}
@@ -0,0 +1,32 @@
+use lsp_types::Url;
+use std::collections::HashMap;
+use std::fs;
+
+/// Documents known to the server
+#[derive(Debug, Default)]
+pub struct World {
+    documents: HashMap<Url, String>,
+}
+
+impl World {
+    pub fn open(&mut self, uri: Url, text: String) {
+        self.documents.insert(uri, text);
+    }
+
+    pub fn close(&mut self, uri: &Url) {
+        self.documents.remove(uri);
+    }
+
+    pub fn open_document(&self, uri: &Url) -> Option<&str> {
+        self.documents.get(uri).map(String::as_str)
+    }
+
+    /// Text of an open document, or of a file on disk
+    pub fn document(&self, uri: &Url) -> Option<String> {
+        self.open_document(uri).map(str::to_owned).or_else(|| {
+            uri.to_file_path()
+                .ok()
+                .and_then(|path| fs::read_to_string(path).ok())
+        })
+    }
+}
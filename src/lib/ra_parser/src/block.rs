@@ -2,15 +2,20 @@ use super::expressions::annotation_expression::AnnotationExpression;
 use super::expressions::content::Content;
 use super::expressions::context_expression::ContextExpression;
 use super::expressions::input_expression::InputExpression;
-use super::expressions::output_expression::OutputExpression;
+use super::expressions::output_expression::{
+    ExpressionMember, MathOperation, OperationKind, OutputExpression, UnaryOperation,
+};
 use super::expressions::reference_expression::ReferenceExpression;
 use super::expressions::traits::*;
 use serde::Serialize;
 use failure::Backtrace;
 
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
+use super::incremental::Relocation;
+use super::syntax::SyntaxKind;
 
 use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
 use ra_lexer::token::{Token, TokenKind};
 
 #[derive(Clone, Debug, PartialEq, Serialize)]
@@ -37,7 +42,10 @@ impl<'a> Default for BlockKind<'a> {
 pub struct Block<'a> {
     pub kind: BlockKind<'a>,
     pub children: Vec<Block<'a>>,
-    first_token: Token<'a>,
+    /// Source covered by the block and its children
+    pub span: Span,
+    /// Token the block starts with, e.g. `>` of an input or `!` of an invocation
+    pub first_token: Token<'a>,
 }
 
 impl<'a> Block<'a> {
@@ -46,6 +54,7 @@ impl<'a> Block<'a> {
 
         Ok(Self {
             kind,
+            span: first_token.span,
             first_token,
             children: Vec::new(),
         })
@@ -61,40 +70,104 @@ impl<'a> Block<'a> {
             TokenKind::Identifier(_)
             | TokenKind::Float(_)
             | TokenKind::Int(_)
-            | TokenKind::OpenParentheses => Ok(BlockKind::Output(OutputExpression::new(token)?)),
+            | TokenKind::OpenParentheses
+            | TokenKind::Minus => Ok(BlockKind::Output(OutputExpression::new(token)?)),
             TokenKind::Exclamation => Ok(BlockKind::Invocation(None, None)),
             TokenKind::Plus | TokenKind::Greater => {
                 Ok(BlockKind::Input(token_kind == TokenKind::Plus, None))
             }
+            // assignment to the parent item `= value`,
+            // `-=` starts as a negation and `+=` as a list input
+            TokenKind::Equals => Ok(BlockKind::Output(Self::assignment(OperationKind::Assign))),
             TokenKind::ContentBlock => Ok(BlockKind::Content(Content::new(token)?)),
             TokenKind::At => Ok(BlockKind::Reference(None)),
             TokenKind::Colon => Ok(BlockKind::Declaration(None)),
             TokenKind::OpenCurlyBrace => Ok(BlockKind::ContextModification(None)),
             TokenKind::HashPound => Ok(BlockKind::Annotation(None)),
+            TokenKind::Boolean(_) | TokenKind::Null => Err(ParserError::ReservedWord(
+                token.content.to_owned(),
+                token.position,
+                Backtrace::new(),
+            )),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!(
-                    "{:?}",
-                    vec![
-                        TokenKind::Identifier(""),
-                        TokenKind::Float(0.0),
-                        TokenKind::Int(0),
-                        TokenKind::OpenParentheses,
-                        TokenKind::Exclamation,
-                        TokenKind::Plus,
-                        TokenKind::Greater,
-                        TokenKind::ContentBlock,
-                        TokenKind::At,
-                        TokenKind::Colon,
-                        TokenKind::OpenCurlyBrace,
-                        TokenKind::HashPound
-                    ]
-                ),
-                token.position.0,
+                expected_one_of(&[
+                    TokenKind::Identifier(""),
+                    TokenKind::Float(0.0),
+                    TokenKind::Int(0),
+                    TokenKind::OpenParentheses,
+                    TokenKind::Exclamation,
+                    TokenKind::Plus,
+                    TokenKind::Greater,
+                    TokenKind::Equals,
+                    TokenKind::ContentBlock,
+                    TokenKind::At,
+                    TokenKind::Colon,
+                    TokenKind::OpenCurlyBrace,
+                    TokenKind::HashPound
+                ]),
+                token.position,
                 Backtrace::new()
             )),
         }
     }
+
+    /// Token naming an item `name` or a declaration `:name`
+    pub fn name(&self) -> Option<&Token<'a>> {
+        match &self.kind {
+            BlockKind::Output(OutputExpression {
+                left,
+                operation: None,
+                right: None,
+            }) => match left.as_ref() {
+                ExpressionMember::ReferenceExpression(ReferenceExpression {
+                    name,
+                    index: None,
+                    next: None,
+                    ..
+                }) => Some(name),
+                _ => None,
+            },
+            BlockKind::Declaration(Some(token)) => Some(token),
+            _ => None,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Children of a kind, e.g. `SyntaxKind::Input`
+    pub fn children_of_kind(&self, kind: SyntaxKind) -> impl Iterator<Item = &Block<'a>> {
+        self.children
+            .iter()
+            .filter(move |child| SyntaxKind::from(&child.kind) == kind)
+    }
+
+    /// Assignment without a target, it assigns to the parent item
+    fn assignment(operation: OperationKind) -> OutputExpression<'a> {
+        OutputExpression {
+            left: Box::new(ExpressionMember::Nil),
+            operation: Some(operation),
+            right: None,
+        }
+    }
+
+    /// `-` waiting for its operand, the start of `-=`
+    fn is_negation(expression: &OutputExpression) -> bool {
+        expression.operation.is_none()
+            && *expression.left
+                == ExpressionMember::Unary(UnaryOperation::Negate, Box::new(ExpressionMember::Nil))
+    }
+
+    fn is_assignment(operation: Option<OperationKind>) -> bool {
+        matches!(
+            operation,
+            Some(OperationKind::Assign)
+                | Some(OperationKind::MathOperation(MathOperation::AddAssign))
+                | Some(OperationKind::MathOperation(MathOperation::SubtractAssign))
+        )
+    }
 }
 
 impl<'a> Leveled for Block<'a> {
@@ -169,15 +242,75 @@ impl<'a> Positioned for Block<'a> {
     }
 }
 
+impl<'a> Spanned for Block<'a> {
+    fn get_span(&self) -> Span {
+        self.span
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for Block<'a> {
+    type Output = Block<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Block<'b> {
+        Block {
+            kind: self.kind.relocate(relocation),
+            children: self.children.iter().map(|child| child.relocate(relocation)).collect(),
+            span: relocation.span(self.span),
+            first_token: self.first_token.relocate(relocation),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for BlockKind<'a> {
+    type Output = BlockKind<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> BlockKind<'b> {
+        match self {
+            BlockKind::Program => BlockKind::Program,
+            BlockKind::Output(expression) => BlockKind::Output(expression.relocate(relocation)),
+            BlockKind::Input(is_list, expression) => {
+                BlockKind::Input(*is_list, expression.relocate(relocation))
+            }
+            BlockKind::Declaration(token) => BlockKind::Declaration(token.relocate(relocation)),
+            BlockKind::Invocation(token, expression) => BlockKind::Invocation(
+                token.relocate(relocation),
+                expression.relocate(relocation),
+            ),
+            BlockKind::Reference(expression) => BlockKind::Reference(expression.relocate(relocation)),
+            BlockKind::ContextModification(expression) => {
+                BlockKind::ContextModification(expression.clone())
+            }
+            BlockKind::Content(content) => BlockKind::Content(content.relocate(relocation)),
+            BlockKind::Annotation(expression) => BlockKind::Annotation(expression.relocate(relocation)),
+            BlockKind::Union(size) => BlockKind::Union(*size),
+        }
+    }
+}
+
 impl<'a> Expandable<'a, Block<'a>, Token<'a>> for Block<'a> {
     fn append_item(self, token: Token<'a>) -> Result<Block<'a>, ParserError> {
         let mut block = self.clone();
         match self.kind {
             BlockKind::Program | BlockKind::Union(_) | BlockKind::Content(_) => {
-                Err(ParserError::InvalidBlock)
+                Err(ParserError::InvalidBlock(token.position))
             }
             BlockKind::Output(expression) => {
+                if token.kind.unwrap() == TokenKind::Equals && Self::is_negation(&expression) {
+                    block.kind = BlockKind::Output(Self::assignment(
+                        OperationKind::MathOperation(MathOperation::SubtractAssign),
+                    ));
+                    return Ok(block);
+                }
                 let updated_expression = expression.append_item(token)?;
+                // without a target only `=`, `+=` and `-=` assign to the parent item,
+                // `== 1` would be dropped as an assignment
+                if *updated_expression.left == ExpressionMember::Nil
+                    && !Self::is_assignment(updated_expression.operation)
+                {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("{}", token),
+                        token.position,
+                        Backtrace::new(),
+                    ));
+                }
                 block.kind = BlockKind::Output(updated_expression);
                 Ok(block)
             }
@@ -189,6 +322,12 @@ impl<'a> Expandable<'a, Block<'a>, Token<'a>> for Block<'a> {
                     if multiple {
                         match token.kind.unwrap() {
                             TokenKind::Greater => return Ok(block),
+                            TokenKind::Equals => {
+                                block.kind = BlockKind::Output(Self::assignment(
+                                    OperationKind::MathOperation(MathOperation::AddAssign),
+                                ));
+                                return Ok(block);
+                            }
                             _ => {}
                         }
                     }
@@ -222,7 +361,7 @@ impl<'a> Expandable<'a, Block<'a>, Token<'a>> for Block<'a> {
             }
             BlockKind::Declaration(declared_token) => {
                 if declared_token.is_some() {
-                    Err(ParserError::InvalidBlock)
+                    Err(ParserError::InvalidBlock(token.position))
                 } else {
                     block.kind = BlockKind::Declaration(Some(token));
                     Ok(block)
@@ -237,8 +376,8 @@ impl<'a> Expandable<'a, Block<'a>, Token<'a>> for Block<'a> {
                         _ => {
                             return Err(ParserError::ExpectedAGotB(
                                 format!("{}", token),
-                                format!("{:?}", vec![TokenKind::Identifier("")]),
-                                token.position.0,
+                                expected_one_of(&[TokenKind::Identifier("")]),
+                                token.position,
                                 Backtrace::new()
                             ))
                         }
@@ -1,29 +1,119 @@
 use ra_lexer::cursor::Position;
+use ra_lexer::diagnostics::{Diagnostic, ToDiagnostic};
 use ra_lexer::errors::{LexerError};
+use ra_lexer::token::TokenKind;
+use std::fmt;
 extern crate failure;
 use failure::{Fail, Backtrace};
 
 
 #[derive(Debug, Fail)]
 pub enum ParserError {
-    #[fail(display = "{} Unexpected indentation level: {}, \n {}", _1, _0, _2)]
     UnexpectedIndentLevel(u16, Position, Backtrace),
-    #[fail(display = "{} Unexpected {}, \n {}", _1, _0, _2)]
-    UnexpectedToken(String, Position, Backtrace),
-    #[fail(display = "{} Expected [{}] got {}, \n {}", _2, _1, _0, _3)]
-    ExpectedAGotB(String, String, Position, Backtrace),
-    #[fail(display = "Unexpected end of input at {}, \n {}", _0, _1)]
+    UnexpectedToken(String, (Position, Position), Backtrace),
+    ExpectedAGotB(String, String, (Position, Position), Backtrace),
     UnexpectedEndOfInput(Position, Backtrace),
-    #[fail(display = "Invalid expression at {}, \n {}", _0, _1)]
-    InvalidExpression(Position, Backtrace),
-    #[fail(display = "Invalid block")]
-    InvalidBlock,
-    #[fail(display = "ContentParsingError: {}, \n {}", _0, _1)]
+    InvalidExpression((Position, Position), Backtrace),
+    InvalidBlock((Position, Position)),
+    ReservedWord(String, (Position, Position), Backtrace),
+    /// Parser gave up on the input without a recoverable error
+    Aborted((Position, Position)),
     ContentParsingError(#[cause] LexerError, Backtrace),
-    #[fail(display = "Error chain: {}, caused by: {}", _1, _0)]
     ChainedError(#[cause] Box<ParserError>, Box<ParserError>)
 }
 
+impl ParserError {
+    /// Stable error code
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParserError::UnexpectedIndentLevel(..) => "P0001",
+            ParserError::UnexpectedToken(..) => "P0002",
+            ParserError::ExpectedAGotB(..) => "P0003",
+            ParserError::UnexpectedEndOfInput(..) => "P0004",
+            ParserError::InvalidExpression(..) => "P0005",
+            ParserError::InvalidBlock(..) => "P0006",
+            ParserError::ReservedWord(..) => "P0007",
+            ParserError::Aborted(..) => "P0008",
+            ParserError::ContentParsingError(e, _) => e.code(),
+            ParserError::ChainedError(_, e) => e.code(),
+        }
+    }
+}
+
+impl ToDiagnostic for ParserError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            ParserError::UnexpectedIndentLevel(level, position, _) => {
+                Diagnostic::error(self.code(), format!("unexpected indentation level {}", level))
+                    .with_primary_label((*position, Position(position.0, position.1 + 1)), "")
+            }
+            ParserError::UnexpectedToken(token, position, _) => {
+                Diagnostic::error(self.code(), format!("unexpected {}", token))
+                    .with_primary_label(*position, "unexpected token")
+            }
+            ParserError::ExpectedAGotB(token, expected, position, _) => {
+                let diagnostic = Diagnostic::error(self.code(), format!("expected {}, found {}", expected, token))
+                    .with_primary_label(*position, format!("expected {}", expected));
+                match expected.as_str() {
+                    "`:`" => diagnostic.with_suggestion("insert a colon", (position.0, position.0), ":"),
+                    "`.`" => diagnostic.with_suggestion("insert a dot", (position.0, position.0), "."),
+                    _ => diagnostic,
+                }
+            }
+            ParserError::UnexpectedEndOfInput(position, _) => {
+                Diagnostic::error(self.code(), "unexpected end of input")
+                    .with_primary_label((*position, Position(position.0, position.1 + 1)), "input ends here")
+            }
+            ParserError::InvalidExpression(position, _) => {
+                Diagnostic::error(self.code(), "invalid expression")
+                    .with_primary_label(*position, "can't continue expression with this token")
+            }
+            ParserError::InvalidBlock(position) => {
+                Diagnostic::error(self.code(), "invalid block")
+                    .with_primary_label(*position, "this block can't be continued")
+                    .with_note("programs, unions and content blocks don't accept expressions")
+            }
+            ParserError::ReservedWord(word, position, _) => {
+                Diagnostic::error(self.code(), format!("`{}` is a reserved word", word))
+                    .with_primary_label(*position, "can't be a name")
+                    .with_note("`true`, `false` and `none` are literals, rename the item or read such a field with `a[\"none\"]`")
+            }
+            ParserError::Aborted(position) => {
+                Diagnostic::error(self.code(), "can't parse the document")
+                    .with_primary_label(*position, "parsing stopped here")
+                    .with_note("the parser failed unexpectedly, the rest of the document isn't checked")
+            }
+            ParserError::ContentParsingError(e, _) => e.to_diagnostic(),
+            ParserError::ChainedError(cause, e) => {
+                let cause = cause.to_diagnostic();
+                let mut diagnostic = e.to_diagnostic();
+                if let Some(label) = cause.primary_label() {
+                    diagnostic = diagnostic.with_secondary_label(label.position, cause.message.clone());
+                }
+                diagnostic.notes.extend(cause.notes);
+                diagnostic
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_diagnostic())
+    }
+}
+
+/// Human readable list of expected tokens
+pub(crate) fn expected_one_of(kinds: &[TokenKind]) -> String {
+    match kinds {
+        [kind] => format!("{}", kind),
+        _ => format!(
+            "one of {}",
+            kinds.iter().map(|kind| format!("{}", kind)).collect::<Vec<String>>().join(", ")
+        ),
+    }
+}
+
 
 impl From<ParserError> for Vec<ParserError> {
     fn from(err: ParserError) -> Vec<ParserError> {
@@ -32,7 +122,7 @@ impl From<ParserError> for Vec<ParserError> {
 }
 
 impl std::error::Error for Box<ParserError> {
-    
+
 }
 
-// impl<'a> error::Error for ParserError<'a> {}
\ No newline at end of file
+// impl<'a> error::Error for ParserError<'a> {}
@@ -1,61 +1,95 @@
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
 use super::traits::*;
+use crate::incremental::Relocation;
 use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
 use ra_lexer::token::{Token, TokenKind};
 use serde::Serialize;
 use failure::Backtrace;
 
+/// Annotation `#context:option`, a segment linked to the following ones
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct AnnotationExpression<'a>(
-    pub Token<'a>,
-    pub Option<Option<Box<AnnotationExpression<'a>>>>,
-);
+pub struct AnnotationExpression<'a> {
+    /// Identifier of the segment
+    pub name: Token<'a>,
+    /// `None` for the last segment, `Some(None)` after a trailing `:`
+    /// while the next segment is being parsed
+    pub next: Option<Option<Box<AnnotationExpression<'a>>>>,
+}
 
 impl<'a> AnnotationExpression<'a> {
     pub fn new(token: Token<'a>) -> Result<Self, ParserError> {
         match token.kind.unwrap() {
-            TokenKind::Identifier(_) => Ok(Self(token, None)),
+            TokenKind::Identifier(_) => Ok(Self {
+                name: token,
+                next: None,
+            }),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!("{:?}", vec![TokenKind::Identifier("")]),
-                token.position.0,
+                expected_one_of(&[TokenKind::Identifier("")]),
+                token.position,
                 Backtrace::new()
             )),
         }
     }
+
+    /// Segment following this one
+    pub fn next(&self) -> Option<&AnnotationExpression<'a>> {
+        match &self.next {
+            Some(Some(next)) => Some(next),
+            _ => None,
+        }
+    }
+
+    /// Names of the segments in order, the context comes first
+    pub fn segments(&self) -> impl Iterator<Item = &Token<'a>> {
+        std::iter::successors(Some(self), |expression| expression.next())
+            .map(|expression| &expression.name)
+    }
 }
 
 impl<'a> Leveled for AnnotationExpression<'a> {
     fn get_level(&self) -> u16 {
-        self.0.level
+        self.name.level
     }
 }
 
 impl<'a> Positioned for AnnotationExpression<'a> {
     fn get_position(&self) -> (Position, Position) {
-        let AnnotationExpression(first_token, next_expression) = self;
-
-        let start_position = first_token.position.0;
-        let end_position = {
-            if next_expression.as_ref().is_some() && next_expression.as_ref().unwrap().is_some() {
-                next_expression
-                    .as_ref()
-                    .unwrap()
-                    .as_ref()
-                    .unwrap()
-                    .get_position()
-                    .1
-            } else {
-                first_token.position.1
-            }
+        let start_position = self.name.position.0;
+        let end_position = match self.next() {
+            Some(next) => next.get_position().1,
+            None => self.name.position.1,
         };
         (start_position, end_position)
     }
 }
 
+impl<'a> Spanned for AnnotationExpression<'a> {
+    fn get_span(&self) -> Span {
+        match self.next() {
+            Some(next) => self.name.span.to(next.get_span()),
+            None => self.name.span,
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for AnnotationExpression<'a> {
+    type Output = AnnotationExpression<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> AnnotationExpression<'b> {
+        AnnotationExpression {
+            name: self.name.relocate(relocation),
+            next: self.next.as_ref().map(|next| next.relocate(relocation)),
+        }
+    }
+}
+
 impl<'a> Expandable<'a, AnnotationExpression<'a>, Token<'a>> for AnnotationExpression<'a> {
     fn append_item(self, token: Token<'a>) -> Result<AnnotationExpression, ParserError> {
-        let AnnotationExpression(first_token, next_expression) = self;
+        let AnnotationExpression {
+            name,
+            next: next_expression,
+        } = self;
 
         if next_expression.is_some() {
             let child_expression;
@@ -64,17 +98,20 @@ impl<'a> Expandable<'a, AnnotationExpression<'a>, Token<'a>> for AnnotationExpre
             } else {
                 child_expression = AnnotationExpression::new(token)?
             }
-            Ok(AnnotationExpression(
-                first_token,
-                Some(Some(Box::new(child_expression))),
-            ))
+            Ok(AnnotationExpression {
+                name,
+                next: Some(Some(Box::new(child_expression))),
+            })
         } else {
             match token.kind.unwrap() {
-                TokenKind::Colon => Ok(AnnotationExpression(first_token, Some(None))),
+                TokenKind::Colon => Ok(AnnotationExpression {
+                    name,
+                    next: Some(None),
+                }),
                 _ => Err(ParserError::ExpectedAGotB(
                     format!("{}", token),
-                    format!("{:?}", vec![TokenKind::Colon]),
-                    token.position.0,
+                    expected_one_of(&[TokenKind::Colon]),
+                    token.position,
                     Backtrace::new()
                 )),
             }
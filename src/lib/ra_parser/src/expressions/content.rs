@@ -1,12 +1,13 @@
 use ra_lexer::cursor::{Cursor, Position};
 use ra_lexer::token::{Token, TokenKind};
-use ra_lexer::tokenize;
+use ra_lexer::try_tokenize_cursor;
 use serde::Serialize;
 
 use super::reference_expression::ReferenceExpression;
-use super::traits::Expandable;
+use super::traits::{Expandable, Relocate};
 use failure::Backtrace;
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
+use crate::incremental::Relocation;
 
 #[derive(Clone, Debug, PartialEq, Serialize)]
 pub enum ContentBlockMember<'a> {
@@ -14,17 +15,28 @@ pub enum ContentBlockMember<'a> {
     Template(ReferenceExpression<'a>),
 }
 
+/// Content block, a member linked to the following ones
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct Content<'a>(ContentBlockMember<'a>, Option<Box<Content<'a>>>);
+pub struct Content<'a> {
+    pub member: ContentBlockMember<'a>,
+    pub next: Option<Box<Content<'a>>>,
+}
 
 impl<'a> Content<'a> {
+    /// Body and template members in order
+    pub fn members(&self) -> Vec<&ContentBlockMember<'a>> {
+        std::iter::successors(Some(self), |content| content.next.as_deref())
+            .map(|content| &content.member)
+            .collect()
+    }
+
     pub fn new(token: Token<'a>) -> Result<Content<'a>, ParserError> {
         match token.kind.unwrap() {
             TokenKind::ContentBlock => Self::parse_content(token),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!("{:?}", vec![TokenKind::ContentBlock]),
-                token.position.0,
+                expected_one_of(&[TokenKind::ContentBlock]),
+                token.position,
                 Backtrace::new()
             )),
         }
@@ -37,11 +49,16 @@ impl<'a> Content<'a> {
             len,
             content,
             position,
+            span,
+            ..
         } = token;
 
-        let mut cursor = Cursor::new(content, position.0, level, 0);
+        let mut cursor = Cursor::new(content, position.0, level, 0).with_offset(span.start + 1); // add 1 for "`"
 
-        let mut content = Content(Self::parse_member(&mut cursor)?, None);
+        let mut content = Content {
+            member: Self::parse_member(&mut cursor)?,
+            next: None,
+        };
 
         while !cursor.is_eof() {
             match Self::parse_member(&mut cursor) {
@@ -59,30 +76,40 @@ impl<'a> Content<'a> {
         let mut string_buffer = String::new();
         let mut token_buffer = cursor.slice(0, 0);
 
-        while let Some(ch) = cursor.bump() {
+        loop {
+            // leave `{{` of a template to the next member
+            if !string_buffer.is_empty() && cursor.first_ahead() == '{' && cursor.second_ahead() == '{' {
+                return Ok(ContentBlockMember::Body(string_buffer));
+            }
+            let ch = match cursor.bump() {
+                Some(ch) => ch,
+                None => break,
+            };
             match ch {
                 '{' => {
                     match cursor.first_ahead() {
                         '{' => {
-                            if string_buffer.len() > 0 {
-                                return Ok(ContentBlockMember::Body(string_buffer));
-                            }
+                            cursor.bump();
                             let start_consumed = cursor.len_consumed();
+                            let start_position = cursor.position;
+                            let start_offset = cursor.offset();
                             while let Some(ch) = cursor.bump() {
                                 match ch {
                                     '}' => match cursor.first_ahead() {
                                         '}' => {
+                                            cursor.bump();
                                             if token_buffer.len() > 0 {
                                                 return Ok(ContentBlockMember::Template(
                                                     Self::parse_reference_from_buffer(
                                                         token_buffer,
-                                                        cursor.position,
+                                                        start_position,
+                                                        start_offset,
                                                     )?,
                                                 ));
                                             }
                                             break;
                                         }
-                                        _ => return Err(ParserError::InvalidBlock),
+                                        _ => return Err(ParserError::InvalidBlock((cursor.position, cursor.position))),
                                     },
                                     _ => {
                                         token_buffer =
@@ -100,6 +127,12 @@ impl<'a> Content<'a> {
                         }
                     }
                 }
+                // escaped backtick "\`" that doesn't close the block or escaped backslash "\\"
+                '\\' if matches!(cursor.first_ahead(), '`' | '\\') => {
+                    if let Some(escaped) = cursor.bump() {
+                        string_buffer.push(escaped);
+                    }
+                }
                 _ => {
                     string_buffer.push(ch);
                 }
@@ -116,30 +149,65 @@ impl<'a> Content<'a> {
     fn parse_reference_from_buffer(
         buffer: &'a str,
         position: Position,
+        offset: usize,
     ) -> Result<ReferenceExpression<'a>, ParserError> {
-        let mut tokens_stream = tokenize(buffer);
+        let mut tokens_stream = try_tokenize_cursor(Cursor::new(buffer, position, 0, 0).with_offset(offset))
+            .map(|token| token.map_err(|e| ParserError::ContentParsingError(e, Backtrace::new())));
 
         match tokens_stream.next() {
             Some(token) => {
-                let mut expression = ReferenceExpression::new(token)?;
+                let mut expression = ReferenceExpression::new(token?)?;
 
-                while let Some(token) = tokens_stream.next() {
-                    expression = expression.append_item(token)?;
+                for token in tokens_stream {
+                    expression = expression.append_item(token?)?;
                 }
 
-                Ok(expression)
+                if expression.is_complete() {
+                    Ok(expression)
+                } else {
+                    Err(ParserError::UnexpectedEndOfInput(position, Backtrace::new()))
+                }
             }
             None => Err(ParserError::UnexpectedEndOfInput(position, Backtrace::new())),
         }
     }
 }
 
+impl<'a, 'b> Relocate<'b> for Content<'a> {
+    type Output = Content<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Content<'b> {
+        Content {
+            member: self.member.relocate(relocation),
+            next: self.next.relocate(relocation),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for ContentBlockMember<'a> {
+    type Output = ContentBlockMember<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> ContentBlockMember<'b> {
+        match self {
+            ContentBlockMember::Body(body) => ContentBlockMember::Body(body.clone()),
+            ContentBlockMember::Template(reference) => {
+                ContentBlockMember::Template(reference.relocate(relocation))
+            }
+        }
+    }
+}
+
 impl<'a> Expandable<'a, Content<'a>, ContentBlockMember<'a>> for Content<'a> {
     fn append_item(self, item: ContentBlockMember<'a>) -> Result<Content<'a>, ParserError> {
-        let Content(current, next) = self;
-        match next {
-            Some(next) => Ok(Content(current, Some(Box::new(next.append_item(item)?)))),
-            None => Ok(Content(current, Some(Box::new(Content(item, None))))),
-        }
+        let Content { member, next } = self;
+        let next = match next {
+            Some(next) => next.append_item(item)?,
+            None => Content {
+                member: item,
+                next: None,
+            },
+        };
+        Ok(Content {
+            member,
+            next: Some(Box::new(next)),
+        })
     }
 }
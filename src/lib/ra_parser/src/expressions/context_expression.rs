@@ -1,11 +1,10 @@
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
 use super::traits::*;
-use ra_lexer::cursor::Position;
 use ra_lexer::token::{Token, TokenKind};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use failure::Backtrace;
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ContextExpressionMemberKind {
     None,
     One,
@@ -13,42 +12,43 @@ pub enum ContextExpressionMemberKind {
     MSpecifier,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ContextExpressionMember {
     Target(ContextExpressionMemberKind),
     Source(ContextExpressionMemberKind),
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct ContextExpression(ContextExpressionMember, ContextExpressionMember);
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// Context switch like `{N:1}`, how many entries there are per how many clients
+pub struct ContextExpression {
+    pub target: ContextExpressionMember,
+    pub source: ContextExpressionMember,
+}
 
 impl<'a> ContextExpression {
     pub fn new(token: Token<'a>) -> Result<Self, ParserError> {
         // println!("{:?}", token);
         match token.kind.unwrap() {
-            TokenKind::Int(1) => Ok(ContextExpression(
-                ContextExpressionMember::Target(ContextExpressionMemberKind::One),
-                ContextExpressionMember::Source(ContextExpressionMemberKind::None),
-            )),
-            TokenKind::Identifier("N") => Ok(ContextExpression(
-                ContextExpressionMember::Target(ContextExpressionMemberKind::N),
-                ContextExpressionMember::Source(ContextExpressionMemberKind::None),
-            )),
-            TokenKind::OpenCurlyBrace => Ok(ContextExpression(
-                ContextExpressionMember::Target(ContextExpressionMemberKind::MSpecifier),
-                ContextExpressionMember::Source(ContextExpressionMemberKind::None),
-            )),
+            TokenKind::Int(1) => Ok(ContextExpression {
+                target: ContextExpressionMember::Target(ContextExpressionMemberKind::One),
+                source: ContextExpressionMember::Source(ContextExpressionMemberKind::None),
+            }),
+            TokenKind::Identifier("N") => Ok(ContextExpression {
+                target: ContextExpressionMember::Target(ContextExpressionMemberKind::N),
+                source: ContextExpressionMember::Source(ContextExpressionMemberKind::None),
+            }),
+            TokenKind::OpenCurlyBrace => Ok(ContextExpression {
+                target: ContextExpressionMember::Target(ContextExpressionMemberKind::MSpecifier),
+                source: ContextExpressionMember::Source(ContextExpressionMemberKind::None),
+            }),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!(
-                    "{:?}",
-                    vec![
-                        TokenKind::Int(1),
-                        TokenKind::Identifier("N"),
-                        TokenKind::OpenCurlyBrace
-                    ]
-                ),
-                token.position.0,
+                expected_one_of(&[
+                    TokenKind::Int(1),
+                    TokenKind::Identifier("N"),
+                    TokenKind::OpenCurlyBrace
+                ]),
+                token.position,
                 Backtrace::new(),
             )),
         }
@@ -61,20 +61,14 @@ impl Leveled for ContextExpression {
     }
 }
 
-impl Positioned for ContextExpression {
-    fn get_position(&self) -> (Position, Position) {
-        todo!()
-    }
-}
-
 impl<'a> Expandable<'a, ContextExpression, Token<'a>> for ContextExpression {
     fn append_item(self, token: Token<'a>) -> Result<ContextExpression, ParserError> {
         match token.kind.unwrap() {
             TokenKind::Colon => Ok(self.clone()),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!("{:?}", vec![TokenKind::Colon]),
-                token.position.0,
+                expected_one_of(&[TokenKind::Colon]),
+                token.position,
                 Backtrace::new(),
             )),
         }
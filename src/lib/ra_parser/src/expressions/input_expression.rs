@@ -1,8 +1,9 @@
 use super::content::Content;
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
 use super::output_expression::OutputExpression;
 use super::reference_expression::ReferenceExpression;
 use super::traits::*;
+use crate::incremental::Relocation;
 use serde::Serialize;
 use failure::Backtrace;
 
@@ -23,41 +24,132 @@ pub enum ValueType<'a> {
     ReferenceExpression(ReferenceExpression<'a>),
 }
 
+/// Arguments `:name=value:flag` of an input or an invocation,
+/// an argument linked to the following ones
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct InputExpression<'a>(
-    Option<ArgumentType<'a>>,
-    Option<ValueType<'a>>,
-    Option<Box<InputExpression<'a>>>,
-);
+pub struct InputExpression<'a> {
+    pub argument: Option<ArgumentType<'a>>,
+    pub value: Option<ValueType<'a>>,
+    pub next: Option<Box<InputExpression<'a>>>,
+}
 
 impl<'a> InputExpression<'a> {
     pub fn new(token: Token<'a>) -> Result<Self, ParserError> {
         match token.kind.unwrap() {
-            TokenKind::Colon => Ok(Self(None, None, None)),
+            TokenKind::Colon => Ok(Self {
+                argument: None,
+                value: None,
+                next: None,
+            }),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!("{:?}", vec![TokenKind::Colon]),
-                token.position.0,
+                expected_one_of(&[TokenKind::Colon]),
+                token.position,
                 Backtrace::new()
             )),
         }
     }
+
+    /// Argument following this one
+    pub fn next(&self) -> Option<&InputExpression<'a>> {
+        self.next.as_deref()
+    }
+
+    /// This and the following arguments in order
+    pub fn arguments(&self) -> impl Iterator<Item = &InputExpression<'a>> {
+        std::iter::successors(Some(self), |expression| expression.next())
+    }
+
+    /// Bracket which closes a group left open in the value of an argument,
+    /// `)` of `:default=(`
+    pub(crate) fn closing_bracket(&self) -> Option<TokenKind<'static>> {
+        self.arguments().find_map(|expression| match &expression.value {
+            Some(ValueType::OutputExpression(value)) => value.closing_bracket(),
+            Some(ValueType::ReferenceExpression(value)) => value.closing_bracket(),
+            _ => None,
+        })
+    }
 }
 
 impl<'a> Leveled for InputExpression<'a> {
     fn get_level(&self) -> u16 {
-        let InputExpression(argument_type, value_type, next) = self;
-        // isf argument_type.is_some() {
-        // argument_type.unwrap().
-        // }
-        todo!()
+        self.arguments()
+            .find_map(|expression| match (&expression.argument, &expression.value) {
+                (Some(ArgumentType::Named(token, _)), _) => Some(token.level),
+                (_, Some(ValueType::Literal(token))) => Some(token.level),
+                (_, Some(ValueType::OutputExpression(value))) => Some(value.get_level()),
+                (_, Some(ValueType::ReferenceExpression(value))) => Some(value.get_level()),
+                _ => None,
+            })
+            .unwrap_or(0)
     }
 }
 
 impl<'a> Positioned for InputExpression<'a> {
     fn get_position(&self) -> (Position, Position) {
-        let InputExpression(argument_type, value_type, next) = self;
-        todo!()
+        let mut positions = self.arguments().filter_map(InputExpression::own_position);
+        match positions.next() {
+            Some(first) => (first.0, positions.last().unwrap_or(first).1),
+            None => (Position::default(), Position::default()),
+        }
+    }
+}
+
+impl<'a> InputExpression<'a> {
+    /// Positions of the name and the value of this argument without the following ones,
+    /// `None` when neither of them has tokens
+    fn own_position(&self) -> Option<(Position, Position)> {
+        let name = match &self.argument {
+            Some(ArgumentType::Named(token, _)) => Some(token.position),
+            _ => None,
+        };
+        let value = match &self.value {
+            Some(ValueType::Literal(token)) => Some(token.position),
+            Some(ValueType::OutputExpression(value)) => Some(value.get_position()),
+            Some(ValueType::ReferenceExpression(value)) => Some(value.get_position()),
+            Some(ValueType::Content(_)) | None => None,
+        };
+        match (name, value) {
+            (Some(name), Some(value)) => Some((name.0, value.1)),
+            (name, value) => name.or(value),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for InputExpression<'a> {
+    type Output = InputExpression<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> InputExpression<'b> {
+        InputExpression {
+            argument: self.argument.relocate(relocation),
+            value: self.value.relocate(relocation),
+            next: self.next.relocate(relocation),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for ArgumentType<'a> {
+    type Output = ArgumentType<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> ArgumentType<'b> {
+        match self {
+            ArgumentType::Named(token, is_flag) => ArgumentType::Named(token.relocate(relocation), *is_flag),
+            ArgumentType::Ordered(index) => ArgumentType::Ordered(*index),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for ValueType<'a> {
+    type Output = ValueType<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> ValueType<'b> {
+        match self {
+            ValueType::Literal(token) => ValueType::Literal(token.relocate(relocation)),
+            ValueType::Content(content) => ValueType::Content(content.relocate(relocation)),
+            ValueType::OutputExpression(expression) => {
+                ValueType::OutputExpression(expression.relocate(relocation))
+            }
+            ValueType::ReferenceExpression(expression) => {
+                ValueType::ReferenceExpression(expression.relocate(relocation))
+            }
+        }
     }
 }
 
@@ -67,7 +159,11 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
         token: Token<'a>,
         depth: Option<u16>,
     ) -> Result<InputExpression<'a>, ParserError> {
-        let InputExpression(argument_type, value_type, next) = self;
+        let InputExpression {
+            argument: argument_type,
+            value: value_type,
+            next,
+        } = self;
 
         let ordered_argument_depth = match depth {
             Some(d) => d,
@@ -76,20 +172,53 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
 
         if argument_type.is_none() {
             match token.kind.unwrap() {
-                TokenKind::Identifier(_) => Ok(InputExpression(
-                    Some(ArgumentType::Named(token, false)),
-                    None,
-                    None,
-                )),
-                TokenKind::Equals => Ok(InputExpression(
-                    Some(ArgumentType::Ordered(ordered_argument_depth)),
-                    None,
-                    None,
-                )),
+                TokenKind::Identifier(_) => Ok(InputExpression {
+                    argument: Some(ArgumentType::Named(token, false)),
+                    value: None,
+                    next: None,
+                }),
+                TokenKind::Equals => Ok(InputExpression {
+                    argument: Some(ArgumentType::Ordered(ordered_argument_depth)),
+                    value: None,
+                    next: None,
+                }),
+                // literal without an assignment `!case:0`
+                TokenKind::StringLiteral(_)
+                | TokenKind::Float(_)
+                | TokenKind::Int(_)
+                | TokenKind::Boolean(_)
+                | TokenKind::Null => Ok(InputExpression {
+                    argument: Some(ArgumentType::Ordered(ordered_argument_depth)),
+                    value: Some(ValueType::Literal(token)),
+                    next: None,
+                }),
+                // expression without an assignment `!if:(a + 1) > b` or `!includes:[1, 2]`
+                TokenKind::OpenParentheses
+                | TokenKind::OpenSquareBrace
+                | TokenKind::OpenCurlyBrace
+                | TokenKind::Minus
+                | TokenKind::Exclamation => {
+                    Ok(InputExpression {
+                        argument: Some(ArgumentType::Ordered(ordered_argument_depth)),
+                        value: Some(ValueType::OutputExpression(OutputExpression::new(token)?)),
+                        next: None,
+                    })
+                }
                 _ => Err(ParserError::ExpectedAGotB(
                     format!("{}", token),
-                    format!("{:?}", vec![TokenKind::Identifier(""), TokenKind::Equals]),
-                    token.position.0,
+                    expected_one_of(&[
+                        TokenKind::Identifier(""),
+                        TokenKind::Equals,
+                        TokenKind::StringLiteral(""),
+                        TokenKind::Float(0.0),
+                        TokenKind::Int(0),
+                        TokenKind::Boolean(true),
+                        TokenKind::Null,
+                        TokenKind::OpenParentheses,
+                        TokenKind::OpenSquareBrace,
+                        TokenKind::OpenCurlyBrace
+                    ]),
+                    token.position,
                     Backtrace::new()
                 )),
             }
@@ -98,18 +227,24 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
                 ArgumentType::Named(tok, assigned) => {
                     if !assigned {
                         return match token.kind.unwrap() {
-                            TokenKind::Equals => Ok(InputExpression(
-                                Some(ArgumentType::Named(tok, true)),
-                                None,
-                                None,
-                            )),
+                            TokenKind::Equals => Ok(InputExpression {
+                                argument: Some(ArgumentType::Named(tok, true)),
+                                value: None,
+                                next: None,
+                            }),
+                            // the name was the start of a value, `!if:a.b` or `!if:a > 1`
+                            TokenKind::Dot => Ok(InputExpression {
+                                argument: Some(ArgumentType::Ordered(ordered_argument_depth)),
+                                value: Some(ValueType::ReferenceExpression(ReferenceExpression::new(tok)?.append_item(token)?)),
+                                next: None,
+                            }),
                             _ => {
-
-                                Ok(InputExpression(
-                                    Some(ArgumentType::Ordered(ordered_argument_depth)),
-                                    Some(ValueType::ReferenceExpression(ReferenceExpression::new(tok)?.append_item(token)?)),
-                                    None
-                                ))
+                                let expression: OutputExpression = ReferenceExpression::new(tok)?.into();
+                                Ok(InputExpression {
+                                    argument: Some(ArgumentType::Ordered(ordered_argument_depth)),
+                                    value: Some(ValueType::OutputExpression(expression.append_item(token)?)),
+                                    next: None,
+                                })
                             }
                         };
                     }
@@ -118,48 +253,53 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
             }
 
             match token.kind.unwrap() {
-                TokenKind::StringLiteral(_) | TokenKind::Float(_) | TokenKind::Int(_) => Ok(
-                    InputExpression(argument_type, Some(ValueType::Literal(token)), None),
-                ),
+                TokenKind::StringLiteral(_)
+                | TokenKind::Float(_)
+                | TokenKind::Int(_)
+                | TokenKind::Boolean(_)
+                | TokenKind::Null => Ok(InputExpression {
+                    argument: argument_type,
+                    value: Some(ValueType::Literal(token)),
+                    next: None,
+                }),
                 TokenKind::ContentBlock => {
                     let content = Content::new(token)?;
-                    Ok(InputExpression(
-                        argument_type,
-                        Some(ValueType::Content(content)),
-                        None,
-                    ))
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: Some(ValueType::Content(content)),
+                        next: None,
+                    })
                 }
-                TokenKind::Identifier(_) => Ok(InputExpression(
-                    argument_type,
-                    Some(ValueType::ReferenceExpression(ReferenceExpression::new(
+                TokenKind::Identifier(_) => Ok(InputExpression {
+                    argument: argument_type,
+                    value: Some(ValueType::ReferenceExpression(ReferenceExpression::new(
                         token,
                     )?)),
-                    None,
-                )),
+                    next: None,
+                }),
                 _ => {
                     match OutputExpression::new(token) {
                         Ok(expression) => {
-                            Ok(InputExpression(
-                                argument_type,
-                                Some(ValueType::OutputExpression(expression)),
-                                None
-                            ))
+                            Ok(InputExpression {
+                                argument: argument_type,
+                                value: Some(ValueType::OutputExpression(expression)),
+                                next: None,
+                            })
                         },
                         Err(e) => {
                             Err(ParserError::ChainedError(
                                 Box::new(e),
                                 Box::new(ParserError::ExpectedAGotB(
                                     format!("{}", token),
-                                    format!(
-                                        "{:?}",
-                                        vec![
-                                            TokenKind::StringLiteral(""),
-                                            TokenKind::Float(0.0),
-                                            TokenKind::Int(0),
-                                            TokenKind::Identifier("")
-                                        ]
-                                    ),
-                                    token.position.0,
+                                    expected_one_of(&[
+                                        TokenKind::StringLiteral(""),
+                                        TokenKind::Float(0.0),
+                                        TokenKind::Int(0),
+                                        TokenKind::Boolean(true),
+                                        TokenKind::Null,
+                                        TokenKind::Identifier("")
+                                    ]),
+                                    token.position,
                                     Backtrace::new()
                                 ))
                             ))               
@@ -169,6 +309,34 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
             }
         } else if next.is_none() {
             match value_type.clone().unwrap() {
+                // `:` after a complete value starts the next argument
+                ValueType::ReferenceExpression(expression)
+                    if token.kind.unwrap() == TokenKind::Colon && expression.is_complete() =>
+                {
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: value_type,
+                        next: Some(Box::new(InputExpression::new(token)?)),
+                    })
+                }
+                ValueType::OutputExpression(expression)
+                    if token.kind.unwrap() == TokenKind::Colon && expression.is_complete() =>
+                {
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: value_type,
+                        next: Some(Box::new(InputExpression::new(token)?)),
+                    })
+                }
+                // literal followed by an operation `!if:1 + a`
+                ValueType::Literal(literal) if token.kind.unwrap() != TokenKind::Colon => {
+                    let next_expression = OutputExpression::new(literal)?.append_item(token)?;
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: Some(ValueType::OutputExpression(next_expression)),
+                        next: None,
+                    })
+                }
                 ValueType::ReferenceExpression(expression) => {
                     let next_expression = match expression.clone().append_item(token) {
                         Ok(expression) => expression,
@@ -177,11 +345,11 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
                                 ParserError::ExpectedAGotB(A, B, position, trace) => {
                                     let mut next_expression: OutputExpression = expression.into();
                                     next_expression = next_expression.append_item(token)?;
-                                    return Ok(InputExpression(
-                                        argument_type,
-                                        Some(ValueType::OutputExpression(next_expression)),
-                                        None
-                                    ))
+                                    return Ok(InputExpression {
+                                        argument: argument_type,
+                                        value: Some(ValueType::OutputExpression(next_expression)),
+                                        next: None,
+                                    })
                                 },
                                 _ => {
                                     return Err(e);
@@ -189,38 +357,38 @@ impl<'a> ByTokenExpandableFromRoot<'a, InputExpression<'a>> for InputExpression<
                             }
                         }
                     };
-                    Ok(InputExpression(
-                        argument_type,
-                        Some(ValueType::ReferenceExpression(next_expression)),
-                        None
-                    ))
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: Some(ValueType::ReferenceExpression(next_expression)),
+                        next: None,
+                    })
                 },
                 ValueType::OutputExpression(expression) => {
                     let next_expression = expression.append_item(token)?;
-                    Ok(InputExpression(
-                        argument_type,
-                        Some(ValueType::OutputExpression(next_expression)),
-                        None
-                    ))
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: Some(ValueType::OutputExpression(next_expression)),
+                        next: None,
+                    })
                 }
                 _ => {
                     let next_expression = InputExpression::new(token)?;
-                    Ok(InputExpression(
-                        argument_type,
-                        value_type,
-                        Some(Box::new(next_expression)),
-                    ))
+                    Ok(InputExpression {
+                        argument: argument_type,
+                        value: value_type,
+                        next: Some(Box::new(next_expression)),
+                    })
                 }
             }
         } else {
             let updated_expression = next
                 .unwrap()
                 .append_item(token, Some(ordered_argument_depth + 1))?;
-            Ok(InputExpression(
-                argument_type,
-                value_type,
-                Some(Box::new(updated_expression)),
-            ))
+            Ok(InputExpression {
+                argument: argument_type,
+                value: value_type,
+                next: Some(Box::new(updated_expression)),
+            })
         }
     }
 }
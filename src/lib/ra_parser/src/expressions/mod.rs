@@ -6,4 +6,6 @@ pub mod annotation_expression;
 pub mod content;
 pub (crate) mod traits;
 
+pub use traits::{Positioned, Spanned};
+
 pub (crate) use super::errors;
\ No newline at end of file
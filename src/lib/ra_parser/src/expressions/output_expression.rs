@@ -1,12 +1,14 @@
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
 use super::reference_expression::ReferenceExpression;
 use super::traits::*;
+use crate::incremental::Relocation;
 use failure::Backtrace;
 use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
 use ra_lexer::token::{Token, TokenKind};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, PartialEq, Copy, Serialize)]
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
 pub enum MathOperation {
     Sum,
     Subtract,
@@ -18,7 +20,7 @@ pub enum MathOperation {
     SubtractAssign,
 }
 
-#[derive(Debug, Clone, PartialEq, Copy, Serialize)]
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
 pub enum ComparisonOperation {
     EqCompare,
     GtCompare,
@@ -28,7 +30,7 @@ pub enum ComparisonOperation {
     NEqCompare,
 }
 
-#[derive(Debug, Clone, PartialEq, Copy, Serialize)]
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
 pub enum LogicOperation {
     AND,  // &
     OR,   // |
@@ -39,7 +41,7 @@ pub enum LogicOperation {
     XNOR, // !|
 }
 
-#[derive(Debug, Clone, PartialEq, Copy, Serialize)]
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
 pub enum OperationKind {
     LogicOperation(LogicOperation),
     MathOperation(MathOperation),
@@ -47,14 +49,40 @@ pub enum OperationKind {
     Assign,
 }
 
+/// Prefix operation `-a` or `!a`
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
+pub enum UnaryOperation {
+    Negate,
+    Not,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum ExpressionMember<'a> {
     Literal(Token<'a>),
+    /// Expression in parentheses, `true` while it's open,
+    /// `(false, None)` right after `(`
     OutputExpression(bool, Option<OutputExpression<'a>>),
     ReferenceExpression(ReferenceExpression<'a>),
+    /// Operation binding tighter than the one it's a member of, `b * c` of `a + b * c`
+    Operation(OutputExpression<'a>),
+    /// Prefix operation, the operand is `Nil` until it's parsed
+    Unary(UnaryOperation, Box<ExpressionMember<'a>>),
+    /// List literal `[a, b]`, `true` while it's open,
+    /// the last item is `Nil` right after `[` or `,`
+    List(bool, Vec<OutputExpression<'a>>),
+    /// Map literal `{key: value}`, `true` while it's open
+    Map(bool, Vec<MapEntry<'a>>),
     Nil,
 }
 
+/// Entry `key: value` of a map literal,
+/// the key is `None` right after `{` or `,` and the value until `:`
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct MapEntry<'a> {
+    pub key: Option<Token<'a>>,
+    pub value: Option<OutputExpression<'a>>,
+}
+
 impl<'a> Default for ExpressionMember<'a> {
     fn default() -> Self {
         Self::Nil
@@ -67,27 +95,204 @@ impl<'a> ExpressionMember<'a> {
             TokenKind::Identifier(_) => Ok(ExpressionMember::ReferenceExpression(
                 ReferenceExpression::new(token)?,
             )),
-            TokenKind::Int(_) | TokenKind::Float(_) | TokenKind::StringLiteral(_) => {
-                Ok(ExpressionMember::Literal(token))
-            }
+            TokenKind::Int(_)
+            | TokenKind::Float(_)
+            | TokenKind::StringLiteral(_)
+            | TokenKind::Boolean(_)
+            | TokenKind::Null => Ok(ExpressionMember::Literal(token)),
             TokenKind::OpenParentheses => Ok(ExpressionMember::OutputExpression(false, None)),
+            TokenKind::OpenSquareBrace => Ok(ExpressionMember::List(
+                true,
+                vec![OutputExpression::default()],
+            )),
+            TokenKind::OpenCurlyBrace => Ok(ExpressionMember::Map(true, vec![MapEntry::default()])),
+            TokenKind::Minus => Ok(ExpressionMember::Unary(
+                UnaryOperation::Negate,
+                Box::new(ExpressionMember::Nil),
+            )),
+            TokenKind::Exclamation => Ok(ExpressionMember::Unary(
+                UnaryOperation::Not,
+                Box::new(ExpressionMember::Nil),
+            )),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!(
-                    "{:?}",
-                    vec![
-                        TokenKind::Identifier(""),
-                        TokenKind::Int(0),
-                        TokenKind::Float(0.0),
-                        TokenKind::StringLiteral(""),
-                        TokenKind::OpenParentheses
-                    ]
-                ),
-                token.position.0,
+                expected_one_of(&[
+                    TokenKind::Identifier(""),
+                    TokenKind::Int(0),
+                    TokenKind::Float(0.0),
+                    TokenKind::StringLiteral(""),
+                    TokenKind::Boolean(true),
+                    TokenKind::Null,
+                    TokenKind::OpenParentheses,
+                    TokenKind::OpenSquareBrace,
+                    TokenKind::OpenCurlyBrace,
+                    TokenKind::Minus,
+                    TokenKind::Exclamation
+                ]),
+                token.position,
                 Backtrace::new(),
             )),
         }
     }
+
+    /// Parentheses, list or map which are not closed yet
+    fn is_open_group(&self) -> bool {
+        matches!(
+            self,
+            ExpressionMember::OutputExpression(true, _)
+                | ExpressionMember::OutputExpression(false, None)
+                | ExpressionMember::List(true, _)
+                | ExpressionMember::Map(true, _)
+        )
+    }
+
+    /// Bracket which closes the innermost group left open, `]` of `[(1), [2`
+    fn closing_bracket(&self) -> Option<TokenKind<'static>> {
+        match self {
+            ExpressionMember::OutputExpression(false, None) => Some(TokenKind::CloseParentheses),
+            ExpressionMember::OutputExpression(true, expression) => expression
+                .as_ref()
+                .and_then(OutputExpression::closing_bracket)
+                .or(Some(TokenKind::CloseParentheses)),
+            ExpressionMember::List(true, items) => items
+                .last()
+                .and_then(OutputExpression::closing_bracket)
+                .or(Some(TokenKind::CloseSquareBrace)),
+            ExpressionMember::Map(true, entries) => entries
+                .last()
+                .and_then(|entry| entry.value.as_ref())
+                .and_then(OutputExpression::closing_bracket)
+                .or(Some(TokenKind::CloseCurlyBrace)),
+            ExpressionMember::ReferenceExpression(reference) => reference.closing_bracket(),
+            _ => None,
+        }
+    }
+
+    /// Continues the last item of a list until `,` or `]`
+    fn append_to_list(
+        mut items: Vec<OutputExpression<'a>>,
+        token: Token<'a>,
+    ) -> Result<ExpressionMember<'a>, ParserError> {
+        let kind = token.kind.unwrap();
+        let item = items.pop().unwrap_or_default();
+        if item.is_nil() {
+            match kind {
+                // `[]` or a trailing coma `[a, b,]`
+                TokenKind::CloseSquareBrace => return Ok(ExpressionMember::List(false, items)),
+                TokenKind::Coma => {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("{}", token),
+                        token.position,
+                        Backtrace::new(),
+                    ))
+                }
+                _ => items.push(OutputExpression::new(token)?),
+            }
+            return Ok(ExpressionMember::List(true, items));
+        }
+
+        let is_separator = kind == TokenKind::Coma || kind == TokenKind::CloseSquareBrace;
+        if !is_separator || item.has_open_group() {
+            items.push(item.append_item(token)?);
+            return Ok(ExpressionMember::List(true, items));
+        }
+        if !item.is_complete() {
+            return Err(ParserError::InvalidExpression(
+                token.position,
+                Backtrace::new(),
+            ));
+        }
+        items.push(item);
+        if kind == TokenKind::Coma {
+            items.push(OutputExpression::default());
+            Ok(ExpressionMember::List(true, items))
+        } else {
+            Ok(ExpressionMember::List(false, items))
+        }
+    }
+
+    /// Continues the last entry of a map until `,` or `}`
+    fn append_to_map(
+        mut entries: Vec<MapEntry<'a>>,
+        token: Token<'a>,
+    ) -> Result<ExpressionMember<'a>, ParserError> {
+        let kind = token.kind.unwrap();
+        let unexpected = || {
+            ParserError::UnexpectedToken(format!("{}", token), token.position, Backtrace::new())
+        };
+        let MapEntry { key, value } = entries.pop().unwrap_or_default();
+        let entry = match (key, value) {
+            // `{}` or a trailing coma `{a: 1,}`
+            (None, _) if kind == TokenKind::CloseCurlyBrace => {
+                return Ok(ExpressionMember::Map(false, entries))
+            }
+            (None, _) => match kind {
+                TokenKind::Identifier(_) | TokenKind::StringLiteral(_) => MapEntry {
+                    key: Some(token),
+                    value: None,
+                },
+                _ => {
+                    return Err(ParserError::ExpectedAGotB(
+                        format!("{}", token),
+                        expected_one_of(&[
+                            TokenKind::Identifier(""),
+                            TokenKind::StringLiteral(""),
+                            TokenKind::CloseCurlyBrace,
+                        ]),
+                        token.position,
+                        Backtrace::new(),
+                    ))
+                }
+            },
+            (Some(key), None) => match kind {
+                TokenKind::Colon => MapEntry {
+                    key: Some(key),
+                    value: Some(OutputExpression::default()),
+                },
+                _ => {
+                    return Err(ParserError::ExpectedAGotB(
+                        format!("{}", token),
+                        expected_one_of(&[TokenKind::Colon]),
+                        token.position,
+                        Backtrace::new(),
+                    ))
+                }
+            },
+            (Some(key), Some(value)) if value.is_nil() => match kind {
+                TokenKind::Coma | TokenKind::CloseCurlyBrace => return Err(unexpected()),
+                _ => MapEntry {
+                    key: Some(key),
+                    value: Some(OutputExpression::new(token)?),
+                },
+            },
+            (Some(key), Some(value)) => {
+                let is_separator = kind == TokenKind::Coma || kind == TokenKind::CloseCurlyBrace;
+                if !is_separator || value.has_open_group() {
+                    MapEntry {
+                        key: Some(key),
+                        value: Some(value.append_item(token)?),
+                    }
+                } else if !value.is_complete() {
+                    return Err(ParserError::InvalidExpression(
+                        token.position,
+                        Backtrace::new(),
+                    ));
+                } else {
+                    entries.push(MapEntry {
+                        key: Some(key),
+                        value: Some(value),
+                    });
+                    if kind == TokenKind::Coma {
+                        entries.push(MapEntry::default());
+                        return Ok(ExpressionMember::Map(true, entries));
+                    }
+                    return Ok(ExpressionMember::Map(false, entries));
+                }
+            }
+        };
+        entries.push(entry);
+        Ok(ExpressionMember::Map(true, entries))
+    }
 }
 
 impl<'a> Leveled for ExpressionMember<'a> {
@@ -97,39 +302,63 @@ impl<'a> Leveled for ExpressionMember<'a> {
             ExpressionMember::Literal(token) => token.level,
             ExpressionMember::ReferenceExpression(expression) => expression.get_level(),
             ExpressionMember::OutputExpression(_, expression) => {
-                expression.as_ref().unwrap().get_level()
+                expression.as_ref().map_or(0, |e| e.get_level())
+            }
+            ExpressionMember::Operation(expression) => expression.get_level(),
+            ExpressionMember::Unary(_, operand) => operand.get_level(),
+            ExpressionMember::List(_, items) => {
+                items.first().map_or(0, |item| item.get_level())
             }
+            ExpressionMember::Map(_, entries) => entries
+                .first()
+                .and_then(|entry| entry.key)
+                .map_or(0, |key| key.level),
         }
     }
 }
 
 impl<'a> Expandable<'a, ExpressionMember<'a>, Token<'a>> for ExpressionMember<'a> {
+    /// Continues parentheses, a list or a map until they're closed, or a reference
     fn append_item(self, token: Token<'a>) -> Result<ExpressionMember<'a>, ParserError> {
+        let is_close = token.kind.unwrap() == TokenKind::CloseParentheses;
         match self {
-            ExpressionMember::OutputExpression(open, expression) => {
-                if open {
-                    match expression {
-                        Some(e) => {
-                            let updated_nested_expression = e.append_item(token)?;
-                            Ok(ExpressionMember::OutputExpression(
-                                true,
-                                Some(updated_nested_expression),
-                            ))
-                        }
-                        None => Ok(ExpressionMember::OutputExpression(
-                            true,
-                            Some(OutputExpression::new(token)?),
-                        )),
-                    }
-                } else {
+            ExpressionMember::OutputExpression(false, None) => {
+                if is_close {
                     Err(ParserError::InvalidExpression(
-                        token.position.0,
+                        token.position,
                         Backtrace::new(),
                     ))
+                } else {
+                    Ok(ExpressionMember::OutputExpression(
+                        true,
+                        Some(OutputExpression::new(token)?),
+                    ))
                 }
             }
+            ExpressionMember::OutputExpression(true, Some(expression)) => {
+                if is_close && !expression.has_open_group() {
+                    if expression.is_complete() {
+                        Ok(ExpressionMember::OutputExpression(false, Some(expression)))
+                    } else {
+                        Err(ParserError::InvalidExpression(
+                            token.position,
+                            Backtrace::new(),
+                        ))
+                    }
+                } else {
+                    Ok(ExpressionMember::OutputExpression(
+                        true,
+                        Some(expression.append_item(token)?),
+                    ))
+                }
+            }
+            ExpressionMember::ReferenceExpression(expression) => Ok(
+                ExpressionMember::ReferenceExpression(expression.append_item(token)?),
+            ),
+            ExpressionMember::List(true, items) => Self::append_to_list(items, token),
+            ExpressionMember::Map(true, entries) => Self::append_to_map(entries, token),
             _ => Err(ParserError::InvalidExpression(
-                token.position.0,
+                token.position,
                 Backtrace::new(),
             )),
         }
@@ -143,155 +372,451 @@ impl<'a> Positioned for ExpressionMember<'a> {
             ExpressionMember::Literal(token) => token.position,
             ExpressionMember::ReferenceExpression(expression) => expression.get_position(),
             ExpressionMember::OutputExpression(_, expression) => {
-                expression.as_ref().unwrap().get_position()
+                expression.as_ref().map(|e| e.get_position()).unwrap_or_default()
+            }
+            ExpressionMember::Operation(expression) => expression.get_position(),
+            ExpressionMember::Unary(_, operand) => operand.get_position(),
+            ExpressionMember::List(_, items) => match (items.first(), items.last()) {
+                (Some(first), Some(last)) => (first.get_position().0, last.get_position().1),
+                _ => (Position::default(), Position::default()),
+            },
+            ExpressionMember::Map(_, entries) => {
+                let start = entries.first().and_then(|entry| entry.key);
+                let end = entries.iter().rev().find_map(|entry| entry.value.as_ref());
+                match (start, end) {
+                    (Some(start), Some(end)) => (start.position.0, end.get_position().1),
+                    _ => (Position::default(), Position::default()),
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Spanned for ExpressionMember<'a> {
+    fn get_span(&self) -> Span {
+        match self {
+            ExpressionMember::Nil => Span::default(),
+            ExpressionMember::Literal(token) => token.span,
+            ExpressionMember::ReferenceExpression(expression) => expression.get_span(),
+            ExpressionMember::OutputExpression(_, expression) => {
+                expression.as_ref().map(|e| e.get_span()).unwrap_or_default()
+            }
+            ExpressionMember::Operation(expression) => expression.get_span(),
+            ExpressionMember::Unary(_, operand) => operand.get_span(),
+            ExpressionMember::List(_, items) => match (items.first(), items.last()) {
+                (Some(first), Some(last)) => first.get_span().to(last.get_span()),
+                _ => Span::default(),
+            },
+            ExpressionMember::Map(_, entries) => {
+                let start = entries.first().and_then(|entry| entry.key);
+                let end = entries.iter().rev().find_map(|entry| entry.value.as_ref());
+                match (start, end) {
+                    (Some(start), Some(end)) => start.span.to(end.get_span()),
+                    _ => Span::default(),
+                }
             }
         }
     }
 }
 
+/// Expression `left operation right`,
+/// a single member has neither an operation nor the right member
 #[derive(Debug, Clone, PartialEq, Default, Serialize)]
-pub struct OutputExpression<'a>(
-    pub Box<ExpressionMember<'a>>,
-    pub Option<OperationKind>,
-    pub Option<Box<ExpressionMember<'a>>>,
-);
+pub struct OutputExpression<'a> {
+    pub left: Box<ExpressionMember<'a>>,
+    /// Operation as far as it's parsed, `!` until `!=` is complete
+    pub operation: Option<OperationKind>,
+    pub right: Option<Box<ExpressionMember<'a>>>,
+}
 
 impl<'a> Leveled for OutputExpression<'a> {
     fn get_level(&self) -> u16 {
-        let OutputExpression(first_member, _, _) = self;
-        first_member.get_level()
+        self.left.get_level()
     }
 }
 
 impl<'a> Expandable<'a, OutputExpression<'a>, Token<'a>> for OutputExpression<'a> {
+    /// Appends the token to the expression as it's written,
+    /// operations are placed by precedence on the right edge of the expression
     fn append_item(self, token: Token<'a>) -> Result<OutputExpression<'a>, ParserError> {
-        let OutputExpression(first_member, op, last_member) = self;
-        // TODO: can this be done without matching?
-
-        match first_member.as_ref() {
-            ExpressionMember::OutputExpression(open, expression) => {
-                if *open {
-                    if token.kind.unwrap() == TokenKind::CloseParentheses {
-                        if expression.is_some() {
-                            Ok(OutputExpression(
-                                Box::new(ExpressionMember::OutputExpression(
-                                    false,
-                                    expression.clone(),
-                                )),
-                                None,
-                                None,
-                            ))
-                        } else {
-                            Err(ParserError::InvalidExpression(
-                                token.position.0,
-                                Backtrace::new(),
-                            ))
-                        }
-                    } else if expression.is_some() {
-                        let next_expression = expression.clone().unwrap().append_item(token)?;
-                        Ok(OutputExpression(
-                            Box::new(ExpressionMember::OutputExpression(
-                                true,
-                                Some(next_expression),
-                            )),
-                            None,
-                            None,
-                        ))
-                    } else {
-                        Err(ParserError::InvalidBlock)
-                    }
-                } else if expression.is_none() {
-                    Ok(OutputExpression(
-                        Box::new(ExpressionMember::OutputExpression(
-                            true,
-                            Some(OutputExpression::new(token)?),
-                        )),
-                        None,
-                        None,
+        let kind = token.kind.unwrap();
+        let member = self.into_member();
+        // `None` while an operand is missing, `Some(true)` when the last one goes on
+        let continues = member.last_operand().map(|last| match last {
+            ExpressionMember::ReferenceExpression(reference) => {
+                matches!(
+                    kind,
+                    TokenKind::Dot | TokenKind::OpenSquareBrace | TokenKind::Question
+                ) || !reference.is_complete()
+            }
+            last => last.is_open_group(),
+        });
+
+        let member = match (member.pending_operation(), continues) {
+            (Some(operation), _) => match Self::parse_operation_second_token(operation, token) {
+                Some(operation) => member.without_pending_operation().with_operation(operation),
+                // `!` only starts `!=`, `!&`, `!!` and `!|`
+                None if operation == OperationKind::LogicOperation(LogicOperation::NOT) => {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("{}", token),
+                        token.position,
+                        Backtrace::new(),
                     ))
-                } else {
-                    Err(ParserError::InvalidBlock)
                 }
-            }
-            ExpressionMember::Literal(tok) => Ok(OutputExpression(
-                Box::new(ExpressionMember::OutputExpression(
-                    false,
-                    Some(OutputExpression::new(tok.clone())?.append_item(token)?),
-                )),
-                None,
-                None,
-            )),
-            _ => {
-                if op.is_none() {
-                    match Self::parse_operation_first_token(token) {
-                        Some(operation) => Ok(OutputExpression(
-                            first_member.clone(),
-                            Some(operation),
-                            None,
-                        )),
-                        None => {
-                            println!("{:?}", first_member);
-                            Err(ParserError::UnexpectedToken(
-                                format!("{}", token),
-                                token.position.0,
-                                Backtrace::new(),
-                            ))
-                        }
-                    }
-                } else {
-                    match Self::parse_operation_second_token(op.unwrap(), token) {
-                        Some(operation) => Ok(OutputExpression(
-                            first_member.clone(),
-                            Some(operation),
-                            None,
-                        )),
-                        None => {
-                            if last_member.is_none() {
-                                Ok(OutputExpression(
-                                    first_member.clone(),
-                                    op.clone(),
-                                    Some(Box::new(ExpressionMember::new(token)?)),
-                                ))
-                            } else {
-                                let child_expression = last_member.unwrap();
-                                let child_member = child_expression.append_item(token)?;
-                                Ok(OutputExpression(
-                                    first_member.clone(),
-                                    op.clone(),
-                                    Some(Box::new(child_member)),
-                                ))
-                            }
-                        }
-                    }
+                None => member.with_operand(ExpressionMember::new(token)?)?,
+            },
+            (None, None) => member.with_operand(ExpressionMember::new(token)?)?,
+            (None, Some(true)) => member.map_last(|last| last.append_item(token))?,
+            (None, Some(false)) => match (Self::parse_operation_first_token(token), kind) {
+                (Some(operation), _) => member.with_operation(operation),
+                // `a -1` is lexed as `a` followed by the number `-1`
+                (None, TokenKind::Int(_)) | (None, TokenKind::Float(_))
+                    if token.content.starts_with('-') =>
+                {
+                    member
+                        .with_operation(OperationKind::MathOperation(MathOperation::Subtract))
+                        .with_operand(ExpressionMember::Literal(unsigned(token)))?
                 }
+                (None, _) => {
+                    return Err(ParserError::UnexpectedToken(
+                        format!("{}", token),
+                        token.position,
+                        Backtrace::new(),
+                    ))
+                }
+            },
+        };
+
+        Ok(Self::from_member(member))
+    }
+}
+
+/// Edits on the right edge of an expression, the only part a token can change.
+///
+/// The edge goes down through operations and prefixes,
+/// a token costs as many steps as the edge is long rather than the whole expression.
+impl<'a> ExpressionMember<'a> {
+    /// Member written last, `None` when an operand is missing
+    fn last_operand(&self) -> Option<&ExpressionMember<'a>> {
+        match self {
+            ExpressionMember::Operation(expression) => expression.last_member(),
+            ExpressionMember::Unary(_, operand) if **operand == ExpressionMember::Nil => None,
+            ExpressionMember::Unary(_, operand) => operand.last_operand(),
+            member => Some(member),
+        }
+    }
+
+    /// Operation waiting for its right operand
+    fn pending_operation(&self) -> Option<OperationKind> {
+        match self {
+            ExpressionMember::Operation(expression) => match &expression.right {
+                Some(right) => right.pending_operation(),
+                None => expression.operation,
+            },
+            ExpressionMember::Unary(_, operand) => operand.pending_operation(),
+            _ => None,
+        }
+    }
+
+    /// Takes back the operation waiting for its operand,
+    /// `a & b !` is `a & b` before `!` becomes `!=`
+    fn without_pending_operation(self) -> ExpressionMember<'a> {
+        match self {
+            ExpressionMember::Operation(OutputExpression {
+                left, right: None, ..
+            }) => *left,
+            ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right: Some(right),
+            }) => ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right: Some(Box::new(right.without_pending_operation())),
+            }),
+            ExpressionMember::Unary(unary, operand) => {
+                ExpressionMember::Unary(unary, Box::new(operand.without_pending_operation()))
             }
+            member => member,
         }
+    }
+
+    /// Places the operation after a complete member.
+    ///
+    /// It takes the right operand of an operation or a prefix binding looser than it does,
+    /// otherwise the whole member becomes its left operand.
+    fn with_operation(self, operation: OperationKind) -> ExpressionMember<'a> {
+        let (left_power, _) = binding_power(operation);
+        match self {
+            ExpressionMember::Operation(OutputExpression {
+                left,
+                operation: Some(current),
+                right: Some(right),
+            }) if left_power >= binding_power(current).1 => {
+                ExpressionMember::Operation(OutputExpression {
+                    left,
+                    operation: Some(current),
+                    right: Some(Box::new(right.with_operation(operation))),
+                })
+            }
+            ExpressionMember::Unary(unary, operand) if left_power >= UNARY_BINDING_POWER => {
+                ExpressionMember::Unary(unary, Box::new(operand.with_operation(operation)))
+            }
+            member => ExpressionMember::Operation(OutputExpression {
+                left: Box::new(member),
+                operation: Some(operation),
+                right: None,
+            }),
+        }
+    }
+
+    /// Puts the operand where one is missing,
+    /// a complete member has no place for it
+    fn with_operand(
+        self,
+        operand: ExpressionMember<'a>,
+    ) -> Result<ExpressionMember<'a>, ParserError> {
+        Ok(match self {
+            ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right,
+            }) => ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right: Some(Box::new(match right {
+                    Some(right) => right.with_operand(operand)?,
+                    None => operand,
+                })),
+            }),
+            ExpressionMember::Unary(unary, current) => {
+                ExpressionMember::Unary(unary, Box::new(current.with_operand(operand)?))
+            }
+            ExpressionMember::Nil => operand,
+            _ => {
+                return Err(ParserError::InvalidExpression(
+                    operand.get_position(),
+                    Backtrace::new(),
+                ))
+            }
+        })
+    }
+
+    /// Replaces the member written last
+    fn map_last<F>(self, map: F) -> Result<ExpressionMember<'a>, ParserError>
+    where
+        F: FnOnce(ExpressionMember<'a>) -> Result<ExpressionMember<'a>, ParserError>,
+    {
+        Ok(match self {
+            ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right: Some(right),
+            }) => ExpressionMember::Operation(OutputExpression {
+                left,
+                operation,
+                right: Some(Box::new(right.map_last(map)?)),
+            }),
+            ExpressionMember::Unary(unary, operand) => {
+                ExpressionMember::Unary(unary, Box::new(operand.map_last(map)?))
+            }
+            member => map(member)?,
+        })
+    }
+}
+
+/// Binding powers `(left, right)` of an operation, the higher the tighter it binds.
+///
+/// Assignments and `^` group to the right, other operations to the left.
+fn binding_power(operation: OperationKind) -> (u8, u8) {
+    match operation {
+        OperationKind::Assign
+        | OperationKind::MathOperation(MathOperation::AddAssign)
+        | OperationKind::MathOperation(MathOperation::SubtractAssign) => (2, 1),
+        OperationKind::LogicOperation(LogicOperation::OR)
+        | OperationKind::LogicOperation(LogicOperation::XOR)
+        | OperationKind::LogicOperation(LogicOperation::NOR)
+        | OperationKind::LogicOperation(LogicOperation::XNOR) => (3, 4),
+        OperationKind::LogicOperation(_) => (5, 6),
+        OperationKind::ComparisonOperation(_) => (7, 8),
+        OperationKind::MathOperation(MathOperation::Sum)
+        | OperationKind::MathOperation(MathOperation::Subtract) => (9, 10),
+        OperationKind::MathOperation(MathOperation::Multiply)
+        | OperationKind::MathOperation(MathOperation::Divide)
+        | OperationKind::MathOperation(MathOperation::Reminder) => (11, 12),
+        OperationKind::MathOperation(MathOperation::Power) => (16, 15),
+    }
+}
+
+/// Prefix operations bind tighter than any operation but `^`, `-a^2` is `-(a^2)`
+const UNARY_BINDING_POWER: u8 = 13;
 
-        // first_member.
+/// Number `-1` as `1`
+fn unsigned(token: Token) -> Token {
+    let kind = match token.kind {
+        Some(TokenKind::Int(value)) => Some(TokenKind::Int(-value)),
+        Some(TokenKind::Float(value)) => Some(TokenKind::Float(-value)),
+        kind => kind,
+    };
+    let (start, end) = token.position;
+    Token {
+        kind,
+        len: token.len - 1,
+        content: &token.content[1..],
+        position: (Position(start.0, start.1 + 1), end),
+        span: Span::new(token.span.start + 1, token.span.end),
+        ..token
     }
 }
 
 impl<'a> Positioned for OutputExpression<'a> {
     fn get_position(&self) -> (Position, Position) {
-        let OutputExpression(first_member, _, last_member) = self;
-        let start_position = first_member.get_position().0;
-        let end_position = {
-            if last_member.is_some() {
-                last_member.clone().unwrap().get_position().1
-            } else {
-                first_member.get_position().1
-            }
+        let start_position = self.left.get_position().0;
+        let end_position = match &self.right {
+            Some(right) => right.get_position().1,
+            None => self.left.get_position().1,
         };
 
         (start_position, end_position)
     }
 }
 
+impl<'a> Spanned for OutputExpression<'a> {
+    fn get_span(&self) -> Span {
+        match &self.right {
+            Some(right) => self.left.get_span().to(right.get_span()),
+            None => self.left.get_span(),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for OutputExpression<'a> {
+    type Output = OutputExpression<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> OutputExpression<'b> {
+        OutputExpression {
+            left: self.left.relocate(relocation),
+            operation: self.operation,
+            right: self.right.relocate(relocation),
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for ExpressionMember<'a> {
+    type Output = ExpressionMember<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> ExpressionMember<'b> {
+        match self {
+            ExpressionMember::Literal(token) => ExpressionMember::Literal(token.relocate(relocation)),
+            ExpressionMember::OutputExpression(is_closed, expression) => {
+                ExpressionMember::OutputExpression(*is_closed, expression.relocate(relocation))
+            }
+            ExpressionMember::ReferenceExpression(expression) => {
+                ExpressionMember::ReferenceExpression(expression.relocate(relocation))
+            }
+            ExpressionMember::Operation(expression) => {
+                ExpressionMember::Operation(expression.relocate(relocation))
+            }
+            ExpressionMember::Unary(operation, operand) => {
+                ExpressionMember::Unary(*operation, operand.relocate(relocation))
+            }
+            ExpressionMember::List(is_open, items) => ExpressionMember::List(
+                *is_open,
+                items.iter().map(|item| item.relocate(relocation)).collect(),
+            ),
+            ExpressionMember::Map(is_open, entries) => ExpressionMember::Map(
+                *is_open,
+                entries
+                    .iter()
+                    .map(|entry| MapEntry {
+                        key: entry.key.relocate(relocation),
+                        value: entry.value.relocate(relocation),
+                    })
+                    .collect(),
+            ),
+            ExpressionMember::Nil => ExpressionMember::Nil,
+        }
+    }
+}
+
 impl<'a> OutputExpression<'a> {
     pub fn new(token: Token<'a>) -> Result<Self, ParserError> {
         let left_member = ExpressionMember::new(token)?;
 
-        Ok(Self(Box::new(left_member), None, None))
+        Ok(Self {
+            left: Box::new(left_member),
+            operation: None,
+            right: None,
+        })
+    }
+
+    /// False while an operation or a prefix waits for its operand,
+    /// parentheses are open or a reference ends with `.`
+    pub fn is_complete(&self) -> bool {
+        match self.last_member() {
+            Some(ExpressionMember::ReferenceExpression(reference)) => reference.is_complete(),
+            Some(member) => !member.is_open_group(),
+            None => false,
+        }
+    }
+
+    /// Placeholder of an item which isn't written yet
+    pub(crate) fn is_nil(&self) -> bool {
+        *self.left == ExpressionMember::Nil && self.operation.is_none()
+    }
+
+    /// Parentheses, a list, a map or brackets of an index which are not closed yet
+    pub(crate) fn has_open_group(&self) -> bool {
+        self.last_member().is_some_and(|member| match member {
+            ExpressionMember::ReferenceExpression(reference) => reference.has_open_index(),
+            member => member.is_open_group(),
+        })
+    }
+
+    /// Bracket which closes the innermost group left open,
+    /// `None` when all of them are closed
+    pub(crate) fn closing_bracket(&self) -> Option<TokenKind<'static>> {
+        self.last_member().and_then(ExpressionMember::closing_bracket)
+    }
+
+    /// Member written last, `None` when an operand is missing
+    fn last_member(&self) -> Option<&ExpressionMember<'a>> {
+        let mut member = match (&self.operation, &self.right) {
+            (Some(_), None) => return None,
+            (_, Some(right)) => right.as_ref(),
+            (None, None) => self.left.as_ref(),
+        };
+        loop {
+            member = match member {
+                ExpressionMember::Operation(expression) => return expression.last_member(),
+                ExpressionMember::Unary(_, operand) => match operand.as_ref() {
+                    ExpressionMember::Nil => return None,
+                    operand => operand,
+                },
+                member => return Some(member),
+            }
+        }
+    }
+
+    /// Expression as a member, the operation when there is one
+    fn into_member(self) -> ExpressionMember<'a> {
+        match (self.operation, self.right) {
+            (None, None) => *self.left,
+            (operation, right) => ExpressionMember::Operation(OutputExpression {
+                left: self.left,
+                operation,
+                right,
+            }),
+        }
+    }
+
+    fn from_member(member: ExpressionMember<'a>) -> Self {
+        match member {
+            ExpressionMember::Operation(expression) => expression,
+            member => OutputExpression {
+                left: Box::new(member),
+                operation: None,
+                right: None,
+            },
+        }
     }
 
     fn parse_operation_first_token(token: Token) -> Option<OperationKind> {
@@ -382,10 +907,10 @@ impl<'a> OutputExpression<'a> {
 
 impl<'a> From<ReferenceExpression<'a>> for OutputExpression<'a> {
     fn from(reference_expression: ReferenceExpression<'a>) -> OutputExpression<'a> {
-        OutputExpression(
-            Box::new(ExpressionMember::ReferenceExpression(reference_expression)),
-            None,
-            None,
-        )
+        OutputExpression {
+            left: Box::new(ExpressionMember::ReferenceExpression(reference_expression)),
+            operation: None,
+            right: None,
+        }
     }
 }
@@ -1,79 +1,258 @@
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
+use super::output_expression::OutputExpression;
 use super::traits::*;
+use crate::incremental::Relocation;
 use serde::Serialize;
 use failure::Backtrace;
 use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
 use ra_lexer::token::{Token, TokenKind};
 
+/// Reference `a.b.c`, a segment linked to the following ones.
+///
+/// Lists are indexed by integer segments, `a.1` or `a.-1` counting from the end,
+/// or by an expression in brackets `a[i + 1]`.
+/// `?` after a segment, `a?.b` or `a?[0]`, gives none instead of the rest
+/// when the segment is missing.
 #[derive(Clone, Debug, PartialEq, Serialize)]
-pub struct ReferenceExpression<'a>(
-    pub Token<'a>,
-    pub Option<Option<Box<ReferenceExpression<'a>>>>,
-);
+pub struct ReferenceExpression<'a> {
+    /// Identifier of the segment, an integer index or `[` of an index in brackets
+    pub name: Token<'a>,
+    /// Expression in brackets, `true` while it's open,
+    /// the expression is `Nil` right after `[`
+    pub index: Option<(bool, OutputExpression<'a>)>,
+    /// Segment followed by `?`
+    pub safe: bool,
+    /// `None` for the last segment, `Some(None)` after a trailing `.`
+    /// while the next segment is being parsed
+    pub next: Option<Option<Box<ReferenceExpression<'a>>>>,
+}
 
 impl<'a> ReferenceExpression<'a> {
     pub fn new(token: Token<'a>) -> Result<Self, ParserError> {
         match token.kind.unwrap() {
-            TokenKind::Identifier(_) => Ok(Self(token, None)),
+            TokenKind::Identifier(_) => Ok(Self::segment(token)),
             _ => Err(ParserError::ExpectedAGotB(
                 format!("{}", token),
-                format!("{:?}", vec![TokenKind::Identifier("")]),
-                token.position.0,
+                expected_one_of(&[TokenKind::Identifier("")]),
+                token.position,
                 Backtrace::new()
             )),
         }
     }
+
+    fn segment(name: Token<'a>) -> Self {
+        Self {
+            name,
+            index: None,
+            safe: false,
+            next: None,
+        }
+    }
+
+    /// Segment following this one
+    pub fn next(&self) -> Option<&ReferenceExpression<'a>> {
+        match &self.next {
+            Some(Some(next)) => Some(next),
+            _ => None,
+        }
+    }
+
+    /// This and the following segments in order
+    pub fn parts(&self) -> impl Iterator<Item = &ReferenceExpression<'a>> {
+        std::iter::successors(Some(self), |expression| expression.next())
+    }
+
+    /// Names of the segments in order, integer indices included
+    /// and indices in brackets left out
+    pub fn segments(&self) -> impl Iterator<Item = &Token<'a>> {
+        self.parts()
+            .filter(|expression| expression.index.is_none())
+            .map(|expression| &expression.name)
+    }
+
+    /// Expressions in brackets in order
+    pub fn indices(&self) -> impl Iterator<Item = &OutputExpression<'a>> {
+        self.parts()
+            .filter_map(|expression| expression.index.as_ref().map(|(_, index)| index))
+    }
+
+    /// Segment indexing a list, `1` of `a.1` or `[i]` of `a[i]`
+    pub fn is_index(&self) -> bool {
+        self.index.is_some() || matches!(self.name.kind, Some(TokenKind::Int(_)))
+    }
+
+    /// Brackets of an index which are not closed yet, `a[0` or `a.b[c[1]`
+    pub(crate) fn has_open_index(&self) -> bool {
+        self.parts().any(|part| matches!(part.index, Some((true, _))))
+    }
+
+    /// Bracket which closes the innermost index left open, `)` of `a[(1`
+    pub(crate) fn closing_bracket(&self) -> Option<TokenKind<'static>> {
+        self.parts().find_map(|part| match &part.index {
+            Some((true, index)) => index.closing_bracket().or(Some(TokenKind::CloseSquareBrace)),
+            _ => None,
+        })
+    }
+
+    /// False while the segment after a trailing `.` or `?` is missing
+    /// or brackets are open
+    pub fn is_complete(&self) -> bool {
+        if let Some((true, _)) = self.index {
+            return false;
+        }
+        match &self.next {
+            None => !self.safe,
+            Some(None) => false,
+            Some(Some(next)) => next.is_complete(),
+        }
+    }
+
+    /// Segments joined with dots, e.g. `recipe.name` or `recipe.pictures.1`
+    pub fn path(&self) -> String {
+        self.segments()
+            .map(|segment| segment.content)
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Starts the segment following this one
+    fn append_segment(self, token: Token<'a>) -> Result<Self, ParserError> {
+        let next = match token.kind.unwrap() {
+            TokenKind::Identifier(_) | TokenKind::Int(_) => Self::segment(token),
+            TokenKind::Boolean(_) | TokenKind::Null => {
+                return Err(ParserError::ReservedWord(
+                    token.content.to_owned(),
+                    token.position,
+                    Backtrace::new(),
+                ))
+            }
+            _ => {
+                return Err(ParserError::ExpectedAGotB(
+                    format!("{}", token),
+                    expected_one_of(&[TokenKind::Identifier(""), TokenKind::Int(0)]),
+                    token.position,
+                    Backtrace::new(),
+                ))
+            }
+        };
+        Ok(ReferenceExpression {
+            next: Some(Some(Box::new(next))),
+            ..self
+        })
+    }
+
+    /// Continues the expression in brackets until `]`
+    fn append_to_index(self, token: Token<'a>) -> Result<Self, ParserError> {
+        let index = match self.index.clone() {
+            Some((true, index)) => index,
+            _ => unreachable!(),
+        };
+        let index = if token.kind.unwrap() == TokenKind::CloseSquareBrace && !index.has_open_group() {
+            if index.is_nil() || !index.is_complete() {
+                return Err(ParserError::InvalidExpression(
+                    token.position,
+                    Backtrace::new(),
+                ));
+            }
+            (false, index)
+        } else if index.is_nil() {
+            (true, OutputExpression::new(token)?)
+        } else {
+            (true, index.append_item(token)?)
+        };
+        Ok(ReferenceExpression {
+            index: Some(index),
+            ..self
+        })
+    }
 }
 
 impl<'a> Leveled for ReferenceExpression<'a> {
     fn get_level(&self) -> u16 {
-        let ReferenceExpression(first_token, _) = self;
-        first_token.level
+        self.name.level
     }
 }
 
 impl<'a> Positioned for ReferenceExpression<'a> {
     fn get_position(&self) -> (Position, Position) {
-        let ReferenceExpression(first_token, next) = self;
-
-        let start_position = first_token.position.0;
+        let start_position = self.name.position.0;
 
-        let end_position = {
-            if next.is_some() && next.as_ref().unwrap().is_some() {
-                next.as_ref().unwrap().as_ref().unwrap().get_position().1
-            } else {
-                first_token.position.1
-            }
+        let end_position = match (self.next(), &self.index) {
+            (Some(next), _) => next.get_position().1,
+            (None, Some((_, index))) if !index.is_nil() => index.get_position().1,
+            (None, _) => self.name.position.1,
         };
 
         (start_position, end_position)
     }
 }
 
+impl<'a> Spanned for ReferenceExpression<'a> {
+    fn get_span(&self) -> Span {
+        match (self.next(), &self.index) {
+            (Some(next), _) => self.name.span.to(next.get_span()),
+            (None, Some((_, index))) if !index.is_nil() => self.name.span.to(index.get_span()),
+            (None, _) => self.name.span,
+        }
+    }
+}
+
+impl<'a, 'b> Relocate<'b> for ReferenceExpression<'a> {
+    type Output = ReferenceExpression<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> ReferenceExpression<'b> {
+        ReferenceExpression {
+            name: self.name.relocate(relocation),
+            index: self
+                .index
+                .as_ref()
+                .map(|(is_open, index)| (*is_open, index.relocate(relocation))),
+            safe: self.safe,
+            next: self.next.as_ref().map(|next| next.relocate(relocation)),
+        }
+    }
+}
+
 impl<'a> Expandable<'a, ReferenceExpression<'a>, Token<'a>> for ReferenceExpression<'a> {
     fn append_item(self, token: Token<'a>) -> Result<ReferenceExpression<'a>, ParserError> {
-        let ReferenceExpression(first_token, next) = self;
-        if next.is_none() {
-            match token.kind.unwrap() {
-                TokenKind::Dot => Ok(ReferenceExpression(first_token, Some(None))),
+        if let Some((true, _)) = self.index {
+            return self.append_to_index(token);
+        }
+        match self.next {
+            Some(Some(next)) => {
+                let updated_expression = next.append_item(token)?;
+                Ok(ReferenceExpression {
+                    next: Some(Some(Box::new(updated_expression))),
+                    ..self
+                })
+            }
+            Some(None) => self.append_segment(token),
+            None => match token.kind.unwrap() {
+                TokenKind::Dot => Ok(ReferenceExpression {
+                    next: Some(None),
+                    ..self
+                }),
+                TokenKind::OpenSquareBrace => {
+                    let index = ReferenceExpression {
+                        index: Some((true, OutputExpression::default())),
+                        ..Self::segment(token)
+                    };
+                    Ok(ReferenceExpression {
+                        next: Some(Some(Box::new(index))),
+                        ..self
+                    })
+                }
+                TokenKind::Question if !self.safe => Ok(ReferenceExpression {
+                    safe: true,
+                    ..self
+                }),
                 _ => Err(ParserError::ExpectedAGotB(
                     format!("{}", token),
-                    format!("{:?}", vec![TokenKind::Dot]),
-                    token.position.0,
+                    expected_one_of(&[TokenKind::Dot, TokenKind::OpenSquareBrace, TokenKind::Question]),
+                    token.position,
                     Backtrace::new()
                 )),
-            }
-        } else if next.is_some() && next.as_ref().unwrap().is_none() {
-            Ok(ReferenceExpression(
-                first_token,
-                Some(Some(Box::new(ReferenceExpression::new(token)?))),
-            ))
-        } else {
-            let updated_expression = next.unwrap().unwrap().append_item(token)?;
-            Ok(ReferenceExpression(
-                first_token,
-                Some(Some(Box::new(updated_expression))),
-            ))
+            },
         }
     }
 }
@@ -1,6 +1,8 @@
 use super::errors::ParserError;
+use crate::incremental::Relocation;
 use ra_lexer::token::Token;
 use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
 
 pub (crate) trait Expandable<'a, Item, ByItem> {
     fn append_item(self, item: ByItem) -> Result<Item, ParserError>;
@@ -14,6 +16,39 @@ pub (crate) trait Leveled {
     fn get_level(&self) -> u16;
 }
 
-pub (crate) trait Positioned {
+/// Start and end positions of what was parsed
+pub trait Positioned {
     fn get_position(&self) -> (Position, Position);
-}
\ No newline at end of file
+}
+
+/// Byte span of what was parsed within the source
+pub trait Spanned {
+    fn get_span(&self) -> Span;
+}
+
+/// Moves borrowed tokens to another version of the source
+pub (crate) trait Relocate<'b> {
+    type Output;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Self::Output;
+}
+
+impl<'a, 'b> Relocate<'b> for Token<'a> {
+    type Output = Token<'b>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Token<'b> {
+        relocation.token(self)
+    }
+}
+
+impl<'b, T: Relocate<'b>> Relocate<'b> for Option<T> {
+    type Output = Option<T::Output>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Self::Output {
+        self.as_ref().map(|item| item.relocate(relocation))
+    }
+}
+
+impl<'b, T: Relocate<'b>> Relocate<'b> for Box<T> {
+    type Output = Box<T::Output>;
+    fn relocate(&self, relocation: &Relocation<'b>) -> Self::Output {
+        Box::new(self.as_ref().relocate(relocation))
+    }
+}
@@ -0,0 +1,213 @@
+use ra_lexer::cursor::{Cursor as LexerCursor, Position};
+use ra_lexer::span::Span;
+use ra_lexer::token::{Token, TokenKind};
+use ra_lexer::{indent_width, try_tokenize_cursor};
+use std::ops::Range;
+
+use super::block::Block;
+use super::cursor::Cursor;
+use super::expressions::traits::{Leveled, Positioned, Relocate};
+use super::parser::{parse, ParseResult};
+
+/// Replacement of a byte range of the source, e.g. typing in an editor
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, text: &str) -> Self {
+        Self {
+            span,
+            text: text.to_owned(),
+        }
+    }
+
+    /// Source with the edit applied
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.text.len());
+        edited.push_str(&source[..self.span.start]);
+        edited.push_str(&self.text);
+        edited.push_str(&source[self.span.end..]);
+        edited
+    }
+
+    /// Change of the source length in bytes
+    fn delta(&self) -> isize {
+        self.text.len() as isize - self.span.len() as isize
+    }
+}
+
+/// Moves tokens of blocks which weren't parsed again to the edited source,
+/// tokens from `from` on are moved by `bytes` and `lines`
+pub(crate) struct Relocation<'b> {
+    source: &'b str,
+    from: usize,
+    bytes: isize,
+    lines: i64,
+}
+
+impl<'b> Relocation<'b> {
+    pub(crate) fn token(&self, token: &Token) -> Token<'b> {
+        let (start, end) = token.position;
+        token.relocated(
+            self.source,
+            self.span(token.span),
+            (self.position(token.span, start), self.position(token.span, end)),
+        )
+    }
+
+    pub(crate) fn span(&self, span: Span) -> Span {
+        if span.start >= self.from {
+            Span::new(
+                (span.start as isize + self.bytes) as usize,
+                (span.end as isize + self.bytes) as usize,
+            )
+        } else {
+            span
+        }
+    }
+
+    fn position(&self, span: Span, position: Position) -> Position {
+        if span.start >= self.from {
+            Position((i64::from(position.0) + self.lines) as u32, position.1)
+        } else {
+            position
+        }
+    }
+}
+
+/// Top-level blocks of `program` touching the edited range of its source
+pub fn affected_blocks(program: &Block, edit: &TextEdit) -> Range<usize> {
+    let children = &program.children;
+    let start = children
+        .iter()
+        .position(|child| child.span.end >= edit.span.start)
+        .unwrap_or(children.len());
+    let end = children
+        .iter()
+        .position(|child| child.span.start > edit.span.end)
+        .unwrap_or(children.len());
+
+    start..end.max(start)
+}
+
+/// Parses `source`, which is `previous_source` after `edit`, reusing the previous result.
+///
+/// Only the top-level blocks touched by the edit and the one before them
+/// are lexed and parsed again, the rest are moved to the new source.
+/// The result is the same as of `parse(source)`, which is used instead
+/// when the previous result has errors or the edit reaches further,
+/// e.g. changes the indentation width or joins a block with the next one.
+#[allow(clippy::result_large_err)] // the result of `parse`
+pub fn reparse<'a>(
+    previous: &ParseResult,
+    previous_source: &str,
+    edit: &TextEdit,
+    source: &'a str,
+) -> ParseResult<'a> {
+    debug_assert_eq!(edit.apply(previous_source), source);
+    match previous {
+        Ok(program) => match reparse_blocks(program, previous_source, edit, source) {
+            Some(program) => Ok(program),
+            None => parse(source),
+        },
+        Err(_) => parse(source),
+    }
+}
+
+fn reparse_blocks<'a>(
+    program: &Block,
+    previous_source: &str,
+    edit: &TextEdit,
+    source: &'a str,
+) -> Option<Block<'a>> {
+    // levels of all lines depend on the width of the first indent
+    let width = indent_width(source);
+    if indent_width(previous_source) != width {
+        return None;
+    }
+
+    let children = &program.children;
+    let affected = affected_blocks(program, edit);
+    // edited lines may turn into children of the block before,
+    // lexing starts from a line where nothing is carried over from the line above
+    let mut first = affected.start.saturating_sub(1);
+    while first > 0 && !starts_line(&children[first]) {
+        first -= 1;
+    }
+    let (start, position) = match first {
+        0 => (0, Position(1, 0)),
+        _ => (children[first].span.start, children[first].get_position().0),
+    };
+
+    // lexing goes on up to the first token of the next block,
+    // it has to come out the same for the rest to be reused
+    let next = children.get(affected.end).map(|block| &block.first_token);
+    let end = match next {
+        Some(token) => (token.span.end as isize + edit.delta()) as usize,
+        None => source.len(),
+    };
+    let cursor = LexerCursor::new(source.get(start..end)?, position, 0, width).with_offset(start);
+    let mut tokens = Vec::new();
+    for token in try_tokenize_cursor(cursor) {
+        let token = token.ok()?;
+        if token.kind != Some(TokenKind::Comment) {
+            tokens.push(token);
+        }
+    }
+
+    let relocation = match (next, tokens.last()) {
+        (Some(next), Some(last)) => {
+            let relocation = Relocation {
+                source,
+                from: next.span.start,
+                bytes: edit.delta(),
+                lines: i64::from((last.position.0).0) - i64::from((next.position.0).0),
+            };
+            if relocation.span(next.span) != last.span
+                || next.kind.as_ref().map(std::mem::discriminant)
+                    != last.kind.as_ref().map(std::mem::discriminant)
+                || relocation.token(next) != *last
+            {
+                return None;
+            }
+            relocation
+        }
+        (Some(_), None) => return None,
+        (None, _) => Relocation {
+            source,
+            from: usize::MAX,
+            bytes: 0,
+            lines: 0,
+        },
+    };
+
+    let next_span = next.map(|token| relocation.span(token.span));
+    let mut cursor = Cursor::new(tokens.into_iter());
+    let mut blocks = Vec::new();
+    while cursor.first_ahead().map(|token| token.span) != next_span {
+        // the next block became a part of an edited one
+        if cursor.is_eof() {
+            return None;
+        }
+        blocks.push(cursor.advance_block().ok()?);
+    }
+
+    let mut reparsed = Block::new(Token::default()).unwrap();
+    reparsed.span = Span::new(0, source.len());
+    reparsed.children = children[..first]
+        .iter()
+        .map(|child| child.relocate(&relocation))
+        .chain(blocks)
+        .chain(children[affected.end..].iter().map(|child| child.relocate(&relocation)))
+        .collect();
+
+    Some(reparsed)
+}
+
+/// Block starts at the beginning of a line with nothing before it
+fn starts_line(block: &Block) -> bool {
+    block.get_level() == 0 && (block.get_position().0).1 == 0
+}
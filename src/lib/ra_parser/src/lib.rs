@@ -8,7 +8,14 @@ mod cursor;
 pub mod block;
 pub mod errors;
 pub mod expressions;
+pub mod incremental;
+pub mod owned;
 pub mod parser;
+pub mod syntax;
+pub mod visit;
+
+pub use ra_lexer::diagnostics;
+pub use expressions::{Positioned, Spanned};
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,700 @@
+//! Owned variant of the syntax tree.
+//!
+//! Borrowed trees can't outlive the source they were parsed from,
+//! owned ones copy the text of their tokens so they can be cached,
+//! sent across threads or saved and loaded without the source.
+//! `as_borrowed` gives the borrowed tree back for analysis.
+
+use ra_lexer::cursor::Position;
+use ra_lexer::span::Span;
+use ra_lexer::token as lexer;
+use serde::{Deserialize, Serialize};
+
+use super::block;
+use super::expressions::annotation_expression;
+use super::expressions::content;
+use super::expressions::context_expression::ContextExpression;
+use super::expressions::input_expression;
+use super::expressions::output_expression::{self, OperationKind, UnaryOperation};
+use super::expressions::reference_expression;
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TokenKind {
+    Ampersand,
+    Asterisk,
+    At,
+    Boolean(bool),
+    CloseCurlyBrace,
+    CloseParentheses,
+    CloseSquareBrace,
+    Colon,
+    Coma,
+    Comment,
+    ContentBlock,
+    Dollar,
+    Dot,
+    Equals,
+    Exclamation,
+    Float(f64),
+    ForwardSlash,
+    Greater,
+    HashPound,
+    /// Text is the content of the token
+    Identifier,
+    Int(i64),
+    Less,
+    Minus,
+    Null,
+    OpenCurlyBrace,
+    OpenParentheses,
+    OpenSquareBrace,
+    Percent,
+    Pipe,
+    Plus,
+    Power,
+    Question,
+    SemiColon,
+    Slash,
+    /// Text is the content of the token
+    StringLiteral,
+    Tilde,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub kind: Option<TokenKind>,
+    pub len: u32,
+    pub content: String,
+    pub position: (Position, Position),
+    pub span: Span,
+    pub level: u16,
+    #[serde(default)]
+    pub raw: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum BlockKind {
+    Program,
+    Output(OutputExpression),
+    Input(bool, Option<InputExpression>),
+    Declaration(Option<Token>),
+    Invocation(Option<Token>, Option<InputExpression>),
+    Reference(Option<ReferenceExpression>),
+    ContextModification(Option<ContextExpression>),
+    Content(Content),
+    Annotation(Option<AnnotationExpression>),
+    Union(usize),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub children: Vec<Block>,
+    pub span: Span,
+    pub first_token: Token,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ExpressionMember {
+    Literal(Token),
+    OutputExpression(bool, Option<OutputExpression>),
+    ReferenceExpression(ReferenceExpression),
+    Operation(OutputExpression),
+    Unary(UnaryOperation, Box<ExpressionMember>),
+    List(bool, Vec<OutputExpression>),
+    Map(bool, Vec<MapEntry>),
+    Nil,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MapEntry {
+    pub key: Option<Token>,
+    pub value: Option<OutputExpression>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputExpression {
+    pub left: Box<ExpressionMember>,
+    pub operation: Option<OperationKind>,
+    pub right: Option<Box<ExpressionMember>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ArgumentType {
+    Named(Token, bool),
+    Ordered(u16),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ValueType {
+    Literal(Token),
+    Content(Content),
+    OutputExpression(OutputExpression),
+    ReferenceExpression(ReferenceExpression),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InputExpression {
+    pub argument: Option<ArgumentType>,
+    pub value: Option<ValueType>,
+    pub next: Option<Box<InputExpression>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReferenceExpression {
+    pub name: Token,
+    pub index: Option<(bool, OutputExpression)>,
+    pub safe: bool,
+    pub next: Option<Option<Box<ReferenceExpression>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AnnotationExpression {
+    pub name: Token,
+    pub next: Option<Option<Box<AnnotationExpression>>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ContentBlockMember {
+    Body(String),
+    Template(ReferenceExpression),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Content {
+    pub member: ContentBlockMember,
+    pub next: Option<Box<Content>>,
+}
+
+impl Block {
+    /// Borrowed tree over the text of this one
+    pub fn as_borrowed(&self) -> block::Block<'_> {
+        self.view()
+    }
+}
+
+impl<'a> From<&block::Block<'a>> for Block {
+    fn from(block: &block::Block<'a>) -> Self {
+        block.to_owned_tree()
+    }
+}
+
+impl<'a> From<block::Block<'a>> for Block {
+    fn from(block: block::Block<'a>) -> Self {
+        block.to_owned_tree()
+    }
+}
+
+/// Copies borrowed text into the owned tree
+trait ToOwnedTree {
+    type Owned;
+    fn to_owned_tree(&self) -> Self::Owned;
+}
+
+/// Borrows text of the owned tree
+trait View<'a> {
+    type Borrowed;
+    fn view(&'a self) -> Self::Borrowed;
+}
+
+impl<T: ToOwnedTree> ToOwnedTree for Option<T> {
+    type Owned = Option<T::Owned>;
+    fn to_owned_tree(&self) -> Self::Owned {
+        self.as_ref().map(ToOwnedTree::to_owned_tree)
+    }
+}
+
+impl<'a, T: View<'a>> View<'a> for Option<T> {
+    type Borrowed = Option<T::Borrowed>;
+    fn view(&'a self) -> Self::Borrowed {
+        self.as_ref().map(View::view)
+    }
+}
+
+impl<T: ToOwnedTree> ToOwnedTree for Box<T> {
+    type Owned = Box<T::Owned>;
+    fn to_owned_tree(&self) -> Self::Owned {
+        Box::new(self.as_ref().to_owned_tree())
+    }
+}
+
+impl<'a, T: View<'a>> View<'a> for Box<T> {
+    type Borrowed = Box<T::Borrowed>;
+    fn view(&'a self) -> Self::Borrowed {
+        Box::new(self.as_ref().view())
+    }
+}
+
+impl<'a> ToOwnedTree for lexer::TokenKind<'a> {
+    type Owned = TokenKind;
+    fn to_owned_tree(&self) -> TokenKind {
+        match self {
+            lexer::TokenKind::Ampersand => TokenKind::Ampersand,
+            lexer::TokenKind::Asterisk => TokenKind::Asterisk,
+            lexer::TokenKind::At => TokenKind::At,
+            lexer::TokenKind::Boolean(value) => TokenKind::Boolean(*value),
+            lexer::TokenKind::CloseCurlyBrace => TokenKind::CloseCurlyBrace,
+            lexer::TokenKind::CloseParentheses => TokenKind::CloseParentheses,
+            lexer::TokenKind::CloseSquareBrace => TokenKind::CloseSquareBrace,
+            lexer::TokenKind::Colon => TokenKind::Colon,
+            lexer::TokenKind::Coma => TokenKind::Coma,
+            lexer::TokenKind::Comment => TokenKind::Comment,
+            lexer::TokenKind::ContentBlock => TokenKind::ContentBlock,
+            lexer::TokenKind::Dollar => TokenKind::Dollar,
+            lexer::TokenKind::Dot => TokenKind::Dot,
+            lexer::TokenKind::Equals => TokenKind::Equals,
+            lexer::TokenKind::Exclamation => TokenKind::Exclamation,
+            lexer::TokenKind::Float(value) => TokenKind::Float(*value),
+            lexer::TokenKind::ForwardSlash => TokenKind::ForwardSlash,
+            lexer::TokenKind::Greater => TokenKind::Greater,
+            lexer::TokenKind::HashPound => TokenKind::HashPound,
+            lexer::TokenKind::Identifier(_) => TokenKind::Identifier,
+            lexer::TokenKind::Int(value) => TokenKind::Int(*value),
+            lexer::TokenKind::Less => TokenKind::Less,
+            lexer::TokenKind::Minus => TokenKind::Minus,
+            lexer::TokenKind::Null => TokenKind::Null,
+            lexer::TokenKind::OpenCurlyBrace => TokenKind::OpenCurlyBrace,
+            lexer::TokenKind::OpenParentheses => TokenKind::OpenParentheses,
+            lexer::TokenKind::OpenSquareBrace => TokenKind::OpenSquareBrace,
+            lexer::TokenKind::Percent => TokenKind::Percent,
+            lexer::TokenKind::Pipe => TokenKind::Pipe,
+            lexer::TokenKind::Plus => TokenKind::Plus,
+            lexer::TokenKind::Power => TokenKind::Power,
+            lexer::TokenKind::Question => TokenKind::Question,
+            lexer::TokenKind::SemiColon => TokenKind::SemiColon,
+            lexer::TokenKind::Slash => TokenKind::Slash,
+            lexer::TokenKind::StringLiteral(_) => TokenKind::StringLiteral,
+            lexer::TokenKind::Tilde => TokenKind::Tilde,
+        }
+    }
+}
+
+impl TokenKind {
+    /// Borrowed kind, identifiers and string literals take `content` as their text
+    fn view<'a>(self, content: &'a str) -> lexer::TokenKind<'a> {
+        match self {
+            TokenKind::Ampersand => lexer::TokenKind::Ampersand,
+            TokenKind::Asterisk => lexer::TokenKind::Asterisk,
+            TokenKind::At => lexer::TokenKind::At,
+            TokenKind::Boolean(value) => lexer::TokenKind::Boolean(value),
+            TokenKind::CloseCurlyBrace => lexer::TokenKind::CloseCurlyBrace,
+            TokenKind::CloseParentheses => lexer::TokenKind::CloseParentheses,
+            TokenKind::CloseSquareBrace => lexer::TokenKind::CloseSquareBrace,
+            TokenKind::Colon => lexer::TokenKind::Colon,
+            TokenKind::Coma => lexer::TokenKind::Coma,
+            TokenKind::Comment => lexer::TokenKind::Comment,
+            TokenKind::ContentBlock => lexer::TokenKind::ContentBlock,
+            TokenKind::Dollar => lexer::TokenKind::Dollar,
+            TokenKind::Dot => lexer::TokenKind::Dot,
+            TokenKind::Equals => lexer::TokenKind::Equals,
+            TokenKind::Exclamation => lexer::TokenKind::Exclamation,
+            TokenKind::Float(value) => lexer::TokenKind::Float(value),
+            TokenKind::ForwardSlash => lexer::TokenKind::ForwardSlash,
+            TokenKind::Greater => lexer::TokenKind::Greater,
+            TokenKind::HashPound => lexer::TokenKind::HashPound,
+            TokenKind::Identifier => lexer::TokenKind::Identifier(content),
+            TokenKind::Int(value) => lexer::TokenKind::Int(value),
+            TokenKind::Less => lexer::TokenKind::Less,
+            TokenKind::Minus => lexer::TokenKind::Minus,
+            TokenKind::Null => lexer::TokenKind::Null,
+            TokenKind::OpenCurlyBrace => lexer::TokenKind::OpenCurlyBrace,
+            TokenKind::OpenParentheses => lexer::TokenKind::OpenParentheses,
+            TokenKind::OpenSquareBrace => lexer::TokenKind::OpenSquareBrace,
+            TokenKind::Percent => lexer::TokenKind::Percent,
+            TokenKind::Pipe => lexer::TokenKind::Pipe,
+            TokenKind::Plus => lexer::TokenKind::Plus,
+            TokenKind::Power => lexer::TokenKind::Power,
+            TokenKind::Question => lexer::TokenKind::Question,
+            TokenKind::SemiColon => lexer::TokenKind::SemiColon,
+            TokenKind::Slash => lexer::TokenKind::Slash,
+            TokenKind::StringLiteral => lexer::TokenKind::StringLiteral(content),
+            TokenKind::Tilde => lexer::TokenKind::Tilde,
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for lexer::Token<'a> {
+    type Owned = Token;
+    fn to_owned_tree(&self) -> Token {
+        Token {
+            kind: self.kind.to_owned_tree(),
+            len: self.len,
+            content: self.content.to_owned(),
+            position: self.position,
+            span: self.span,
+            level: self.level,
+            raw: self.raw,
+        }
+    }
+}
+
+impl<'a> View<'a> for Token {
+    type Borrowed = lexer::Token<'a>;
+    fn view(&'a self) -> lexer::Token<'a> {
+        lexer::Token {
+            kind: self.kind.map(|kind| kind.view(&self.content)),
+            len: self.len,
+            content: &self.content,
+            position: self.position,
+            span: self.span,
+            level: self.level,
+            raw: self.raw,
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for block::Block<'a> {
+    type Owned = Block;
+    fn to_owned_tree(&self) -> Block {
+        Block {
+            kind: self.kind.to_owned_tree(),
+            children: self.children.iter().map(ToOwnedTree::to_owned_tree).collect(),
+            span: self.span,
+            first_token: self.first_token.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for Block {
+    type Borrowed = block::Block<'a>;
+    fn view(&'a self) -> block::Block<'a> {
+        block::Block {
+            kind: self.kind.view(),
+            children: self.children.iter().map(View::view).collect(),
+            span: self.span,
+            first_token: self.first_token.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for block::BlockKind<'a> {
+    type Owned = BlockKind;
+    fn to_owned_tree(&self) -> BlockKind {
+        match self {
+            block::BlockKind::Program => BlockKind::Program,
+            block::BlockKind::Output(expression) => BlockKind::Output(expression.to_owned_tree()),
+            block::BlockKind::Input(is_list, expression) => {
+                BlockKind::Input(*is_list, expression.to_owned_tree())
+            }
+            block::BlockKind::Declaration(token) => BlockKind::Declaration(token.to_owned_tree()),
+            block::BlockKind::Invocation(token, expression) => {
+                BlockKind::Invocation(token.to_owned_tree(), expression.to_owned_tree())
+            }
+            block::BlockKind::Reference(expression) => {
+                BlockKind::Reference(expression.to_owned_tree())
+            }
+            block::BlockKind::ContextModification(expression) => {
+                BlockKind::ContextModification(expression.clone())
+            }
+            block::BlockKind::Content(content) => BlockKind::Content(content.to_owned_tree()),
+            block::BlockKind::Annotation(expression) => {
+                BlockKind::Annotation(expression.to_owned_tree())
+            }
+            block::BlockKind::Union(size) => BlockKind::Union(*size),
+        }
+    }
+}
+
+impl<'a> View<'a> for BlockKind {
+    type Borrowed = block::BlockKind<'a>;
+    fn view(&'a self) -> block::BlockKind<'a> {
+        match self {
+            BlockKind::Program => block::BlockKind::Program,
+            BlockKind::Output(expression) => block::BlockKind::Output(expression.view()),
+            BlockKind::Input(is_list, expression) => {
+                block::BlockKind::Input(*is_list, expression.view())
+            }
+            BlockKind::Declaration(token) => block::BlockKind::Declaration(token.view()),
+            BlockKind::Invocation(token, expression) => {
+                block::BlockKind::Invocation(token.view(), expression.view())
+            }
+            BlockKind::Reference(expression) => block::BlockKind::Reference(expression.view()),
+            BlockKind::ContextModification(expression) => {
+                block::BlockKind::ContextModification(expression.clone())
+            }
+            BlockKind::Content(content) => block::BlockKind::Content(content.view()),
+            BlockKind::Annotation(expression) => block::BlockKind::Annotation(expression.view()),
+            BlockKind::Union(size) => block::BlockKind::Union(*size),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for output_expression::OutputExpression<'a> {
+    type Owned = OutputExpression;
+    fn to_owned_tree(&self) -> OutputExpression {
+        OutputExpression {
+            left: self.left.to_owned_tree(),
+            operation: self.operation,
+            right: self.right.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for OutputExpression {
+    type Borrowed = output_expression::OutputExpression<'a>;
+    fn view(&'a self) -> output_expression::OutputExpression<'a> {
+        output_expression::OutputExpression {
+            left: self.left.view(),
+            operation: self.operation,
+            right: self.right.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for output_expression::ExpressionMember<'a> {
+    type Owned = ExpressionMember;
+    fn to_owned_tree(&self) -> ExpressionMember {
+        match self {
+            output_expression::ExpressionMember::Literal(token) => {
+                ExpressionMember::Literal(token.to_owned_tree())
+            }
+            output_expression::ExpressionMember::OutputExpression(is_closed, expression) => {
+                ExpressionMember::OutputExpression(*is_closed, expression.to_owned_tree())
+            }
+            output_expression::ExpressionMember::ReferenceExpression(expression) => {
+                ExpressionMember::ReferenceExpression(expression.to_owned_tree())
+            }
+            output_expression::ExpressionMember::Operation(expression) => {
+                ExpressionMember::Operation(expression.to_owned_tree())
+            }
+            output_expression::ExpressionMember::Unary(operation, operand) => {
+                ExpressionMember::Unary(*operation, operand.to_owned_tree())
+            }
+            output_expression::ExpressionMember::List(is_open, items) => ExpressionMember::List(
+                *is_open,
+                items.iter().map(ToOwnedTree::to_owned_tree).collect(),
+            ),
+            output_expression::ExpressionMember::Map(is_open, entries) => ExpressionMember::Map(
+                *is_open,
+                entries.iter().map(ToOwnedTree::to_owned_tree).collect(),
+            ),
+            output_expression::ExpressionMember::Nil => ExpressionMember::Nil,
+        }
+    }
+}
+
+impl<'a> View<'a> for ExpressionMember {
+    type Borrowed = output_expression::ExpressionMember<'a>;
+    fn view(&'a self) -> output_expression::ExpressionMember<'a> {
+        match self {
+            ExpressionMember::Literal(token) => {
+                output_expression::ExpressionMember::Literal(token.view())
+            }
+            ExpressionMember::OutputExpression(is_closed, expression) => {
+                output_expression::ExpressionMember::OutputExpression(*is_closed, expression.view())
+            }
+            ExpressionMember::ReferenceExpression(expression) => {
+                output_expression::ExpressionMember::ReferenceExpression(expression.view())
+            }
+            ExpressionMember::Operation(expression) => {
+                output_expression::ExpressionMember::Operation(expression.view())
+            }
+            ExpressionMember::Unary(operation, operand) => {
+                output_expression::ExpressionMember::Unary(*operation, operand.view())
+            }
+            ExpressionMember::List(is_open, items) => output_expression::ExpressionMember::List(
+                *is_open,
+                items.iter().map(View::view).collect(),
+            ),
+            ExpressionMember::Map(is_open, entries) => output_expression::ExpressionMember::Map(
+                *is_open,
+                entries.iter().map(View::view).collect(),
+            ),
+            ExpressionMember::Nil => output_expression::ExpressionMember::Nil,
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for output_expression::MapEntry<'a> {
+    type Owned = MapEntry;
+    fn to_owned_tree(&self) -> MapEntry {
+        MapEntry {
+            key: self.key.to_owned_tree(),
+            value: self.value.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for MapEntry {
+    type Borrowed = output_expression::MapEntry<'a>;
+    fn view(&'a self) -> output_expression::MapEntry<'a> {
+        output_expression::MapEntry {
+            key: self.key.view(),
+            value: self.value.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for input_expression::InputExpression<'a> {
+    type Owned = InputExpression;
+    fn to_owned_tree(&self) -> InputExpression {
+        InputExpression {
+            argument: self.argument.to_owned_tree(),
+            value: self.value.to_owned_tree(),
+            next: self.next.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for InputExpression {
+    type Borrowed = input_expression::InputExpression<'a>;
+    fn view(&'a self) -> input_expression::InputExpression<'a> {
+        input_expression::InputExpression {
+            argument: self.argument.view(),
+            value: self.value.view(),
+            next: self.next.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for input_expression::ArgumentType<'a> {
+    type Owned = ArgumentType;
+    fn to_owned_tree(&self) -> ArgumentType {
+        match self {
+            input_expression::ArgumentType::Named(token, is_flag) => {
+                ArgumentType::Named(token.to_owned_tree(), *is_flag)
+            }
+            input_expression::ArgumentType::Ordered(index) => ArgumentType::Ordered(*index),
+        }
+    }
+}
+
+impl<'a> View<'a> for ArgumentType {
+    type Borrowed = input_expression::ArgumentType<'a>;
+    fn view(&'a self) -> input_expression::ArgumentType<'a> {
+        match self {
+            ArgumentType::Named(token, is_flag) => {
+                input_expression::ArgumentType::Named(token.view(), *is_flag)
+            }
+            ArgumentType::Ordered(index) => input_expression::ArgumentType::Ordered(*index),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for input_expression::ValueType<'a> {
+    type Owned = ValueType;
+    fn to_owned_tree(&self) -> ValueType {
+        match self {
+            input_expression::ValueType::Literal(token) => ValueType::Literal(token.to_owned_tree()),
+            input_expression::ValueType::Content(content) => {
+                ValueType::Content(content.to_owned_tree())
+            }
+            input_expression::ValueType::OutputExpression(expression) => {
+                ValueType::OutputExpression(expression.to_owned_tree())
+            }
+            input_expression::ValueType::ReferenceExpression(expression) => {
+                ValueType::ReferenceExpression(expression.to_owned_tree())
+            }
+        }
+    }
+}
+
+impl<'a> View<'a> for ValueType {
+    type Borrowed = input_expression::ValueType<'a>;
+    fn view(&'a self) -> input_expression::ValueType<'a> {
+        match self {
+            ValueType::Literal(token) => input_expression::ValueType::Literal(token.view()),
+            ValueType::Content(content) => input_expression::ValueType::Content(content.view()),
+            ValueType::OutputExpression(expression) => {
+                input_expression::ValueType::OutputExpression(expression.view())
+            }
+            ValueType::ReferenceExpression(expression) => {
+                input_expression::ValueType::ReferenceExpression(expression.view())
+            }
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for reference_expression::ReferenceExpression<'a> {
+    type Owned = ReferenceExpression;
+    fn to_owned_tree(&self) -> ReferenceExpression {
+        ReferenceExpression {
+            name: self.name.to_owned_tree(),
+            index: self
+                .index
+                .as_ref()
+                .map(|(is_open, index)| (*is_open, index.to_owned_tree())),
+            safe: self.safe,
+            next: self.next.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for ReferenceExpression {
+    type Borrowed = reference_expression::ReferenceExpression<'a>;
+    fn view(&'a self) -> reference_expression::ReferenceExpression<'a> {
+        reference_expression::ReferenceExpression {
+            name: self.name.view(),
+            index: self
+                .index
+                .as_ref()
+                .map(|(is_open, index)| (*is_open, index.view())),
+            safe: self.safe,
+            next: self.next.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for annotation_expression::AnnotationExpression<'a> {
+    type Owned = AnnotationExpression;
+    fn to_owned_tree(&self) -> AnnotationExpression {
+        AnnotationExpression {
+            name: self.name.to_owned_tree(),
+            next: self.next.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for AnnotationExpression {
+    type Borrowed = annotation_expression::AnnotationExpression<'a>;
+    fn view(&'a self) -> annotation_expression::AnnotationExpression<'a> {
+        annotation_expression::AnnotationExpression {
+            name: self.name.view(),
+            next: self.next.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for content::Content<'a> {
+    type Owned = Content;
+    fn to_owned_tree(&self) -> Content {
+        Content {
+            member: self.member.to_owned_tree(),
+            next: self.next.to_owned_tree(),
+        }
+    }
+}
+
+impl<'a> View<'a> for Content {
+    type Borrowed = content::Content<'a>;
+    fn view(&'a self) -> content::Content<'a> {
+        content::Content {
+            member: self.member.view(),
+            next: self.next.view(),
+        }
+    }
+}
+
+impl<'a> ToOwnedTree for content::ContentBlockMember<'a> {
+    type Owned = ContentBlockMember;
+    fn to_owned_tree(&self) -> ContentBlockMember {
+        match self {
+            content::ContentBlockMember::Body(body) => ContentBlockMember::Body(body.clone()),
+            content::ContentBlockMember::Template(reference) => {
+                ContentBlockMember::Template(reference.to_owned_tree())
+            }
+        }
+    }
+}
+
+impl<'a> View<'a> for ContentBlockMember {
+    type Borrowed = content::ContentBlockMember<'a>;
+    fn view(&'a self) -> content::ContentBlockMember<'a> {
+        match self {
+            ContentBlockMember::Body(body) => content::ContentBlockMember::Body(body.clone()),
+            ContentBlockMember::Template(reference) => {
+                content::ContentBlockMember::Template(reference.view())
+            }
+        }
+    }
+}
@@ -1,17 +1,33 @@
 use ra_lexer::token::{Token, TokenKind};
-use ra_lexer::tokenize;
+use ra_lexer::try_tokenize;
+use ra_lexer::errors::LexerError;
+use ra_lexer::span::Span;
 
 use super::block::{Block, BlockKind};
 use super::cursor::Cursor;
-use super::errors::ParserError;
+use super::errors::{expected_one_of, ParserError};
 use super::expressions::traits::{Leveled, Expandable, Positioned};
 use failure::Backtrace;
+use std::cell::RefCell;
 
-pub fn parse<'a>(input: &'a str) -> Result<Block<'a>, (Vec<ParserError>, Block<'a>)> {
-    let stream = tokenize(input).filter(|tok| tok.kind.unwrap() != TokenKind::Comment);
+/// Program, or errors together with the blocks which could be parsed
+pub type ParseResult<'a> = Result<Block<'a>, (Vec<ParserError>, Block<'a>)>;
+
+pub fn parse<'a>(input: &'a str) -> ParseResult<'a> {
+    let lexer_error: RefCell<Option<LexerError>> = RefCell::new(None);
+    let stream = try_tokenize(input)
+        .map_while(|result| match result {
+            Ok(tok) => Some(tok),
+            Err(e) => {
+                lexer_error.replace(Some(e));
+                None
+            }
+        })
+        .filter(|tok| tok.kind.unwrap() != TokenKind::Comment);
     let mut errors: Vec<ParserError> = Vec::new();
     let program = {
         let mut block = Block::new(Token::default()).unwrap();
+        block.span = Span::new(0, input.len());
         let mut tokens_cursor = Cursor::new(stream);
         loop {
             if !tokens_cursor.is_eof() {
@@ -27,6 +43,10 @@ pub fn parse<'a>(input: &'a str) -> Result<Block<'a>, (Vec<ParserError>, Block<'
         block
     };
 
+    if let Some(e) = lexer_error.into_inner() {
+        errors.push(ParserError::ContentParsingError(e, Backtrace::new()));
+    }
+
     if errors.len() > 0 {
         Err((errors, program))
     } else {
@@ -38,7 +58,7 @@ impl<'token, I> Cursor<'token, I>
 where
     I: Iterator<Item = Token<'token>>,
 {
-    fn advance_block<'a>(&'a mut self) -> Result<Block<'token>, Vec<ParserError>> {
+    pub(crate) fn advance_block<'a>(&'a mut self) -> Result<Block<'token>, Vec<ParserError>> {
         let first_token = self.bump();
         let mut errors: Vec<ParserError> = Vec::new();
         match first_token {
@@ -81,10 +101,15 @@ where
     ) -> (Option<Block<'token>>, &'errors mut Vec<ParserError>) {
         let lvl = block.get_level();
         let line_number = (block.get_position().0).0;
+        let mut last_token = block.first_token;
+        let errors_before = errors.len();
         while self.first_ahead().is_some()
             && self.first_ahead().unwrap().level == lvl
             && (self.first_ahead().unwrap().position.0).0 == line_number {
-                match block.clone().append_item(self.bump().unwrap()) {
+                let token = self.bump().unwrap();
+                last_token = token;
+                block.span = block.span.to(token.span);
+                match block.clone().append_item(token) {
                     Ok(blk) => {
                         block = blk;
                     },
@@ -94,6 +119,30 @@ where
                 }
         }
 
+        // expression ending with an operator, a `.` or open parentheses
+        if let BlockKind::Output(expression) = &block.kind {
+            if !expression.is_complete() && errors.len() == errors_before {
+                errors.push(ParserError::UnexpectedToken(
+                    format!("{}", last_token),
+                    last_token.position,
+                    Backtrace::new(),
+                ));
+            }
+        }
+        // argument value with parentheses, a list or a map left open
+        if let BlockKind::Input(_, Some(expression)) | BlockKind::Invocation(_, Some(expression)) = &block.kind {
+            if let Some(bracket) = expression.closing_bracket() {
+                if errors.len() == errors_before {
+                    errors.push(ParserError::ExpectedAGotB(
+                        "end of line".to_owned(),
+                        expected_one_of(&[bracket]),
+                        last_token.position,
+                        Backtrace::new(),
+                    ));
+                }
+            }
+        }
+
         (Some(block), errors)
     }
 
@@ -106,6 +155,7 @@ where
         while !self.is_eof() && self.first_ahead().unwrap().level == block.get_level() + 1 {
             match self.advance_block() {
                 Ok(child) => {
+                    block.span = block.span.to(child.span);
                     block.children.push(child);
                 }
                 Err(errs) => {
@@ -137,6 +187,7 @@ where
             match self.advance_block() {
                 Ok(blk) => {
                     union_size += 1;
+                    union_block.span = union_block.span.to(blk.span);
                     union_block.children.push(blk);
                 }
                 Err(errs) => {
@@ -0,0 +1,330 @@
+use super::block::{Block, BlockKind};
+use super::errors::ParserError;
+use super::parser::parse;
+use ra_lexer::cursor::{is_end_of_line, is_whitespace, Position};
+use ra_lexer::span::Span;
+use ra_lexer::token::{Token, TokenKind};
+use ra_lexer::try_tokenize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TriviaKind {
+    Whitespace,
+    LineBreak,
+    Comment,
+    /// Source lexer couldn't tokenize
+    Skipped,
+}
+
+/// Part of the source which doesn't affect the program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trivia<'a> {
+    pub kind: TriviaKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// Token with trivia around it.
+///
+/// Trailing trivia is everything on the same line after the token,
+/// up to and including the line break. Leading trivia is the rest,
+/// e.g. comments on the lines above.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxToken<'a> {
+    pub token: Token<'a>,
+    pub text: &'a str,
+    pub leading: Vec<Trivia<'a>>,
+    pub trailing: Vec<Trivia<'a>>,
+}
+
+impl<'a> SyntaxToken<'a> {
+    /// End of input, carries trivia after the last token
+    pub fn is_eof(&self) -> bool {
+        self.token.kind.is_none()
+    }
+
+    pub fn push_text(&self, buffer: &mut String) {
+        for trivia in self.leading.iter() {
+            buffer.push_str(trivia.text);
+        }
+        buffer.push_str(self.text);
+        for trivia in self.trailing.iter() {
+            buffer.push_str(trivia.text);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SyntaxKind {
+    Program,
+    Output,
+    Input,
+    Declaration,
+    Invocation,
+    Reference,
+    ContextModification,
+    Content,
+    Annotation,
+    Union,
+}
+
+impl<'a> From<&BlockKind<'a>> for SyntaxKind {
+    fn from(kind: &BlockKind<'a>) -> Self {
+        match kind {
+            BlockKind::Program => SyntaxKind::Program,
+            BlockKind::Output(_) => SyntaxKind::Output,
+            BlockKind::Input(..) => SyntaxKind::Input,
+            BlockKind::Declaration(_) => SyntaxKind::Declaration,
+            BlockKind::Invocation(..) => SyntaxKind::Invocation,
+            BlockKind::Reference(_) => SyntaxKind::Reference,
+            BlockKind::ContextModification(_) => SyntaxKind::ContextModification,
+            BlockKind::Content(_) => SyntaxKind::Content,
+            BlockKind::Annotation(_) => SyntaxKind::Annotation,
+            BlockKind::Union(_) => SyntaxKind::Union,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+/// Node of a lossless syntax tree, mirrors a `Block`.
+///
+/// Tokens belong to the innermost block spanning them,
+/// together with their trivia they add up to the original source.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyntaxNode<'a> {
+    pub kind: SyntaxKind,
+    pub span: Span,
+    pub children: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> SyntaxNode<'a> {
+    /// Source text of the node including trivia
+    pub fn text(&self) -> String {
+        let mut buffer = String::new();
+        self.push_text(&mut buffer);
+        buffer
+    }
+
+    pub fn push_text(&self, buffer: &mut String) {
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => node.push_text(buffer),
+                SyntaxElement::Token(token) => token.push_text(buffer),
+            }
+        }
+    }
+
+    /// Child nodes
+    pub fn nodes(&self) -> impl Iterator<Item = &SyntaxNode<'a>> {
+        self.children.iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            _ => None,
+        })
+    }
+
+    /// All tokens of the node and its descendants in source order
+    pub fn tokens(&self) -> Vec<&SyntaxToken<'a>> {
+        let mut tokens = Vec::new();
+        for child in self.children.iter() {
+            match child {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    pub fn first_token(&self) -> Option<&SyntaxToken<'a>> {
+        self.children.iter().find_map(|child| match child {
+            SyntaxElement::Node(node) => node.first_token(),
+            SyntaxElement::Token(token) => Some(token),
+        })
+    }
+
+    /// Comments on the lines right above the node, e.g. documentation
+    pub fn leading_comments(&self) -> Vec<&'a str> {
+        self.first_token()
+            .map(|token| comments(&token.leading))
+            .unwrap_or_default()
+    }
+
+    /// Comments on the first line of the node
+    pub fn trailing_comments(&self) -> Vec<&'a str> {
+        let first_line = match self.first_token() {
+            Some(token) => (token.token.position.0).0,
+            None => return Vec::new(),
+        };
+        self.tokens()
+            .into_iter()
+            .filter(|token| (token.token.position.1).0 == first_line)
+            .flat_map(|token| comments(&token.trailing))
+            .collect()
+    }
+}
+
+fn comments<'a>(trivia: &[Trivia<'a>]) -> Vec<&'a str> {
+    trivia
+        .iter()
+        .filter(|trivia| trivia.kind == TriviaKind::Comment)
+        .map(|trivia| trivia.text)
+        .collect()
+}
+
+/// Parses `input` into both the `Block` tree and a lossless syntax tree
+pub fn parse_lossless<'a>(
+    input: &'a str,
+) -> (
+    SyntaxNode<'a>,
+    Result<Block<'a>, (Vec<ParserError>, Block<'a>)>,
+) {
+    let parsed = parse(input);
+    let tree = match &parsed {
+        Ok(program) => syntax_tree(input, program),
+        Err((_, program)) => syntax_tree(input, program),
+    };
+    (tree, parsed)
+}
+
+/// Builds a lossless syntax tree for an already parsed `program`
+pub fn syntax_tree<'a>(input: &'a str, program: &Block<'a>) -> SyntaxNode<'a> {
+    let tokens = syntax_tokens(input);
+    let mut tokens = tokens.into_iter().peekable();
+    let mut root = build_node(program, &mut tokens);
+    root.children.extend(tokens.map(SyntaxElement::Token));
+    root.span = Span::new(0, input.len());
+    root
+}
+
+fn build_node<'a, I>(block: &Block<'a>, tokens: &mut std::iter::Peekable<I>) -> SyntaxNode<'a>
+where
+    I: Iterator<Item = SyntaxToken<'a>>,
+{
+    let mut children = Vec::new();
+    let mut block_children: Vec<&Block<'a>> = block.children.iter().collect();
+    block_children.sort_by_key(|child| child.span.start);
+
+    for child in block_children {
+        while let Some(token) =
+            tokens.next_if(|token| !token.is_eof() && token.token.span.start < child.span.start)
+        {
+            children.push(SyntaxElement::Token(token));
+        }
+        children.push(SyntaxElement::Node(build_node(child, tokens)));
+    }
+
+    while let Some(token) =
+        tokens.next_if(|token| !token.is_eof() && token.token.span.end <= block.span.end)
+    {
+        children.push(SyntaxElement::Token(token));
+    }
+
+    SyntaxNode {
+        kind: SyntaxKind::from(&block.kind),
+        span: block.span,
+        children,
+    }
+}
+
+/// Significant tokens with trivia attached, the last one is end of input
+fn syntax_tokens(input: &str) -> Vec<SyntaxToken<'_>> {
+    let mut tokens: Vec<SyntaxToken> = Vec::new();
+    let mut pending: Vec<Trivia> = Vec::new();
+    let mut offset = 0;
+    let mut end_position = Position(1, 0);
+
+    for result in try_tokenize(input) {
+        let token = match result {
+            Ok(token) => token,
+            Err(_) => break,
+        };
+        pending.extend(gap_trivia(input, offset, token.span.start));
+        offset = token.span.end;
+        end_position = token.position.1;
+
+        if token.kind == Some(TokenKind::Comment) {
+            pending.push(Trivia {
+                kind: TriviaKind::Comment,
+                text: &input[token.span.start..token.span.end],
+                span: token.span,
+            });
+            continue;
+        }
+
+        attach(&mut tokens, &mut pending);
+        tokens.push(SyntaxToken {
+            token,
+            text: &input[token.span.start..token.span.end],
+            leading: Vec::new(),
+            trailing: Vec::new(),
+        });
+        tokens.last_mut().unwrap().leading = std::mem::take(&mut pending);
+    }
+
+    let skipped =
+        input[offset..].trim_start_matches(|ch: char| is_whitespace(&ch) || is_end_of_line(&ch));
+    let skipped_start = input.len() - skipped.len();
+    pending.extend(gap_trivia(input, offset, skipped_start));
+    if !skipped.is_empty() {
+        pending.push(Trivia {
+            kind: TriviaKind::Skipped,
+            text: skipped,
+            span: Span::new(skipped_start, input.len()),
+        });
+    }
+
+    attach(&mut tokens, &mut pending);
+    tokens.push(SyntaxToken {
+        token: Token {
+            position: (end_position, end_position),
+            span: Span::new(input.len(), input.len()),
+            ..Token::default()
+        },
+        text: "",
+        leading: pending,
+        trailing: Vec::new(),
+    });
+    tokens
+}
+
+/// Moves trivia on the line of the previous token into its trailing trivia
+fn attach<'a>(tokens: &mut Vec<SyntaxToken<'a>>, pending: &mut Vec<Trivia<'a>>) {
+    if let Some(previous) = tokens.last_mut() {
+        let same_line = pending
+            .iter()
+            .position(|trivia| trivia.kind == TriviaKind::LineBreak)
+            .map_or(pending.len(), |line_break| line_break + 1);
+        previous.trailing.extend(pending.drain(..same_line));
+    }
+}
+
+/// Splits whitespace between tokens into line breaks and the rest
+fn gap_trivia(input: &str, start: usize, end: usize) -> Vec<Trivia<'_>> {
+    let mut trivia = Vec::new();
+    let mut chars = input[start..end].char_indices().peekable();
+
+    while let Some((offset, ch)) = chars.next() {
+        let piece_start = start + offset;
+        let kind = if is_end_of_line(&ch) {
+            if ch == '\r' {
+                chars.next_if(|(_, next)| *next == '\n');
+            }
+            TriviaKind::LineBreak
+        } else {
+            while chars.next_if(|(_, next)| !is_end_of_line(next)).is_some() {}
+            TriviaKind::Whitespace
+        };
+        let piece_end = chars
+            .peek()
+            .map_or(end, |(next_offset, _)| start + next_offset);
+        trivia.push(Trivia {
+            kind,
+            text: &input[piece_start..piece_end],
+            span: Span::new(piece_start, piece_end),
+        });
+    }
+    trivia
+}
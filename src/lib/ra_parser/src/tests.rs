@@ -1,5 +1,6 @@
 mod lib {
     use ra_lexer::cursor::Position;
+    use ra_lexer::span::Span;
     use ra_lexer::token::{Token, TokenKind};
 
     use crate::block::BlockKind;
@@ -7,6 +8,7 @@ mod lib {
     use crate::expressions::output_expression::{MathOperation, OperationKind, OutputExpression};
     use crate::expressions::reference_expression::ReferenceExpression;
     use crate::parser::parse;
+    use crate::diagnostics::{Renderer, ToDiagnostic};
 
     #[test]
     fn it_should_return_program_block() {
@@ -19,23 +21,32 @@ mod lib {
         let parsed = program.children.iter().nth(0).unwrap();
         assert_eq!(
             parsed.kind,
-            BlockKind::Output(OutputExpression(
-                Box::new(ExpressionMember::ReferenceExpression(ReferenceExpression(Token {
-                    kind: Some(TokenKind::Identifier("abc")),
-                    position: (Position(1, 0), Position(1, 3)),
-                    len: 3,
-                    content: "abc",
-                    level: 0,
-                }, None))),
-                Some(OperationKind::MathOperation(MathOperation::Sum)),
-                Some(Box::new(ExpressionMember::Literal(Token {
+            BlockKind::Output(OutputExpression {
+                left: Box::new(ExpressionMember::ReferenceExpression(ReferenceExpression {
+                    name: Token {
+                        kind: Some(TokenKind::Identifier("abc")),
+                        position: (Position(1, 0), Position(1, 3)),
+                        span: Span::new(0, 3),
+                        len: 3,
+                        content: "abc",
+                        level: 0,
+                        raw: false,
+                    },
+                    index: None,
+                    safe: false,
+                    next: None,
+                })),
+                operation: Some(OperationKind::MathOperation(MathOperation::Sum)),
+                right: Some(Box::new(ExpressionMember::Literal(Token {
                     kind: Some(TokenKind::Int(2)),
                     position: (Position(1, 6), Position(1, 7)),
+                    span: Span::new(6, 7),
                     level: 0,
                     content: "2",
-                    len: 1
+                    len: 1,
+                    raw: false,
                 })))
-            ))
+            })
         )
         // assert_eq!(parsed.expression.1, Some(OperationKind::MathOperation(MathOperation::Sum)));
 
@@ -62,6 +73,66 @@ mod lib {
         // );
     }
 
+    #[test]
+    fn it_should_report_readable_errors() {
+        let (errors, _) = parse("abc.").expect_err("should fail");
+        assert_eq!(format!("{}", errors[0]), "error[P0002]: unexpected `.` at [1:3]");
+
+        let rendered = Renderer::new(false).render("abc.", &errors[0].to_diagnostic());
+        assert_eq!(
+            rendered,
+            "error[P0002]: unexpected `.`\n \
+             --> 1:4\n  \
+              |\n\
+             1 | abc.\n  \
+              |    ^ unexpected token\n"
+        );
+    }
+
+    #[test]
+    fn it_should_span_blocks_with_their_children() {
+        let input = "a\n  b + 1\nc";
+        let program = parse(input).expect("can't parse");
+        assert_eq!(program.span, Span::new(0, input.len()));
+        assert_eq!(program.children[0].span, Span::new(0, 9));
+        assert_eq!(program.children[0].children[0].span, Span::new(4, 9));
+        assert_eq!(program.children[1].span, Span::new(10, 11));
+    }
+
+    #[test]
+    fn it_should_parse_assignments_to_parent_item() {
+        let program = parse("votes\n    += 1\n    -= 2\n    = 0\n").expect("can't parse");
+        let operations: Vec<Option<OperationKind>> = program.children[0]
+            .children
+            .iter()
+            .map(|child| match &child.kind {
+                BlockKind::Output(OutputExpression {
+                    left,
+                    operation,
+                    right: Some(_),
+                }) => {
+                    assert_eq!(**left, ExpressionMember::Nil);
+                    *operation
+                }
+                kind => panic!("not an assignment {:?}", kind),
+            })
+            .collect();
+        assert_eq!(
+            operations,
+            vec![
+                Some(OperationKind::MathOperation(MathOperation::AddAssign)),
+                Some(OperationKind::MathOperation(MathOperation::SubtractAssign)),
+                Some(OperationKind::Assign),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_reject_operations_without_a_target() {
+        let (errors, _) = parse("y\n    == 1\n").expect_err("should fail");
+        assert!(format!("{}", errors[0]).contains("unexpected `=`"));
+    }
+
     use ra_dev_tools::insta::{assert_json_snapshot, assert_snapshot};
     
     use std::fs::{File, DirEntry};
@@ -124,3 +195,813 @@ mod lib {
             assert_json_snapshot!(example.path().to_str().unwrap(), block_tree)
     }
 }
+
+mod syntax {
+    use ra_dev_tools::make_example_tests;
+
+    use crate::syntax::{parse_lossless, SyntaxElement, SyntaxKind, TriviaKind};
+
+    #[test]
+    fn it_should_reconstruct_source_with_trivia() {
+        let input = "// header\nabc + 2 // sum\n\n    \nxyz\n";
+        let (tree, _) = parse_lossless(input);
+        assert_eq!(tree.text(), input);
+    }
+
+    #[test]
+    fn it_should_mirror_blocks() {
+        let (tree, parsed) = parse_lossless("abc + 2\nxyz");
+        let program = parsed.expect("can't parse");
+        assert_eq!(tree.kind, SyntaxKind::Program);
+        let kinds: Vec<SyntaxKind> = tree.nodes().map(|node| node.kind).collect();
+        let block_kinds: Vec<SyntaxKind> = program.children.iter().map(|block| SyntaxKind::from(&block.kind)).collect();
+        assert_eq!(kinds, block_kinds);
+        assert_eq!(tree.nodes().next().unwrap().text(), "abc + 2\n");
+    }
+
+    #[test]
+    fn it_should_attach_comments() {
+        let (tree, _) = parse_lossless("a // c1\n// c2\nb");
+        let nodes: Vec<_> = tree.nodes().collect();
+        assert_eq!(nodes[0].trailing_comments(), vec!["// c1"]);
+        assert_eq!(nodes[1].leading_comments(), vec!["// c2"]);
+    }
+
+    #[test]
+    fn it_should_keep_input_lexer_couldnt_tokenize() {
+        let input = "abc\n  def\n   ghi";
+        let (tree, _) = parse_lossless(input);
+        assert_eq!(tree.text(), input);
+        match tree.children.last() {
+            Some(SyntaxElement::Token(eof)) => {
+                assert!(eof.is_eof());
+                assert_eq!(eof.leading.last().unwrap().kind, TriviaKind::Skipped);
+            }
+            _ => panic!("no end of input token"),
+        }
+    }
+
+    #[make_example_tests]
+    #[test]
+    fn it_should_be_lossless(contents: String, file_name: String) {
+        let (tree, _) = parse_lossless(&contents);
+        assert_eq!(tree.text(), contents, "{}", file_name);
+    }
+}
+
+mod incremental {
+    use proptest::prelude::*;
+    use proptest::test_runner::{Config, TestRunner};
+    use ra_dev_tools::make_example_tests;
+    use ra_lexer::span::Span;
+
+    use crate::incremental::{affected_blocks, reparse, TextEdit};
+    use crate::parser::{parse, ParseResult};
+
+    /// Same blocks and same errors
+    fn same(a: &ParseResult, b: &ParseResult) -> bool {
+        match (a, b) {
+            (Ok(a), Ok(b)) => a == b,
+            (Err((a_errors, a)), Err((b_errors, b))) => {
+                a == b
+                    && a_errors.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+                        == b_errors.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+            }
+            _ => false,
+        }
+    }
+
+    fn edit(source: &str, start: usize, end: usize, text: &str) -> (TextEdit, String) {
+        let edit = TextEdit::new(Span::new(start, end), text);
+        let edited = edit.apply(source);
+        (edit, edited)
+    }
+
+    #[test]
+    fn it_should_apply_edits() {
+        let (_, edited) = edit("abc\nxyz", 1, 2, "BB");
+        assert_eq!(edited, "aBBc\nxyz");
+        let (_, edited) = edit("abc", 3, 3, "\ndef");
+        assert_eq!(edited, "abc\ndef");
+    }
+
+    #[test]
+    fn it_should_find_affected_blocks() {
+        let source = "a\n    b\nc\nd\n";
+        let program = parse(source).unwrap();
+        assert_eq!(affected_blocks(&program, &TextEdit::new(Span::new(6, 7), "x")), 0..1);
+        assert_eq!(affected_blocks(&program, &TextEdit::new(Span::new(8, 8), "x")), 1..2);
+        assert_eq!(affected_blocks(&program, &TextEdit::new(Span::new(9, 11), "")), 1..3);
+        assert_eq!(affected_blocks(&program, &TextEdit::new(Span::new(12, 12), "e")), 3..3);
+    }
+
+    #[test]
+    fn it_should_reparse_edited_block() {
+        let source = "a\n    b\nc + 1\n    d\ne\n    `f {{d}}`\n";
+        let previous = parse(source);
+        let (edit, edited) = edit(source, 8, 9, "counter");
+        let reparsed = reparse(&previous, source, &edit, &edited);
+        assert!(reparsed.is_ok());
+        assert!(same(&reparsed, &parse(&edited)));
+    }
+
+    #[test]
+    fn it_should_reparse_lines_joining_blocks() {
+        let source = "a\nb\n    c\nd\n";
+        let previous = parse(source);
+        for (start, end, text) in [(2, 2, "    "), (4, 4, "\n"), (11, 11, "    "), (0, 4, "")].iter() {
+            let (edit, edited) = edit(source, *start, *end, text);
+            assert!(same(&reparse(&previous, source, &edit, &edited), &parse(&edited)), "{:?}", edited);
+        }
+    }
+
+    #[test]
+    fn it_should_reparse_when_indent_width_changes() {
+        let source = "a\n  b\nc\n  d\n";
+        let previous = parse(source);
+        let (edit, edited) = edit(source, 2, 4, "    ");
+        assert!(same(&reparse(&previous, source, &edit, &edited), &parse(&edited)));
+    }
+
+    #[test]
+    fn it_should_reparse_blocks_after_arguments() {
+        let source = "a\nb\n    > :default=42\n        !number:min=0\nc\n";
+        let previous = parse(source);
+        assert!(previous.is_ok());
+        let (edit, edited) = edit(source, 44, 44, "d\n");
+        let reparsed = reparse(&previous, source, &edit, &edited);
+        assert!(reparsed.is_ok());
+        assert!(same(&reparsed, &parse(&edited)));
+    }
+
+    #[test]
+    fn it_should_reparse_after_errors() {
+        let source = "a\n    b\n   c\nd\n";
+        let previous = parse(source);
+        assert!(previous.is_err());
+        let (edit, edited) = edit(source, 8, 8, " ");
+        let reparsed = reparse(&previous, source, &edit, &edited);
+        assert!(reparsed.is_ok());
+        assert!(same(&reparsed, &parse(&edited)));
+    }
+
+    const TEXTS: &[&str] = &[
+        "", "a", "x1", " ", "    ", "\n", "\n    ", "\n\n", ":", "!", "!if:", ">", "+", "=", ",",
+        "@", ".", "'", "`", "{{a}}", "// c\n", "/*", "*/", "\nb\n", "\n    b\n",
+    ];
+
+    /// Top-level blocks which parse on their own,
+    /// `reparse` only reuses results without errors
+    fn parsing_blocks(contents: &str) -> String {
+        let mut blocks: Vec<String> = Vec::new();
+        for line in contents.split_inclusive('\n') {
+            match blocks.last_mut() {
+                Some(block) if line.starts_with(char::is_whitespace) => block.push_str(line),
+                _ => blocks.push(line.to_owned()),
+            }
+        }
+        blocks.retain(|block| parse(block).is_ok());
+        blocks.concat()
+    }
+
+    #[make_example_tests]
+    #[test]
+    fn it_should_reparse_as_parse(contents: String, file_name: String) {
+        let contents = parsing_blocks(&contents);
+        let previous = parse(&contents);
+        assert!(previous.is_ok(), "{}", file_name);
+        let boundary = |offset: usize| {
+            (0..=offset.min(contents.len())).rev().find(|&i| contents.is_char_boundary(i)).unwrap()
+        };
+        let mut runner = TestRunner::new(Config {
+            cases: 256,
+            failure_persistence: None,
+            ..Config::default()
+        });
+        let edits = (0..=contents.len(), 0..12usize, proptest::sample::select(TEXTS));
+        runner
+            .run(&edits, |(start, removed, text)| {
+                let (edit, edited) = edit(&contents, boundary(start), boundary(start + removed), text);
+                let parsed = parse(&edited);
+                let reparsed = reparse(&previous, &contents, &edit, &edited);
+                prop_assert!(same(&reparsed, &parsed), "{}: {:?}", file_name, edit);
+                Ok(())
+            })
+            .unwrap();
+    }
+}
+
+mod owned {
+    use ra_dev_tools::make_example_tests;
+    use std::thread;
+
+    use crate::block::BlockKind;
+    use crate::owned::{self, TokenKind};
+    use crate::parser::parse;
+
+    #[test]
+    fn it_should_outlive_source() {
+        let program: owned::Block = {
+            let source = String::from("name\n    >\n");
+            parse(&source).unwrap().into()
+        };
+        let name = thread::spawn(move || program.children[0].first_token.clone())
+            .join()
+            .unwrap();
+        assert_eq!(name.kind, Some(TokenKind::Identifier));
+        assert_eq!(name.content, "name");
+    }
+
+    #[test]
+    fn it_should_borrow_text_of_owned_tree() {
+        let program: owned::Block = parse("`hello {{name}}`").unwrap().into();
+        let borrowed = program.as_borrowed();
+        assert!(matches!(borrowed.children[0].kind, BlockKind::Content(_)));
+        assert_eq!(borrowed, parse("`hello {{name}}`").unwrap());
+    }
+
+    #[make_example_tests]
+    #[test]
+    fn it_should_round_trip_through_json(contents: String, file_name: String) {
+        let parsed = match parse(&contents) {
+            Ok(program) => program,
+            Err((_, program)) => program,
+        };
+        let json = serde_json::to_string(&owned::Block::from(&parsed)).unwrap();
+        let loaded: owned::Block = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.as_borrowed(), parsed, "{}", file_name);
+    }
+}
+
+mod visit {
+    use ra_lexer::token::{Token, TokenKind};
+
+    use crate::block::{Block, BlockKind};
+    use crate::expressions::input_expression::ArgumentType;
+    use crate::expressions::reference_expression::ReferenceExpression;
+    use crate::parser::parse;
+    use crate::syntax::SyntaxKind;
+    use crate::visit::{walk_block, Visitor, VisitorMut};
+
+    const SOURCE: &str = "item\n    >\n    !my_rule : argument_1 = 10 : argument_2 = `something`\n    @other.a.count\n";
+
+    #[test]
+    fn it_should_iterate_over_reference_segments() {
+        let program = parse(SOURCE).unwrap();
+        let reference = program.children[0]
+            .children_of_kind(SyntaxKind::Reference)
+            .find_map(|block| match &block.kind {
+                BlockKind::Reference(reference) => reference.as_ref(),
+                _ => None,
+            })
+            .unwrap();
+        let segments: Vec<&str> = reference.segments().map(|token| token.content).collect();
+        assert_eq!(segments, vec!["other", "a", "count"]);
+        assert_eq!(reference.path(), "other.a.count");
+    }
+
+    #[test]
+    fn it_should_iterate_over_invocation_arguments() {
+        let program = parse(SOURCE).unwrap();
+        let invocation = program.children[0]
+            .children_of_kind(SyntaxKind::Invocation)
+            .next()
+            .unwrap();
+        let arguments: Vec<&str> = match &invocation.kind {
+            BlockKind::Invocation(Some(rule), Some(expression)) => {
+                assert_eq!(rule.content, "my_rule");
+                expression
+                    .arguments()
+                    .filter_map(|argument| match &argument.argument {
+                        Some(ArgumentType::Named(token, _)) => Some(token.content),
+                        _ => None,
+                    })
+                    .collect()
+            }
+            _ => panic!("not an invocation"),
+        };
+        assert_eq!(arguments, vec!["argument_1", "argument_2"]);
+    }
+
+    #[test]
+    fn it_should_name_blocks() {
+        let program = parse("item\n    >\n:rule\nitem + 2\n").unwrap();
+        let names: Vec<Option<&str>> = program
+            .children
+            .iter()
+            .map(|block| block.name().map(|token| token.content))
+            .collect();
+        assert_eq!(names, vec![Some("item"), Some("rule"), None]);
+    }
+
+    #[test]
+    fn it_should_span_blocks() {
+        let program = parse(SOURCE).unwrap();
+        let item = &program.children[0];
+        assert_eq!(item.span(), item.span);
+        assert_eq!(item.span().start, 0);
+        assert_eq!(&SOURCE[item.span().start..item.first_token.span.end], "item");
+        assert_eq!(item.children_of_kind(SyntaxKind::Input).count(), 1);
+        assert_eq!(item.children_of_kind(SyntaxKind::Content).count(), 0);
+    }
+
+    #[derive(Default)]
+    struct References(Vec<String>);
+
+    impl<'b, 'a: 'b> Visitor<'b, 'a> for References {
+        fn visit_reference_expression(&mut self, expression: &'b ReferenceExpression<'a>) {
+            self.0.push(expression.path());
+        }
+    }
+
+    #[test]
+    fn it_should_visit_references() {
+        let program = parse("a\n    `{{b}} and {{c.d}}`\n@e.f\n").unwrap();
+        let mut visitor = References::default();
+        visitor.visit_block(&program);
+        assert_eq!(visitor.0, vec!["a", "b", "c.d", "e.f"]);
+    }
+
+    #[derive(Default)]
+    struct Tokens(usize);
+
+    impl<'b, 'a: 'b> Visitor<'b, 'a> for Tokens {
+        fn visit_block(&mut self, block: &'b Block<'a>) {
+            if block.kind != BlockKind::Program {
+                walk_block(self, block);
+            }
+        }
+
+        fn visit_token(&mut self, _token: &'b Token<'a>) {
+            self.0 += 1;
+        }
+    }
+
+    #[test]
+    fn it_should_stop_walking_where_visitor_doesnt_walk() {
+        let program = parse("a + b\n").unwrap();
+        let mut visitor = Tokens::default();
+        visitor.visit_block(&program);
+        assert_eq!(visitor.0, 0);
+        visitor.visit_block(&program.children[0]);
+        assert_eq!(visitor.0, 2);
+    }
+
+    struct Rename;
+
+    impl<'a> VisitorMut<'a> for Rename {
+        fn visit_token_mut(&mut self, token: &mut Token<'a>) {
+            if token.content == "old" {
+                token.content = "new";
+                token.kind = Some(TokenKind::Identifier("new"));
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_change_visited_tokens() {
+        let mut program = parse("old + 1\n@other.old\n").unwrap();
+        Rename.visit_block_mut(&mut program);
+        let mut visitor = References::default();
+        visitor.visit_block(&program);
+        assert_eq!(visitor.0, vec!["new", "other.new"]);
+        assert_eq!(program.children[1].name(), None);
+    }
+
+    #[derive(Default)]
+    struct Contents(Vec<String>);
+
+    impl<'b, 'a: 'b> Visitor<'b, 'a> for Contents {
+        fn visit_token(&mut self, token: &'b Token<'a>) {
+            self.0.push(token.content.to_owned());
+        }
+    }
+
+    impl<'a> VisitorMut<'a> for Contents {
+        fn visit_token_mut(&mut self, token: &mut Token<'a>) {
+            self.0.push(token.content.to_owned());
+        }
+    }
+
+    #[test]
+    fn it_should_walk_references_in_the_same_order() {
+        let mut program = parse("x = a.b[i + 1].0[c]?.d\n").unwrap();
+        let mut visited = Contents::default();
+        Visitor::visit_block(&mut visited, &program);
+        let mut visited_mut = Contents::default();
+        visited_mut.visit_block_mut(&mut program);
+        assert_eq!(visited.0, visited_mut.0);
+        assert_eq!(visited.0, vec!["x", "a", "b", "i", "1", "0", "c", "d"]);
+    }
+}
+
+mod expressions {
+    use ra_lexer::token::TokenKind;
+    use ra_lexer::tokenize;
+
+    use crate::block::BlockKind;
+    use crate::expressions::input_expression::ValueType;
+    use crate::expressions::content::ContentBlockMember;
+    use crate::expressions::output_expression::{
+        ExpressionMember, OperationKind, OutputExpression, UnaryOperation,
+    };
+    use crate::expressions::reference_expression::ReferenceExpression;
+    use crate::expressions::traits::{Expandable, Leveled};
+    use crate::parser::parse;
+
+    /// Expression on the first line, operations in parentheses
+    fn grouped(source: &str) -> String {
+        let program = parse(source).expect("can't parse");
+        match &program.children[0].kind {
+            BlockKind::Output(expression) => expression_text(expression),
+            kind => panic!("not an output {:?}", kind),
+        }
+    }
+
+    fn expression_text(expression: &OutputExpression) -> String {
+        match (&expression.operation, &expression.right) {
+            (Some(operation), Some(right)) => format!(
+                "({} {} {})",
+                member_text(&expression.left),
+                operation_text(operation),
+                member_text(right)
+            ),
+            _ => member_text(&expression.left),
+        }
+    }
+
+    fn member_text(member: &ExpressionMember) -> String {
+        match member {
+            ExpressionMember::Literal(token) => token.content.to_string(),
+            ExpressionMember::ReferenceExpression(reference) => reference_text(reference),
+            ExpressionMember::OutputExpression(_, Some(expression))
+            | ExpressionMember::Operation(expression) => expression_text(expression),
+            ExpressionMember::Unary(UnaryOperation::Negate, operand) => {
+                format!("-{}", member_text(operand))
+            }
+            ExpressionMember::Unary(UnaryOperation::Not, operand) => {
+                format!("!{}", member_text(operand))
+            }
+            ExpressionMember::List(_, items) => {
+                let items: Vec<String> = items.iter().map(expression_text).collect();
+                format!("[{}]", items.join(", "))
+            }
+            ExpressionMember::Map(_, entries) => {
+                let entries: Vec<String> = entries
+                    .iter()
+                    .map(|entry| {
+                        let key = entry.key.map_or("?", |key| key.content);
+                        let value = entry.value.as_ref().map_or("?".into(), expression_text);
+                        format!("{}: {}", key, value)
+                    })
+                    .collect();
+                format!("{{{}}}", entries.join(", "))
+            }
+            ExpressionMember::OutputExpression(_, None) | ExpressionMember::Nil => "?".into(),
+        }
+    }
+
+    fn reference_text(reference: &ReferenceExpression) -> String {
+        let mut text = String::new();
+        for part in reference.parts() {
+            match &part.index {
+                Some((_, index)) => text.push_str(&format!("[{}]", expression_text(index))),
+                None if text.is_empty() => text.push_str(part.name.content),
+                None => text.push_str(&format!(".{}", part.name.content)),
+            }
+            if part.safe {
+                text.push('?');
+            }
+        }
+        text
+    }
+
+    fn operation_text(operation: &OperationKind) -> String {
+        format!("{:?}", operation)
+            .trim_start_matches("MathOperation(")
+            .trim_start_matches("LogicOperation(")
+            .trim_start_matches("ComparisonOperation(")
+            .trim_end_matches(')')
+            .to_string()
+    }
+
+    #[test]
+    fn it_should_bind_tighter_operations_first() {
+        assert_eq!(grouped("a + b * c\n"), "(a Sum (b Multiply c))");
+        assert_eq!(grouped("a * b + c\n"), "((a Multiply b) Sum c)");
+        assert_eq!(grouped("a + b > c * 2\n"), "((a Sum b) GtCompare (c Multiply 2))");
+        assert_eq!(grouped("a > 1 & b < 2\n"), "((a GtCompare 1) AND (b LsCompare 2))");
+        assert_eq!(grouped("a & b | c & d\n"), "((a AND b) OR (c AND d))");
+        assert_eq!(grouped("a = b + 1\n"), "(a Assign (b Sum 1))");
+        assert_eq!(grouped("a * b ^ 2\n"), "(a Multiply (b Power 2))");
+        assert_eq!(grouped("a % b - c / d\n"), "((a Reminder b) Subtract (c Divide d))");
+    }
+
+    #[test]
+    fn it_should_associate_operations() {
+        assert_eq!(grouped("a - b - c\n"), "((a Subtract b) Subtract c)");
+        assert_eq!(grouped("a / b * c\n"), "((a Divide b) Multiply c)");
+        assert_eq!(grouped("a ^ b ^ c\n"), "(a Power (b Power c))");
+        assert_eq!(grouped("a = b = c\n"), "(a Assign (b Assign c))");
+    }
+
+    /// Operations from the loosest to the tightest binding ones,
+    /// `true` for the levels grouping to the right
+    const PRECEDENCE: &[(&[(&str, &str)], bool)] = &[
+        (&[("=", "Assign"), ("+=", "AddAssign"), ("-=", "SubtractAssign")], true),
+        (&[("|", "OR"), ("||", "XOR"), ("!!", "NOR"), ("!|", "XNOR")], false),
+        (&[("&", "AND"), ("!&", "NAND")], false),
+        (
+            &[
+                ("==", "EqCompare"),
+                ("!=", "NEqCompare"),
+                (">", "GtCompare"),
+                ("<", "LsCompare"),
+                (">=", "GtEqCompare"),
+                ("<=", "LsEqCompare"),
+            ],
+            false,
+        ),
+        (&[("+", "Sum"), ("-", "Subtract")], false),
+        (&[("*", "Multiply"), ("/", "Divide"), ("%", "Reminder")], false),
+        (&[("^", "Power")], true),
+    ];
+
+    #[test]
+    fn it_should_bind_each_level_tighter_than_the_looser_ones() {
+        for (index, (looser, _)) in PRECEDENCE.iter().enumerate() {
+            for (tighter, _) in &PRECEDENCE[index + 1..] {
+                for (loose, loose_name) in looser.iter() {
+                    for (tight, tight_name) in tighter.iter() {
+                        assert_eq!(
+                            grouped(&format!("a {} b {} c\n", loose, tight)),
+                            format!("(a {} (b {} c))", loose_name, tight_name)
+                        );
+                        assert_eq!(
+                            grouped(&format!("a {} b {} c\n", tight, loose)),
+                            format!("((a {} b) {} c)", tight_name, loose_name)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_parse_long_expressions_quickly() {
+        let terms: Vec<String> = (0..2_000).map(|index| format!("a{} * 2", index)).collect();
+        let source = format!("x = {}", terms.join(" + "));
+        let started = std::time::Instant::now();
+        let mut tokens = tokenize(&source);
+        let mut expression = OutputExpression::new(tokens.next().unwrap()).unwrap();
+        for token in tokens {
+            expression = expression.append_item(token).unwrap();
+        }
+        assert!(started.elapsed() < std::time::Duration::from_secs(2));
+        assert_eq!(expression.operation, Some(OperationKind::Assign));
+    }
+
+    #[test]
+    fn it_should_associate_operations_of_the_same_level() {
+        for (level, right) in PRECEDENCE.iter() {
+            for (first, first_name) in level.iter() {
+                for (second, second_name) in level.iter() {
+                    let expected = if *right {
+                        format!("(a {} (b {} c))", first_name, second_name)
+                    } else {
+                        format!("((a {} b) {} c)", first_name, second_name)
+                    };
+                    assert_eq!(grouped(&format!("a {} b {} c\n", first, second)), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_parse_unary_operations() {
+        assert_eq!(grouped("x = -a + b\n"), "(x Assign (-a Sum b))");
+        assert_eq!(grouped("x = !a & b\n"), "(x Assign (!a AND b))");
+        assert_eq!(grouped("a * -b\n"), "(a Multiply -b)");
+        assert_eq!(grouped("x = -a ^ 2\n"), "(x Assign -(a Power 2))");
+        assert_eq!(grouped("(!a)\n"), "!a");
+        assert_eq!(grouped("-a ^ 2\n"), "-(a Power 2)");
+        assert_eq!(grouped("-1 + 2\n"), "(-1 Sum 2)");
+        assert_eq!(grouped("- b * 2\n"), "(-b Multiply 2)");
+        assert_eq!(grouped("- 1\n"), "-1");
+    }
+
+    #[test]
+    fn it_should_parse_expressions_starting_with_literals() {
+        assert_eq!(grouped("1 + a\n"), "(1 Sum a)");
+        assert_eq!(grouped("2.5 * a - 1\n"), "((2.5 Multiply a) Subtract 1)");
+        assert_eq!(grouped("x = 'a' + b\n"), "(x Assign (a Sum b))");
+        assert_eq!(grouped("(1 + 2) * 3\n"), "((1 Sum 2) Multiply 3)");
+    }
+
+    #[test]
+    fn it_should_group_in_parentheses() {
+        assert_eq!(grouped("(a + b) * c\n"), "((a Sum b) Multiply c)");
+        assert_eq!(grouped("a * (b - (c + 1))\n"), "(a Multiply (b Subtract (c Sum 1)))");
+    }
+
+    #[test]
+    fn it_should_split_signed_numbers_after_operands() {
+        assert_eq!(grouped("a-1\n"), "(a Subtract 1)");
+        assert_eq!(grouped("a * -1\n"), "(a Multiply -1)");
+    }
+
+    #[test]
+    fn it_should_parse_keyword_literals() {
+        assert_eq!(grouped("a = true\n"), "(a Assign true)");
+        assert_eq!(grouped("a != none & !false\n"), "((a NEqCompare none) AND !false)");
+    }
+
+    #[test]
+    fn it_should_reserve_keyword_literals() {
+        for source in &["none\n    >\n", "true\n    >\n", "a\n    false\n        >\n", "x = a.none\n", "x = a?.true + 1\n"] {
+            let (errors, _) = parse(source).expect_err(source);
+            assert_eq!(errors[0].code(), "P0007", "{}", source);
+            assert!(format!("{}", errors[0]).contains("is a reserved word"), "{}", source);
+        }
+        assert_eq!(grouped("x = a[\"none\"]\n"), "(x Assign a[none])");
+        assert_eq!(grouped("nothing = a.nonempty\n"), "(nothing Assign a.nonempty)");
+    }
+
+    #[test]
+    fn it_should_parse_lists() {
+        assert_eq!(grouped("a = [1, b + 2, \"c\"]\n"), "(a Assign [1, (b Sum 2), c])");
+        assert_eq!(grouped("a = []\n"), "(a Assign [])");
+        assert_eq!(grouped("a = [1, 2,]\n"), "(a Assign [1, 2])");
+        assert_eq!(grouped("a = [[1], [(2 + 3) * 4]]\n"), "(a Assign [[1], [((2 Sum 3) Multiply 4)]])");
+        assert_eq!(grouped("a = [1] + b\n"), "(a Assign ([1] Sum b))");
+    }
+
+    #[test]
+    fn it_should_parse_maps() {
+        assert_eq!(grouped("a = {b: 1, \"c d\": e.f}\n"), "(a Assign {b: 1, c d: e.f})");
+        assert_eq!(grouped("a = {}\n"), "(a Assign {})");
+        assert_eq!(grouped("a = {b: [1, {c: none}],}\n"), "(a Assign {b: [1, {c: none}]})");
+    }
+
+    #[test]
+    fn it_should_reject_malformed_collections() {
+        for source in &["a = [1 2]\n", "a = [1,,]\n", "a = [1\n", "a = {b}\n", "a = {1: 2}\n", "a = {b: }\n"] {
+            let (errors, _) = parse(source).expect_err(source);
+            assert!(!errors.is_empty(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn it_should_take_literals_as_argument_values() {
+        let program = parse("a\n    !format:email=true:options=[1, 2]:labels={yes: \"y\"}\n    !includes:[1, 2]\n").unwrap();
+        let values: Vec<String> = program.children[0]
+            .children
+            .iter()
+            .flat_map(|invocation| match &invocation.kind {
+                BlockKind::Invocation(_, Some(expression)) => expression.arguments().collect(),
+                _ => vec![],
+            })
+            .map(|argument| match &argument.value {
+                Some(ValueType::Literal(token)) => token.content.to_string(),
+                Some(ValueType::OutputExpression(expression)) => expression_text(expression),
+                value => panic!("unexpected value {:?}", value),
+            })
+            .collect();
+        assert_eq!(values, vec!["true", "[1, 2]", "{yes: y}", "[1, 2]"]);
+    }
+
+    #[test]
+    fn it_should_reject_argument_values_left_open() {
+        for (source, expected) in &[
+            ("x\n    > :default=(\n", "`)`"),
+            ("x\n    > :default=(1 + 2\n", "`)`"),
+            ("x\n    > :default=[1,\n", "`]`"),
+            ("x\n    !includes:[(1\n", "`)`"),
+            ("x\n    > :default={a: 1\n", "`}`"),
+            ("x\n    !if:a[1\n", "`]`"),
+        ] {
+            let (errors, _) = parse(source).expect_err(source);
+            let message = format!("{}", errors[0]);
+            assert!(message.contains(&format!("expected {}, found end of line", expected)), "{}: {}", source, message);
+        }
+    }
+
+    #[test]
+    fn it_should_level_empty_parentheses() {
+        assert_eq!(ExpressionMember::OutputExpression(false, None).get_level(), 0);
+        assert_eq!(ExpressionMember::OutputExpression(true, None).get_level(), 0);
+    }
+
+    #[test]
+    fn it_should_parse_indices() {
+        assert_eq!(grouped("x = a.1\n"), "(x Assign a.1)");
+        assert_eq!(grouped("x = a.b.-1\n"), "(x Assign a.b.-1)");
+        assert_eq!(grouped("x = m.0.1\n"), "(x Assign m.0.1)");
+        assert_eq!(grouped("x = a?.2.10.b\n"), "(x Assign a?.2.10.b)");
+        assert_eq!(grouped("x = a[i + 1].b\n"), "(x Assign a[(i Sum 1)].b)");
+        assert_eq!(grouped("x = a[b[0]][c.1] * 2\n"), "(x Assign (a[b[0]][c.1] Multiply 2))");
+        assert_eq!(grouped("x = [a[0], {b: c[1]}]\n"), "(x Assign [a[0], {b: c[1]}])");
+    }
+
+    #[test]
+    fn it_should_parse_safe_access() {
+        assert_eq!(grouped("x = a?.b\n"), "(x Assign a?.b)");
+        assert_eq!(grouped("x = a.b?[0]?.c + 1\n"), "(x Assign (a.b?[0]?.c Sum 1))");
+    }
+
+    #[test]
+    fn it_should_index_in_templates() {
+        let program = parse("a\n    `{{recipe.pictures.1}} {{steps[0].picture}}`\n").unwrap();
+        let references: Vec<String> = match &program.children[0].children[0].kind {
+            BlockKind::Content(content) => content
+                .members()
+                .into_iter()
+                .filter_map(|member| match member {
+                    ContentBlockMember::Template(reference) => Some(reference_text(reference)),
+                    ContentBlockMember::Body(_) => None,
+                })
+                .collect(),
+            kind => panic!("not a content {:?}", kind),
+        };
+        assert_eq!(references, vec!["recipe.pictures.1", "steps[0].picture"]);
+    }
+
+    #[test]
+    fn it_should_split_content_into_bodies_and_templates() {
+        let program = parse("a\n    `b {{c}} d{{e}}{{f}}`\n").unwrap();
+        let members: Vec<String> = match &program.children[0].children[0].kind {
+            BlockKind::Content(content) => content
+                .members()
+                .into_iter()
+                .map(|member| match member {
+                    ContentBlockMember::Body(body) => body.clone(),
+                    ContentBlockMember::Template(reference) => {
+                        format!("{{{{{}}}}}", reference_text(reference))
+                    }
+                })
+                .collect(),
+            kind => panic!("not a content {:?}", kind),
+        };
+        assert_eq!(members, vec!["b ", "{{c}}", " d", "{{e}}", "{{f}}"]);
+    }
+
+    #[test]
+    fn it_should_reject_malformed_templates_after_text() {
+        for source in &["a\n    `b {{c + 1}}`\n", "a\n    `b {{..c}}`\n", "a\n    `b {{c}`\n"] {
+            let (errors, _) = parse(source).expect_err(source);
+            assert!(!errors.is_empty(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn it_should_unescape_backticks_in_content() {
+        let program = parse("a\n    `run \\`ra\\` {{b}}`\n").unwrap();
+        let bodies: Vec<String> = match &program.children[0].children[0].kind {
+            BlockKind::Content(content) => content
+                .members()
+                .into_iter()
+                .filter_map(|member| match member {
+                    ContentBlockMember::Body(body) => Some(body.clone()),
+                    ContentBlockMember::Template(_) => None,
+                })
+                .collect(),
+            kind => panic!("not a content {:?}", kind),
+        };
+        assert_eq!(bodies, vec!["run `ra` "]);
+    }
+
+    #[test]
+    fn it_should_unescape_backslashes_in_content() {
+        for (source, body) in &[("x\n    `a\\\\`\n", "a\\"), ("x\n    `a\\\\\\` b`\n", "a\\` b")] {
+            let program = parse(source).unwrap();
+            match &program.children[0].children[0].kind {
+                BlockKind::Content(content) => {
+                    assert_eq!(content.member, ContentBlockMember::Body(body.to_string()))
+                }
+                kind => panic!("not a content {:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_reject_malformed_indices() {
+        for source in &["x = a[\n", "x = a[]\n", "x = a[1\n", "x = a?\n", "x = a??.b\n", "x = a.[0]\n", "a\n    `{{b[}}`\n"] {
+            let (errors, _) = parse(source).expect_err(source);
+            assert!(!errors.is_empty(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn it_should_lex_asterisk() {
+        let tokens = ra_lexer::tokenize("a * b").collect::<Vec<_>>();
+        assert!(tokens.iter().any(|token| token.kind == Some(TokenKind::Asterisk)));
+    }
+
+    #[test]
+    fn it_should_reject_incomplete_expressions() {
+        for source in &["a +\n", "(a + b\n", "a.\n"] {
+            let (errors, _) = parse(source).expect_err(source);
+            assert!(!errors.is_empty(), "{}", source);
+        }
+    }
+}
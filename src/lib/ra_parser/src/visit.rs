@@ -0,0 +1,376 @@
+//! Walking the syntax tree.
+//!
+//! Visitors override the methods for the nodes they're interested in,
+//! every method walks into the node by default. An overridden method
+//! calls the matching `walk_*` function to keep walking into the node.
+
+use ra_lexer::token::Token;
+
+use super::block::{Block, BlockKind};
+use super::expressions::annotation_expression::AnnotationExpression;
+use super::expressions::content::{Content, ContentBlockMember};
+use super::expressions::input_expression::{ArgumentType, InputExpression, ValueType};
+use super::expressions::output_expression::{ExpressionMember, OutputExpression};
+use super::expressions::reference_expression::ReferenceExpression;
+
+pub trait Visitor<'b, 'a: 'b> {
+    fn visit_block(&mut self, block: &'b Block<'a>) {
+        walk_block(self, block)
+    }
+
+    fn visit_output_expression(&mut self, expression: &'b OutputExpression<'a>) {
+        walk_output_expression(self, expression)
+    }
+
+    fn visit_expression_member(&mut self, member: &'b ExpressionMember<'a>) {
+        walk_expression_member(self, member)
+    }
+
+    fn visit_input_expression(&mut self, expression: &'b InputExpression<'a>) {
+        walk_input_expression(self, expression)
+    }
+
+    fn visit_value(&mut self, value: &'b ValueType<'a>) {
+        walk_value(self, value)
+    }
+
+    fn visit_reference_expression(&mut self, expression: &'b ReferenceExpression<'a>) {
+        walk_reference_expression(self, expression)
+    }
+
+    fn visit_annotation_expression(&mut self, expression: &'b AnnotationExpression<'a>) {
+        walk_annotation_expression(self, expression)
+    }
+
+    fn visit_content(&mut self, content: &'b Content<'a>) {
+        walk_content(self, content)
+    }
+
+    fn visit_token(&mut self, _token: &'b Token<'a>) {}
+}
+
+/// Visits the expression of a block, then its children
+pub fn walk_block<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(visitor: &mut V, block: &'b Block<'a>) {
+    match &block.kind {
+        BlockKind::Output(expression) => visitor.visit_output_expression(expression),
+        BlockKind::Input(_, expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_input_expression(expression);
+            }
+        }
+        BlockKind::Declaration(token) => {
+            if let Some(token) = token {
+                visitor.visit_token(token);
+            }
+        }
+        BlockKind::Invocation(token, expression) => {
+            if let Some(token) = token {
+                visitor.visit_token(token);
+            }
+            if let Some(expression) = expression {
+                visitor.visit_input_expression(expression);
+            }
+        }
+        BlockKind::Reference(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_reference_expression(expression);
+            }
+        }
+        BlockKind::Content(content) => visitor.visit_content(content),
+        BlockKind::Annotation(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_annotation_expression(expression);
+            }
+        }
+        BlockKind::Program | BlockKind::ContextModification(_) | BlockKind::Union(_) => {}
+    }
+    for child in block.children.iter() {
+        visitor.visit_block(child);
+    }
+}
+
+pub fn walk_output_expression<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &'b OutputExpression<'a>,
+) {
+    visitor.visit_expression_member(&expression.left);
+    if let Some(right) = &expression.right {
+        visitor.visit_expression_member(right);
+    }
+}
+
+pub fn walk_expression_member<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(
+    visitor: &mut V,
+    member: &'b ExpressionMember<'a>,
+) {
+    match member {
+        ExpressionMember::Literal(token) => visitor.visit_token(token),
+        ExpressionMember::OutputExpression(_, Some(expression)) => {
+            visitor.visit_output_expression(expression)
+        }
+        ExpressionMember::ReferenceExpression(expression) => {
+            visitor.visit_reference_expression(expression)
+        }
+        ExpressionMember::Operation(expression) => visitor.visit_output_expression(expression),
+        ExpressionMember::Unary(_, operand) => visitor.visit_expression_member(operand),
+        ExpressionMember::List(_, items) => {
+            for item in items {
+                visitor.visit_output_expression(item);
+            }
+        }
+        ExpressionMember::Map(_, entries) => {
+            for entry in entries {
+                if let Some(key) = &entry.key {
+                    visitor.visit_token(key);
+                }
+                if let Some(value) = &entry.value {
+                    visitor.visit_output_expression(value);
+                }
+            }
+        }
+        ExpressionMember::OutputExpression(_, None) | ExpressionMember::Nil => {}
+    }
+}
+
+/// Visits name and value of every argument
+pub fn walk_input_expression<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &'b InputExpression<'a>,
+) {
+    for argument in expression.arguments() {
+        if let Some(ArgumentType::Named(token, _)) = &argument.argument {
+            visitor.visit_token(token);
+        }
+        if let Some(value) = &argument.value {
+            visitor.visit_value(value);
+        }
+    }
+}
+
+pub fn walk_value<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(visitor: &mut V, value: &'b ValueType<'a>) {
+    match value {
+        ValueType::Literal(token) => visitor.visit_token(token),
+        ValueType::Content(content) => visitor.visit_content(content),
+        ValueType::OutputExpression(expression) => visitor.visit_output_expression(expression),
+        ValueType::ReferenceExpression(expression) => visitor.visit_reference_expression(expression),
+    }
+}
+
+/// Visits the name of each segment or the expression of its index, in order
+pub fn walk_reference_expression<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &'b ReferenceExpression<'a>,
+) {
+    for part in expression.parts() {
+        match &part.index {
+            Some((_, index)) => visitor.visit_output_expression(index),
+            None => visitor.visit_token(&part.name),
+        }
+    }
+}
+
+pub fn walk_annotation_expression<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &'b AnnotationExpression<'a>,
+) {
+    for segment in expression.segments() {
+        visitor.visit_token(segment);
+    }
+}
+
+/// Visits references of the templates `{{item}}`
+pub fn walk_content<'b, 'a: 'b, V: Visitor<'b, 'a> + ?Sized>(visitor: &mut V, content: &'b Content<'a>) {
+    for member in content.members() {
+        if let ContentBlockMember::Template(reference) = member {
+            visitor.visit_reference_expression(reference);
+        }
+    }
+}
+
+/// Visitor which may change the nodes it visits
+pub trait VisitorMut<'a> {
+    fn visit_block_mut(&mut self, block: &mut Block<'a>) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_output_expression_mut(&mut self, expression: &mut OutputExpression<'a>) {
+        walk_output_expression_mut(self, expression)
+    }
+
+    fn visit_expression_member_mut(&mut self, member: &mut ExpressionMember<'a>) {
+        walk_expression_member_mut(self, member)
+    }
+
+    fn visit_input_expression_mut(&mut self, expression: &mut InputExpression<'a>) {
+        walk_input_expression_mut(self, expression)
+    }
+
+    fn visit_value_mut(&mut self, value: &mut ValueType<'a>) {
+        walk_value_mut(self, value)
+    }
+
+    fn visit_reference_expression_mut(&mut self, expression: &mut ReferenceExpression<'a>) {
+        walk_reference_expression_mut(self, expression)
+    }
+
+    fn visit_annotation_expression_mut(&mut self, expression: &mut AnnotationExpression<'a>) {
+        walk_annotation_expression_mut(self, expression)
+    }
+
+    fn visit_content_mut(&mut self, content: &mut Content<'a>) {
+        walk_content_mut(self, content)
+    }
+
+    fn visit_token_mut(&mut self, _token: &mut Token<'a>) {}
+}
+
+pub fn walk_block_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, block: &mut Block<'a>) {
+    match &mut block.kind {
+        BlockKind::Output(expression) => visitor.visit_output_expression_mut(expression),
+        BlockKind::Input(_, expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_input_expression_mut(expression);
+            }
+        }
+        BlockKind::Declaration(token) => {
+            if let Some(token) = token {
+                visitor.visit_token_mut(token);
+            }
+        }
+        BlockKind::Invocation(token, expression) => {
+            if let Some(token) = token {
+                visitor.visit_token_mut(token);
+            }
+            if let Some(expression) = expression {
+                visitor.visit_input_expression_mut(expression);
+            }
+        }
+        BlockKind::Reference(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_reference_expression_mut(expression);
+            }
+        }
+        BlockKind::Content(content) => visitor.visit_content_mut(content),
+        BlockKind::Annotation(expression) => {
+            if let Some(expression) = expression {
+                visitor.visit_annotation_expression_mut(expression);
+            }
+        }
+        BlockKind::Program | BlockKind::ContextModification(_) | BlockKind::Union(_) => {}
+    }
+    for child in block.children.iter_mut() {
+        visitor.visit_block_mut(child);
+    }
+}
+
+pub fn walk_output_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut OutputExpression<'a>,
+) {
+    visitor.visit_expression_member_mut(&mut expression.left);
+    if let Some(right) = &mut expression.right {
+        visitor.visit_expression_member_mut(right);
+    }
+}
+
+pub fn walk_expression_member_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    member: &mut ExpressionMember<'a>,
+) {
+    match member {
+        ExpressionMember::Literal(token) => visitor.visit_token_mut(token),
+        ExpressionMember::OutputExpression(_, Some(expression)) => {
+            visitor.visit_output_expression_mut(expression)
+        }
+        ExpressionMember::ReferenceExpression(expression) => {
+            visitor.visit_reference_expression_mut(expression)
+        }
+        ExpressionMember::Operation(expression) => visitor.visit_output_expression_mut(expression),
+        ExpressionMember::Unary(_, operand) => visitor.visit_expression_member_mut(operand),
+        ExpressionMember::List(_, items) => {
+            for item in items {
+                visitor.visit_output_expression_mut(item);
+            }
+        }
+        ExpressionMember::Map(_, entries) => {
+            for entry in entries {
+                if let Some(key) = &mut entry.key {
+                    visitor.visit_token_mut(key);
+                }
+                if let Some(value) = &mut entry.value {
+                    visitor.visit_output_expression_mut(value);
+                }
+            }
+        }
+        ExpressionMember::OutputExpression(_, None) | ExpressionMember::Nil => {}
+    }
+}
+
+pub fn walk_input_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut InputExpression<'a>,
+) {
+    let mut current = Some(expression);
+    while let Some(argument) = current {
+        if let Some(ArgumentType::Named(token, _)) = &mut argument.argument {
+            visitor.visit_token_mut(token);
+        }
+        if let Some(value) = &mut argument.value {
+            visitor.visit_value_mut(value);
+        }
+        current = argument.next.as_deref_mut();
+    }
+}
+
+pub fn walk_value_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, value: &mut ValueType<'a>) {
+    match value {
+        ValueType::Literal(token) => visitor.visit_token_mut(token),
+        ValueType::Content(content) => visitor.visit_content_mut(content),
+        ValueType::OutputExpression(expression) => visitor.visit_output_expression_mut(expression),
+        ValueType::ReferenceExpression(expression) => {
+            visitor.visit_reference_expression_mut(expression)
+        }
+    }
+}
+
+/// Visits the name of each segment or the expression of its index, in order
+pub fn walk_reference_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut ReferenceExpression<'a>,
+) {
+    let mut current = Some(expression);
+    while let Some(segment) = current {
+        match &mut segment.index {
+            Some((_, index)) => visitor.visit_output_expression_mut(index),
+            None => visitor.visit_token_mut(&mut segment.name),
+        }
+        current = match &mut segment.next {
+            Some(Some(next)) => Some(next),
+            _ => None,
+        };
+    }
+}
+
+pub fn walk_annotation_expression_mut<'a, V: VisitorMut<'a> + ?Sized>(
+    visitor: &mut V,
+    expression: &mut AnnotationExpression<'a>,
+) {
+    let mut current = Some(expression);
+    while let Some(segment) = current {
+        visitor.visit_token_mut(&mut segment.name);
+        current = match &mut segment.next {
+            Some(Some(next)) => Some(next),
+            _ => None,
+        };
+    }
+}
+
+pub fn walk_content_mut<'a, V: VisitorMut<'a> + ?Sized>(visitor: &mut V, content: &mut Content<'a>) {
+    let mut current = Some(content);
+    while let Some(content) = current {
+        if let ContentBlockMember::Template(reference) = &mut content.member {
+            visitor.visit_reference_expression_mut(reference);
+        }
+        current = content.next.as_deref_mut();
+    }
+}
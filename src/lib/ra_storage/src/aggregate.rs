@@ -0,0 +1,455 @@
+use super::errors::StorageResult;
+use super::store::{Dataset, Store, Submission};
+use ra_analysis::items::reference_path;
+use ra_lexer::token::{Token, TokenKind};
+use ra_parser::expressions::output_expression::{
+    ComparisonOperation, ExpressionMember, LogicOperation, MathOperation, OperationKind,
+    OutputExpression, UnaryOperation,
+};
+use ra_parser::expressions::reference_expression::ReferenceExpression;
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Aggregate applied to a field with a trailing segment, e.g. `recipe.rating.avg`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    Distinct,
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(Aggregate::Count),
+            "sum" => Some(Aggregate::Sum),
+            "avg" => Some(Aggregate::Avg),
+            "min" => Some(Aggregate::Min),
+            "max" => Some(Aggregate::Max),
+            "distinct" => Some(Aggregate::Distinct),
+            _ => None,
+        }
+    }
+
+    /// Aggregates values, numeric aggregates skip values which aren't numbers
+    pub fn apply(self, values: &[Value]) -> Value {
+        let numbers = || values.iter().filter_map(Value::as_f64);
+        match self {
+            Aggregate::Count => Value::from(values.len()),
+            Aggregate::Sum => number(numbers().sum()),
+            Aggregate::Avg => {
+                let count = numbers().count();
+                if count == 0 {
+                    Value::Null
+                } else {
+                    number(numbers().sum::<f64>() / count as f64)
+                }
+            }
+            Aggregate::Min => numbers()
+                .fold(None, |min: Option<f64>, n| {
+                    Some(min.map_or(n, |min| min.min(n)))
+                })
+                .map_or(Value::Null, number),
+            Aggregate::Max => numbers()
+                .fold(None, |max: Option<f64>, n| {
+                    Some(max.map_or(n, |max| max.max(n)))
+                })
+                .map_or(Value::Null, number),
+            Aggregate::Distinct => {
+                let mut distinct: Vec<Value> = Vec::new();
+                for value in values.iter() {
+                    if !distinct.iter().any(|known| equals(known, value)) {
+                        distinct.push(value.clone());
+                    }
+                }
+                Value::Array(distinct)
+            }
+        }
+    }
+}
+
+/// Direction of `!sort`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+impl Order {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "asc" => Some(Order::Asc),
+            "desc" => Some(Order::Desc),
+            _ => None,
+        }
+    }
+}
+
+/// Collected values expressions are evaluated against.
+///
+/// A record holds values of one client,
+/// contexts of many clients hold a record per client.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Records {
+    records: Vec<BTreeMap<String, Value>>,
+}
+
+impl Records {
+    pub fn new(records: Vec<BTreeMap<String, Value>>) -> Self {
+        Self { records }
+    }
+
+    /// Context of a single client
+    pub fn client(values: BTreeMap<String, Value>) -> Self {
+        Self::new(vec![values])
+    }
+
+    pub fn submissions(submissions: &[Submission]) -> Self {
+        Self::new(
+            submissions
+                .iter()
+                .map(|submission| submission.values.clone())
+                .collect(),
+        )
+    }
+
+    /// Rows of a dataset across epochs, fields missing in an epoch are left out
+    pub fn dataset(dataset: &Dataset) -> Self {
+        Self::new(
+            dataset
+                .rows
+                .iter()
+                .map(|row| {
+                    row.values
+                        .iter()
+                        .filter_map(|(field, value)| Some((field.clone(), value.clone()?)))
+                        .collect()
+                })
+                .collect(),
+        )
+    }
+
+    /// Everything clients submitted to an epoch of a sequence
+    pub fn query<S: Store + ?Sized>(store: &S, sequence: &str, epoch: u32) -> StorageResult<Self> {
+        Ok(Self::submissions(&store.submissions(sequence, epoch)?))
+    }
+
+    pub fn records(&self) -> &[BTreeMap<String, Value>] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Values of a field across records, entries of `+>` lists are flattened
+    pub fn values(&self, field: &str) -> Vec<Value> {
+        self.records
+            .iter()
+            .filter_map(|record| record.get(field))
+            .flat_map(|value| match value {
+                Value::Array(entries) => entries.clone(),
+                value => vec![value.clone()],
+            })
+            .filter(|value| !value.is_null())
+            .collect()
+    }
+
+    fn has_field(&self, field: &str) -> bool {
+        self.records.iter().any(|record| record.contains_key(field))
+    }
+
+    /// Value of a reference path.
+    ///
+    /// A field of a single record resolves to its value,
+    /// of many records to the list of their values.
+    /// A trailing aggregate like `.count` aggregates the values of the field.
+    pub fn get(&self, path: &[&str]) -> Value {
+        let field = path.join(".");
+        if self.has_field(&field) {
+            return match self.records.as_slice() {
+                [record] => record.get(&field).cloned().unwrap_or(Value::Null),
+                _ => Value::Array(self.values(&field)),
+            };
+        }
+        match path.split_last() {
+            Some((last, prefix)) if !prefix.is_empty() => match Aggregate::from_name(last) {
+                Some(aggregate) => aggregate.apply(&self.values(&prefix.join("."))),
+                None => Value::Null,
+            },
+            _ => Value::Null,
+        }
+    }
+
+    pub fn evaluate(&self, expression: &OutputExpression) -> Value {
+        let OutputExpression {
+            left: first,
+            operation,
+            right: last,
+        } = expression;
+        let left = self.member(first);
+        match (operation, last) {
+            (Some(operation), Some(last)) => apply(*operation, &left, &self.member(last)),
+            _ => left,
+        }
+    }
+
+    pub(crate) fn member(&self, member: &ExpressionMember) -> Value {
+        match member {
+            ExpressionMember::Literal(token) => match token.kind {
+                Some(TokenKind::Int(value)) => Value::from(value),
+                Some(TokenKind::Float(value)) => number(value),
+                Some(TokenKind::StringLiteral(_)) => Value::from(token.value().unwrap_or_default()),
+                Some(TokenKind::Boolean(value)) => Value::Bool(value),
+                _ => Value::Null,
+            },
+            ExpressionMember::ReferenceExpression(reference) => self.reference(reference),
+            ExpressionMember::OutputExpression(_, Some(expression))
+            | ExpressionMember::Operation(expression) => self.evaluate(expression),
+            ExpressionMember::Unary(UnaryOperation::Negate, operand) => math(
+                MathOperation::Subtract,
+                &Value::from(0),
+                &self.member(operand),
+            ),
+            ExpressionMember::Unary(UnaryOperation::Not, operand) => {
+                Value::Bool(!is_truthy(&self.member(operand)))
+            }
+            ExpressionMember::List(_, items) => {
+                Value::Array(items.iter().map(|item| self.evaluate(item)).collect())
+            }
+            ExpressionMember::Map(_, entries) => Value::Object(
+                entries
+                    .iter()
+                    .filter_map(|entry| match (&entry.key, &entry.value) {
+                        (Some(key), Some(value)) => Some((map_key(key), self.evaluate(value))),
+                        _ => None,
+                    })
+                    .collect(),
+            ),
+            ExpressionMember::OutputExpression(_, None) | ExpressionMember::Nil => Value::Null,
+        }
+    }
+
+    /// Value of a reference, indices and fields following the item path
+    /// are looked up in the value of the item.
+    /// Anything missing is none, with or without `?`.
+    fn reference(&self, reference: &ReferenceExpression) -> Value {
+        let path = reference_path(reference);
+        reference
+            .parts()
+            .skip(path.len())
+            .fold(self.get(&path), |value, part| {
+                let key = match (&part.index, part.name.kind) {
+                    (Some((_, index)), _) => self.evaluate(index),
+                    (None, Some(TokenKind::Int(index))) => Value::from(index),
+                    (None, _) => Value::from(part.name.content),
+                };
+                element(&value, &key)
+            })
+    }
+
+    /// Records ordered by the value of `key` evaluated against each of them,
+    /// records without a value come last
+    pub fn sort(&self, key: &OutputExpression, order: Order) -> Self {
+        let mut keyed: Vec<(Value, &BTreeMap<String, Value>)> = self
+            .records
+            .iter()
+            .map(|record| (Self::client(record.clone()).evaluate(key), record))
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| match order {
+            Order::Desc if !a.is_null() && !b.is_null() => compare(b, a),
+            _ => compare(a, b),
+        });
+        Self::new(
+            keyed
+                .into_iter()
+                .map(|(_, record)| record.clone())
+                .collect(),
+        )
+    }
+
+    /// Records `predicate` holds for
+    pub fn filter(&self, predicate: &OutputExpression) -> Self {
+        Self::new(
+            self.records
+                .iter()
+                .filter(|record| is_truthy(&Self::client((*record).clone()).evaluate(predicate)))
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// `!includes`: a list includes a value or every value of another list,
+/// a text includes another text ignoring case
+pub fn includes(haystack: &Value, needle: &Value) -> bool {
+    match (haystack, needle) {
+        (_, Value::Null) => true,
+        (Value::Array(entries), Value::Array(needles)) => needles
+            .iter()
+            .all(|needle| entries.iter().any(|entry| equals(entry, needle))),
+        (Value::Array(entries), needle) => entries.iter().any(|entry| equals(entry, needle)),
+        (Value::String(text), Value::String(needle)) => {
+            text.to_lowercase().contains(&needle.to_lowercase())
+        }
+        (haystack, needle) => equals(haystack, needle),
+    }
+}
+
+/// Element of a list at an index, negative indices count from the end,
+/// or a field of a map
+fn element(value: &Value, key: &Value) -> Value {
+    let found = match (value, key) {
+        (Value::Array(entries), Value::Number(index)) => index
+            .as_i64()
+            .map(|index| {
+                if index < 0 {
+                    index + entries.len() as i64
+                } else {
+                    index
+                }
+            })
+            .and_then(|index| usize::try_from(index).ok())
+            .and_then(|index| entries.get(index)),
+        (Value::Object(fields), Value::String(field)) => fields.get(field),
+        _ => None,
+    };
+    found.cloned().unwrap_or(Value::Null)
+}
+
+/// Key of a map literal entry, a name or a string literal
+fn map_key(key: &Token) -> String {
+    match key.kind {
+        Some(TokenKind::StringLiteral(_)) => key.value().unwrap_or_default().into_owned(),
+        _ => key.content.to_owned(),
+    }
+}
+
+/// Values conditions treat as true
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(value) => *value,
+        Value::Number(value) => value.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(value) => !value.is_empty(),
+        Value::Array(entries) => !entries.is_empty(),
+        Value::Object(entries) => !entries.is_empty(),
+    }
+}
+
+fn apply(operation: OperationKind, left: &Value, right: &Value) -> Value {
+    match operation {
+        OperationKind::MathOperation(operation) => math(operation, left, right),
+        OperationKind::ComparisonOperation(operation) => {
+            // a missing value is neither greater nor less than anything
+            let ordering = || match (left, right) {
+                (Value::Null, _) | (_, Value::Null) => None,
+                _ => Some(compare(left, right)),
+            };
+            Value::Bool(match operation {
+                ComparisonOperation::EqCompare => equals(left, right),
+                ComparisonOperation::NEqCompare => !equals(left, right),
+                ComparisonOperation::GtCompare => ordering() == Some(Ordering::Greater),
+                ComparisonOperation::LsCompare => ordering() == Some(Ordering::Less),
+                ComparisonOperation::GtEqCompare => {
+                    matches!(ordering(), Some(Ordering::Greater | Ordering::Equal))
+                }
+                ComparisonOperation::LsEqCompare => {
+                    matches!(ordering(), Some(Ordering::Less | Ordering::Equal))
+                }
+            })
+        }
+        OperationKind::LogicOperation(operation) => {
+            let (left, right) = (is_truthy(left), is_truthy(right));
+            match operation {
+                LogicOperation::AND => Value::Bool(left && right),
+                LogicOperation::OR => Value::Bool(left || right),
+                LogicOperation::XOR => Value::Bool(left != right),
+                LogicOperation::NAND => Value::Bool(!(left && right)),
+                LogicOperation::NOR => Value::Bool(!(left || right)),
+                LogicOperation::XNOR => Value::Bool(left == right),
+                LogicOperation::NOT => Value::Null,
+            }
+        }
+        OperationKind::Assign => Value::Null,
+    }
+}
+
+pub(crate) fn math(operation: MathOperation, left: &Value, right: &Value) -> Value {
+    if let (Some(a), Some(b)) = (left.as_i64(), right.as_i64()) {
+        let result = match operation {
+            MathOperation::Sum => a.checked_add(b),
+            MathOperation::Subtract => a.checked_sub(b),
+            MathOperation::Multiply => a.checked_mul(b),
+            MathOperation::Reminder => a.checked_rem(b),
+            _ => None,
+        };
+        if let Some(result) = result {
+            return Value::from(result);
+        }
+    }
+    let (a, b) = match (left.as_f64(), right.as_f64()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return Value::Null,
+    };
+    match operation {
+        MathOperation::Sum => number(a + b),
+        MathOperation::Subtract => number(a - b),
+        MathOperation::Multiply => number(a * b),
+        MathOperation::Divide => number(a / b),
+        MathOperation::Reminder => number(a % b),
+        MathOperation::Power => number(a.powf(b)),
+        MathOperation::AddAssign | MathOperation::SubtractAssign => Value::Null,
+    }
+}
+
+/// Infinite and undefined results are `null`
+fn number(value: f64) -> Value {
+    Number::from_f64(value).map_or(Value::Null, Value::Number)
+}
+
+/// Numbers are equal by value whether they're integers or not
+fn equals(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Numbers by value, texts alphabetically, anything else by its kind,
+/// missing values last
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a.len().cmp(&b.len()),
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn rank(value: &Value) -> u8 {
+    match value {
+        Value::Bool(_) => 0,
+        Value::Number(_) => 1,
+        Value::String(_) => 2,
+        Value::Array(_) => 3,
+        Value::Object(_) => 4,
+        Value::Null => 5,
+    }
+}
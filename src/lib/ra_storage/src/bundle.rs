@@ -0,0 +1,23 @@
+use super::errors::{StorageError, StorageResult};
+use ra_analysis::bundle::{Bundle, FormatVersion, FORMAT_VERSION};
+use serde_json::Value;
+use std::io::{Read, Write};
+
+/// Writes a bundle as compact JSON
+pub fn write_bundle<W: Write>(bundle: &Bundle, writer: W) -> StorageResult<()> {
+    Ok(serde_json::to_writer(writer, bundle)?)
+}
+
+/// Reads a bundle this runtime is able to run.
+///
+/// The version is checked before anything else,
+/// bundles of another major version may have any shape.
+pub fn load_bundle<R: Read>(reader: R) -> StorageResult<Bundle> {
+    let value: Value = serde_json::from_reader(reader)?;
+    let version = value.get("version").cloned().unwrap_or(Value::Null);
+    let version: FormatVersion = serde_json::from_value(version)?;
+    if !FORMAT_VERSION.can_load(version) {
+        return Err(StorageError::UnsupportedBundle(version));
+    }
+    Ok(serde_json::from_value(value)?)
+}
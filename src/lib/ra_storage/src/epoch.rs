@@ -0,0 +1,119 @@
+use super::identity::to_hex;
+use ra_analysis::assignments::assigned_items;
+use ra_analysis::identity::identity_fields;
+use ra_analysis::items::item_paths;
+use ra_analysis::types::item_input;
+use ra_parser::block::Block;
+use ra_parser::syntax::{syntax_tree, SyntaxElement, SyntaxNode};
+use sha2::{Digest, Sha256};
+
+/// Version of a sequence, a new one starts whenever
+/// structure of the sequence changes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Epoch {
+    pub number: u32,
+    pub hash: String,
+    /// Paths of items accepting input, e.g. `recipe.name`
+    pub fields: Vec<String>,
+    /// Fields of identity items, never exported
+    pub identity: Vec<String>,
+    /// Items changing only through declared assignments
+    pub assignable: Vec<String>,
+}
+
+/// Epoch as stored, epochs stored before identity fields
+/// and assigned items were kept have to be migrated
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct StoredEpoch {
+    number: u32,
+    hash: String,
+    fields: Vec<String>,
+    identity: Option<Vec<String>>,
+    assignable: Option<Vec<String>>,
+}
+
+impl StoredEpoch {
+    /// `None` until the epoch is migrated
+    pub(crate) fn into_epoch(self) -> Option<Epoch> {
+        Some(Epoch {
+            number: self.number,
+            hash: self.hash,
+            fields: self.fields,
+            identity: self.identity?,
+            assignable: self.assignable?,
+        })
+    }
+
+    /// Fills in lists the epoch was stored without.
+    ///
+    /// They're taken from `current` when it has the same structure,
+    /// otherwise source of the epoch is gone and all its fields are taken
+    /// for identifying, so none of them is exported.
+    pub(crate) fn migrate(self, current: &Epoch) -> Epoch {
+        let same = self.hash == current.hash;
+        let identity = match self.identity {
+            Some(identity) => identity,
+            None if same => current.identity.clone(),
+            None => self.fields.clone(),
+        };
+        let assignable = match self.assignable {
+            Some(assignable) => assignable,
+            None if same => current.assignable.clone(),
+            None => Vec::new(),
+        };
+        Epoch {
+            number: self.number,
+            hash: self.hash,
+            fields: self.fields,
+            identity,
+            assignable,
+        }
+    }
+}
+
+impl Epoch {
+    pub fn new(number: u32, source: &str, program: &Block) -> Self {
+        Self {
+            number,
+            hash: structural_hash(source, program),
+            fields: sequence_fields(program),
+            identity: identity_fields(program),
+            assignable: assigned_items(program),
+        }
+    }
+
+    pub fn has_field(&self, field: &str) -> bool {
+        self.fields.iter().any(|f| f == field)
+    }
+}
+
+/// Hash of blocks and their tokens,
+/// whitespace and comments don't affect it
+pub fn structural_hash(source: &str, program: &Block) -> String {
+    let mut hasher = Sha256::new();
+    hash_node(&syntax_tree(source, program), &mut hasher);
+    to_hex(&hasher.finalize())
+}
+
+fn hash_node(node: &SyntaxNode, hasher: &mut Sha256) {
+    hasher.update(format!("({:?}", node.kind));
+    for child in node.children.iter() {
+        match child {
+            SyntaxElement::Node(node) => hash_node(node, hasher),
+            SyntaxElement::Token(token) if !token.is_eof() => {
+                hasher.update(format!(" {:?}", token.token.kind));
+            }
+            SyntaxElement::Token(_) => {}
+        }
+    }
+    hasher.update(")");
+}
+
+/// Items accepting input, in document order
+pub fn sequence_fields(program: &Block) -> Vec<String> {
+    item_paths(program)
+        .into_iter()
+        .filter(|(_, item)| item_input(item).is_some())
+        .map(|(path, _)| path)
+        .collect()
+}
@@ -0,0 +1,99 @@
+use failure::Backtrace;
+use ra_analysis::bundle::{FormatVersion, FORMAT_VERSION};
+use std::fmt;
+use std::io;
+
+#[derive(Debug, Fail)]
+pub enum StorageError {
+    Io(#[cause] io::Error, Backtrace),
+    Serialization(#[cause] serde_json::Error, Backtrace),
+    InvalidSequenceName(String),
+    UnknownEpoch(String, u32),
+    EpochTaken(String, u32),
+    UnmigratedEpochs(String),
+    InvalidSecret,
+    MissingIdentity(String),
+    IdentityTaken(String),
+    PrivateField(String),
+    ReadOnly(String),
+    UndeclaredAssignment(String),
+    UnsupportedBundle(FormatVersion),
+    UnknownLocale(String),
+    InvalidNumber(String, String),
+}
+
+pub type StorageResult<T> = Result<T, StorageError>;
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Io(e, _) => write!(f, "storage io error: {}", e),
+            StorageError::Serialization(e, _) => write!(f, "corrupted storage: {}", e),
+            StorageError::InvalidSequenceName(name) => {
+                write!(f, "`{}` can't be used as a sequence name", name)
+            }
+            StorageError::UnknownEpoch(sequence, epoch) => {
+                write!(f, "sequence `{}` has no epoch {}", sequence, epoch)
+            }
+            StorageError::EpochTaken(sequence, epoch) => write!(
+                f,
+                "epoch {} of sequence `{}` was opened by another store with a different structure",
+                epoch, sequence
+            ),
+            StorageError::UnmigratedEpochs(sequence) => write!(
+                f,
+                "epochs of sequence `{}` were stored without identity fields, migrate them first",
+                sequence
+            ),
+            StorageError::InvalidSecret => write!(f, "identity secret is corrupted"),
+            StorageError::MissingIdentity(field) => {
+                write!(f, "identity field `{}` has no value", field)
+            }
+            StorageError::PrivateField(field) => {
+                write!(
+                    f,
+                    "`{}` belongs to an identity and can't be referenced",
+                    field
+                )
+            }
+            StorageError::ReadOnly(field) => {
+                write!(f, "`{}` only changes through its assignments", field)
+            }
+            StorageError::UndeclaredAssignment(field) => {
+                write!(f, "sequence declares no assignment to `{}`", field)
+            }
+            StorageError::UnsupportedBundle(version) => {
+                write!(
+                    f,
+                    "bundle format {} can't be loaded, runtime supports {}",
+                    version, FORMAT_VERSION
+                )
+            }
+            StorageError::UnknownLocale(tag) => {
+                write!(f, "numbers can't be read in locale `{}`", tag)
+            }
+            StorageError::InvalidNumber(field, text) => {
+                write!(f, "`{}` of `{}` isn't a number", text, field)
+            }
+            StorageError::IdentityTaken(sequence) => {
+                write!(
+                    f,
+                    "identity is already used by another client of `{}`",
+                    sequence
+                )
+            }
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        StorageError::Io(e, Backtrace::new())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        StorageError::Serialization(e, Backtrace::new())
+    }
+}
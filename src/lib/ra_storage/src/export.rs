@@ -0,0 +1,173 @@
+use super::epoch::Epoch;
+use super::errors::{StorageError, StorageResult};
+use super::identity::{Identities, Secret};
+use super::store::Store;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Rows sharing quasi-identifying values with fewer than `k`
+    /// other rows of the epoch get these values suppressed
+    pub k_anonymity: Option<usize>,
+    /// Fields which could identify a client when combined,
+    /// all exported fields when empty
+    pub quasi_identifiers: Vec<String>,
+}
+
+/// Public data of a sequence epoch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EpochExport {
+    pub sequence: String,
+    pub epoch: u32,
+    /// Exported fields, identity fields left out
+    pub fields: Vec<String>,
+    pub rows: Vec<ExportRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRow {
+    pub epoch: u32,
+    /// Pseudonym of the client
+    pub client: String,
+    pub values: BTreeMap<String, Value>,
+}
+
+/// Collects public data of every epoch of a sequence.
+///
+/// Identity fields are stripped and clients are replaced
+/// with pseudonyms, so exports of different sequences can't be joined.
+pub fn export<S: Store + ?Sized>(
+    store: &S,
+    sequence: &str,
+    secret: &Secret,
+    options: &ExportOptions,
+) -> StorageResult<Vec<EpochExport>> {
+    let mut exports = Vec::new();
+    for epoch in store.epochs(sequence)? {
+        exports.push(export_epoch(store, sequence, &epoch, secret, options)?);
+    }
+    Ok(exports)
+}
+
+fn export_epoch<S: Store + ?Sized>(
+    store: &S,
+    sequence: &str,
+    epoch: &Epoch,
+    secret: &Secret,
+    options: &ExportOptions,
+) -> StorageResult<EpochExport> {
+    let identities = Identities::with_fields(secret, sequence, epoch.identity.clone());
+    let fields: Vec<String> = epoch
+        .fields
+        .iter()
+        .filter(|field| !identities.is_identifying(field))
+        .cloned()
+        .collect();
+
+    let mut rows: Vec<ExportRow> = store
+        .submissions(sequence, epoch.number)?
+        .into_iter()
+        .map(|submission| ExportRow {
+            epoch: epoch.number,
+            client: identities.client_pseudonym(&submission.client).to_string(),
+            values: fields
+                .iter()
+                .map(|field| {
+                    let value = submission.values.get(field).cloned().unwrap_or(Value::Null);
+                    (field.clone(), value)
+                })
+                .collect(),
+        })
+        .collect();
+
+    if let Some(k) = options.k_anonymity {
+        let quasi_identifiers: Vec<String> = if options.quasi_identifiers.is_empty() {
+            fields.clone()
+        } else {
+            options
+                .quasi_identifiers
+                .iter()
+                .filter(|field| fields.contains(field))
+                .cloned()
+                .collect()
+        };
+        suppress(&mut rows, &quasi_identifiers, k);
+    }
+
+    Ok(EpochExport {
+        sequence: sequence.to_owned(),
+        epoch: epoch.number,
+        fields,
+        rows,
+    })
+}
+
+/// Suppresses quasi-identifying values of rows in groups smaller than `k`
+fn suppress(rows: &mut [ExportRow], quasi_identifiers: &[String], k: usize) {
+    let key = |row: &ExportRow| -> String {
+        let values: Vec<&Value> = quasi_identifiers
+            .iter()
+            .map(|field| row.values.get(field).unwrap_or(&Value::Null))
+            .collect();
+        serde_json::to_string(&values).unwrap_or_default()
+    };
+
+    let mut groups: HashMap<String, usize> = HashMap::new();
+    for row in rows.iter() {
+        *groups.entry(key(row)).or_default() += 1;
+    }
+    for row in rows.iter_mut() {
+        if groups[&key(row)] < k {
+            for field in quasi_identifiers.iter() {
+                row.values.insert(field.clone(), Value::Null);
+            }
+        }
+    }
+}
+
+impl EpochExport {
+    /// One JSON object per row, values next to `epoch` and `client`
+    pub fn write_json_lines<W: Write>(&self, mut writer: W) -> StorageResult<()> {
+        for row in self.rows.iter() {
+            let mut object = serde_json::Map::new();
+            object.insert("epoch".to_owned(), Value::from(row.epoch));
+            object.insert("client".to_owned(), Value::from(row.client.clone()));
+            for field in self.fields.iter() {
+                object.insert(field.clone(), row.values[field].clone());
+            }
+            serde_json::to_writer(&mut writer, &object)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Header of `epoch`, `client` and the fields, a row per client,
+    /// lists and objects are written as JSON
+    pub fn write_csv<W: Write>(&self, writer: W) -> StorageResult<()> {
+        let mut csv = csv::Writer::from_writer(writer);
+        let mut header = vec!["epoch".to_owned(), "client".to_owned()];
+        header.extend(self.fields.iter().cloned());
+        csv.write_record(&header).map_err(csv_error)?;
+        for row in self.rows.iter() {
+            let mut record = vec![row.epoch.to_string(), row.client.clone()];
+            record.extend(self.fields.iter().map(|field| match &row.values[field] {
+                Value::Null => String::new(),
+                Value::String(text) => text.clone(),
+                value => value.to_string(),
+            }));
+            csv.write_record(&record).map_err(csv_error)?;
+        }
+        csv.flush()?;
+        Ok(())
+    }
+}
+
+fn csv_error(e: csv::Error) -> StorageError {
+    let e = match e.into_kind() {
+        csv::ErrorKind::Io(e) => e,
+        kind => std::io::Error::other(format!("{:?}", kind)),
+    };
+    StorageError::from(e)
+}
@@ -0,0 +1,238 @@
+use super::epoch::{Epoch, StoredEpoch};
+use super::errors::{StorageError, StorageResult};
+use super::identity::{Claim, Pseudonym, Secret};
+use super::store::{Store, Submission, Update};
+use ra_parser::block::Block;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Store embedded in a directory.
+///
+/// Every sequence gets a directory with `epochs.json`
+/// and a `<epoch>.jsonl` log of submissions,
+/// later lines of the log replace earlier ones of the same client.
+/// Updates of assigned items are appended to `<epoch>.updates.jsonl`
+/// and identities clients took to `claims.jsonl`,
+/// `epochs.lock` is held while epochs are rewritten.
+#[derive(Debug)]
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl FileStore {
+    pub fn open<P: AsRef<Path>>(root: P) -> StorageResult<Self> {
+        fs::create_dir_all(root.as_ref())?;
+        Ok(Self {
+            root: root.as_ref().to_owned(),
+        })
+    }
+
+    /// Secret of this store pseudonyms are derived with
+    pub fn secret(&self) -> StorageResult<Secret> {
+        Secret::load_or_create(self.root.join("secret"))
+    }
+
+    /// Fills in identity fields and assigned items of epochs stored without them,
+    /// `source` and `program` are the current version of the sequence.
+    ///
+    /// Epochs of another structure get all their fields taken for identifying,
+    /// so none of their values is exported.
+    pub fn migrate_epochs(
+        &mut self,
+        sequence: &str,
+        source: &str,
+        program: &Block,
+    ) -> StorageResult<()> {
+        let _lock = self.lock_epochs(sequence)?;
+        let stored = self.stored_epochs(sequence)?;
+        if stored.is_empty() {
+            return Ok(());
+        }
+        let current = Epoch::new(0, source, program);
+        let epochs: Vec<Epoch> = stored
+            .into_iter()
+            .map(|epoch| epoch.migrate(&current))
+            .collect();
+        self.write_epochs(sequence, &epochs)
+    }
+
+    fn sequence_dir(&self, sequence: &str) -> StorageResult<PathBuf> {
+        let valid = !sequence.is_empty()
+            && !sequence.starts_with('.')
+            && sequence
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-' || ch == '.');
+        if !valid {
+            return Err(StorageError::InvalidSequenceName(sequence.to_owned()));
+        }
+        Ok(self.root.join(sequence))
+    }
+
+    fn log_path(&self, sequence: &str, epoch: u32) -> StorageResult<PathBuf> {
+        Ok(self
+            .sequence_dir(sequence)?
+            .join(format!("{}.jsonl", epoch)))
+    }
+
+    fn updates_path(&self, sequence: &str, epoch: u32) -> StorageResult<PathBuf> {
+        Ok(self
+            .sequence_dir(sequence)?
+            .join(format!("{}.updates.jsonl", epoch)))
+    }
+
+    fn stored_epochs(&self, sequence: &str) -> StorageResult<Vec<StoredEpoch>> {
+        let path = self.sequence_dir(sequence)?.join("epochs.json");
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Waits for other stores over the same directory to finish rewriting epochs.
+    ///
+    /// `epochs.json` is replaced on every write, the lock is held on `epochs.lock`
+    /// until the returned file is dropped.
+    fn lock_epochs(&self, sequence: &str) -> StorageResult<File> {
+        let dir = self.sequence_dir(sequence)?;
+        fs::create_dir_all(&dir)?;
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join("epochs.lock"))?;
+        lock.lock()?;
+        Ok(lock)
+    }
+
+    fn write_epochs(&self, sequence: &str, epochs: &[Epoch]) -> StorageResult<()> {
+        let dir = self.sequence_dir(sequence)?;
+        fs::create_dir_all(&dir)?;
+        // write aside and rename so a crash can't leave half written epochs
+        let temporary = dir.join("epochs.json.tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(epochs)?)?;
+        fs::rename(temporary, dir.join("epochs.json"))?;
+        Ok(())
+    }
+
+    fn append<T: Serialize>(path: PathBuf, entry: &T) -> StorageResult<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+        // writers of other stores over the same directory wait for the whole line
+        log.lock()?;
+        log.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    fn read_lines<T: DeserializeOwned>(path: PathBuf) -> StorageResult<Vec<T>> {
+        let mut log = match File::open(path) {
+            Ok(log) => log,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        // a line being appended isn't read half written
+        log.lock_shared()?;
+        let mut lines = String::new();
+        log.read_to_string(&mut lines)?;
+        let mut entries = Vec::new();
+        for line in lines.lines().filter(|line| !line.trim().is_empty()) {
+            entries.push(serde_json::from_str(line)?);
+        }
+        Ok(entries)
+    }
+}
+
+impl Store for FileStore {
+    fn epochs(&self, sequence: &str) -> StorageResult<Vec<Epoch>> {
+        self.stored_epochs(sequence)?
+            .into_iter()
+            .map(|epoch| {
+                epoch
+                    .into_epoch()
+                    .ok_or_else(|| StorageError::UnmigratedEpochs(sequence.to_owned()))
+            })
+            .collect()
+    }
+
+    fn add_epoch(&mut self, sequence: &str, epoch: Epoch) -> StorageResult<()> {
+        let _lock = self.lock_epochs(sequence)?;
+        let mut epochs = self.epochs(sequence)?;
+        // another store may have opened an epoch since this one was numbered
+        if let Some(taken) = epochs.iter().find(|taken| taken.number == epoch.number) {
+            if taken.hash == epoch.hash {
+                return Ok(());
+            }
+            return Err(StorageError::EpochTaken(sequence.to_owned(), epoch.number));
+        }
+        epochs.push(epoch);
+        self.write_epochs(sequence, &epochs)
+    }
+
+    fn save(&mut self, submission: Submission) -> StorageResult<()> {
+        self.epoch(&submission.sequence, submission.epoch)?;
+        Self::append(
+            self.log_path(&submission.sequence, submission.epoch)?,
+            &submission,
+        )
+    }
+
+    fn submissions(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Submission>> {
+        self.epoch(sequence, epoch)?;
+        // latest submission of each client, in the order they were saved
+        let mut submissions: Vec<Option<Submission>> = Vec::new();
+        let mut latest: HashMap<String, usize> = HashMap::new();
+        for submission in Self::read_lines::<Submission>(self.log_path(sequence, epoch)?)? {
+            if let Some(index) = latest.insert(submission.client.clone(), submissions.len()) {
+                submissions[index] = None;
+            }
+            submissions.push(Some(submission));
+        }
+        Ok(submissions.into_iter().flatten().collect())
+    }
+
+    fn record(&mut self, update: Update) -> StorageResult<()> {
+        self.epoch(&update.sequence, update.epoch)?;
+        Self::append(self.updates_path(&update.sequence, update.epoch)?, &update)
+    }
+
+    fn updates(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Update>> {
+        self.epoch(sequence, epoch)?;
+        Self::read_lines(self.updates_path(sequence, epoch)?)
+    }
+
+    fn claim(
+        &mut self,
+        sequence: &str,
+        pseudonym: &Pseudonym,
+        client: &str,
+    ) -> StorageResult<String> {
+        let dir = self.sequence_dir(sequence)?;
+        fs::create_dir_all(&dir)?;
+        let mut log = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(dir.join("claims.jsonl"))?;
+        // other stores over the same directory wait, so nobody claims in between
+        log.lock()?;
+        let mut claims = String::new();
+        log.read_to_string(&mut claims)?;
+        for line in claims.lines().filter(|line| !line.trim().is_empty()) {
+            let claim: Claim = serde_json::from_str(line)?;
+            if &claim.pseudonym == pseudonym {
+                return Ok(claim.client);
+            }
+        }
+        let mut line = serde_json::to_string(&Claim {
+            pseudonym: pseudonym.clone(),
+            client: client.to_owned(),
+        })?;
+        line.push('\n');
+        log.write_all(line.as_bytes())?;
+        Ok(client.to_owned())
+    }
+}
@@ -0,0 +1,172 @@
+use super::errors::{StorageError, StorageResult};
+use hmac::{Hmac, Mac};
+use ra_analysis::identity::identity_fields;
+use ra_parser::block::Block;
+use serde_json::Value;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+const SECRET_LEN: usize = 32;
+
+/// Local secret pseudonyms are derived with,
+/// it never leaves the runtime
+pub struct Secret([u8; SECRET_LEN]);
+
+impl Secret {
+    pub fn generate() -> StorageResult<Self> {
+        let mut bytes = [0; SECRET_LEN];
+        getrandom::getrandom(&mut bytes).map_err(std::io::Error::from)?;
+        Ok(Secret(bytes))
+    }
+
+    /// Reads the secret from `path`, generates and saves one if there is none
+    pub fn load_or_create<P: AsRef<Path>>(path: P) -> StorageResult<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            let hex = fs::read_to_string(path)?;
+            return Self::from_hex(hex.trim()).ok_or(StorageError::InvalidSecret);
+        }
+        let secret = Self::generate()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, to_hex(&secret.0))?;
+        Ok(secret)
+    }
+
+    pub fn from_bytes(bytes: [u8; SECRET_LEN]) -> Self {
+        Secret(bytes)
+    }
+
+    fn from_hex(hex: &str) -> Option<Self> {
+        if hex.len() != SECRET_LEN * 2 {
+            return None;
+        }
+        let mut bytes = [0; SECRET_LEN];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+        }
+        Some(Secret(bytes))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(..)")
+    }
+}
+
+/// Client identity unique per consuming sequence
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Pseudonym(String);
+
+impl Pseudonym {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Pseudonym {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Keeps identifying values of a sequence away from everything but the runtime.
+///
+/// Values of identity items are replaced by a pseudonym derived
+/// with a keyed hash, the same identity always gets the same pseudonym
+/// within a sequence and different ones across sequences.
+/// Which client an identity belongs to is kept by the `Store`.
+#[derive(Debug)]
+pub struct Identities<'s> {
+    secret: &'s Secret,
+    sequence: String,
+    fields: Vec<String>,
+}
+
+impl<'s> Identities<'s> {
+    pub fn new(secret: &'s Secret, sequence: &str, program: &Block) -> Self {
+        Self::with_fields(secret, sequence, identity_fields(program))
+    }
+
+    pub fn with_fields(secret: &'s Secret, sequence: &str, fields: Vec<String>) -> Self {
+        Self {
+            secret,
+            sequence: sequence.to_owned(),
+            fields,
+        }
+    }
+
+    pub fn sequence(&self) -> &str {
+        &self.sequence
+    }
+
+    /// Identity fields of the sequence
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn is_identifying(&self, field: &str) -> bool {
+        self.fields.iter().any(|f| f == field)
+    }
+
+    /// Pseudonym of a client known by another id, e.g. a session
+    pub fn client_pseudonym(&self, client: &str) -> Pseudonym {
+        let mut mac = self.mac(b"client");
+        update_prefixed(&mut mac, client.as_bytes());
+        Pseudonym(to_hex(&mac.finalize().into_bytes()))
+    }
+
+    pub fn pseudonym(&self, values: &BTreeMap<String, Value>) -> StorageResult<Pseudonym> {
+        let mut mac = self.mac(b"identity");
+        for field in self.fields.iter() {
+            let value = values
+                .get(field)
+                .filter(|value| !value.is_null())
+                .ok_or_else(|| StorageError::MissingIdentity(field.clone()))?;
+            update_prefixed(&mut mac, field.as_bytes());
+            update_prefixed(&mut mac, serde_json::to_string(value)?.as_bytes());
+        }
+        Ok(Pseudonym(to_hex(&mac.finalize().into_bytes())))
+    }
+
+    /// Keyed hash of the sequence in the `domain` of a kind of pseudonyms,
+    /// every input is length prefixed so different inputs can't run together
+    fn mac(&self, domain: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret.0).expect("any key length is accepted");
+        update_prefixed(&mut mac, domain);
+        update_prefixed(&mut mac, self.sequence.as_bytes());
+        mac
+    }
+
+    /// Values without identity fields, that's all rules,
+    /// templates and exports are given
+    pub fn strip(&self, values: &BTreeMap<String, Value>) -> BTreeMap<String, Value> {
+        values
+            .iter()
+            .filter(|(field, _)| !self.is_identifying(field))
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// Identity of a sequence a client has taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claim {
+    pub pseudonym: Pseudonym,
+    pub client: String,
+}
+
+fn update_prefixed(mac: &mut Hmac<Sha256>, bytes: &[u8]) {
+    mac.update(&(bytes.len() as u64).to_be_bytes());
+    mac.update(bytes);
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,33 @@
+#[macro_use]
+extern crate failure_derive;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate serde;
+
+pub mod aggregate;
+pub mod bundle;
+pub mod epoch;
+pub mod errors;
+pub mod export;
+pub mod file;
+pub mod identity;
+pub mod locale;
+pub mod memory;
+pub mod sources;
+pub mod store;
+
+pub use aggregate::{includes, is_truthy, Aggregate, Order, Records};
+pub use bundle::{load_bundle, write_bundle};
+pub use epoch::{sequence_fields, structural_hash, Epoch};
+pub use errors::{StorageError, StorageResult};
+pub use export::{export, EpochExport, ExportOptions, ExportRow};
+pub use file::FileStore;
+pub use identity::{Claim, Identities, Pseudonym, Secret};
+pub use locale::{parse_answers, NumberLocale};
+pub use memory::MemoryStore;
+pub use sources::{live_options, options_references, resolve, EpochSelector, SequenceReference};
+pub use store::{Dataset, Row, Store, Submission, Update};
+
+#[cfg(test)]
+mod tests;
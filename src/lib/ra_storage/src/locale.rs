@@ -0,0 +1,122 @@
+use super::errors::{StorageError, StorageResult};
+use ra_analysis::bundle::CompiledSequence;
+use ra_analysis::locale::number_separators;
+use ra_analysis::types::InputType;
+use serde_json::{Number, Value};
+use std::collections::BTreeMap;
+
+/// Locale of sequences declaring none
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// How numbers are written in a locale, `1,234.5` in `en` and `1.234,5` in `de`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NumberLocale {
+    /// Separator of the fractional part
+    pub decimal: char,
+    /// Separators of thousands, any of them may be used
+    pub grouping: Vec<char>,
+}
+
+impl NumberLocale {
+    /// Locale of a language tag like `de` or `de-CH`, `None` when it's not known
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let (decimal, grouping) = number_separators(tag)?;
+        Some(Self {
+            decimal,
+            grouping: grouping.to_vec(),
+        })
+    }
+
+    /// Locale a sequence declares with `#locale`
+    pub fn of_sequence(sequence: &CompiledSequence) -> StorageResult<Self> {
+        let tag = sequence.locale.as_deref().unwrap_or(DEFAULT_LOCALE);
+        Self::from_tag(tag).ok_or_else(|| StorageError::UnknownLocale(tag.to_owned()))
+    }
+
+    /// Number a client typed, e.g. `-1 234,5` in `fr`.
+    ///
+    /// Thousands are grouped by three digits, so `1.5` isn't a number in `de`
+    /// rather than being read as fifteen.
+    pub fn parse(&self, text: &str) -> Option<Value> {
+        let text = text.trim();
+        let (negative, text) = match text.chars().next()? {
+            '-' | '−' => (true, &text[text.chars().next()?.len_utf8()..]),
+            '+' => (false, &text[1..]),
+            _ => (false, text),
+        };
+        let (whole, fraction) = match text.split_once(self.decimal) {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (text, None),
+        };
+
+        let groups: Vec<&str> = whole.split(|ch| self.grouping.contains(&ch)).collect();
+        let grouped = groups.iter().enumerate().all(|(index, group)| {
+            let digits = group.chars().all(|ch| ch.is_ascii_digit());
+            match index {
+                0 if groups.len() > 1 => digits && (1..=3).contains(&group.len()),
+                0 => digits,
+                _ => digits && group.len() == 3,
+            }
+        });
+        let fraction_digits =
+            fraction.is_none_or(|fraction| fraction.chars().all(|ch| ch.is_ascii_digit()));
+        if !grouped || !fraction_digits || (whole.is_empty() && fraction.is_none_or(str::is_empty))
+        {
+            return None;
+        }
+
+        let sign = if negative { "-" } else { "" };
+        let whole = groups.concat();
+        match fraction {
+            None => format!("{}{}", sign, whole)
+                .parse::<i64>()
+                .ok()
+                .map(Value::from),
+            Some(fraction) => format!("{}{}.{}", sign, whole, fraction)
+                .parse::<f64>()
+                .ok()
+                .and_then(Number::from_f64)
+                .map(Value::Number),
+        }
+    }
+}
+
+/// Answers with numbers clients typed as text read in the locale of the sequence,
+/// other values are kept as they are and empty answers are none
+pub fn parse_answers(
+    sequence: &CompiledSequence,
+    values: BTreeMap<String, Value>,
+) -> StorageResult<BTreeMap<String, Value>> {
+    let locale = NumberLocale::of_sequence(sequence)?;
+    values
+        .into_iter()
+        .map(|(field, value)| {
+            let is_number = sequence.field(&field).is_some_and(|compiled| {
+                matches!(&compiled.input_type, InputType::Number)
+                    || matches!(&compiled.input_type, InputType::List(element) if **element == InputType::Number)
+            });
+            let value = if is_number {
+                parse_number(&locale, &field, value)?
+            } else {
+                value
+            };
+            Ok((field, value))
+        })
+        .collect()
+}
+
+fn parse_number(locale: &NumberLocale, field: &str, value: Value) -> StorageResult<Value> {
+    match value {
+        Value::String(text) if text.trim().is_empty() => Ok(Value::Null),
+        Value::String(text) => locale
+            .parse(&text)
+            .ok_or_else(|| StorageError::InvalidNumber(field.to_owned(), text)),
+        Value::Array(entries) => Ok(Value::Array(
+            entries
+                .into_iter()
+                .map(|entry| parse_number(locale, field, entry))
+                .collect::<StorageResult<_>>()?,
+        )),
+        value => Ok(value),
+    }
+}
@@ -0,0 +1,87 @@
+use super::epoch::Epoch;
+use super::errors::{StorageError, StorageResult};
+use super::identity::Pseudonym;
+use super::store::{Store, Submission, Update};
+use std::collections::HashMap;
+
+/// Store keeping everything in memory, e.g. for tests or previews
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    epochs: HashMap<String, Vec<Epoch>>,
+    submissions: HashMap<(String, u32), Vec<Submission>>,
+    updates: HashMap<(String, u32), Vec<Update>>,
+    claims: HashMap<(String, Pseudonym), String>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn epochs(&self, sequence: &str) -> StorageResult<Vec<Epoch>> {
+        Ok(self.epochs.get(sequence).cloned().unwrap_or_default())
+    }
+
+    fn add_epoch(&mut self, sequence: &str, epoch: Epoch) -> StorageResult<()> {
+        self.epochs
+            .entry(sequence.to_owned())
+            .or_default()
+            .push(epoch);
+        Ok(())
+    }
+
+    fn save(&mut self, submission: Submission) -> StorageResult<()> {
+        self.epoch(&submission.sequence, submission.epoch)?;
+        let submissions = self
+            .submissions
+            .entry((submission.sequence.clone(), submission.epoch))
+            .or_default();
+        submissions.retain(|saved| saved.client != submission.client);
+        submissions.push(submission);
+        Ok(())
+    }
+
+    fn submissions(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Submission>> {
+        if !self.epochs(sequence)?.iter().any(|e| e.number == epoch) {
+            return Err(StorageError::UnknownEpoch(sequence.to_owned(), epoch));
+        }
+        Ok(self
+            .submissions
+            .get(&(sequence.to_owned(), epoch))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn record(&mut self, update: Update) -> StorageResult<()> {
+        self.epoch(&update.sequence, update.epoch)?;
+        self.updates
+            .entry((update.sequence.clone(), update.epoch))
+            .or_default()
+            .push(update);
+        Ok(())
+    }
+
+    fn updates(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Update>> {
+        self.epoch(sequence, epoch)?;
+        Ok(self
+            .updates
+            .get(&(sequence.to_owned(), epoch))
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn claim(
+        &mut self,
+        sequence: &str,
+        pseudonym: &Pseudonym,
+        client: &str,
+    ) -> StorageResult<String> {
+        Ok(self
+            .claims
+            .entry((sequence.to_owned(), pseudonym.clone()))
+            .or_insert_with(|| client.to_owned())
+            .clone())
+    }
+}
@@ -0,0 +1,122 @@
+use super::errors::{StorageError, StorageResult};
+use super::store::Store;
+use ra_analysis::items::reference_path;
+use ra_analysis::types::arguments;
+use ra_parser::block::{Block, BlockKind};
+use serde_json::Value;
+
+/// Epoch collected values are read from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum EpochSelector {
+    Latest,
+    Pinned(u32),
+}
+
+/// Reference `@sequence.field` to values collected by another sequence
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SequenceReference {
+    pub sequence: String,
+    pub field: String,
+    pub epoch: EpochSelector,
+}
+
+impl SequenceReference {
+    /// First segment of the path names the sequence, the rest is the field
+    pub fn new(path: &[&str], epoch: EpochSelector) -> Option<Self> {
+        let (sequence, field) = path.split_first()?;
+        if field.is_empty() {
+            return None;
+        }
+        Some(Self {
+            sequence: (*sequence).to_owned(),
+            field: field.join("."),
+            epoch,
+        })
+    }
+}
+
+/// References among `!options` of an input,
+/// `!options:epoch=2` pins the epoch, latest one is used otherwise
+pub fn options_references(input: &Block) -> Vec<SequenceReference> {
+    input
+        .children
+        .iter()
+        .filter_map(|child| match &child.kind {
+            BlockKind::Invocation(Some(token), expression) if token.content == "options" => {
+                let epoch = expression
+                    .as_ref()
+                    .map(arguments)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .find(|argument| argument.name.as_deref() == Some("epoch"))
+                    .and_then(|argument| argument.value?.parse().ok())
+                    .map_or(EpochSelector::Latest, EpochSelector::Pinned);
+                Some((child, epoch))
+            }
+            _ => None,
+        })
+        .flat_map(|(options, epoch)| {
+            options
+                .children
+                .iter()
+                .filter_map(move |child| match &child.kind {
+                    BlockKind::Reference(Some(reference)) => {
+                        SequenceReference::new(&reference_path(reference), epoch)
+                    }
+                    _ => None,
+                })
+        })
+        .collect()
+}
+
+/// Distinct values collected for the referenced field, lists are flattened.
+///
+/// `None` when the sequence never collected such a field,
+/// the reference then points at a static declaration.
+pub fn resolve<S: Store + ?Sized>(
+    store: &S,
+    reference: &SequenceReference,
+) -> StorageResult<Option<Vec<Value>>> {
+    let epoch = match reference.epoch {
+        EpochSelector::Latest => match store.current_epoch(&reference.sequence)? {
+            Some(epoch) => epoch,
+            None => return Ok(None),
+        },
+        EpochSelector::Pinned(number) => store.epoch(&reference.sequence, number)?,
+    };
+    if epoch.identity.contains(&reference.field) {
+        return Err(StorageError::PrivateField(reference.field.clone()));
+    }
+    if !epoch.has_field(&reference.field) {
+        return Ok(None);
+    }
+
+    let mut values: Vec<Value> = Vec::new();
+    for submission in store.submissions(&reference.sequence, epoch.number)? {
+        let collected = match submission.values.get(&reference.field) {
+            Some(Value::Array(entries)) => entries.clone(),
+            Some(value) => vec![value.clone()],
+            None => continue,
+        };
+        for value in collected {
+            if !value.is_null() && !values.contains(&value) {
+                values.push(value);
+            }
+        }
+    }
+    Ok(Some(values))
+}
+
+/// Live options of an input built from other sequences,
+/// static references are left to the analysis
+pub fn live_options<S: Store + ?Sized>(store: &S, input: &Block) -> StorageResult<Vec<Value>> {
+    let mut options: Vec<Value> = Vec::new();
+    for reference in options_references(input) {
+        for value in resolve(store, &reference)?.unwrap_or_default() {
+            if !options.contains(&value) {
+                options.push(value);
+            }
+        }
+    }
+    Ok(options)
+}
@@ -0,0 +1,309 @@
+use super::aggregate::{math, Records};
+use super::epoch::Epoch;
+use super::errors::{StorageError, StorageResult};
+use super::identity::{Identities, Pseudonym};
+use super::locale::parse_answers;
+use ra_analysis::assignments::{assignments, AssignmentKind};
+use ra_analysis::bundle::CompiledSequence;
+use ra_parser::block::Block;
+use ra_parser::expressions::output_expression::MathOperation;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Values one client submitted to a sequence during an epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub sequence: String,
+    pub epoch: u32,
+    pub client: String,
+    pub values: BTreeMap<String, Value>,
+}
+
+/// Change a client made to an assigned item during an epoch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Update {
+    pub sequence: String,
+    pub epoch: u32,
+    pub client: String,
+    pub field: String,
+    pub kind: AssignmentKind,
+    pub value: Value,
+}
+
+impl Update {
+    /// Value of the field after the update
+    pub fn apply(&self, current: Option<&Value>) -> Value {
+        let operation = match self.kind {
+            AssignmentKind::Set => return self.value.clone(),
+            AssignmentKind::Add => MathOperation::Sum,
+            AssignmentKind::Subtract => MathOperation::Subtract,
+        };
+        let current = current.cloned().unwrap_or_else(|| Value::from(0));
+        match math(operation, &current, &self.value) {
+            Value::Null => current,
+            value => value,
+        }
+    }
+}
+
+/// Submissions across epochs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Dataset {
+    pub fields: Vec<String>,
+    /// Which of the fields existed in each epoch
+    pub mapping: BTreeMap<u32, Vec<String>>,
+    pub rows: Vec<Row>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Row {
+    pub epoch: u32,
+    pub client: String,
+    /// `None` when the field didn't exist in the epoch
+    /// or the client didn't fill it in
+    pub values: BTreeMap<String, Option<Value>>,
+}
+
+/// Storage of sequence epochs and submissions.
+///
+/// Backends implement the primitives,
+/// epoch detection and queries are shared.
+pub trait Store {
+    /// Epochs of a sequence, oldest first
+    fn epochs(&self, sequence: &str) -> StorageResult<Vec<Epoch>>;
+
+    fn add_epoch(&mut self, sequence: &str, epoch: Epoch) -> StorageResult<()>;
+
+    /// Saves a submission replacing the previous one
+    /// of the same client in the same epoch
+    fn save(&mut self, submission: Submission) -> StorageResult<()>;
+
+    fn submissions(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Submission>>;
+
+    /// Appends an update, updates are never replaced
+    fn record(&mut self, update: Update) -> StorageResult<()>;
+
+    /// Updates of an epoch in the order they were recorded
+    fn updates(&self, sequence: &str, epoch: u32) -> StorageResult<Vec<Update>>;
+
+    /// Gives an identity of a sequence to `client` unless another client has it,
+    /// returns the client it belongs to.
+    /// Claims hold across epochs and must be checked and taken at once.
+    fn claim(
+        &mut self,
+        sequence: &str,
+        pseudonym: &Pseudonym,
+        client: &str,
+    ) -> StorageResult<String>;
+
+    fn current_epoch(&self, sequence: &str) -> StorageResult<Option<Epoch>> {
+        Ok(self.epochs(sequence)?.pop())
+    }
+
+    fn epoch(&self, sequence: &str, number: u32) -> StorageResult<Epoch> {
+        self.epochs(sequence)?
+            .into_iter()
+            .find(|epoch| epoch.number == number)
+            .ok_or_else(|| StorageError::UnknownEpoch(sequence.to_owned(), number))
+    }
+
+    /// Returns current epoch of the sequence,
+    /// starts a new one if the structure has changed
+    fn update_sequence(
+        &mut self,
+        sequence: &str,
+        source: &str,
+        program: &Block,
+    ) -> StorageResult<Epoch> {
+        let current = self.current_epoch(sequence)?;
+        let number = current.as_ref().map_or(1, |epoch| epoch.number + 1);
+        let epoch = Epoch::new(number, source, program);
+        match current {
+            Some(current) if current.hash == epoch.hash => Ok(current),
+            _ => {
+                self.add_epoch(sequence, epoch.clone())?;
+                Ok(epoch)
+            }
+        }
+    }
+
+    /// Saves values of a client in the current epoch as they are
+    fn submit(
+        &mut self,
+        sequence: &str,
+        client: &str,
+        values: BTreeMap<String, Value>,
+    ) -> StorageResult<Submission> {
+        let epoch = self
+            .current_epoch(sequence)?
+            .ok_or_else(|| StorageError::UnknownEpoch(sequence.to_owned(), 1))?;
+        if let Some(field) = values.keys().find(|field| epoch.assignable.contains(field)) {
+            return Err(StorageError::ReadOnly(field.clone()));
+        }
+        let submission = Submission {
+            sequence: sequence.to_owned(),
+            epoch: epoch.number,
+            client: client.to_owned(),
+            values,
+        };
+        self.save(submission.clone())?;
+        Ok(submission)
+    }
+
+    /// Saves answers a client typed in the current epoch,
+    /// numbers are read in the locale of the compiled sequence
+    fn submit_answers(
+        &mut self,
+        sequence: &CompiledSequence,
+        client: &str,
+        values: BTreeMap<String, Value>,
+    ) -> StorageResult<Submission> {
+        let values = parse_answers(sequence, values)?;
+        self.submit(&sequence.name, client, values)
+    }
+
+    /// Saves values of a client under its pseudonym,
+    /// identifying values aren't stored.
+    ///
+    /// Identities are unique within a sequence, claiming one
+    /// which already belongs to another client fails.
+    fn submit_identified(
+        &mut self,
+        identities: &Identities,
+        client: &str,
+        values: &BTreeMap<String, Value>,
+    ) -> StorageResult<Submission> {
+        let pseudonym = identities.pseudonym(values)?;
+        if self.claim(identities.sequence(), &pseudonym, client)? != client {
+            return Err(StorageError::IdentityTaken(
+                identities.sequence().to_owned(),
+            ));
+        }
+        self.submit(
+            identities.sequence(),
+            pseudonym.as_str(),
+            identities.strip(values),
+        )
+    }
+
+    /// Values of assigned items with updates applied in order.
+    ///
+    /// Increments and decrements are recorded as deltas rather than results,
+    /// so concurrent ones of different clients add up instead of overwriting.
+    fn assigned(&self, sequence: &str, epoch: u32) -> StorageResult<BTreeMap<String, Value>> {
+        let mut values = BTreeMap::new();
+        for update in self.updates(sequence, epoch)? {
+            let value = update.apply(values.get(&update.field));
+            values.insert(update.field, value);
+        }
+        Ok(values)
+    }
+
+    /// Runs assignments `program` declares for `target` on behalf of a client,
+    /// values are evaluated against the client's submission and assigned items.
+    ///
+    /// Assigned items can't be submitted, so this is the only way they change.
+    fn assign(
+        &mut self,
+        sequence: &str,
+        program: &Block,
+        client: &str,
+        target: &str,
+    ) -> StorageResult<Value> {
+        let epoch = self
+            .current_epoch(sequence)?
+            .ok_or_else(|| StorageError::UnknownEpoch(sequence.to_owned(), 1))?;
+        let declared: Vec<_> = assignments(program)
+            .into_iter()
+            .filter(|assignment| assignment.target == target)
+            .collect();
+        if declared.is_empty() || !epoch.assignable.iter().any(|field| field == target) {
+            return Err(StorageError::UndeclaredAssignment(target.to_owned()));
+        }
+
+        for assignment in declared {
+            let mut values = self
+                .submission(sequence, epoch.number, client)?
+                .map(|submission| submission.values)
+                .unwrap_or_default();
+            values.extend(self.assigned(sequence, epoch.number)?);
+            let value = Records::client(values).member(assignment.value);
+            self.record(Update {
+                sequence: sequence.to_owned(),
+                epoch: epoch.number,
+                client: client.to_owned(),
+                field: target.to_owned(),
+                kind: assignment.kind,
+                value,
+            })?;
+        }
+        Ok(self
+            .assigned(sequence, epoch.number)?
+            .remove(target)
+            .unwrap_or(Value::Null))
+    }
+
+    fn submission(
+        &self,
+        sequence: &str,
+        epoch: u32,
+        client: &str,
+    ) -> StorageResult<Option<Submission>> {
+        Ok(self
+            .submissions(sequence, epoch)?
+            .into_iter()
+            .find(|submission| submission.client == client))
+    }
+
+    /// Reads `fields` from submissions of all epochs,
+    /// all fields ever existed when none given
+    fn query(&self, sequence: &str, fields: &[&str]) -> StorageResult<Dataset> {
+        let epochs = self.epochs(sequence)?;
+        let fields: Vec<String> = if fields.is_empty() {
+            let mut all: Vec<String> = Vec::new();
+            for field in epochs.iter().flat_map(|epoch| epoch.fields.iter()) {
+                if !all.contains(field) {
+                    all.push(field.clone());
+                }
+            }
+            all
+        } else {
+            fields.iter().map(|field| (*field).to_owned()).collect()
+        };
+
+        let mut mapping = BTreeMap::new();
+        let mut rows = Vec::new();
+        for epoch in epochs.iter() {
+            let existing: Vec<String> = fields
+                .iter()
+                .filter(|field| epoch.has_field(field))
+                .cloned()
+                .collect();
+            for submission in self.submissions(sequence, epoch.number)? {
+                let values = fields
+                    .iter()
+                    .map(|field| {
+                        let value = if existing.contains(field) {
+                            submission.values.get(field).cloned()
+                        } else {
+                            None
+                        };
+                        (field.clone(), value)
+                    })
+                    .collect();
+                rows.push(Row {
+                    epoch: epoch.number,
+                    client: submission.client,
+                    values,
+                });
+            }
+            mapping.insert(epoch.number, existing);
+        }
+
+        Ok(Dataset {
+            fields,
+            mapping,
+            rows,
+        })
+    }
+}
@@ -0,0 +1,1055 @@
+mod epoch {
+    use crate::epoch::{sequence_fields, structural_hash};
+    use ra_parser::parser::parse;
+
+    const SOURCE: &str = "recipe\n    name\n        >\n    servings\n        > :default=2\n";
+
+    fn hash(source: &str) -> String {
+        structural_hash(source, &parse(source).expect("can't parse"))
+    }
+
+    #[test]
+    fn it_should_ignore_whitespace_and_comments() {
+        let commented =
+            "recipe // what we cook\n    name\n        >\n\n    servings\n        > :default=2\n";
+        assert_eq!(hash(SOURCE), hash(commented));
+    }
+
+    #[test]
+    fn it_should_change_hash_with_structure() {
+        assert_ne!(hash(SOURCE), hash("recipe\n    name\n        >\n"));
+        assert_ne!(
+            hash(SOURCE),
+            hash("recipe\n    name\n        >\n    servings\n        > :default=4\n")
+        );
+    }
+
+    #[test]
+    fn it_should_list_fields_accepting_input() {
+        let program = parse(SOURCE).expect("can't parse");
+        assert_eq!(
+            sequence_fields(&program),
+            vec!["recipe.name", "recipe.servings"]
+        );
+    }
+}
+
+mod store {
+    use crate::{Epoch, FileStore, MemoryStore, StorageError, Store};
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+
+    const FIRST: &str = "name\n    >\nage\n    >\n";
+    const SECOND: &str = "name\n    >\nemail\n    >\n";
+
+    fn values(pairs: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), v.clone()))
+            .collect()
+    }
+
+    fn update(store: &mut dyn Store, source: &str) -> u32 {
+        let program = parse(source).expect("can't parse");
+        store
+            .update_sequence("survey", source, &program)
+            .expect("can't update")
+            .number
+    }
+
+    fn fill(store: &mut dyn Store) {
+        assert_eq!(update(store, FIRST), 1);
+        store
+            .submit(
+                "survey",
+                "a",
+                values(&[("name", json!("Ann")), ("age", json!(30))]),
+            )
+            .unwrap();
+        store
+            .submit("survey", "b", values(&[("name", json!("Bob"))]))
+            .unwrap();
+        store
+            .submit(
+                "survey",
+                "a",
+                values(&[("name", json!("Ann")), ("age", json!(31))]),
+            )
+            .unwrap();
+        assert_eq!(update(store, FIRST), 1);
+        assert_eq!(update(store, SECOND), 2);
+        store
+            .submit(
+                "survey",
+                "a",
+                values(&[("name", json!("Ann")), ("email", json!("a@b.c"))]),
+            )
+            .unwrap();
+    }
+
+    fn check(store: &dyn Store) {
+        let first = store.submissions("survey", 1).unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(
+            store.submission("survey", 1, "a").unwrap().unwrap().values["age"],
+            json!(31)
+        );
+
+        let dataset = store.query("survey", &[]).unwrap();
+        assert_eq!(dataset.fields, vec!["name", "age", "email"]);
+        assert_eq!(dataset.mapping[&1], vec!["name", "age"]);
+        assert_eq!(dataset.mapping[&2], vec!["name", "email"]);
+        assert_eq!(dataset.rows.len(), 3);
+        let last = dataset.rows.last().unwrap();
+        assert_eq!((last.epoch, last.client.as_str()), (2, "a"));
+        assert_eq!(last.values["age"], None);
+        assert_eq!(last.values["email"], Some(json!("a@b.c")));
+    }
+
+    fn temporary_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ra_storage_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn it_should_keep_epochs_in_memory() {
+        let mut store = MemoryStore::new();
+        fill(&mut store);
+        check(&store);
+    }
+
+    #[test]
+    fn it_should_persist_epochs_in_files() {
+        let dir = temporary_dir("persist");
+        {
+            let mut store = FileStore::open(&dir).unwrap();
+            fill(&mut store);
+        }
+        let store = FileStore::open(&dir).unwrap();
+        check(&store);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_keep_whole_lines_of_concurrent_writers() {
+        let dir = temporary_dir("concurrent");
+        update(&mut FileStore::open(&dir).unwrap(), FIRST);
+        let long = "x".repeat(1 << 16);
+        let writers: Vec<_> = (0..4)
+            .map(|writer| {
+                let (dir, long) = (dir.clone(), long.clone());
+                std::thread::spawn(move || {
+                    let mut store = FileStore::open(&dir).unwrap();
+                    for round in 0..20 {
+                        let name = format!("{}{}", long, round);
+                        store
+                            .submit(
+                                "survey",
+                                &writer.to_string(),
+                                values(&[("name", json!(name))]),
+                            )
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let submissions = FileStore::open(&dir)
+            .unwrap()
+            .submissions("survey", 1)
+            .unwrap();
+        assert_eq!(submissions.len(), 4);
+        let last = json!(format!("{}19", long));
+        assert!(submissions
+            .iter()
+            .all(|submission| submission.values["name"] == last));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_open_an_epoch_once_for_concurrent_stores() {
+        let dir = temporary_dir("concurrent_epochs");
+        let program = parse(FIRST).expect("can't parse");
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let (dir, epoch) = (dir.clone(), Epoch::new(1, FIRST, &program));
+                std::thread::spawn(move || {
+                    FileStore::open(&dir)
+                        .unwrap()
+                        .add_epoch("survey", epoch)
+                        .unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let mut store = FileStore::open(&dir).unwrap();
+        assert_eq!(store.epochs("survey").unwrap().len(), 1);
+        let other = parse(SECOND).expect("can't parse");
+        assert!(matches!(
+            store.add_epoch("survey", Epoch::new(1, SECOND, &other)),
+            Err(StorageError::EpochTaken(_, 1))
+        ));
+        assert_eq!(update(&mut store, SECOND), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_migrate_epochs_stored_without_identity_fields() {
+        const IDENTIFIED: &str = "contact\n    !identity\n    email\n        >\nage\n    >\n";
+        let dir = temporary_dir("migrate");
+        let mut store = FileStore::open(&dir).unwrap();
+        let epochs: Vec<Value> = [(1, SECOND), (2, IDENTIFIED)]
+            .iter()
+            .map(|(number, source)| {
+                let program = parse(source).expect("can't parse");
+                let mut epoch =
+                    serde_json::to_value(Epoch::new(*number, source, &program)).unwrap();
+                let stored = epoch.as_object_mut().unwrap();
+                stored.remove("identity");
+                stored.remove("assignable");
+                epoch
+            })
+            .collect();
+        std::fs::create_dir_all(dir.join("survey")).unwrap();
+        std::fs::write(
+            dir.join("survey").join("epochs.json"),
+            Value::from(epochs).to_string(),
+        )
+        .unwrap();
+        assert!(matches!(
+            store.epochs("survey"),
+            Err(StorageError::UnmigratedEpochs(_))
+        ));
+
+        let program = parse(IDENTIFIED).expect("can't parse");
+        store
+            .migrate_epochs("survey", IDENTIFIED, &program)
+            .unwrap();
+        let epochs = store.epochs("survey").unwrap();
+        assert_eq!(epochs[0].identity, vec!["name", "email"]);
+        assert_eq!(epochs[1].identity, vec!["contact.email"]);
+        assert!(epochs.iter().all(|epoch| epoch.assignable.is_empty()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_reject_unknown_epochs_and_invalid_names() {
+        let dir = temporary_dir("reject");
+        let mut store = FileStore::open(&dir).unwrap();
+        assert!(matches!(
+            store.submit("survey", "a", BTreeMap::new()),
+            Err(StorageError::UnknownEpoch(..))
+        ));
+        assert!(matches!(
+            store.epochs("../survey"),
+            Err(StorageError::InvalidSequenceName(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+mod identity {
+    use crate::{FileStore, Identities, MemoryStore, Secret, StorageError, Store};
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    const SOURCE: &str = "contact\n    !identity\n    email\n        >\nage\n    >\n";
+
+    fn values(email: &str, age: i64) -> BTreeMap<String, Value> {
+        let mut values = BTreeMap::new();
+        values.insert("contact.email".to_owned(), json!(email));
+        values.insert("age".to_owned(), json!(age));
+        values
+    }
+
+    #[test]
+    fn it_should_derive_stable_pseudonyms_per_sequence() {
+        let secret = Secret::from_bytes([7; 32]);
+        let program = parse(SOURCE).expect("can't parse");
+        let survey = Identities::new(&secret, "survey", &program);
+        let other = Identities::new(&secret, "other", &program);
+
+        let ann = survey.pseudonym(&values("ann@example.com", 30)).unwrap();
+        assert_eq!(
+            ann,
+            survey.pseudonym(&values("ann@example.com", 31)).unwrap()
+        );
+        assert_ne!(
+            ann,
+            survey.pseudonym(&values("bob@example.com", 30)).unwrap()
+        );
+        assert_ne!(
+            ann,
+            other.pseudonym(&values("ann@example.com", 30)).unwrap()
+        );
+        assert_ne!(
+            ann,
+            Identities::new(&Secret::from_bytes([8; 32]), "survey", &program)
+                .pseudonym(&values("ann@example.com", 30))
+                .unwrap()
+        );
+        assert!(matches!(
+            survey.pseudonym(&BTreeMap::new()),
+            Err(StorageError::MissingIdentity(_))
+        ));
+    }
+
+    #[test]
+    fn it_should_keep_pseudonyms_of_clients_and_identities_apart() {
+        let secret = Secret::from_bytes([7; 32]);
+        let mut identity = BTreeMap::new();
+        identity.insert("a".to_owned(), json!("x"));
+        let pseudonym = Identities::with_fields(&secret, "s\u{1}", vec!["a".to_owned()])
+            .pseudonym(&identity)
+            .unwrap();
+        let client =
+            Identities::with_fields(&secret, "s", Vec::new()).client_pseudonym("\0a\0\"x\"");
+        assert_ne!(pseudonym, client);
+        assert_ne!(
+            Identities::with_fields(&secret, "s", Vec::new()).client_pseudonym("\u{1}a"),
+            Identities::with_fields(&secret, "s\u{1}", Vec::new()).client_pseudonym("a")
+        );
+    }
+
+    #[test]
+    fn it_should_store_submissions_without_identifying_values() {
+        let secret = Secret::from_bytes([7; 32]);
+        let program = parse(SOURCE).expect("can't parse");
+        let identities = Identities::new(&secret, "survey", &program);
+        let mut store = MemoryStore::new();
+        store.update_sequence("survey", SOURCE, &program).unwrap();
+
+        let submission = store
+            .submit_identified(&identities, "session-1", &values("ann@example.com", 30))
+            .unwrap();
+        assert_eq!(submission.values.keys().collect::<Vec<_>>(), vec!["age"]);
+        let stored = serde_json::to_string(&store.submissions("survey", 1).unwrap()).unwrap();
+        assert!(!stored.contains("ann@example.com"));
+
+        assert!(matches!(
+            store.submit_identified(&identities, "session-2", &values("ann@example.com", 40)),
+            Err(StorageError::IdentityTaken(_))
+        ));
+        store
+            .submit_identified(&identities, "session-1", &values("ann@example.com", 31))
+            .unwrap();
+        assert_eq!(store.submissions("survey", 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn it_should_keep_claims_in_the_store() {
+        let dir = std::env::temp_dir().join(format!("ra_storage_claims_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let secret = Secret::from_bytes([7; 32]);
+        let program = parse(SOURCE).expect("can't parse");
+        let mut first = FileStore::open(&dir).unwrap();
+        first.update_sequence("survey", SOURCE, &program).unwrap();
+        first
+            .submit_identified(
+                &Identities::new(&secret, "survey", &program),
+                "session-1",
+                &values("ann@example.com", 30),
+            )
+            .unwrap();
+
+        let mut second = FileStore::open(&dir).unwrap();
+        let identities = Identities::new(&secret, "survey", &program);
+        assert!(matches!(
+            second.submit_identified(&identities, "session-2", &values("ann@example.com", 40)),
+            Err(StorageError::IdentityTaken(_))
+        ));
+        second
+            .submit_identified(&identities, "session-1", &values("ann@example.com", 31))
+            .unwrap();
+        second
+            .submit_identified(&identities, "session-2", &values("bob@example.com", 40))
+            .unwrap();
+        assert_eq!(second.submissions("survey", 1).unwrap().len(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_keep_the_secret() {
+        let path = std::env::temp_dir().join(format!("ra_storage_secret_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let program = parse(SOURCE).expect("can't parse");
+        let first = Secret::load_or_create(&path).unwrap();
+        let second = Secret::load_or_create(&path).unwrap();
+        let identity = values("ann@example.com", 30);
+        assert_eq!(
+            Identities::new(&first, "survey", &program)
+                .pseudonym(&identity)
+                .unwrap(),
+            Identities::new(&second, "survey", &program)
+                .pseudonym(&identity)
+                .unwrap()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
+
+mod export {
+    use crate::{export, ExportOptions, Identities, MemoryStore, Secret, Store};
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    const SOURCE: &str = "email\n    >\n        !identity\ncity\n    >\nage\n    >\n";
+
+    fn filled() -> (MemoryStore, Secret) {
+        let secret = Secret::from_bytes([3; 32]);
+        let program = parse(SOURCE).expect("can't parse");
+        let mut store = MemoryStore::new();
+        store.update_sequence("survey", SOURCE, &program).unwrap();
+        let identities = Identities::new(&secret, "survey", &program);
+        for (index, (email, city, age)) in [
+            ("a@x.org", "Oslo", 30),
+            ("b@x.org", "Oslo", 30),
+            ("c@x.org", "Rome", 41),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let values: BTreeMap<String, Value> = vec![
+                ("email".to_owned(), json!(email)),
+                ("city".to_owned(), json!(city)),
+                ("age".to_owned(), json!(age)),
+            ]
+            .into_iter()
+            .collect();
+            store
+                .submit_identified(&identities, &index.to_string(), &values)
+                .unwrap();
+        }
+        // submitted without identity handling, raw client id and email
+        let mut raw = BTreeMap::new();
+        raw.insert("email".to_owned(), json!("d@x.org"));
+        raw.insert("city".to_owned(), json!("Rome"));
+        store.submit("survey", "session-42", raw).unwrap();
+        (store, secret)
+    }
+
+    #[test]
+    fn it_should_export_json_lines_without_identities() {
+        let (store, secret) = filled();
+        let exports = export(&store, "survey", &secret, &ExportOptions::default()).unwrap();
+        assert_eq!(exports.len(), 1);
+        assert_eq!(exports[0].fields, vec!["city", "age"]);
+
+        let mut output = Vec::new();
+        exports[0].write_json_lines(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 4);
+        assert!(!output.contains("@x.org"));
+        assert!(!output.contains("session-42"));
+        let last: Value = serde_json::from_str(output.lines().last().unwrap()).unwrap();
+        assert_eq!(last["city"], json!("Rome"));
+        assert_eq!(last["age"], Value::Null);
+        assert_eq!(last["client"].as_str().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn it_should_suppress_small_groups() {
+        let (store, secret) = filled();
+        let options = ExportOptions {
+            k_anonymity: Some(2),
+            quasi_identifiers: vec!["city".to_owned()],
+        };
+        let exports = export(&store, "survey", &secret, &options).unwrap();
+        let cities: Vec<&Value> = exports[0]
+            .rows
+            .iter()
+            .map(|row| &row.values["city"])
+            .collect();
+        assert_eq!(
+            cities,
+            vec![
+                &json!("Oslo"),
+                &json!("Oslo"),
+                &json!("Rome"),
+                &json!("Rome")
+            ]
+        );
+
+        let options = ExportOptions {
+            k_anonymity: Some(2),
+            quasi_identifiers: vec![],
+        };
+        let exports = export(&store, "survey", &secret, &options).unwrap();
+        let ages: Vec<&Value> = exports[0]
+            .rows
+            .iter()
+            .map(|row| &row.values["age"])
+            .collect();
+        assert_eq!(
+            ages,
+            vec![&json!(30), &json!(30), &Value::Null, &Value::Null]
+        );
+    }
+
+    #[test]
+    fn it_should_export_csv() {
+        let (store, secret) = filled();
+        let exports = export(&store, "survey", &secret, &ExportOptions::default()).unwrap();
+        let mut output = Vec::new();
+        exports[0].write_csv(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "epoch,client,city,age");
+        assert!(lines[1].starts_with("1,") && lines[1].ends_with(",Oslo,30"));
+        assert!(lines[4].ends_with(",Rome,"));
+    }
+}
+
+mod sources {
+    use crate::{
+        live_options, options_references, resolve, EpochSelector, MemoryStore, SequenceReference,
+        StorageError, Store,
+    };
+    use ra_analysis::items::find_item;
+    use ra_analysis::types::item_input;
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    const CATALOGUE: &str = "email\n    >\n        !identity\ncountry\n    >\ntags\n    +>\n";
+    const CATALOGUE_V2: &str = "email\n    >\n        !identity\ncountry\n    >\n";
+    const RECIPE: &str = "cuisine\n    >\n        !options:\n            @catalogue.country\n            @catalogue.tags\n            @resources.countries\n        !options:epoch=1\n            @catalogue.country\n";
+
+    fn submit(store: &mut MemoryStore, client: &str, pairs: &[(&str, Value)]) {
+        let values: BTreeMap<String, Value> = pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), v.clone()))
+            .collect();
+        store.submit("catalogue", client, values).unwrap();
+    }
+
+    fn filled() -> MemoryStore {
+        let mut store = MemoryStore::new();
+        let program = parse(CATALOGUE).expect("can't parse");
+        store
+            .update_sequence("catalogue", CATALOGUE, &program)
+            .unwrap();
+        submit(
+            &mut store,
+            "a",
+            &[
+                ("country", json!("Italy")),
+                ("tags", json!(["pasta", "quick"])),
+            ],
+        );
+        submit(
+            &mut store,
+            "b",
+            &[("country", json!("Japan")), ("tags", json!(["quick"]))],
+        );
+        submit(&mut store, "c", &[("country", json!("Italy"))]);
+        let program = parse(CATALOGUE_V2).expect("can't parse");
+        store
+            .update_sequence("catalogue", CATALOGUE_V2, &program)
+            .unwrap();
+        submit(&mut store, "d", &[("country", json!("Peru"))]);
+        store
+    }
+
+    #[test]
+    fn it_should_find_references_among_options() {
+        let program = parse(RECIPE).expect("can't parse");
+        let input = item_input(find_item(&program, &["cuisine"]).unwrap()).unwrap();
+        let references = options_references(input);
+        assert_eq!(references.len(), 4);
+        assert_eq!(
+            references[3],
+            SequenceReference {
+                sequence: "catalogue".to_owned(),
+                field: "country".to_owned(),
+                epoch: EpochSelector::Pinned(1),
+            }
+        );
+    }
+
+    #[test]
+    fn it_should_resolve_latest_and_pinned_epochs() {
+        let store = filled();
+        let latest =
+            SequenceReference::new(&["catalogue", "country"], EpochSelector::Latest).unwrap();
+        assert_eq!(resolve(&store, &latest).unwrap(), Some(vec![json!("Peru")]));
+        let pinned =
+            SequenceReference::new(&["catalogue", "tags"], EpochSelector::Pinned(1)).unwrap();
+        assert_eq!(
+            resolve(&store, &pinned).unwrap(),
+            Some(vec![json!("pasta"), json!("quick")])
+        );
+        let removed =
+            SequenceReference::new(&["catalogue", "tags"], EpochSelector::Latest).unwrap();
+        assert_eq!(resolve(&store, &removed).unwrap(), None);
+        let identity =
+            SequenceReference::new(&["catalogue", "email"], EpochSelector::Latest).unwrap();
+        assert!(matches!(
+            resolve(&store, &identity),
+            Err(StorageError::PrivateField(_))
+        ));
+    }
+
+    #[test]
+    fn it_should_build_live_options() {
+        let store = filled();
+        let program = parse(RECIPE).expect("can't parse");
+        let input = item_input(find_item(&program, &["cuisine"]).unwrap()).unwrap();
+        assert_eq!(
+            live_options(&store, input).unwrap(),
+            vec![json!("Peru"), json!("Italy"), json!("Japan")]
+        );
+    }
+}
+
+mod aggregate {
+    use crate::{includes, Aggregate, MemoryStore, Order, Records, Store};
+    use ra_parser::block::BlockKind;
+    use ra_parser::expressions::input_expression::{InputExpression, ValueType};
+    use ra_parser::expressions::output_expression::OutputExpression;
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    fn record(value: Value) -> BTreeMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn recipes() -> Records {
+        Records::new(vec![
+            record(
+                json!({"recipe.name": "Pasta", "recipe.rating": 12, "recipe.votesCount": 4, "recipe.tags": ["quick", "italian"]}),
+            ),
+            record(
+                json!({"recipe.name": "Ramen", "recipe.rating": 10, "recipe.votesCount": 2, "recipe.tags": ["japanese"]}),
+            ),
+            record(json!({"recipe.name": "Salad", "recipe.tags": ["quick"]})),
+        ])
+    }
+
+    /// Condition of the first `!if` in the source
+    fn condition(source: &str) -> OutputExpression<'_> {
+        let program = parse(source).expect("can't parse");
+        let invocation = &program.children[0].children[0];
+        match &invocation.kind {
+            BlockKind::Invocation(
+                _,
+                Some(InputExpression {
+                    value: Some(value), ..
+                }),
+            ) => match value {
+                ValueType::OutputExpression(expression) => expression.clone(),
+                ValueType::ReferenceExpression(reference) => reference.clone().into(),
+                value => panic!("not a condition {:?}", value),
+            },
+            kind => panic!("not a condition {:?}", kind),
+        }
+    }
+
+    fn names(records: &Records) -> Vec<&Value> {
+        records
+            .records()
+            .iter()
+            .map(|record| &record["recipe.name"])
+            .collect()
+    }
+
+    #[test]
+    fn it_should_aggregate_values() {
+        let values = vec![json!(1), json!(4), json!(4), json!("x")];
+        assert_eq!(Aggregate::Count.apply(&values), json!(4));
+        assert_eq!(Aggregate::Sum.apply(&values), json!(9.0));
+        assert_eq!(Aggregate::Avg.apply(&values), json!(3.0));
+        assert_eq!(Aggregate::Min.apply(&values), json!(1.0));
+        assert_eq!(Aggregate::Max.apply(&values), json!(4.0));
+        assert_eq!(Aggregate::Distinct.apply(&values), json!([1, 4, "x"]));
+        assert_eq!(Aggregate::Avg.apply(&[]), Value::Null);
+    }
+
+    #[test]
+    fn it_should_aggregate_lists_and_clients() {
+        let records = recipes();
+        assert_eq!(records.get(&["recipe", "tags", "count"]), json!(4));
+        assert_eq!(
+            records.get(&["recipe", "tags", "distinct"]),
+            json!(["quick", "italian", "japanese"])
+        );
+        assert_eq!(records.get(&["recipe", "rating", "avg"]), json!(11.0));
+        assert_eq!(records.get(&["recipe", "rating"]), json!([12, 10]));
+        let single = Records::client(records.records()[0].clone());
+        assert_eq!(single.get(&["recipe", "tags", "count"]), json!(2));
+        assert_eq!(single.get(&["recipe", "name"]), json!("Pasta"));
+    }
+
+    #[test]
+    fn it_should_evaluate_conditions() {
+        let records = Records::client(record(json!({"my_initial_block": [1, 2, 3, 4]})));
+        let expression = condition("b\n    !if:my_initial_block.count > 3\n        x\n");
+        assert_eq!(records.evaluate(&expression), json!(true));
+        let expression = condition("b\n    !if:my_initial_block.count - 3\n        x\n");
+        assert_eq!(records.evaluate(&expression), json!(1));
+    }
+
+    #[test]
+    fn it_should_evaluate_by_precedence() {
+        let records = Records::client(record(json!({"a": 2, "b": 3})));
+        let expression = condition("x\n    !if:1 + a * b\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!(7));
+        let expression = condition("x\n    !if:(1 + a) * -b\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!(-9));
+        let expression = condition("x\n    !if:a > 1 & !(b < 2)\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!(true));
+    }
+
+    #[test]
+    fn it_should_evaluate_literals() {
+        let records = Records::client(record(json!({"a": 2})));
+        let expression = condition("x\n    !if:[a, true, none, \"b\"]\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!([2, true, null, "b"]));
+        let expression = condition("x\n    !if:{a: a + 1, \"b c\": [false]}\n        y\n");
+        assert_eq!(
+            records.evaluate(&expression),
+            json!({"a": 3, "b c": [false]})
+        );
+        let expression = condition("x\n    !if:a != none\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!(true));
+    }
+
+    #[test]
+    fn it_should_evaluate_strings_by_their_value() {
+        let records = Records::client(record(json!({})));
+        let expression =
+            condition("x\n    !if:[\"say \\\"hi\\\"\\n\", r\"C:\\docs\"]\n        y\n");
+        assert_eq!(
+            records.evaluate(&expression),
+            json!(["say \"hi\"\n", "C:\\docs"])
+        );
+        let expression = condition("x\n    !if:{\"a\\tb\": 1}\n        y\n");
+        assert_eq!(records.evaluate(&expression), json!({"a\tb": 1}));
+    }
+
+    #[test]
+    fn it_should_evaluate_indices() {
+        let records = Records::client(record(json!({
+            "recipe.pictures": ["a.png", "b.png", "c.png"],
+            "steps": [{"picture": "d.png"}],
+            "i": 1
+        })));
+        let evaluate = |source: &str| {
+            let source = format!("x\n    !if:{}\n        y\n", source);
+            records.evaluate(&condition(&source))
+        };
+        assert_eq!(evaluate("recipe.pictures.1"), json!("b.png"));
+        assert_eq!(evaluate("recipe.pictures.-1"), json!("c.png"));
+        assert_eq!(evaluate("recipe.pictures[i + 1]"), json!("c.png"));
+        assert_eq!(evaluate("recipe.pictures[-3]"), json!("a.png"));
+        assert_eq!(evaluate("steps.0.picture"), json!("d.png"));
+        assert_eq!(evaluate("steps[0][\"picture\"]"), json!("d.png"));
+        assert_eq!(evaluate("recipe.pictures.3"), json!(null));
+        assert_eq!(evaluate("recipe.pictures.-4"), json!(null));
+        assert_eq!(evaluate("steps.1?.picture"), json!(null));
+        assert_eq!(evaluate("i?[0]"), json!(null));
+    }
+
+    #[test]
+    fn it_should_sort_and_filter_records() {
+        let records = recipes();
+        let key = condition("b\n    !if:recipe.rating\n        x\n");
+        assert_eq!(
+            names(&records.sort(&key, Order::Desc)),
+            vec!["Pasta", "Ramen", "Salad"]
+        );
+        assert_eq!(
+            names(&records.sort(&key, Order::Asc)),
+            vec!["Ramen", "Pasta", "Salad"]
+        );
+        let predicate = condition("b\n    !if:recipe.rating > 10\n        x\n");
+        assert_eq!(names(&records.filter(&predicate)), vec!["Pasta"]);
+    }
+
+    #[test]
+    fn it_should_leave_records_without_a_value_out_of_comparisons() {
+        let records = recipes();
+        let predicate = condition("b\n    !if:recipe.rating < 11\n        x\n");
+        assert_eq!(names(&records.filter(&predicate)), vec!["Ramen"]);
+        let predicate = condition("b\n    !if:recipe.rating >= 10\n        x\n");
+        assert_eq!(names(&records.filter(&predicate)), vec!["Pasta", "Ramen"]);
+        let unrated = Records::new(vec![
+            record(json!({"recipe.name": "Salad"})),
+            record(json!({"recipe.name": "Pasta", "recipe.rating": 12})),
+            record(json!({"recipe.name": "Soup", "recipe.rating": "good"})),
+        ]);
+        let key = condition("b\n    !if:recipe.rating\n        x\n");
+        for order in [Order::Asc, Order::Desc] {
+            assert_eq!(names(&unrated.sort(&key, order))[2], "Salad");
+        }
+    }
+
+    #[test]
+    fn it_should_check_inclusion() {
+        assert!(includes(&json!("Pasta al pomodoro"), &json!("pasta")));
+        assert!(includes(&json!(["quick", "italian"]), &json!("quick")));
+        assert!(includes(
+            &json!(["quick", "italian"]),
+            &json!(["italian", "quick"])
+        ));
+        assert!(!includes(&json!(["quick"]), &json!(["italian", "quick"])));
+        assert!(includes(&json!(["quick"]), &Value::Null));
+    }
+
+    #[test]
+    fn it_should_query_collected_data() {
+        let source = "tags\n    +>\n";
+        let mut store = MemoryStore::new();
+        store
+            .update_sequence("tags", source, &parse(source).expect("can't parse"))
+            .unwrap();
+        for (client, tags) in [("a", json!(["x", "y"])), ("b", json!(["y"]))].iter() {
+            let values = record(json!({ "tags": tags }));
+            store.submit("tags", client, values).unwrap();
+        }
+        let records = Records::query(&store, "tags", 1).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records.get(&["tags", "count"]), json!(3));
+        assert_eq!(records.get(&["tags", "total"]), Value::Null);
+    }
+}
+
+mod assignments {
+    use crate::{FileStore, MemoryStore, StorageError, Store};
+    use ra_parser::parser::parse;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+    use std::env;
+    use std::fs;
+
+    const SOURCE: &str = "rating\n    >\nvotesCount\n    += 1\nlastRating\n    = rating\n";
+
+    fn values(value: Value) -> BTreeMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn vote<S: Store>(store: &mut S) {
+        let program = parse(SOURCE).expect("can't parse");
+        store.update_sequence("recipe", SOURCE, &program).unwrap();
+        store
+            .submit("recipe", "a", values(json!({"rating": 4})))
+            .unwrap();
+        store
+            .submit("recipe", "b", values(json!({"rating": 2})))
+            .unwrap();
+        assert_eq!(
+            store.assign("recipe", &program, "a", "votesCount").unwrap(),
+            json!(1)
+        );
+        assert_eq!(
+            store.assign("recipe", &program, "b", "votesCount").unwrap(),
+            json!(2)
+        );
+        assert_eq!(
+            store.assign("recipe", &program, "b", "lastRating").unwrap(),
+            json!(2)
+        );
+        assert_eq!(
+            store.assigned("recipe", 1).unwrap(),
+            values(json!({"votesCount": 2, "lastRating": 2}))
+        );
+    }
+
+    #[test]
+    fn it_should_merge_increments_of_clients() {
+        vote(&mut MemoryStore::new());
+        let dir = env::temp_dir().join(format!("ra_storage_assignments_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        vote(&mut FileStore::open(&dir).unwrap());
+        let reopened = FileStore::open(&dir).unwrap();
+        assert_eq!(
+            reopened.assigned("recipe", 1).unwrap()["votesCount"],
+            json!(2)
+        );
+        assert_eq!(reopened.updates("recipe", 1).unwrap().len(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn it_should_change_outputs_only_through_assignments() {
+        let mut store = MemoryStore::new();
+        let program = parse(SOURCE).expect("can't parse");
+        store.update_sequence("recipe", SOURCE, &program).unwrap();
+        assert!(matches!(
+            store.submit("recipe", "a", values(json!({"votesCount": 100}))),
+            Err(StorageError::ReadOnly(_))
+        ));
+        assert!(matches!(
+            store.assign("recipe", &program, "a", "rating"),
+            Err(StorageError::UndeclaredAssignment(_))
+        ));
+        assert!(store.assigned("recipe", 1).unwrap().is_empty());
+    }
+}
+
+mod locale {
+    use crate::{parse_answers, MemoryStore, NumberLocale, StorageError, Store};
+    use ra_analysis::bundle::compile;
+    use ra_parser::parser;
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    fn parse(tag: &str, text: &str) -> Option<Value> {
+        NumberLocale::from_tag(tag).unwrap().parse(text)
+    }
+
+    #[test]
+    fn it_should_parse_numbers_by_locale() {
+        assert_eq!(parse("en", "1,234.5"), Some(json!(1234.5)));
+        assert_eq!(parse("de", "1.234,5"), Some(json!(1234.5)));
+        assert_eq!(parse("fr", "-1\u{202f}234,5"), Some(json!(-1234.5)));
+        assert_eq!(parse("de-CH", "1'234.5"), Some(json!(1234.5)));
+        assert_eq!(parse("en_US", " 1500 "), Some(json!(1500)));
+        assert_eq!(parse("de", "0,5"), Some(json!(0.5)));
+    }
+
+    #[test]
+    fn it_should_refuse_ambiguous_numbers() {
+        for (tag, text) in &[
+            ("en", "1,5"),
+            ("de", "1.5"),
+            ("de", "1,5,0"),
+            ("en", "1,2345"),
+            ("en", "1.5.0"),
+            ("en", "1e3"),
+            ("en", "-"),
+            ("en", "twelve"),
+        ] {
+            assert_eq!(parse(tag, text), None, "{} in {}", text, tag);
+        }
+        assert_eq!(NumberLocale::from_tag("tlh"), None);
+    }
+
+    #[test]
+    fn it_should_parse_answers_in_sequence_locale() {
+        let source = "#locale:de\nweight\n    >\n        !number\nsizes\n    +>\n        !number\nname\n    >\n        !text\n";
+        let bundle = compile(&[("recipe.ra", source)]).unwrap();
+        let sequence = bundle.sequence("recipe").unwrap();
+        let values: BTreeMap<String, Value> = vec![
+            ("weight".to_owned(), json!("1.250,5")),
+            ("sizes".to_owned(), json!(["1", "2,5", 3])),
+            ("name".to_owned(), json!("1,5")),
+        ]
+        .into_iter()
+        .collect();
+        let parsed = parse_answers(sequence, values).unwrap();
+        assert_eq!(parsed["weight"], json!(1250.5));
+        assert_eq!(parsed["sizes"], json!([1, 2.5, 3]));
+        assert_eq!(parsed["name"], json!("1,5"));
+
+        let values = vec![("weight".to_owned(), json!("1.5"))]
+            .into_iter()
+            .collect();
+        assert!(matches!(
+            parse_answers(sequence, values),
+            Err(StorageError::InvalidNumber(..))
+        ));
+        let mut unknown = sequence.clone();
+        unknown.locale = Some("tlh".to_owned());
+        assert!(matches!(
+            parse_answers(&unknown, BTreeMap::new()),
+            Err(StorageError::UnknownLocale(..))
+        ));
+    }
+
+    #[test]
+    fn it_should_submit_answers_in_sequence_locale() {
+        let source = "#locale:de\nweight\n    >\n        !number\n";
+        let bundle = compile(&[("recipe.ra", source)]).unwrap();
+        let sequence = bundle.sequence("recipe").unwrap();
+        let mut store = MemoryStore::new();
+        store
+            .update_sequence("recipe", source, &parser::parse(source).unwrap())
+            .unwrap();
+        let values = vec![("weight".to_owned(), json!("1.250,5"))]
+            .into_iter()
+            .collect();
+        let submission = store.submit_answers(sequence, "a", values).unwrap();
+        assert_eq!(submission.values["weight"], json!(1250.5));
+        assert_eq!(
+            store.submissions("recipe", 1).unwrap()[0].values["weight"],
+            json!(1250.5)
+        );
+    }
+}
+
+mod bundle {
+    use crate::{load_bundle, write_bundle, StorageError};
+    use ra_analysis::bundle::{compile, FormatVersion, FORMAT_VERSION};
+    use serde_json::{json, Value};
+
+    const SOURCE: &str = "recipe\n    name\n        >\n    #ln:en `{{name}}`\n";
+
+    #[test]
+    fn it_should_load_written_bundle() {
+        let bundle = compile(&[("recipe.ra", SOURCE)]).unwrap();
+        let mut written = Vec::new();
+        write_bundle(&bundle, &mut written).unwrap();
+        assert!(!written.contains(&b'\n'));
+        assert_eq!(load_bundle(written.as_slice()).unwrap(), bundle);
+    }
+
+    #[test]
+    fn it_should_refuse_incompatible_bundles() {
+        let bundle = compile(&[("recipe.ra", SOURCE)]).unwrap();
+        let mut value = serde_json::to_value(&bundle).unwrap();
+        value["version"] =
+            json!({"major": FORMAT_VERSION.major, "minor": FORMAT_VERSION.minor + 1});
+        let written = serde_json::to_vec(&value).unwrap();
+        assert!(matches!(
+            load_bundle(written.as_slice()),
+            Err(StorageError::UnsupportedBundle(_))
+        ));
+
+        // another major version is refused whatever it contains
+        let written = serde_json::to_vec(
+            &json!({"version": {"major": FORMAT_VERSION.major + 1, "minor": 0}}),
+        )
+        .unwrap();
+        assert!(matches!(
+            load_bundle(written.as_slice()),
+            Err(StorageError::UnsupportedBundle(_))
+        ));
+    }
+
+    /// `name\n    >\n` compiled before references got indices
+    const BUNDLE_1_0: &str = r#"{"version":{"major":1,"minor":0},"sequences":[{"name":"recipe","file":"recipe.ra","program":{"kind":"Program","children":[{"kind":{"Output":[{"ReferenceExpression":[{"kind":"Identifier","len":4,"content":"name","position":[[1,0],[1,4]],"span":{"start":0,"end":4},"level":0},null]},null,null]},"children":[{"kind":{"Input":[false,null]},"children":[],"span":{"start":9,"end":10},"first_token":{"kind":"Greater","len":1,"content":">","position":[[2,4],[2,5]],"span":{"start":9,"end":10},"level":1}}],"span":{"start":0,"end":10},"first_token":{"kind":"Identifier","len":4,"content":"name","position":[[1,0],[1,4]],"span":{"start":0,"end":4},"level":0}}],"span":{"start":0,"end":11},"first_token":{"kind":null,"len":1,"content":"","position":[[0,0],[0,1]],"span":{"start":0,"end":0},"level":0}},"fields":[{"path":"name","input_type":"Unknown","multiple":false,"rules":[],"default":null}],"references":[],"contents":[],"source_map":[{"item":"name","span":{"start":0,"end":10},"start":[1,0],"end":[2,5]}]}]}"#;
+
+    #[test]
+    fn it_should_refuse_bundles_of_the_previous_format() {
+        assert!(matches!(
+            load_bundle(BUNDLE_1_0.as_bytes()),
+            Err(StorageError::UnsupportedBundle(FormatVersion {
+                major: 1,
+                minor: 0
+            }))
+        ));
+    }
+
+    #[test]
+    fn it_should_refuse_files_which_are_not_bundles() {
+        for written in [b"[1]".to_vec(), serde_json::to_vec(&Value::Null).unwrap()] {
+            assert!(matches!(
+                load_bundle(written.as_slice()),
+                Err(StorageError::Serialization(..))
+            ));
+        }
+    }
+}
@@ -1,22 +1,237 @@
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
 use std::env;
+use std::process;
 // extern crate ra;
 use ra_parser;
+use ra_parser::diagnostics::{Renderer, ToDiagnostic};
+use ra_fmt::{format, FormatOptions};
+use ra_analysis::bundle::compile;
+use ra_analysis::diff::diff;
+use ra_storage::{export, write_bundle, ExportOptions, FileStore};
 // use ra_parser;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let path = Path::new(&args[1]);
-    // println!("{:?}", &path);
-    let mut file = File::open(path).unwrap();
-    
-    let mut contents = String::new();
-    file.read_to_string(&mut contents).unwrap();
-    // let bts:() = file.bytes();
 
-    let mut prog = ra_parser::parser::parse(&contents);
+    match args.get(1).map(String::as_str) {
+        Some("fmt") => fmt(&args[2..]),
+        Some("diff") if args.len() == 4 => sequence_diff(&args[2], &args[3]),
+        Some("export") if args.len() >= 4 => export_sequence(&args[2], &args[3], &args[4..]),
+        Some("compile") if args.len() >= 3 => compile_bundle(&args[2..]),
+        // commands with missing or extra arguments aren't files to parse
+        Some("diff") | Some("export") | Some("compile") => usage(),
+        Some(_) => parse(&args[1]),
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: ra <file.ra>\n       ra fmt [--check] [<path>...]\n       ra diff <old.ra> <new.ra>\n       ra export <store> <sequence> [--csv] [--k <n>] [--quasi <field,...>] [--out <dir>]\n       ra compile <file.ra>... [--out <bundle.json>]");
+    process::exit(2);
+}
+
+fn parse(file_name: &str) {
+    let contents = fs::read_to_string(file_name).unwrap();
+    println!("{:?}", parse_or_exit(file_name, &contents));
+}
+
+/// Prints changes between two versions of a sequence
+/// and what happens to the data collected with the old one
+fn sequence_diff(old_file: &str, new_file: &str) {
+    let old_contents = fs::read_to_string(old_file).unwrap();
+    let new_contents = fs::read_to_string(new_file).unwrap();
+    let old = parse_or_exit(old_file, &old_contents);
+    let new = parse_or_exit(new_file, &new_contents);
+    print!("{}", diff(&old, &new));
+}
+
+fn parse_or_exit<'a>(file_name: &str, contents: &'a str) -> ra_parser::block::Block<'a> {
+    match ra_parser::parser::parse(contents) {
+        Ok(program) => program,
+        Err((errors, _)) => {
+            let renderer = renderer(file_name);
+            for error in errors.iter() {
+                eprintln!("{}", renderer.render(contents, &error.to_diagnostic()));
+            }
+            process::exit(1);
+        }
+    }
+}
+
+/// Writes public data of every epoch of a sequence,
+/// to `<sequence>.<epoch>.jsonl` or `.csv` files when `--out` is given
+fn export_sequence(root: &str, sequence: &str, args: &[String]) {
+    let mut csv = false;
+    let mut out = None;
+    let mut options = ExportOptions::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.clone().next()) {
+            ("--csv", _) => csv = true,
+            ("--k", Some(k)) => {
+                options.k_anonymity = Some(k.parse().unwrap_or_else(|_| usage()));
+                args.next();
+            }
+            ("--quasi", Some(fields)) => {
+                options.quasi_identifiers = fields.split(',').map(str::to_owned).collect();
+                args.next();
+            }
+            ("--out", Some(dir)) => {
+                out = Some(PathBuf::from(dir));
+                args.next();
+            }
+            _ => usage(),
+        }
+    }
+
+    let exported = FileStore::open(root).and_then(|store| {
+        let secret = store.secret()?;
+        export(&store, sequence, &secret, &options)
+    });
+    let exports = exported.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    for epoch in exports.iter() {
+        let written = match &out {
+            Some(dir) => {
+                let extension = if csv { "csv" } else { "jsonl" };
+                let path = dir.join(format!("{}.{}.{}", sequence, epoch.epoch, extension));
+                fs::create_dir_all(dir)
+                    .map_err(From::from)
+                    .and_then(|_| fs::File::create(&path).map_err(From::from))
+                    .and_then(|file| if csv { epoch.write_csv(file) } else { epoch.write_json_lines(file) })
+            }
+            None if csv => epoch.write_csv(io::stdout()),
+            None => epoch.write_json_lines(io::stdout()),
+        };
+        if let Err(e) = written {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+/// Compiles sequences referencing each other into a single bundle,
+/// written to stdout unless `--out` is given
+fn compile_bundle(args: &[String]) {
+    let mut files = Vec::new();
+    let mut out = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().unwrap_or_else(|| usage())),
+            file => files.push(file),
+        }
+    }
+    if files.is_empty() {
+        usage();
+    }
+
+    let contents: Vec<String> = files.iter().map(|file| fs::read_to_string(file).unwrap()).collect();
+    let sources: Vec<(&str, &str)> = files
+        .iter()
+        .zip(contents.iter())
+        .map(|(file, contents)| (*file, contents.as_str()))
+        .collect();
+    let bundle = compile(&sources).unwrap_or_else(|errors| {
+        for error in errors.iter() {
+            let (_, contents) = sources.iter().find(|(file, _)| *file == error.file).unwrap();
+            eprintln!("{}", renderer(&error.file).render(contents, &error.diagnostic));
+        }
+        process::exit(1);
+    });
+
+    let written = match out {
+        Some(path) => fs::File::create(path)
+            .map_err(From::from)
+            .and_then(|file| write_bundle(&bundle, file)),
+        None => write_bundle(&bundle, io::stdout()),
+    };
+    if let Err(e) = written {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+/// Formats files in place, or stdin to stdout when no paths given.
+/// With `--check` only reports files which aren't formatted.
+fn fmt(args: &[String]) {
+    if args.iter().any(|arg| arg.starts_with("--") && arg != "--check") {
+        usage();
+    }
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<PathBuf> = args
+        .iter()
+        .filter(|arg| *arg != "--check")
+        .map(PathBuf::from)
+        .collect();
+    let options = FormatOptions::default();
+
+    if paths.is_empty() {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).unwrap();
+        match format(&contents, &options) {
+            Ok(formatted) if check => {
+                if formatted != contents {
+                    eprintln!("stdin is not formatted");
+                    process::exit(1);
+                }
+            }
+            Ok(formatted) => print!("{}", formatted),
+            Err(e) => {
+                eprintln!("{}", renderer("stdin").render(&contents, &e.to_diagnostic()));
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut failed = false;
+    for path in ra_files(&paths) {
+        let contents = fs::read_to_string(&path).unwrap();
+        match format(&contents, &options) {
+            Ok(formatted) if formatted == contents => {}
+            Ok(_) if check => {
+                eprintln!("{} is not formatted", path.display());
+                failed = true;
+            }
+            Ok(formatted) => fs::write(&path, formatted).unwrap(),
+            Err(e) => {
+                let file_name = path.display().to_string();
+                eprintln!("{}", renderer(&file_name).render(&contents, &e.to_diagnostic()));
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Given files and `.ra` files found in given directories
+fn ra_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(path)
+                .unwrap()
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| entry.is_dir() || entry.extension().is_some_and(|extension| extension == "ra"))
+                .collect();
+            entries.sort();
+            files.extend(ra_files(&entries));
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
 
-    println!("{:?}", prog);
+fn renderer(file_name: &str) -> Renderer {
+    Renderer::new(env::var_os("NO_COLOR").is_none()).with_file_name(file_name)
 }